@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct IniFile {
     path: PathBuf,
     sections: HashMap<String, Section>,
@@ -61,6 +61,9 @@ impl IniFile {
                 }
             }
         }
+        if let Some(curr_sect) = curr_sect {
+            sections.push(curr_sect);
+        }
         Some(sections)
     }
 
@@ -81,6 +84,12 @@ impl IniFile {
         sect.get(key.to_string())
     }
 
+    /// Names of all sections, like `core` or `remote "origin"`, in no
+    /// particular order.
+    pub fn sections(&self) -> impl Iterator<Item = &str> {
+        self.sections.keys().map(String::as_str)
+    }
+
     /// Read the value of [key] in [section] if it exists.
     pub fn get<F: std::str::FromStr>(&self, section: &str, key: &str) -> Option<F> {
         let str = self.get_string(section, key)?;
@@ -96,15 +105,6 @@ impl IniFile {
     }
 }
 
-impl Default for IniFile {
-    fn default() -> Self {
-        Self {
-            path: PathBuf::default(),
-            sections: HashMap::default(),
-        }
-    }
-}
-
 #[derive(Debug)]
 struct Section {
     pub(crate) name: String,
@@ -136,7 +136,7 @@ pub enum IniFileOpenError {
     FormatError,
 }
 
-fn tokenize(data: &String) -> Vec<IniToken> {
+fn tokenize(data: &str) -> Vec<IniToken> {
     let mut tokens = Vec::new();
     for line in data.lines() {
         let line = line.trim();
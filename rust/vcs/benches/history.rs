@@ -0,0 +1,103 @@
+//! History traversal on a synthetic repository of 100k commits, with and
+//! without commit-graph.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs;
+use std::path::{Path, PathBuf};
+use vcs::git::object_id::{ObjectFormat, ObjectId};
+use vcs::git::objects::{GitCommit, GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+use vcs::git::signature::Signature;
+
+const COMMITS: usize = 100_000;
+/// A side branch is merged back every this many commits.
+const MERGE_EVERY: usize = 10;
+/// Commit the `old` branch forks from, far from the tip.
+const FORK_AT: usize = 1_000;
+
+/// Create the repository once and reuse it for later runs: `master` with
+/// [COMMITS] commits including merges and `old` forked at [FORK_AT].
+fn synthetic_repo() -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("history_100k");
+    if path.join("complete").exists() {
+        return path;
+    }
+    if path.exists() {
+        fs::remove_dir_all(&path).unwrap();
+    }
+    let repo = Repository::init(path.clone(), ObjectFormat::Sha1).unwrap();
+    let tree = repo.object_write_raw(GitObjectType::Tree, &[]).unwrap();
+    let commit = |parents: Vec<ObjectId>, n: usize, message: &str| {
+        let signature = Signature {
+            name: "Bench".to_string(),
+            email: "bench@example.com".to_string(),
+            time: 1_500_000_000 + n as i64 * 60,
+            tz_offset: 0,
+        };
+        repo.object_write(GitObject::Commit(GitCommit::new(
+            tree,
+            parents,
+            signature.clone(),
+            signature,
+            format!("{message} {n}\n"),
+        )))
+        .unwrap()
+    };
+
+    let mut tip = commit(Vec::new(), 0, "Commit");
+    let mut side = tip;
+    let mut n = 1;
+    while n < COMMITS {
+        if n == FORK_AT {
+            let old = commit(vec![tip], n, "Old");
+            repo.ref_update("refs/heads/old", old).unwrap();
+        }
+        if n % MERGE_EVERY == 0 {
+            side = commit(vec![side], n, "Side");
+            tip = commit(vec![tip, side], n + 1, "Merge");
+            side = tip;
+            n += 2;
+        } else {
+            tip = commit(vec![tip], n, "Commit");
+            n += 1;
+        }
+    }
+    repo.ref_update("refs/heads/master", tip).unwrap();
+    fs::write(path.join("complete"), "").unwrap();
+    path
+}
+
+fn history(c: &mut Criterion) {
+    let path = synthetic_repo();
+    let graph = path.join(".git/objects/info/commit-graph");
+    let _ = fs::remove_file(&graph);
+
+    let mut group = c.benchmark_group("history");
+    group.sample_size(10);
+    for with_graph in [false, true] {
+        if with_graph {
+            let repo = Repository::new(path.clone(), None).unwrap();
+            repo.write_commit_graph().unwrap();
+        }
+        let repo = Repository::new(path.clone(), None).unwrap();
+        assert_eq!(repo.commit_graph().is_some(), with_graph);
+        let master = repo.rev_parse("master").unwrap();
+        let old = repo.rev_parse("old").unwrap();
+        let suffix = if with_graph {
+            "commit-graph"
+        } else {
+            "objects"
+        };
+
+        group.bench_function(format!("log/{suffix}"), |b| {
+            b.iter(|| assert!(repo.walk_commits(vec![master]).count() == COMMITS))
+        });
+        group.bench_function(format!("merge-base/{suffix}"), |b| {
+            b.iter(|| assert_eq!(repo.merge_bases(master, old).len(), 1))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, history);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use vcs::git::archive::{ArchiveFormat, ArchiveOptions};
+use vcs::git::repo::Repository;
+
+/// Write an archive of [tree_ish] to [output], stdout if not given. The
+/// format defaults to the one implied by the name of [output], else tar.
+pub fn archive(
+    repo: &Repository,
+    tree_ish: &str,
+    format: Option<ArchiveFormat>,
+    prefix: Option<String>,
+    output: Option<&str>,
+    paths: Vec<String>,
+) -> bool {
+    let Some(id) = repo.rev_parse(tree_ish) else {
+        eprintln!("not a valid object name: {tree_ish}");
+        return false;
+    };
+    let format = format
+        .or_else(|| output.and_then(ArchiveFormat::from_file_name))
+        .unwrap_or(ArchiveFormat::Tar);
+    let options = ArchiveOptions {
+        format,
+        prefix: prefix.unwrap_or_default(),
+        paths,
+    };
+    let out: Box<dyn Write> = match output {
+        Some(output) => match File::create(output) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("could not create archive file '{output}': {err}");
+                return false;
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut out = BufWriter::new(out);
+    match repo
+        .archive(id, &options, &mut out)
+        .and_then(|()| out.flush())
+    {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("{err}");
+            false
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::commands::log::{print_commit, LogOptions};
+use std::io;
+use std::process::Command;
+use vcs::git::bisect::{BisectError, BisectStep, BisectTerm};
+use vcs::git::object_id::ObjectId;
+use vcs::git::objects::{GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+
+/// Start a bisection with the bad commit and good ones in [revs].
+pub fn bisect_start(repo: &Repository, revs: &[String]) -> bool {
+    report(repo, repo.bisect_start(revs))
+}
+
+/// Mark [revs] (`HEAD` if empty) as [term] and check out the next commit.
+pub fn bisect_mark(repo: &Repository, term: BisectTerm, revs: &[String]) -> bool {
+    let head = ["HEAD".to_string()];
+    let revs = if revs.is_empty() { &head[..] } else { revs };
+    for rev in revs {
+        let Some(commit) = repo
+            .rev_parse(rev)
+            .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+        else {
+            eprintln!("{}", BisectError::InvalidRevision(rev.to_string()));
+            return false;
+        };
+        if let Err(err) = repo.bisect_mark(term, commit) {
+            eprintln!("{err}");
+            return false;
+        }
+    }
+    report(repo, repo.bisect_next())
+}
+
+/// End the bisection and go back to where it started or to [commit].
+pub fn bisect_reset(repo: &Repository, commit: Option<&str>) -> bool {
+    match repo.bisect_reset(commit) {
+        Ok(()) => true,
+        // Like git, there being nothing to reset isn't an error.
+        Err(BisectError::NotBisecting) => {
+            eprintln!("{}", BisectError::NotBisecting);
+            true
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            false
+        }
+    }
+}
+
+pub fn bisect_log(repo: &Repository) -> bool {
+    match repo.bisect_log() {
+        Ok(log) => {
+            print!("{log}");
+            true
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            false
+        }
+    }
+}
+
+/// Bisect automatically: run [command] on every commit to test, exit code 0
+/// means good, 125 skip and up to 127 bad. Other codes stop the bisection.
+pub fn bisect_run(repo: &Repository, command: &[String]) -> bool {
+    let shown = command.join(" ");
+    match repo.bisect_next() {
+        Ok(BisectStep::Testing { .. }) => {}
+        Ok(step) => return report(repo, Ok(step)),
+        Err(err) => {
+            eprintln!("{err}");
+            return false;
+        }
+    }
+    loop {
+        println!("running {shown}");
+        let status = match command {
+            [script] => Command::new("sh").arg("-c").arg(script).status(),
+            [program, args @ ..] => Command::new(program).args(args).status(),
+            [] => return false,
+        };
+        let term = match status.map(|status| status.code()) {
+            Ok(Some(0)) => BisectTerm::Good,
+            Ok(Some(125)) => BisectTerm::Skip,
+            Ok(Some(1..=127)) => BisectTerm::Bad,
+            Ok(code) => {
+                let code = code.unwrap_or(-1);
+                eprintln!("bisect run failed: exit code {code} from '{shown}' is < 0 or >= 128");
+                return false;
+            }
+            Err(err) => {
+                eprintln!("bisect run failed: {err}");
+                return false;
+            }
+        };
+        let head = repo.rev_parse("HEAD");
+        if let Err(err) = head
+            .ok_or(BisectError::IOError)
+            .and_then(|head| repo.bisect_mark(term, head))
+        {
+            eprintln!("{err}");
+            return false;
+        }
+        let step = repo.bisect_next();
+        let testing = matches!(step, Ok(BisectStep::Testing { .. }));
+        let found = matches!(step, Ok(BisectStep::Found(_)));
+        if !report(repo, step) {
+            return false;
+        }
+        if found {
+            println!("bisect found first bad commit");
+        }
+        if !testing {
+            return found;
+        }
+    }
+}
+
+/// Print where the bisection stands after a step.
+fn report(repo: &Repository, step: Result<BisectStep, BisectError>) -> bool {
+    match step {
+        Ok(BisectStep::Waiting { bad, good }) => {
+            match (bad, good) {
+                (false, 0) => println!("status: waiting for both good and bad commits"),
+                (true, _) => println!("status: waiting for good commit(s), bad commit known"),
+                (false, 1) => println!("status: waiting for bad commit, 1 good commit known"),
+                (false, good) => {
+                    println!("status: waiting for bad commit, {good} good commits known")
+                }
+            }
+            true
+        }
+        Ok(BisectStep::Testing {
+            commit,
+            remaining,
+            steps,
+        }) => {
+            let revisions = if remaining == 1 {
+                "revision"
+            } else {
+                "revisions"
+            };
+            let steps_word = if steps == 1 { "step" } else { "steps" };
+            println!(
+                "Bisecting: {remaining} {revisions} left to test after this (roughly {steps} {steps_word})"
+            );
+            let subject = subject(repo, commit);
+            println!("[{commit}] {subject}");
+            true
+        }
+        Ok(BisectStep::Found(commit)) => {
+            println!("{commit} is the first bad commit");
+            let Some(GitObject::Commit(object)) = repo.object_read(commit) else {
+                return false;
+            };
+            let options = LogOptions {
+                max_count: None,
+                oneline: false,
+                show_signature: false,
+            };
+            print_commit(repo, commit, &object, &options, &mut io::stdout()).is_ok()
+        }
+        Ok(BisectStep::OnlySkipped(commits)) => {
+            println!("There are only 'skip'ped commits left to test.");
+            println!("The first bad commit could be any of:");
+            for commit in commits {
+                println!("{commit}");
+            }
+            println!("We cannot bisect more!");
+            false
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            false
+        }
+    }
+}
+
+fn subject(repo: &Repository, id: ObjectId) -> String {
+    match repo.object_read(id) {
+        Some(GitObject::Commit(commit)) => {
+            let message = commit.get_message().unwrap_or_default();
+            message.lines().next().unwrap_or_default().to_string()
+        }
+        _ => String::new(),
+    }
+}
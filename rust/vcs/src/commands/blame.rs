@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+use std::io;
+use std::io::Write;
+use vcs::git::blame::{Blame, BlameLine};
+use vcs::git::object_id::ObjectId;
+use vcs::git::objects::{GitCommit, GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+
+/// How `blame` prints its result.
+#[derive(PartialEq, Eq)]
+pub enum BlameFormat {
+    /// One human readable line per line of the file.
+    Default,
+    /// Machine readable, commit details only on first occurrence.
+    Porcelain,
+    /// Machine readable, commit details for every line.
+    LinePorcelain,
+}
+
+/// Show which commit last changed each line of [path] at [rev].
+///
+/// [range] is `<start>,<end>`, where either may be omitted and `<end>` may be
+/// `+<count>`.
+pub fn blame(
+    repo: &Repository,
+    rev: &str,
+    path: &str,
+    range: Option<&str>,
+    format: BlameFormat,
+) -> bool {
+    let Some(start) = repo
+        .rev_parse(rev)
+        .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+    else {
+        eprintln!("Unknown revision {rev}");
+        return false;
+    };
+    let range = match range.map(parse_range) {
+        None => None,
+        Some(Some(range)) => Some(range),
+        Some(None) => {
+            eprintln!("Invalid line range {}", range.unwrap_or_default());
+            return false;
+        }
+    };
+    let Some(blame) = repo.blame(start, path, range) else {
+        eprintln!("No such path {path} in {rev}");
+        return false;
+    };
+    if let Some((first, _)) = range {
+        if first > blame.lines.len().max(1) {
+            eprintln!("File {path} has only {} lines", blame.lines.len());
+            return false;
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    let res = match format {
+        BlameFormat::Default => print_default(repo, &blame, path, &mut stdout),
+        _ => print_porcelain(
+            repo,
+            &blame,
+            format == BlameFormat::LinePorcelain,
+            &mut stdout,
+        ),
+    };
+    res.and_then(|_| stdout.flush()).is_ok()
+}
+
+/// Parse `-L` arguments into a 1-based inclusive range.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (start, end) = range.split_once(',').unwrap_or((range, ""));
+    let start = if start.is_empty() {
+        1
+    } else {
+        start.parse::<usize>().ok()?.max(1)
+    };
+    let end = if end.is_empty() {
+        usize::MAX
+    } else if let Some(count) = end.strip_prefix('+') {
+        start + count.parse::<usize>().ok()?.max(1) - 1
+    } else {
+        end.parse().ok()?
+    };
+    if end < start {
+        return Some((end.max(1), start));
+    }
+    Some((start, end))
+}
+
+fn read_commit(repo: &Repository, id: ObjectId) -> Option<GitCommit> {
+    match repo.object_read(id)? {
+        GitObject::Commit(commit) => Some(commit),
+        _ => None,
+    }
+}
+
+/// `<id> [<path>] (<author> <date> <line>) <content>`, the path is only
+/// shown if some lines come from a file with a different name.
+fn print_default(
+    repo: &Repository,
+    blame: &Blame,
+    path: &str,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let authors = blame
+        .origins
+        .iter()
+        .map(|origin| {
+            read_commit(repo, origin.commit)
+                .and_then(|commit| commit.get_author())
+                .map(|author| {
+                    let date = author.format_date("%Y-%m-%d %H:%M:%S %z");
+                    (author.name, date)
+                })
+                .unwrap_or_default()
+        })
+        .collect::<Vec<(String, String)>>();
+    let author_width = authors
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let line_width = blame
+        .origins
+        .last()
+        .map_or(1, |origin| origin.final_line.to_string().len());
+    let show_path = blame.origins.iter().any(|origin| origin.path != path);
+    let path_width = blame
+        .origins
+        .iter()
+        .map(|origin| origin.path.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for (origin, (author, date)) in blame.origins.iter().zip(authors) {
+        let id = origin.commit.to_string();
+        if origin.boundary {
+            write!(out, "^{}", &id[..7])?;
+        } else {
+            write!(out, "{}", &id[..8])?;
+        }
+        if show_path {
+            let padding = path_width - origin.path.chars().count();
+            write!(out, " {}{}", origin.path, " ".repeat(padding))?;
+        }
+        let padding = author_width - author.chars().count();
+        write!(
+            out,
+            " ({author}{} {date} {:>line_width$}) ",
+            " ".repeat(padding),
+            origin.final_line
+        )?;
+        write_line(out, &blame.lines[origin.final_line - 1])?;
+    }
+    Ok(())
+}
+
+/// git's porcelain format: a header per line, commit details the first time
+/// a commit appears (or always if [repeat]) and the content after a tab.
+fn print_porcelain(
+    repo: &Repository,
+    blame: &Blame,
+    repeat: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut shown = HashSet::new();
+    let mut group_start = true;
+    for (i, origin) in blame.origins.iter().enumerate() {
+        if group_start {
+            let len = blame.origins[i..]
+                .iter()
+                .zip(blame.origins[i + 1..].iter())
+                .take_while(|(a, b)| continues(a, b))
+                .count()
+                + 1;
+            writeln!(
+                out,
+                "{} {} {} {len}",
+                origin.commit, origin.orig_line, origin.final_line
+            )?;
+        } else {
+            writeln!(
+                out,
+                "{} {} {}",
+                origin.commit, origin.orig_line, origin.final_line
+            )?;
+        }
+        if shown.insert(origin.commit) || repeat {
+            print_details(repo, origin, out)?;
+        }
+        write!(out, "\t")?;
+        write_line(out, &blame.lines[origin.final_line - 1])?;
+        group_start = blame
+            .origins
+            .get(i + 1)
+            .is_none_or(|next| !continues(origin, next));
+    }
+    Ok(())
+}
+
+/// Whether [next] directly follows [line] in the same commit and file.
+fn continues(line: &BlameLine, next: &BlameLine) -> bool {
+    line.commit == next.commit
+        && line.path == next.path
+        && line.orig_line + 1 == next.orig_line
+        && line.final_line + 1 == next.final_line
+}
+
+fn print_details(repo: &Repository, origin: &BlameLine, out: &mut impl Write) -> io::Result<()> {
+    let commit = read_commit(repo, origin.commit);
+    for (role, signature) in [
+        ("author", commit.as_ref().and_then(|c| c.get_author())),
+        ("committer", commit.as_ref().and_then(|c| c.get_commiter())),
+    ] {
+        if let Some(signature) = signature {
+            writeln!(out, "{role} {}", signature.name)?;
+            writeln!(out, "{role}-mail <{}>", signature.email)?;
+            writeln!(out, "{role}-time {}", signature.time)?;
+            writeln!(out, "{role}-tz {}", signature.tz_string())?;
+        }
+    }
+    let message = commit.and_then(|c| c.get_message()).unwrap_or_default();
+    writeln!(
+        out,
+        "summary {}",
+        message.lines().next().unwrap_or_default()
+    )?;
+    if origin.boundary {
+        writeln!(out, "boundary")?;
+    }
+    if let Some((previous, path)) = &origin.previous {
+        writeln!(out, "previous {previous} {path}")?;
+    }
+    writeln!(out, "filename {}", origin.path)
+}
+
+/// Write a line of the file, adding a line ending if it has none.
+fn write_line(out: &mut impl Write, line: &[u8]) -> io::Result<()> {
+    out.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        writeln!(out)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::io;
+use std::io::{BufRead, Write};
+use vcs::git::object_id::ObjectId;
+use vcs::git::objects::{GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+
+/// What `cat-file` prints about an object.
+pub enum CatFileMode {
+    /// `-t`: the object type.
+    Type,
+    /// `-s`: the object size in bytes.
+    Size,
+    /// `-e`: nothing, the exit status tells whether the object exists.
+    Exists,
+    /// `-p`: the contents formatted for humans.
+    Pretty,
+    /// `<type>`: the raw contents, peeled to the given type.
+    Raw(GitObjectType),
+}
+
+/// Print information about a single [object] as selected by [mode].
+pub fn cat_file(repo: &Repository, mode: CatFileMode, object: &str) -> bool {
+    let Some(id) = repo.rev_parse(object) else {
+        if !matches!(mode, CatFileMode::Exists) {
+            eprintln!("Not a valid object name {object}");
+        }
+        return false;
+    };
+    let mut stdout = io::stdout().lock();
+    let res = match mode {
+        CatFileMode::Exists => return true,
+        CatFileMode::Type => repo
+            .object_open(id)
+            .map(|obj| writeln!(stdout, "{}", obj.obj_type().name())),
+        CatFileMode::Size => repo
+            .object_open(id)
+            .map(|obj| writeln!(stdout, "{}", obj.size())),
+        CatFileMode::Pretty => pretty_print(repo, id, &mut stdout),
+        CatFileMode::Raw(obj_type) => repo
+            .object_peel(id, obj_type)
+            .and_then(|id| repo.object_open(id))
+            .map(|mut obj| io::copy(&mut obj, &mut stdout).map(|_| ())),
+    };
+    match res {
+        Some(Ok(())) => stdout.flush().is_ok(),
+        Some(Err(err)) => {
+            eprintln!("Failed to write object: {err}");
+            false
+        }
+        None => {
+            eprintln!("Failed to read object {id}");
+            false
+        }
+    }
+}
+
+/// Read object names from stdin, one per line, and print `<id> <type>
+/// <size>` for each. With [contents] the raw contents follow each header.
+pub fn cat_file_batch(repo: &Repository, contents: bool) -> bool {
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            return false;
+        };
+        let name = line.trim();
+        let res = match repo
+            .rev_parse(name)
+            .and_then(|id| Some((id, repo.object_open(id)?)))
+        {
+            None => writeln!(stdout, "{name} missing"),
+            Some((id, mut obj)) => {
+                writeln!(stdout, "{id} {} {}", obj.obj_type().name(), obj.size()).and_then(|_| {
+                    if contents {
+                        io::copy(&mut obj, &mut stdout)?;
+                        writeln!(stdout)?;
+                    }
+                    Ok(())
+                })
+            }
+        };
+        // Flush after every object so scripts can read responses interactively.
+        if res.and_then(|_| stdout.flush()).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Blobs, commits and tags are printed as they are, trees one entry per line
+/// as `<mode> <type> <id>\t<path>`.
+fn pretty_print(repo: &Repository, id: ObjectId, out: &mut impl Write) -> Option<io::Result<()>> {
+    let mut reader = repo.object_open(id)?;
+    if reader.obj_type() != GitObjectType::Tree {
+        return Some(io::copy(&mut reader, out).map(|_| ()));
+    }
+    let GitObject::Tree(tree) = reader.into_object(repo.object_format()).ok()? else {
+        return None;
+    };
+    for entry in tree.entries() {
+        let res = writeln!(
+            out,
+            "{:0>6} {} {}\t{}",
+            entry.mode_str(),
+            entry.obj_type().name(),
+            entry.obj_hash(),
+            entry.path()
+        );
+        if res.is_err() {
+            return Some(res);
+        }
+    }
+    Some(Ok(()))
+}
@@ -0,0 +1,27 @@
+use vcs::git::objects::GitObject;
+use vcs::git::repo::Repository;
+
+/// Switch to the branch or commit [rev].
+pub fn checkout(repo: &Repository, rev: &str) -> bool {
+    let id = match repo.checkout(rev) {
+        Ok(id) => id,
+        Err(err) => {
+            eprintln!("{err}");
+            return false;
+        }
+    };
+    match repo.head_ref() {
+        Some(head) if head == format!("refs/heads/{rev}") => {
+            eprintln!("Switched to branch '{rev}'")
+        }
+        _ => {
+            let subject = match repo.object_read(id) {
+                Some(GitObject::Commit(commit)) => commit.get_message().unwrap_or_default(),
+                _ => String::new(),
+            };
+            let subject = subject.lines().next().unwrap_or_default();
+            eprintln!("HEAD is now at {} {subject}", &id.to_string()[..7]);
+        }
+    }
+    true
+}
@@ -0,0 +1,33 @@
+use vcs::git::clean::CleanOptions;
+use vcs::git::repo::Repository;
+
+/// Remove untracked files, refusing to do anything without [force] or
+/// [CleanOptions::dry_run] unless `clean.requireForce` is false.
+pub fn clean(repo: &Repository, options: CleanOptions, force: bool) -> bool {
+    if repo.is_bare() {
+        eprintln!("this operation must be run in a work tree");
+        return false;
+    }
+    let require_force = repo
+        .config_string("clean", "requireForce")
+        .is_none_or(|value| value != "false");
+    if require_force && !force && !options.dry_run {
+        eprintln!(
+            "clean.requireForce defaults to true and neither -n nor -f given; refusing to clean"
+        );
+        return false;
+    }
+    let Some(removed) = repo.clean(&options) else {
+        eprintln!("Failed to remove untracked files");
+        return false;
+    };
+    let verb = if options.dry_run {
+        "Would remove"
+    } else {
+        "Removing"
+    };
+    for path in removed {
+        println!("{verb} {path}");
+    }
+    true
+}
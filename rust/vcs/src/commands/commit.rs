@@ -0,0 +1,30 @@
+use vcs::git::commit::CommitOptions;
+use vcs::git::objects::GitObject;
+use vcs::git::repo::Repository;
+
+/// Commit the index with the paragraphs [messages] and print a summary.
+pub fn commit(repo: &Repository, messages: &[String], options: &CommitOptions) -> bool {
+    let message = messages.join("\n\n");
+    let id = match repo.commit(&message, options) {
+        Ok(id) => id,
+        Err(err) => {
+            eprintln!("{err}");
+            return false;
+        }
+    };
+    let branch = repo
+        .head_ref()
+        .and_then(|head| Some(head.strip_prefix("refs/heads/")?.to_string()))
+        .unwrap_or("detached HEAD".to_string());
+    let (root, subject) = match repo.object_read(id) {
+        Some(GitObject::Commit(commit)) => (
+            commit.get_parents().is_empty(),
+            commit.get_message().unwrap_or_default(),
+        ),
+        _ => (false, String::new()),
+    };
+    let root = if root { " (root-commit)" } else { "" };
+    let subject = subject.lines().next().unwrap_or_default();
+    println!("[{branch}{root} {}] {subject}", &id.to_string()[..7]);
+    true
+}
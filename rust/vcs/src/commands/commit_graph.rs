@@ -0,0 +1,12 @@
+use vcs::git::repo::Repository;
+
+/// Write the commit-graph file for all commits reachable from refs.
+pub fn commit_graph_write(repo: &Repository) -> bool {
+    match repo.write_commit_graph() {
+        Some(_) => true,
+        None => {
+            eprintln!("failed to write commit-graph");
+            false
+        }
+    }
+}
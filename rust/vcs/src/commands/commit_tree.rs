@@ -0,0 +1,72 @@
+use std::io::Read;
+use vcs::git::objects::{GitCommit, GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+
+/// Print the id of the tree created from the index.
+pub fn write_tree(repo: &Repository) -> bool {
+    match repo.write_tree() {
+        Some(tree) => {
+            println!("{tree}");
+            true
+        }
+        None => {
+            eprintln!("Failed to write tree, is the index unmerged?");
+            false
+        }
+    }
+}
+
+/// Create a commit of [tree] with [parents] and print its id.
+pub fn commit_tree(repo: &Repository, tree: &str, parents: &[String], message: &[String]) -> bool {
+    let Some(tree) = repo
+        .rev_parse(tree)
+        .and_then(|id| repo.object_peel(id, GitObjectType::Tree))
+    else {
+        eprintln!("Not a valid tree object: {tree}");
+        return false;
+    };
+    let mut parent_ids = Vec::new();
+    for parent in parents {
+        match repo
+            .rev_parse(parent)
+            .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+        {
+            Some(id) => parent_ids.push(id),
+            None => {
+                eprintln!("Not a valid commit: {parent}");
+                return false;
+            }
+        }
+    }
+    let (Some(author), Some(committer)) = (repo.author_signature(), repo.committer_signature())
+    else {
+        eprintln!("Author identity unknown, set user.name and user.email");
+        return false;
+    };
+
+    // Like git, each -m is a paragraph. Without -m the message is read from
+    // stdin.
+    let mut message = if message.is_empty() {
+        let mut message = String::new();
+        if std::io::stdin().read_to_string(&mut message).is_err() {
+            return false;
+        }
+        message
+    } else {
+        message.join("\n\n")
+    };
+    if !message.ends_with('\n') {
+        message.push('\n');
+    }
+    let commit = GitCommit::new(tree, parent_ids, author, committer, message);
+    match repo.object_write(GitObject::Commit(commit)) {
+        Ok(id) => {
+            println!("{id}");
+            true
+        }
+        Err(err) => {
+            eprintln!("unable to write commit object: {err}");
+            false
+        }
+    }
+}
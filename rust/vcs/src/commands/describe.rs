@@ -0,0 +1,26 @@
+use vcs::git::describe::DescribeOptions;
+use vcs::git::objects::GitObjectType;
+use vcs::git::repo::Repository;
+
+/// Print the name of [commit_ish] (`HEAD` by default) relative to the
+/// closest reachable tag.
+pub fn describe(repo: &Repository, commit_ish: Option<&str>, options: &DescribeOptions) -> bool {
+    let rev = commit_ish.unwrap_or("HEAD");
+    let Some(id) = repo
+        .rev_parse(rev)
+        .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+    else {
+        eprintln!("Not a valid object name {rev}");
+        return false;
+    };
+    match repo.describe(id, options) {
+        Ok(name) => {
+            println!("{name}");
+            true
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            false
+        }
+    }
+}
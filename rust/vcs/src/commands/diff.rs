@@ -0,0 +1,20 @@
+use vcs::git::repo::Repository;
+
+/// Print the changes between the index and the work tree as patch, with
+/// [staged] between `HEAD` and the index.
+pub fn diff(repo: &Repository, staged: bool) -> bool {
+    let Some(diffs) = repo.diff_files(staged) else {
+        eprintln!("this operation must be run in a work tree");
+        return false;
+    };
+    for diff in diffs {
+        match repo.diff_patch(&diff) {
+            Some(patch) => print!("{patch}"),
+            None => {
+                eprintln!("unable to read {}", diff.path);
+                return false;
+            }
+        }
+    }
+    true
+}
@@ -0,0 +1,27 @@
+use vcs::git::fsck::FsckError;
+use vcs::git::repo::Repository;
+
+/// Verify the connectivity and validity of all objects.
+///
+/// Dangling objects are always listed, all unreachable ones with
+/// [unreachable]. Fails if any object is broken.
+pub fn fsck(repo: &Repository, unreachable: bool) -> bool {
+    let report = repo.fsck();
+    for error in &report.errors {
+        match error {
+            FsckError::BrokenLink { to, .. } => {
+                println!("{error}");
+                println!("missing {} {}", to.0.name(), to.1);
+            }
+            _ => eprintln!("error: {error}"),
+        }
+    }
+    for (obj_type, id, dangling) in &report.unreachable {
+        if unreachable {
+            println!("unreachable {} {id}", obj_type.name());
+        } else if *dangling {
+            println!("dangling {} {id}", obj_type.name());
+        }
+    }
+    report.errors.is_empty()
+}
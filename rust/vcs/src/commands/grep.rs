@@ -0,0 +1,56 @@
+use std::io;
+use std::io::Write;
+use vcs::git::grep::GrepOptions;
+use vcs::git::repo::Repository;
+
+/// Print the lines matching [pattern] in [tree_ish] or the work tree,
+/// prefixed with the path (and [tree_ish]) and with [line_number] the
+/// line number. Returns whether anything matched.
+pub fn grep(
+    repo: &Repository,
+    pattern: &str,
+    tree_ish: Option<&str>,
+    line_number: bool,
+    options: &GrepOptions,
+) -> bool {
+    let id = match tree_ish {
+        Some(rev) => match repo.rev_parse(rev) {
+            Some(id) => Some(id),
+            None => {
+                eprintln!("unable to resolve revision: {rev}");
+                return false;
+            }
+        },
+        None => None,
+    };
+    let files = match repo.grep(pattern, id, options) {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("{err}");
+            return false;
+        }
+    };
+    let prefix = tree_ish.map(|rev| format!("{rev}:")).unwrap_or_default();
+    let mut stdout = io::stdout().lock();
+    for file in &files {
+        let path = format!("{prefix}{}", file.path);
+        let res = if options.files_with_matches {
+            writeln!(stdout, "{path}")
+        } else if file.binary {
+            writeln!(stdout, "Binary file {path} matches")
+        } else {
+            file.lines.iter().try_for_each(|(number, line)| {
+                match line_number {
+                    true => write!(stdout, "{path}:{number}:")?,
+                    false => write!(stdout, "{path}:")?,
+                }
+                stdout.write_all(line)?;
+                writeln!(stdout)
+            })
+        };
+        if res.is_err() {
+            return false;
+        }
+    }
+    !files.is_empty()
+}
@@ -0,0 +1,109 @@
+use std::io;
+use std::io::Write;
+use vcs::git::object_id::ObjectId;
+use vcs::git::objects::{GitCommit, GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+
+pub struct LogOptions {
+    /// Stop after this many commits.
+    pub max_count: Option<usize>,
+    /// Print `<abbreviated id> <subject>` per commit.
+    pub oneline: bool,
+    /// Verify and report the signature of every commit.
+    pub show_signature: bool,
+}
+
+/// Print the history reachable from [revs] (`HEAD` if empty).
+pub fn log(repo: &Repository, revs: &[String], options: LogOptions) -> bool {
+    let Some((starts, hidden)) = resolve_revs(repo, revs) else {
+        return false;
+    };
+    let mut stdout = io::stdout().lock();
+    let walk = repo.walk_commits(starts).hide(hidden);
+    for (i, id) in walk.enumerate() {
+        if options.max_count.is_some_and(|max| i >= max) {
+            break;
+        }
+        let Some(GitObject::Commit(commit)) = repo.object_read(id) else {
+            return false;
+        };
+        // Commits are separated by a blank line.
+        if i > 0 && !options.oneline && writeln!(stdout).is_err() {
+            return false;
+        }
+        let res = if options.oneline {
+            let subject = commit.get_message().unwrap_or_default();
+            let subject = subject.lines().next().unwrap_or_default();
+            writeln!(stdout, "{} {subject}", &id.to_string()[..7])
+        } else {
+            print_commit(repo, id, &commit, &options, &mut stdout)
+        };
+        if res.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Resolve [revs] (`HEAD` if empty) to the commits to start from and, for
+/// `^<rev>`, the commits whose history is excluded.
+pub fn resolve_revs(repo: &Repository, revs: &[String]) -> Option<(Vec<ObjectId>, Vec<ObjectId>)> {
+    let revs = if revs.is_empty() {
+        vec!["HEAD".to_string()]
+    } else {
+        revs.to_vec()
+    };
+    let mut starts = Vec::new();
+    let mut hidden = Vec::new();
+    for rev in &revs {
+        let (rev, hide) = match rev.strip_prefix('^') {
+            Some(rev) => (rev, true),
+            None => (rev.as_str(), false),
+        };
+        match repo
+            .rev_parse(rev)
+            .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+        {
+            Some(id) if hide => hidden.push(id),
+            Some(id) => starts.push(id),
+            None => {
+                eprintln!("Unknown revision {rev}");
+                return None;
+            }
+        }
+    }
+
+    Some((starts, hidden))
+}
+
+pub fn print_commit(
+    repo: &Repository,
+    id: ObjectId,
+    commit: &GitCommit,
+    options: &LogOptions,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    writeln!(out, "commit {id}")?;
+    if options.show_signature {
+        if let Some((payload, signature)) = commit.signed_payload() {
+            writeln!(out, "{}", repo.verify_signature(&payload, &signature))?;
+        }
+    }
+    let parents = commit.get_parents();
+    if parents.len() > 1 {
+        let parents = parents
+            .iter()
+            .map(|parent| parent.to_string()[..7].to_string())
+            .collect::<Vec<String>>();
+        writeln!(out, "Merge: {}", parents.join(" "))?;
+    }
+    if let Some(author) = commit.get_author() {
+        writeln!(out, "Author: {} <{}>", author.name, author.email)?;
+        writeln!(out, "Date:   {}", author.date_string())?;
+    }
+    writeln!(out)?;
+    for line in commit.get_message().unwrap_or_default().trim_end().lines() {
+        writeln!(out, "    {line}")?;
+    }
+    Ok(())
+}
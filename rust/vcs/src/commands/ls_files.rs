@@ -0,0 +1,30 @@
+use std::io;
+use std::io::Write;
+use vcs::git::repo::Repository;
+
+/// List the staged files, with [stage] as `<mode> <id> <stage>\t<path>`.
+pub fn ls_files(repo: &Repository, stage: bool) -> bool {
+    let Some(index) = repo.index() else {
+        eprintln!("Failed to read the index");
+        return false;
+    };
+    let mut stdout = io::stdout().lock();
+    for entry in index.iter() {
+        let name = entry.name().to_string_lossy();
+        let res = if stage {
+            writeln!(
+                stdout,
+                "{} {} {}\t{name}",
+                entry.mode_str(),
+                entry.hash(),
+                entry.stage()
+            )
+        } else {
+            writeln!(stdout, "{name}")
+        };
+        if res.is_err() {
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,58 @@
+use std::io;
+use std::io::Write;
+use vcs::git::object_id::ObjectId;
+use vcs::git::objects::{GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+
+/// List the contents of [tree_ish] as `<mode> <type> <id>\t<path>`.
+///
+/// With [recursive] subtrees are listed instead of shown as entries.
+pub fn ls_tree(repo: &Repository, tree_ish: &str, recursive: bool, name_only: bool) -> bool {
+    let Some(tree) = repo
+        .rev_parse(tree_ish)
+        .and_then(|id| repo.object_peel(id, GitObjectType::Tree))
+    else {
+        eprintln!("Not a tree object: {tree_ish}");
+        return false;
+    };
+    let mut stdout = io::stdout().lock();
+    list(repo, tree, "", recursive, name_only, &mut stdout).is_some()
+}
+
+fn list(
+    repo: &Repository,
+    tree: ObjectId,
+    prefix: &str,
+    recursive: bool,
+    name_only: bool,
+    out: &mut impl Write,
+) -> Option<()> {
+    let GitObject::Tree(tree) = repo.object_read(tree)? else {
+        return None;
+    };
+    for entry in tree.entries() {
+        let path = format!("{prefix}{}", entry.path());
+        if recursive && entry.obj_type() == GitObjectType::Tree {
+            list(
+                repo,
+                *entry.obj_hash(),
+                &format!("{path}/"),
+                recursive,
+                name_only,
+                out,
+            )?;
+        } else if name_only {
+            writeln!(out, "{path}").ok()?;
+        } else {
+            writeln!(
+                out,
+                "{:0>6} {} {}\t{path}",
+                entry.mode_str(),
+                entry.obj_type().name(),
+                entry.obj_hash()
+            )
+            .ok()?;
+        }
+    }
+    Some(())
+}
@@ -0,0 +1,26 @@
+use vcs::git::objects::GitObjectType;
+use vcs::git::repo::Repository;
+
+/// Print the best common ancestor of [one] and [two], all of them with
+/// [all]. Fails if there is none.
+pub fn merge_base(repo: &Repository, one: &str, two: &str, all: bool) -> bool {
+    let mut commits = Vec::new();
+    for rev in [one, two] {
+        match repo
+            .rev_parse(rev)
+            .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+        {
+            Some(id) => commits.push(id),
+            None => {
+                eprintln!("Not a valid object name {rev}");
+                return false;
+            }
+        }
+    }
+    let bases = repo.merge_bases(commits[0], commits[1]);
+    let count = if all { bases.len() } else { 1 };
+    for base in bases.iter().take(count) {
+        println!("{base}");
+    }
+    !bases.is_empty()
+}
@@ -0,0 +1,38 @@
+use std::path::Path;
+use vcs::git::repo::Repository;
+
+pub mod archive;
+pub mod bisect;
+pub mod blame;
+pub mod cat_file;
+pub mod checkout;
+pub mod clean;
+pub mod commit;
+pub mod commit_graph;
+pub mod commit_tree;
+pub mod describe;
+pub mod diff;
+pub mod fsck;
+pub mod grep;
+pub mod log;
+pub mod ls_files;
+pub mod ls_tree;
+pub mod merge_base;
+pub mod reset;
+pub mod shortlog;
+pub mod sparse_checkout;
+pub mod status;
+pub mod submodule;
+pub mod verify;
+pub mod worktree;
+
+/// Find the repository containing [path] or print why that is not possible.
+pub fn open_repo(path: &str) -> Option<Repository> {
+    match Repository::discover(Path::new(path)) {
+        Ok(repo) => Some(repo),
+        Err(err) => {
+            eprintln!("{:?}", err);
+            None
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use vcs::git::objects::{GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+use vcs::git::reset::ResetMode;
+use vcs::git::status::WorkTreeChange;
+
+/// Reset the current branch to [commit] (`HEAD` by default), or only the
+/// index entries of [paths] if any are given.
+pub fn reset(repo: &Repository, mode: ResetMode, commit: Option<&str>, paths: &[String]) -> bool {
+    let rev = commit.unwrap_or("HEAD");
+    let Some(target) = repo
+        .rev_parse(rev)
+        .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+    else {
+        eprintln!("Failed to resolve '{rev}' as a valid revision");
+        return false;
+    };
+
+    if !paths.is_empty() {
+        if mode != ResetMode::Mixed {
+            eprintln!("Cannot do {mode:?} reset with paths");
+            return false;
+        }
+        if repo.reset_paths(target, paths).is_none() {
+            eprintln!("Failed to reset paths");
+            return false;
+        }
+        print_unstaged(repo);
+        return true;
+    }
+
+    if mode != ResetMode::Soft && repo.is_bare() {
+        let mode = format!("{mode:?}").to_lowercase();
+        eprintln!("{mode} reset is not allowed in a bare repository");
+        return false;
+    }
+    if repo.reset(target, mode).is_none() {
+        eprintln!("Failed to reset to {target}");
+        return false;
+    }
+    match mode {
+        ResetMode::Hard => {
+            let subject = match repo.object_read(target) {
+                Some(GitObject::Commit(commit)) => commit.get_message().unwrap_or_default(),
+                _ => String::new(),
+            };
+            let subject = subject.lines().next().unwrap_or_default();
+            println!("HEAD is now at {} {subject}", &target.to_string()[..7]);
+        }
+        ResetMode::Mixed => print_unstaged(repo),
+        ResetMode::Soft => {}
+    }
+    true
+}
+
+/// List tracked files whose work tree content differs from the index.
+fn print_unstaged(repo: &Repository) {
+    let Some(changes) = repo.work_tree_changes() else {
+        return;
+    };
+    if !changes.is_empty() {
+        println!("Unstaged changes after reset:");
+    }
+    for (path, change) in changes {
+        let status = match change {
+            WorkTreeChange::Modified => "M",
+            WorkTreeChange::Deleted => "D",
+        };
+        println!("{status}\t{}", path.display());
+    }
+}
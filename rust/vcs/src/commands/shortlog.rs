@@ -0,0 +1,91 @@
+use crate::commands::log::resolve_revs;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+use vcs::git::objects::GitObject;
+use vcs::git::repo::Repository;
+
+pub struct ShortlogOptions {
+    /// Only print the number of commits per author.
+    pub summary: bool,
+    /// Order authors by their number of commits instead of by name.
+    pub numbered: bool,
+    /// Show the email of each author.
+    pub email: bool,
+}
+
+/// Print the subjects of the commits reachable from [revs] (`HEAD` if
+/// empty) grouped by author, with names and emails mapped by `.mailmap`.
+pub fn shortlog(repo: &Repository, revs: &[String], options: &ShortlogOptions) -> bool {
+    let Some((starts, hidden)) = resolve_revs(repo, revs) else {
+        return false;
+    };
+    let mailmap = repo.mailmap();
+    let mut authors = BTreeMap::<String, Vec<String>>::new();
+    for id in repo.walk_commits(starts).hide(hidden) {
+        let Some(GitObject::Commit(commit)) = repo.object_read(id) else {
+            return false;
+        };
+        let Some(author) = commit.get_author() else {
+            continue;
+        };
+        let (name, email) = mailmap.map(&author.name, &author.email);
+        let key = match options.email {
+            true => format!("{name} <{email}>"),
+            false => name,
+        };
+        let message = commit.get_message().unwrap_or_default();
+        authors.entry(key).or_default().push(subject(&message));
+    }
+
+    let mut authors: Vec<_> = authors.into_iter().collect();
+    if options.numbered {
+        authors.sort_by_key(|(_, subjects)| Reverse(subjects.len()));
+    }
+    let mut stdout = io::stdout().lock();
+    for (author, subjects) in authors {
+        let res = if options.summary {
+            writeln!(stdout, "{:6}\t{author}", subjects.len())
+        } else {
+            print_author(&mut stdout, &author, &subjects)
+        };
+        if res.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Print [author] with its number of commits and then [subjects], which are
+/// newest first, oldest first.
+fn print_author(out: &mut impl Write, author: &str, subjects: &[String]) -> io::Result<()> {
+    writeln!(out, "{author} ({}):", subjects.len())?;
+    for subject in subjects.iter().rev() {
+        writeln!(out, "      {subject}")?;
+    }
+    writeln!(out)
+}
+
+/// The first paragraph of [message] on one line, without a leading
+/// `[PATCH ...]` like in mailed patches.
+fn subject(message: &str) -> String {
+    let paragraph: Vec<_> = message
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .take_while(|line| !line.trim().is_empty())
+        .map(str::trim_end)
+        .collect();
+    let subject = paragraph.join(" ");
+    let subject = match subject
+        .strip_prefix("[PATCH")
+        .and_then(|s| s.split_once(']'))
+    {
+        Some((_, rest)) => rest.trim_start(),
+        None => &subject,
+    };
+    match subject.is_empty() {
+        true => "<none>".to_string(),
+        false => subject.to_string(),
+    }
+}
@@ -0,0 +1,54 @@
+use vcs::git::repo::Repository;
+use vcs::git::sparse_checkout::{SparseCheckout, SparseCheckoutError};
+
+/// Enable the sparse checkout, keeping the directories checked out before.
+pub fn sparse_checkout_init(repo: &mut Repository) -> bool {
+    report(repo.sparse_checkout_init())
+}
+
+/// Only check out the top level files and [dirs].
+pub fn sparse_checkout_set(repo: &mut Repository, dirs: &[String]) -> bool {
+    report(repo.sparse_checkout_set(&SparseCheckout::new(dirs)))
+}
+
+/// Check out [dirs] as well.
+pub fn sparse_checkout_add(repo: &mut Repository, dirs: &[String]) -> bool {
+    report(repo.sparse_checkout_add(dirs))
+}
+
+/// Print the checked out directories.
+pub fn sparse_checkout_list(repo: &Repository) -> bool {
+    let Some(sparse) = repo.sparse_checkout() else {
+        eprintln!("{}", SparseCheckoutError::NotSparse);
+        return false;
+    };
+    for dir in sparse.dirs() {
+        println!("{dir}");
+    }
+    true
+}
+
+/// Check out all files again.
+pub fn sparse_checkout_disable(repo: &mut Repository) -> bool {
+    report(repo.sparse_checkout_disable().map(|_| Vec::new()))
+}
+
+/// Print the error or the changed files that were kept outside of the
+/// sparse checkout.
+fn report(result: Result<Vec<String>, SparseCheckoutError>) -> bool {
+    match result {
+        Ok(kept) => {
+            if !kept.is_empty() {
+                eprintln!("warning: The following paths are not up to date and were left despite sparse patterns:");
+                for path in kept {
+                    eprintln!("\t{path}");
+                }
+            }
+            true
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            false
+        }
+    }
+}
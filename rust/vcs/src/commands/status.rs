@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use vcs::git::repo::Repository;
+
+/// Show the staged, unstaged and untracked changes, as `XY <path>` lines
+/// with [short].
+pub fn status(repo: &Repository, short: bool) -> bool {
+    let Some(status) = repo.status() else {
+        eprintln!("this operation must be run in a work tree");
+        return false;
+    };
+
+    if short {
+        let mut codes = BTreeMap::<&str, [char; 2]>::new();
+        let staged = [
+            (&status.added, 'A'),
+            (&status.modified, 'M'),
+            (&status.deleted, 'D'),
+        ];
+        for (paths, code) in staged {
+            for path in paths {
+                codes.entry(path).or_insert([' '; 2])[0] = code;
+            }
+        }
+        let unstaged = [
+            (&status.unstaged_modified, 'M'),
+            (&status.unstaged_deleted, 'D'),
+        ];
+        for (paths, code) in unstaged {
+            for path in paths {
+                codes.entry(path).or_insert([' '; 2])[1] = code;
+            }
+        }
+        for (path, [x, y]) in codes {
+            println!("{x}{y} {path}");
+        }
+        for path in &status.untracked {
+            println!("?? {path}");
+        }
+        return true;
+    }
+
+    match status.active_branch.as_str() {
+        "HEAD" => match repo.rev_parse("HEAD") {
+            Some(head) => println!("HEAD detached at {}", &head.to_string()[..7]),
+            None => println!("Not currently on any branch."),
+        },
+        branch => println!("On branch {branch}"),
+    }
+    if let Some(percentage) = status.sparse_percentage {
+        println!("You are in a sparse checkout with {percentage}% of tracked files present.\n");
+    }
+    if repo.rev_parse("HEAD").is_none() {
+        println!("\nNo commits yet\n");
+    }
+    let staged =
+        !status.added.is_empty() || !status.modified.is_empty() || !status.deleted.is_empty();
+    let unstaged = !status.unstaged_modified.is_empty() || !status.unstaged_deleted.is_empty();
+    if staged {
+        println!("Changes to be committed:");
+        let mut lines = BTreeMap::new();
+        for (paths, label) in [
+            (&status.added, "new file:"),
+            (&status.modified, "modified:"),
+            (&status.deleted, "deleted:"),
+        ] {
+            for path in paths {
+                lines.insert(path, label);
+            }
+        }
+        for (path, label) in lines {
+            println!("\t{label:<12}{path}");
+        }
+        println!();
+    }
+    if unstaged {
+        println!("Changes not staged for commit:");
+        let mut lines = BTreeMap::new();
+        for (paths, label) in [
+            (&status.unstaged_modified, "modified:"),
+            (&status.unstaged_deleted, "deleted:"),
+        ] {
+            for path in paths {
+                lines.insert(path, label);
+            }
+        }
+        for (path, label) in lines {
+            println!("\t{label:<12}{path}");
+        }
+        println!();
+    }
+    if !status.untracked.is_empty() {
+        println!("Untracked files:");
+        for path in &status.untracked {
+            println!("\t{path}");
+        }
+        println!();
+    }
+    if !staged {
+        if unstaged {
+            println!("no changes added to commit");
+        } else if !status.untracked.is_empty() {
+            println!("nothing added to commit but untracked files present");
+        } else {
+            println!("nothing to commit, working tree clean");
+        }
+    }
+    true
+}
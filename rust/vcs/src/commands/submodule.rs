@@ -0,0 +1,109 @@
+use vcs::git::object_id::ObjectId;
+use vcs::git::objects::GitObjectType;
+use vcs::git::repo::Repository;
+use vcs::git::submodule::Submodule;
+
+/// Print the recorded commit of each submodule, prefixed with `-` if it
+/// isn't checked out and `+` if a different commit is checked out.
+pub fn submodule_status(repo: &Repository, paths: &[String]) -> bool {
+    for status in repo.submodule_status() {
+        if !selected(&status.submodule, paths) {
+            continue;
+        }
+        let path = &status.submodule.path;
+        match status.checked_out {
+            None => println!("-{} {path}", status.recorded),
+            Some(head) => {
+                let (prefix, id) = if head == status.recorded {
+                    (' ', status.recorded)
+                } else {
+                    ('+', head)
+                };
+                let name = repo
+                    .submodule_repo(&status.submodule)
+                    .and_then(|sub| ref_name(&sub, head))
+                    .unwrap_or_else(|| head.to_string()[..7].to_string());
+                println!("{prefix}{id} {path} ({name})");
+            }
+        }
+    }
+    true
+}
+
+/// Register the urls of the submodules in the repository config.
+pub fn submodule_init(repo: &mut Repository, paths: &[String]) -> bool {
+    for submodule in repo.submodules() {
+        if !selected(&submodule, paths) {
+            continue;
+        }
+        if let Some(url) = repo.submodule_init(&submodule) {
+            eprintln!(
+                "Submodule '{}' ({url}) registered for path '{}'",
+                submodule.name, submodule.path
+            );
+        }
+    }
+    true
+}
+
+/// Clone missing submodules and check out their recorded commits,
+/// initializing them first with [init].
+pub fn submodule_update(repo: &mut Repository, init: bool, paths: &[String]) -> bool {
+    if init && !submodule_init(repo, paths) {
+        return false;
+    }
+    let mut success = true;
+    for submodule in repo.submodules() {
+        let section = format!("submodule \"{}\"", submodule.name);
+        if !selected(&submodule, paths) || repo.config_string(&section, "url").is_none() {
+            continue;
+        }
+        if repo.submodule_repo(&submodule).is_none() {
+            if let Some(work_tree) = repo.work_tree() {
+                let path = work_tree.join(&submodule.path);
+                eprintln!("Cloning into '{}'...", path.display());
+            }
+        }
+        match repo.submodule_update(&submodule) {
+            Ok(Some(id)) => println!("Submodule path '{}': checked out '{id}'", submodule.path),
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("{err}");
+                success = false;
+            }
+        }
+    }
+    success
+}
+
+/// Whether [submodule] is below one of [paths], all are if empty.
+fn selected(submodule: &Submodule, paths: &[String]) -> bool {
+    paths.is_empty()
+        || paths.iter().any(|path| {
+            let path = path.trim_end_matches('/');
+            path == "." || submodule.path == path || submodule.path.starts_with(&format!("{path}/"))
+        })
+}
+
+/// A ref pointing at [id] for display: a tag, preferring annotated ones,
+/// else a branch like `heads/master` or a remote branch.
+fn ref_name(repo: &Repository, id: ObjectId) -> Option<String> {
+    let refs = repo.refs();
+    let tags = refs
+        .iter()
+        .filter_map(|(name, target)| Some((name.strip_prefix("refs/tags/")?, target)))
+        .filter(|(_, target)| repo.object_peel(**target, GitObjectType::Commit) == Some(id))
+        .collect::<Vec<_>>();
+    let annotated = tags
+        .iter()
+        .find(|(_, target)| **target != id)
+        .or_else(|| tags.first());
+    if let Some((name, _)) = annotated {
+        return Some(name.to_string());
+    }
+    ["refs/heads/", "refs/remotes/"].iter().find_map(|prefix| {
+        refs.iter()
+            .find(|(name, target)| name.starts_with(prefix) && *target == id)
+            .map(|(name, _)| name["refs/".len()..].to_string())
+    })
+}
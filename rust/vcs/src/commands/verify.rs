@@ -0,0 +1,51 @@
+use vcs::git::objects::{GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+
+/// Check the signatures of [commits], reporting the result on stderr.
+pub fn verify_commit(repo: &Repository, commits: &[String]) -> bool {
+    let mut success = true;
+    for name in commits {
+        let payload = repo
+            .rev_parse(name)
+            .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+            .and_then(|id| match repo.object_read(id)? {
+                GitObject::Commit(commit) => Some(commit.signed_payload()),
+                _ => None,
+            });
+        success &= verify(repo, name, payload);
+    }
+    success
+}
+
+/// Check the signatures of [tags], reporting the result on stderr.
+pub fn verify_tag(repo: &Repository, tags: &[String]) -> bool {
+    let mut success = true;
+    for name in tags {
+        let payload = repo
+            .rev_parse(name)
+            .and_then(|id| match repo.object_read(id)? {
+                GitObject::Tag(tag) => Some(tag.signed_payload()),
+                _ => None,
+            });
+        success &= verify(repo, name, payload);
+    }
+    success
+}
+
+fn verify(repo: &Repository, name: &str, payload: Option<Option<(Vec<u8>, String)>>) -> bool {
+    match payload {
+        None => {
+            eprintln!("{name}: not a valid object of the expected type");
+            false
+        }
+        Some(None) => {
+            eprintln!("{name}: no signature found");
+            false
+        }
+        Some(Some((payload, signature))) => {
+            let verification = repo.verify_signature(&payload, &signature);
+            eprintln!("{verification}");
+            verification.is_good()
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+use vcs::git::objects::{GitObject, GitObjectType};
+use vcs::git::repo::Repository;
+
+/// Create a worktree at [path] the way `git worktree add` picks what to
+/// check out: a [new_branch] at [commit_ish], an existing branch named by
+/// [commit_ish], a detached [commit_ish] or, without [commit_ish], a branch
+/// named after the directory.
+pub fn worktree_add(
+    repo: &Repository,
+    path: &str,
+    commit_ish: Option<&str>,
+    new_branch: Option<&str>,
+    detach: bool,
+) -> bool {
+    let existing_branch = commit_ish
+        .filter(|_| new_branch.is_none() && !detach)
+        .filter(|name| repo.rev_parse(&format!("refs/heads/{name}")).is_some());
+    let dir_branch = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .filter(|_| commit_ish.is_none() && new_branch.is_none() && !detach);
+
+    let rev = commit_ish.unwrap_or("HEAD");
+    let Some(commit) = repo
+        .rev_parse(rev)
+        .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+    else {
+        eprintln!("invalid reference: {rev}");
+        return false;
+    };
+
+    let exists = |name: &str| repo.rev_parse(&format!("refs/heads/{name}")).is_some();
+    let branch = match (existing_branch, new_branch, dir_branch.as_deref()) {
+        (Some(name), _, _) => {
+            eprintln!("Preparing worktree (checking out '{name}')");
+            Some(name)
+        }
+        (_, Some(name), _) if exists(name) => {
+            eprintln!("a branch named '{name}' already exists");
+            return false;
+        }
+        (_, _, Some(name)) if exists(name) => {
+            eprintln!("Preparing worktree (checking out '{name}')");
+            Some(name)
+        }
+        (_, Some(name), _) | (_, _, Some(name)) => {
+            eprintln!("Preparing worktree (new branch '{name}')");
+            Some(name)
+        }
+        _ => {
+            let short = &commit.to_string()[..7];
+            eprintln!("Preparing worktree (detached HEAD {short})");
+            None
+        }
+    };
+    let branch = branch.map(|name| format!("refs/heads/{name}"));
+
+    let worktree = match repo.worktree_add(Path::new(path), commit, branch.as_deref()) {
+        Ok(worktree) => worktree,
+        Err(err) => {
+            eprintln!("{err}");
+            return false;
+        }
+    };
+    let head = worktree.head.unwrap_or(commit);
+    let subject = match repo.object_read(head) {
+        Some(GitObject::Commit(commit)) => commit.get_message().unwrap_or_default(),
+        _ => String::new(),
+    };
+    let subject = subject.lines().next().unwrap_or_default();
+    println!("HEAD is now at {} {subject}", &head.to_string()[..7]);
+    true
+}
+
+/// Print the worktrees with their checked out commit and branch.
+pub fn worktree_list(repo: &Repository, porcelain: bool) -> bool {
+    let worktrees = repo.worktrees();
+    let width = worktrees
+        .iter()
+        .map(|worktree| worktree.path.as_os_str().len())
+        .max()
+        .unwrap_or_default();
+    for worktree in worktrees {
+        let head = worktree
+            .head
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "0".repeat(repo.object_format().hex_len()));
+        if porcelain {
+            println!("worktree {}", worktree.path.display());
+            if worktree.bare {
+                println!("bare");
+            } else {
+                println!("HEAD {head}");
+                match &worktree.branch {
+                    Some(branch) => println!("branch {branch}"),
+                    None => println!("detached"),
+                }
+            }
+            println!();
+        } else {
+            let branch = match &worktree.branch {
+                Some(branch) => format!("[{}]", branch.trim_start_matches("refs/heads/")),
+                None => "(detached HEAD)".to_string(),
+            };
+            let path = worktree.path.display().to_string();
+            if worktree.bare {
+                println!("{path:<width$}  (bare)");
+            } else {
+                println!("{path:<width$}  {} {branch}", &head[..7]);
+            }
+        }
+    }
+    true
+}
+
+/// Remove the linked worktree [worktree] (path or name).
+pub fn worktree_remove(repo: &Repository, worktree: &str, force: bool) -> bool {
+    match repo.worktree_remove(worktree, force) {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("{err}");
+            false
+        }
+    }
+}
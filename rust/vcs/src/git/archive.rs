@@ -0,0 +1,496 @@
+use crate::git::attributes::{AttributeValue, Attributes};
+use crate::git::object_id::ObjectId;
+use crate::git::objects::{GitObject, GitObjectType};
+use crate::git::repo::Repository;
+use chrono::{DateTime, Datelike, Local, Timelike};
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::{Compression, Crc};
+use std::io;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Tar files are written in records of 20 blocks of 512 bytes, like git.
+const TAR_BLOCK: usize = 512;
+const TAR_RECORD: usize = 20 * TAR_BLOCK;
+const MODE_DIR: u32 = 0o040000;
+const MODE_SYMLINK: u32 = 0o120000;
+const MODE_GITLINK: u32 = 0o160000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ArchiveFormat {
+    Tar,
+    /// Tar compressed with gzip.
+    #[value(name = "tar.gz", alias = "tgz")]
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// The format implied by the extension of the file [name], like git.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ArchiveOptions {
+    pub format: ArchiveFormat,
+    /// Prepended to every path, a directory if it ends with `/`.
+    pub prefix: String,
+    /// Only archive these files or directories, everything if empty. Each
+    /// has to match at least one file.
+    pub paths: Vec<String>,
+}
+
+/// Receives the entries of an archive in tree order, directories end with
+/// `/`.
+trait ArchiveWriter {
+    fn add(&mut self, path: &str, mode: u32, id: ObjectId, data: &[u8]) -> io::Result<()>;
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+impl Repository {
+    /// Write the tree of [tree_ish] as archive to [out].
+    ///
+    /// Files get the mode from the tree and, for commits, the commit time as
+    /// modification time (else the current time). Their content is converted
+    /// like on checkout, files with the `export-ignore` attribute are left
+    /// out. Submodules are empty directories.
+    pub fn archive(
+        &self,
+        tree_ish: ObjectId,
+        options: &ArchiveOptions,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "not a tree-ish");
+        let tree = self
+            .object_peel(tree_ish, GitObjectType::Tree)
+            .ok_or_else(invalid)?;
+        let commit = self.object_peel(tree_ish, GitObjectType::Commit);
+        let commit_time = commit.and_then(|id| match self.object_read(id)? {
+            GitObject::Commit(commit) => Some(commit.get_commiter()?.time),
+            _ => None,
+        });
+        let time = commit_time.unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH);
+            now.map_or(0, |now| now.as_secs() as i64)
+        });
+        let entries = self.tree_flatten(tree).ok_or_else(invalid)?;
+        let unmatched = options
+            .paths
+            .iter()
+            .find(|spec| !entries.iter().any(|(path, _, _)| matches_path(spec, path)));
+        if let Some(spec) = unmatched {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("pathspec '{spec}' did not match any files"),
+            ));
+        }
+        let mut walk = ArchiveWalk {
+            repo: self,
+            attributes: self.checkout_attributes(&entries),
+            options,
+            pending: Vec::new(),
+        };
+        match options.format {
+            ArchiveFormat::Tar => walk.run(tree, &mut TarWriter::new(out, self, time, commit)?),
+            ArchiveFormat::TarGz => {
+                let mut out = GzEncoder::new(out, Compression::default());
+                walk.run(tree, &mut TarWriter::new(&mut out, self, time, commit)?)?;
+                out.try_finish()
+            }
+            ArchiveFormat::Zip => walk.run(tree, &mut ZipWriter::new(out, time, commit)),
+        }
+    }
+}
+
+struct ArchiveWalk<'a> {
+    repo: &'a Repository,
+    attributes: Attributes<'a>,
+    options: &'a ArchiveOptions,
+    /// With paths, directories are only written once something in them is.
+    pending: Vec<(String, ObjectId)>,
+}
+
+impl ArchiveWalk<'_> {
+    fn run(&mut self, tree: ObjectId, writer: &mut impl ArchiveWriter) -> io::Result<()> {
+        let prefix = &self.options.prefix;
+        if prefix.ends_with('/') {
+            let dir = format!("{}/", prefix.trim_end_matches('/'));
+            writer.add(&dir, MODE_DIR | 0o777, tree, &[])?;
+        }
+        self.visit(writer, tree, "")?;
+        writer.finish()
+    }
+
+    fn visit(
+        &mut self,
+        writer: &mut impl ArchiveWriter,
+        tree: ObjectId,
+        base: &str,
+    ) -> io::Result<()> {
+        let missing = |id| io::Error::new(io::ErrorKind::NotFound, format!("missing object {id}"));
+        let Some(GitObject::Tree(tree)) = self.repo.object_read(tree) else {
+            return Err(missing(tree));
+        };
+        for entry in tree.entries() {
+            let path = format!("{base}{}", entry.path());
+            let mode = u32::from_str_radix(&entry.mode_str(), 8).unwrap_or(0o100644);
+            let id = *entry.obj_hash();
+            let selected = self.selected(&path);
+            let is_tree = entry.obj_type() == GitObjectType::Tree;
+            if !(selected || is_tree && self.contains_selected(&path)) {
+                continue;
+            }
+            if self.attributes.get(&path, "export-ignore") == Some(AttributeValue::Set) {
+                continue;
+            }
+            if is_tree {
+                let dir = format!("{path}/");
+                let len = self.pending.len();
+                if self.options.paths.is_empty() {
+                    self.write(writer, &dir, mode, id, &[])?;
+                } else {
+                    self.pending.push((dir.clone(), id));
+                }
+                self.visit(writer, id, &dir)?;
+                self.pending.truncate(len);
+                continue;
+            }
+            for (dir, id) in std::mem::take(&mut self.pending) {
+                self.write(writer, &dir, MODE_DIR, id, &[])?;
+            }
+            match mode & 0o170000 {
+                MODE_GITLINK => self.write(writer, &format!("{path}/"), mode, id, &[])?,
+                MODE_SYMLINK => {
+                    let target = self.repo.blob_data(id).ok_or_else(|| missing(id))?;
+                    self.write(writer, &path, mode, id, &target)?
+                }
+                _ => {
+                    let data = self.repo.blob_data(id).ok_or_else(|| missing(id))?;
+                    let conversion = self.attributes.conversion(&path);
+                    let data = self
+                        .repo
+                        .convert_to_work_tree(&path, &conversion, data)
+                        .ok_or_else(|| io::Error::other(format!("{path}: filter failed")))?;
+                    self.write(writer, &path, mode, id, &data)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write(
+        &self,
+        writer: &mut impl ArchiveWriter,
+        path: &str,
+        mode: u32,
+        id: ObjectId,
+        data: &[u8],
+    ) -> io::Result<()> {
+        writer.add(&format!("{}{path}", self.options.prefix), mode, id, data)
+    }
+
+    /// Whether [path] is one of the paths or inside one.
+    fn selected(&self, path: &str) -> bool {
+        let paths = &self.options.paths;
+        paths.is_empty() || paths.iter().any(|spec| matches_path(spec, path))
+    }
+
+    /// Whether one of the paths is inside the directory [path].
+    fn contains_selected(&self, path: &str) -> bool {
+        let dir = format!("{path}/");
+        self.options.paths.iter().any(|spec| spec.starts_with(&dir))
+    }
+}
+
+/// Whether [path] is the file or directory [spec] or inside it.
+fn matches_path(spec: &str, path: &str) -> bool {
+    let spec = spec.trim_end_matches('/');
+    spec == "." || path == spec || path.starts_with(&format!("{spec}/"))
+}
+
+/// Writes ustar archives like `git archive`: a pax global header with the
+/// commit id, paths too long for the header in pax extended headers and
+/// permissions limited by `tar.umask` (default `002`).
+struct TarWriter<W: Write> {
+    out: W,
+    time: i64,
+    umask: u32,
+    written: usize,
+}
+
+impl<W: Write> TarWriter<W> {
+    fn new(out: W, repo: &Repository, time: i64, commit: Option<ObjectId>) -> io::Result<Self> {
+        let umask = repo
+            .config_string("tar", "umask")
+            .and_then(|umask| u32::from_str_radix(&umask, 8).ok())
+            .unwrap_or(0o002);
+        let mut writer = TarWriter {
+            out,
+            time,
+            umask,
+            written: 0,
+        };
+        if let Some(commit) = commit {
+            let record = pax_record("comment", commit.to_string().as_bytes());
+            let mut header = writer.header(0o100666, b'g', record.len());
+            header[..17].copy_from_slice(b"pax_global_header");
+            writer.write_entry(header, &record)?;
+        }
+        Ok(writer)
+    }
+
+    /// A header for an entry of [typeflag] and [size] without name.
+    fn header(&self, mode: u32, typeflag: u8, size: usize) -> [u8; TAR_BLOCK] {
+        let mut header = [0; TAR_BLOCK];
+        let mut field = |offset: usize, value: String| {
+            header[offset..offset + value.len()].copy_from_slice(value.as_bytes());
+        };
+        field(100, format!("{:07o}", mode & 0o7777));
+        field(108, format!("{:07o}", 0));
+        field(116, format!("{:07o}", 0));
+        let size = if mode & 0o170000 == 0o100000 { size } else { 0 };
+        field(124, format!("{size:011o}"));
+        field(136, format!("{:011o}", self.time));
+        field(265, "root".to_string());
+        field(297, "root".to_string());
+        field(329, format!("{:07o}", 0));
+        field(337, format!("{:07o}", 0));
+        header[156] = typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header
+    }
+
+    /// Write [header] with its checksum and [data] padded to full blocks.
+    fn write_entry(&mut self, mut header: [u8; TAR_BLOCK], data: &[u8]) -> io::Result<()> {
+        header[148..156].fill(b' ');
+        let checksum = header.iter().map(|&byte| byte as u32).sum::<u32>();
+        header[148..156].copy_from_slice(format!("{checksum:07o}\0").as_bytes());
+        self.write_padded(&header)?;
+        self.write_padded(data)
+    }
+
+    fn write_padded(&mut self, data: &[u8]) -> io::Result<()> {
+        self.out.write_all(data)?;
+        let padding = (TAR_BLOCK - data.len() % TAR_BLOCK) % TAR_BLOCK;
+        self.out.write_all(&[0; TAR_BLOCK][..padding])?;
+        self.written += data.len() + padding;
+        Ok(())
+    }
+}
+
+impl<W: Write> ArchiveWriter for TarWriter<W> {
+    fn add(&mut self, path: &str, mode: u32, id: ObjectId, data: &[u8]) -> io::Result<()> {
+        let (typeflag, mode) = match mode & 0o170000 {
+            MODE_DIR | MODE_GITLINK => (b'5', (MODE_DIR | 0o777) & !self.umask),
+            MODE_SYMLINK => (b'2', mode | 0o777),
+            _ if mode & 0o100 != 0 => (b'0', (mode | 0o777) & !self.umask),
+            _ => (b'0', (mode | 0o666) & !self.umask),
+        };
+        let mut header = self.header(mode, typeflag, data.len());
+        let mut extended = Vec::new();
+        let path = path.as_bytes();
+        if path.len() > 100 {
+            let prefix_len = path_prefix_len(path, 155);
+            let rest = path.len() - prefix_len - 1;
+            if prefix_len > 0 && rest <= 100 {
+                header[345..345 + prefix_len].copy_from_slice(&path[..prefix_len]);
+                header[..rest].copy_from_slice(&path[prefix_len + 1..]);
+            } else {
+                let name = format!("{id}.data");
+                header[..name.len()].copy_from_slice(name.as_bytes());
+                extended.extend(pax_record("path", path));
+            }
+        } else {
+            header[..path.len()].copy_from_slice(path);
+        }
+        if typeflag == b'2' {
+            if data.len() > 100 {
+                let link = format!("see {id}.paxheader");
+                header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+                extended.extend(pax_record("linkpath", data));
+            } else {
+                header[157..157 + data.len()].copy_from_slice(data);
+            }
+        }
+        if !extended.is_empty() {
+            let mut pax = self.header(0o100666, b'x', extended.len());
+            let name = format!("{id}.paxheader");
+            pax[..name.len()].copy_from_slice(name.as_bytes());
+            self.write_entry(pax, &extended)?;
+        }
+        let data = if typeflag == b'0' { data } else { &[] };
+        self.write_entry(header, data)
+    }
+
+    /// End with at least two zero blocks, filling the last record.
+    fn finish(&mut self) -> io::Result<()> {
+        let mut tail = TAR_RECORD - self.written % TAR_RECORD;
+        if tail < 2 * TAR_BLOCK {
+            tail += TAR_RECORD;
+        }
+        self.out.write_all(&vec![0; tail])?;
+        self.out.flush()
+    }
+}
+
+/// Length of the directory part of [path] that fits into [max_len] bytes,
+/// like git's `get_path_prefix`.
+fn path_prefix_len(path: &[u8], max_len: usize) -> usize {
+    let mut i = path.len();
+    if i > 1 && path[i - 1] == b'/' {
+        i -= 1;
+    }
+    i = i.min(max_len);
+    loop {
+        i -= 1;
+        if i == 0 || path[i] == b'/' {
+            return i;
+        }
+    }
+}
+
+/// A pax record `<len> <key>=<value>\n`, the length counting itself.
+fn pax_record(key: &str, value: &[u8]) -> Vec<u8> {
+    let mut len = key.len() + value.len() + 4;
+    let mut digits = 1;
+    while len / 10 >= digits {
+        len += 1;
+        digits *= 10;
+    }
+    let mut record = format!("{len} {key}=").into_bytes();
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
+
+/// Writes zip archives with the commit id as comment, deflating file
+/// content. Archives larger than 4 GiB (zip64) aren't supported.
+struct ZipWriter<W: Write> {
+    out: W,
+    /// Modification time as MS-DOS date and time, and unix time.
+    dos_date: u16,
+    dos_time: u16,
+    time: u32,
+    comment: String,
+    offset: u32,
+    central: Vec<u8>,
+    entries: u16,
+}
+
+impl<W: Write> ZipWriter<W> {
+    fn new(out: W, time: i64, commit: Option<ObjectId>) -> Self {
+        let local = DateTime::from_timestamp(time, 0)
+            .unwrap_or_default()
+            .with_timezone(&Local);
+        let year = (local.year().clamp(1980, 2107) - 1980) as u16;
+        let dos_date = year << 9 | (local.month() as u16) << 5 | local.day() as u16;
+        let dos_time = (local.hour() as u16) << 11
+            | (local.minute() as u16) << 5
+            | (local.second() as u16 / 2);
+        ZipWriter {
+            out,
+            dos_date,
+            dos_time,
+            time: time as u32,
+            comment: commit.map(|id| id.to_string()).unwrap_or_default(),
+            offset: 0,
+            central: Vec::new(),
+            entries: 0,
+        }
+    }
+}
+
+impl<W: Write> ArchiveWriter for ZipWriter<W> {
+    fn add(&mut self, path: &str, mode: u32, _id: ObjectId, data: &[u8]) -> io::Result<()> {
+        let too_large = || io::Error::other("archive too large for zip");
+        let (file_mode, dos_attributes) = match mode & 0o170000 {
+            MODE_DIR | MODE_GITLINK => (MODE_DIR | 0o755, 0x10),
+            MODE_SYMLINK => (MODE_SYMLINK | 0o777, 0),
+            _ if mode & 0o100 != 0 => (0o100755, 0),
+            _ => (0o100644, 0),
+        };
+        let mut crc = Crc::new();
+        crc.update(data);
+        let deflate = file_mode & 0o170000 == 0o100000 && !data.is_empty();
+        let compressed = if deflate {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()?
+        } else {
+            data.to_vec()
+        };
+        let (method, version): (u16, u16) = if deflate { (8, 20) } else { (0, 10) };
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let compressed_size = u32::try_from(compressed.len()).map_err(|_| too_large())?;
+        let name_len = u16::try_from(path.len()).map_err(|_| too_large())?;
+        // Extended timestamp with the modification time.
+        let mut extra = Vec::new();
+        extra.extend_from_slice(&0x5455u16.to_le_bytes());
+        extra.extend_from_slice(&5u16.to_le_bytes());
+        extra.push(1);
+        extra.extend_from_slice(&self.time.to_le_bytes());
+
+        let mut common = Vec::new();
+        common.extend_from_slice(&version.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&self.dos_time.to_le_bytes());
+        common.extend_from_slice(&self.dos_date.to_le_bytes());
+        common.extend_from_slice(&crc.sum().to_le_bytes());
+        common.extend_from_slice(&compressed_size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&name_len.to_le_bytes());
+        common.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+
+        let mut local = 0x04034b50u32.to_le_bytes().to_vec();
+        local.extend_from_slice(&common);
+        local.extend_from_slice(path.as_bytes());
+        local.extend_from_slice(&extra);
+        self.out.write_all(&local)?;
+        self.out.write_all(&compressed)?;
+
+        self.central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        // Made by unix, version 2.0.
+        self.central.extend_from_slice(&0x0314u16.to_le_bytes());
+        self.central.extend_from_slice(&common);
+        self.central.extend_from_slice(&0u16.to_le_bytes());
+        self.central.extend_from_slice(&0u16.to_le_bytes());
+        self.central.extend_from_slice(&0u16.to_le_bytes());
+        self.central
+            .extend_from_slice(&(file_mode << 16 | dos_attributes).to_le_bytes());
+        self.central.extend_from_slice(&self.offset.to_le_bytes());
+        self.central.extend_from_slice(path.as_bytes());
+        self.central.extend_from_slice(&extra);
+
+        let len = u32::try_from(local.len() + compressed.len()).map_err(|_| too_large())?;
+        self.offset = self.offset.checked_add(len).ok_or_else(too_large)?;
+        self.entries = self.entries.checked_add(1).ok_or_else(too_large)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.write_all(&self.central)?;
+        let mut end = 0x06054b50u32.to_le_bytes().to_vec();
+        end.extend_from_slice(&0u16.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        end.extend_from_slice(&self.entries.to_le_bytes());
+        end.extend_from_slice(&self.entries.to_le_bytes());
+        end.extend_from_slice(&(self.central.len() as u32).to_le_bytes());
+        end.extend_from_slice(&self.offset.to_le_bytes());
+        end.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        end.extend_from_slice(self.comment.as_bytes());
+        self.out.write_all(&end)?;
+        self.out.flush()
+    }
+}
@@ -0,0 +1,517 @@
+use crate::git::filter::{FilterDirection, FilterDriver};
+use crate::git::object_id::ObjectId;
+use crate::git::repo::{expand_home, Repository};
+use globset::{GlobBuilder, GlobMatcher};
+use std::collections::HashMap;
+use std::fs;
+
+/// How many bytes diff looks at to decide whether content is binary, like
+/// git.
+const BINARY_CHECK_LEN: usize = 8000;
+
+/// State of an attribute set for a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AttributeValue {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+}
+
+/// A line of an attributes file.
+struct Rule {
+    glob: GlobMatcher,
+    /// Patterns without `/` match the file name at any depth.
+    basename: bool,
+    /// [None] for `!attr`, which makes the attribute unspecified again.
+    attributes: Vec<(String, Option<AttributeValue>)>,
+}
+
+/// Decides the attributes of paths, following `.gitattributes` files,
+/// `info/attributes` and `core.attributesFile`.
+///
+/// `.gitattributes` files are loaded lazily per directory, deeper files take
+/// precedence over higher ones and later lines over earlier ones. Only the
+/// built-in `binary` macro is supported, `[attr]` definitions are ignored.
+pub struct Attributes<'r> {
+    repo: &'r Repository,
+    /// Stored `.gitattributes` blobs by directory (`""` for the root).
+    blobs: HashMap<String, ObjectId>,
+    /// Read the work tree file before the stored one, when adding files.
+    /// Checking out prefers the stored one.
+    prefer_work_tree: bool,
+    dirs: HashMap<String, Vec<Rule>>,
+    /// `info/attributes`, highest priority.
+    info: Vec<Rule>,
+    /// `core.attributesFile`, lowest priority.
+    global: Vec<Rule>,
+    auto_crlf: AutoCrlf,
+    eol_crlf: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AutoCrlf {
+    False,
+    True,
+    Input,
+}
+
+/// How line endings of a file are converted between the repository (always
+/// LF) and the work tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEndings {
+    /// No conversion.
+    Binary,
+    /// Always text, with CRLF in the work tree if [crlf].
+    Text { crlf: bool },
+    /// Text unless the content looks binary (`text=auto`, `core.autocrlf`).
+    Auto { crlf: bool },
+}
+
+/// How the content of a file is converted between the repository and the
+/// work tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conversion {
+    /// Driver of the `filter` attribute, if it is configured.
+    pub filter: Option<FilterDriver>,
+    pub line_endings: LineEndings,
+}
+
+impl<'r> Attributes<'r> {
+    fn new(repo: &'r Repository, blobs: HashMap<String, ObjectId>, prefer_work_tree: bool) -> Self {
+        let info = fs::read(repo.common_dir().join("info").join("attributes"))
+            .map(|data| parse(&data))
+            .unwrap_or_default();
+        let global = repo
+            .config_string("core", "attributesFile")
+            .and_then(|path| fs::read(expand_home(&path)).ok())
+            .map(|data| parse(&data))
+            .unwrap_or_default();
+        let auto_crlf = match repo.config_string("core", "autocrlf") {
+            Some(value) if value.eq_ignore_ascii_case("input") => AutoCrlf::Input,
+            Some(value) if is_true(&value) => AutoCrlf::True,
+            _ => AutoCrlf::False,
+        };
+        let eol_crlf = match auto_crlf {
+            AutoCrlf::True => true,
+            AutoCrlf::Input => false,
+            AutoCrlf::False => repo
+                .config_string("core", "eol")
+                .is_some_and(|eol| eol.eq_ignore_ascii_case("crlf")),
+        };
+        Attributes {
+            repo,
+            blobs,
+            prefer_work_tree,
+            dirs: HashMap::new(),
+            info,
+            global,
+            auto_crlf,
+            eol_crlf,
+        }
+    }
+
+    /// The state of the attribute [name] for [path] (relative to the work
+    /// tree, `/` separated), [None] if unspecified.
+    pub fn get(&mut self, path: &str, name: &str) -> Option<AttributeValue> {
+        if let Some(value) = find(&self.info, "", path, name) {
+            return value;
+        }
+        let mut dir = path;
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            dir = parent;
+            if let Some(value) = find(self.dir_rules(dir), dir, path, name) {
+                return value;
+            }
+        }
+        if let Some(value) = find(self.dir_rules(""), "", path, name) {
+            return value;
+        }
+        find(&self.global, "", path, name).flatten()
+    }
+
+    /// Rules of the `.gitattributes` file in [dir].
+    fn dir_rules(&mut self, dir: &str) -> &[Rule] {
+        let repo = self.repo;
+        let blob = self.blobs.get(dir).copied();
+        let prefer_work_tree = self.prefer_work_tree;
+        self.dirs.entry(dir.to_string()).or_insert_with(|| {
+            let work_tree = || {
+                let path = repo.work_tree()?.join(dir).join(".gitattributes");
+                fs::read(path).ok()
+            };
+            let stored = || repo.blob_data(blob?);
+            let data = if prefer_work_tree {
+                work_tree().or_else(stored)
+            } else {
+                stored().or_else(work_tree)
+            };
+            data.map(|data| parse(&data)).unwrap_or_default()
+        })
+    }
+
+    /// Line ending conversion of [path] from its `text` and `eol` attributes
+    /// and `core.autocrlf`.
+    pub fn line_endings(&mut self, path: &str) -> LineEndings {
+        #[derive(PartialEq)]
+        enum Text {
+            Unspecified,
+            Set,
+            Auto,
+            Binary,
+        }
+        let text = |value| match value {
+            Some(AttributeValue::Set) => Text::Set,
+            Some(AttributeValue::Unset) => Text::Binary,
+            Some(AttributeValue::Value(value)) if value == "auto" => Text::Auto,
+            // The old name of `text`, `crlf=input` is `eol=lf`.
+            Some(AttributeValue::Value(value)) if value == "input" => Text::Set,
+            _ => Text::Unspecified,
+        };
+        let mut action = text(self.get(path, "text"));
+        if action == Text::Unspecified {
+            action = text(self.get(path, "crlf"));
+        }
+        if action == Text::Binary {
+            return LineEndings::Binary;
+        }
+        let eol = match self.get(path, "eol") {
+            Some(AttributeValue::Value(eol)) if eol == "crlf" => Some(true),
+            Some(AttributeValue::Value(eol)) if eol == "lf" => Some(false),
+            _ => None,
+        };
+        let eol = eol.or_else(|| {
+            let input = self.get(path, "crlf") == Some(AttributeValue::Value("input".to_string()));
+            input.then_some(false)
+        });
+        match (action, eol) {
+            (Text::Auto, eol) => LineEndings::Auto {
+                crlf: eol.unwrap_or(self.eol_crlf),
+            },
+            (Text::Set, eol) => LineEndings::Text {
+                crlf: eol.unwrap_or(self.eol_crlf),
+            },
+            // `eol` alone makes a file text.
+            (_, Some(crlf)) => LineEndings::Text { crlf },
+            _ => match self.auto_crlf {
+                AutoCrlf::False => LineEndings::Binary,
+                AutoCrlf::True => LineEndings::Auto { crlf: true },
+                AutoCrlf::Input => LineEndings::Auto { crlf: false },
+            },
+        }
+    }
+
+    /// Filter and line ending conversion of [path].
+    pub fn conversion(&mut self, path: &str) -> Conversion {
+        let filter = match self.get(path, "filter") {
+            Some(AttributeValue::Value(name)) => self.repo.filter_driver(&name),
+            _ => None,
+        };
+        Conversion {
+            filter,
+            line_endings: self.line_endings(path),
+        }
+    }
+
+    /// Whether diff treats [path] with [data] as binary: `-diff` (or
+    /// `binary`) makes it binary, `diff` text, otherwise a NUL byte in the
+    /// first 8000 bytes does.
+    pub fn is_binary(&mut self, path: &str, data: &[u8]) -> bool {
+        match self.get(path, "diff") {
+            Some(AttributeValue::Unset) => true,
+            Some(AttributeValue::Set) => false,
+            _ => buffer_is_binary(data),
+        }
+    }
+}
+
+/// Whether [data] has a NUL byte in the first 8000 bytes.
+pub(crate) fn buffer_is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
+
+/// Counts of line endings and characters, like git's `text_stat`.
+#[derive(Default)]
+struct Stats {
+    nul: usize,
+    lone_cr: usize,
+    lone_lf: usize,
+    crlf: usize,
+    printable: usize,
+    nonprintable: usize,
+}
+
+impl Stats {
+    fn new(data: &[u8]) -> Self {
+        let mut stats = Stats::default();
+        let mut i = 0;
+        while i < data.len() {
+            match data[i] {
+                b'\r' if data.get(i + 1) == Some(&b'\n') => {
+                    stats.crlf += 1;
+                    i += 1;
+                }
+                b'\r' => stats.lone_cr += 1,
+                b'\n' => stats.lone_lf += 1,
+                127 => stats.nonprintable += 1,
+                // Backspace, tab, escape and form feed.
+                8 | 9 | 27 | 12 => stats.printable += 1,
+                0 => {
+                    stats.nul += 1;
+                    stats.nonprintable += 1;
+                }
+                c if c < 32 => stats.nonprintable += 1,
+                _ => stats.printable += 1,
+            }
+            i += 1;
+        }
+        // A trailing DOS end of file marker doesn't count.
+        if data.last() == Some(&0x1a) {
+            stats.nonprintable -= 1;
+        }
+        stats
+    }
+
+    fn is_binary(&self) -> bool {
+        self.lone_cr > 0 || self.nul > 0 || (self.printable >> 7) < self.nonprintable
+    }
+}
+
+impl LineEndings {
+    /// Convert the work tree content [data] for storing: CRLF becomes LF.
+    ///
+    /// With [LineEndings::Auto] binary looking content is kept, so is text
+    /// whose stored version [indexed] already has CRLF, to not change files
+    /// committed before the attributes were.
+    pub fn to_git(self, data: Vec<u8>, indexed: impl FnOnce() -> Option<Vec<u8>>) -> Vec<u8> {
+        if self == LineEndings::Binary || data.is_empty() {
+            return data;
+        }
+        let stats = Stats::new(&data);
+        if let LineEndings::Auto { .. } = self {
+            let indexed_crlf = || {
+                indexed().is_some_and(|indexed| {
+                    let stats = Stats::new(&indexed);
+                    stats.crlf > 0 && !stats.is_binary()
+                })
+            };
+            if stats.is_binary() || (stats.crlf > 0 && indexed_crlf()) {
+                return data;
+            }
+        }
+        if stats.crlf == 0 {
+            return data;
+        }
+        let mut out = Vec::with_capacity(data.len() - stats.crlf);
+        for (i, &c) in data.iter().enumerate() {
+            if c != b'\r' || data.get(i + 1) != Some(&b'\n') {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    /// Convert the stored content [data] for the work tree: LF becomes CRLF
+    /// if configured.
+    ///
+    /// With [LineEndings::Auto] binary looking content and content that
+    /// already has CRs is kept.
+    pub fn to_work_tree(self, data: Vec<u8>) -> Vec<u8> {
+        let auto = match self {
+            LineEndings::Text { crlf: true } => false,
+            LineEndings::Auto { crlf: true } => true,
+            _ => return data,
+        };
+        let stats = Stats::new(&data);
+        if stats.lone_lf == 0
+            || (auto && (stats.lone_cr > 0 || stats.crlf > 0 || stats.is_binary()))
+        {
+            return data;
+        }
+        let mut out = Vec::with_capacity(data.len() + stats.lone_lf);
+        for (i, &c) in data.iter().enumerate() {
+            if c == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+                out.push(b'\r');
+            }
+            out.push(c);
+        }
+        out
+    }
+}
+
+impl Repository {
+    /// Convert the work tree content [data] of [path] for storing: run the
+    /// clean filter, then normalize line endings (see [LineEndings::to_git]
+    /// for [indexed]). [None] if a required filter fails.
+    pub fn convert_to_git(
+        &self,
+        path: &str,
+        conversion: &Conversion,
+        data: Vec<u8>,
+        indexed: Option<ObjectId>,
+    ) -> Option<Vec<u8>> {
+        let data = match &conversion.filter {
+            Some(driver) => self.filter(driver, FilterDirection::Clean, path, data)?,
+            None => data,
+        };
+        Some(
+            conversion
+                .line_endings
+                .to_git(data, || self.blob_data(indexed?)),
+        )
+    }
+
+    /// Convert the stored content [data] of [path] for the work tree:
+    /// convert line endings, then run the smudge filter. [None] if a
+    /// required filter fails.
+    pub fn convert_to_work_tree(
+        &self,
+        path: &str,
+        conversion: &Conversion,
+        data: Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        let data = conversion.line_endings.to_work_tree(data);
+        match &conversion.filter {
+            Some(driver) => self.filter(driver, FilterDirection::Smudge, path, data),
+            None => Some(data),
+        }
+    }
+
+    /// Attributes for adding work tree files, `.gitattributes` are read from
+    /// the work tree and else the index.
+    pub fn attributes(&self) -> Attributes<'_> {
+        let blobs = self
+            .index()
+            .map(|index| {
+                let files = index
+                    .iter()
+                    .filter(|entry| entry.stage() == 0)
+                    .map(|entry| (entry.name().to_string_lossy().to_string(), *entry.hash()));
+                attribute_blobs(files)
+            })
+            .unwrap_or_default();
+        Attributes::new(self, blobs, true)
+    }
+
+    /// Attributes for checking out the tree with the flattened [entries],
+    /// `.gitattributes` are read from [entries] and else the work tree.
+    pub(crate) fn checkout_attributes(
+        &self,
+        entries: &[(String, String, ObjectId)],
+    ) -> Attributes<'_> {
+        let files = entries.iter().map(|(path, _, id)| (path.clone(), *id));
+        Attributes::new(self, attribute_blobs(files), false)
+    }
+}
+
+/// `.gitattributes` blobs of [files] by directory.
+fn attribute_blobs(files: impl Iterator<Item = (String, ObjectId)>) -> HashMap<String, ObjectId> {
+    files
+        .filter_map(|(path, id)| match path.rsplit_once('/') {
+            Some((dir, ".gitattributes")) => Some((dir.to_string(), id)),
+            None if path == ".gitattributes" => Some((String::new(), id)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The state [rules] of the attributes file in [dir] give [name] for
+/// [path], [None] if no rule mentions it.
+fn find(rules: &[Rule], dir: &str, path: &str, name: &str) -> Option<Option<AttributeValue>> {
+    let relative = match dir {
+        "" => path,
+        dir => path.strip_prefix(dir)?.strip_prefix('/')?,
+    };
+    let file_name = relative.rsplit('/').next().unwrap_or(relative);
+    rules.iter().rev().find_map(|rule| {
+        let candidate = if rule.basename { file_name } else { relative };
+        if !rule.glob.is_match(candidate) {
+            return None;
+        }
+        rule.attributes
+            .iter()
+            .rev()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.clone())
+    })
+}
+
+/// Parse the lines `<pattern> <attr>...` of an attributes file.
+fn parse(data: &[u8]) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    for line in String::from_utf8_lossy(data).lines() {
+        let mut words = line.split_whitespace();
+        let Some(pattern) = words.next() else {
+            continue;
+        };
+        // Negative patterns are forbidden, directories never match.
+        if pattern.starts_with('#')
+            || pattern.starts_with("[attr]")
+            || pattern.starts_with('!')
+            || pattern.ends_with('/')
+        {
+            continue;
+        }
+        let mut attributes = Vec::new();
+        for word in words {
+            let (name, value) = if let Some(name) = word.strip_prefix('-') {
+                (name, Some(AttributeValue::Unset))
+            } else if let Some(name) = word.strip_prefix('!') {
+                (name, None)
+            } else if let Some((name, value)) = word.split_once('=') {
+                (name, Some(AttributeValue::Value(value.to_string())))
+            } else {
+                (word, Some(AttributeValue::Set))
+            };
+            if name == "binary" && value == Some(AttributeValue::Set) {
+                for macro_name in ["diff", "merge", "text"] {
+                    attributes.push((macro_name.to_string(), Some(AttributeValue::Unset)));
+                }
+            }
+            attributes.push((name.to_string(), value));
+        }
+        let basename = !pattern.contains('/');
+        let glob = GlobBuilder::new(pattern.trim_start_matches('/'))
+            .literal_separator(true)
+            .backslash_escape(true)
+            .build();
+        if let Ok(glob) = glob {
+            rules.push(Rule {
+                glob: glob.compile_matcher(),
+                basename,
+                attributes,
+            });
+        }
+    }
+    rules
+}
+
+fn is_true(value: &str) -> bool {
+    ["true", "yes", "on", "1"].contains(&value.to_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_line_endings() {
+        let text = LineEndings::Text { crlf: true };
+        assert_eq!(text.to_git(b"a\r\nb\rc\n".to_vec(), || None), b"a\nb\rc\n");
+        assert_eq!(text.to_work_tree(b"a\nb\r\n".to_vec()), b"a\r\nb\r\n");
+
+        let auto = LineEndings::Auto { crlf: true };
+        assert_eq!(auto.to_git(b"a\r\n\0".to_vec(), || None), b"a\r\n\0");
+        assert_eq!(
+            auto.to_git(b"a\r\n".to_vec(), || Some(b"b\r\n".to_vec())),
+            b"a\r\n"
+        );
+        assert_eq!(auto.to_work_tree(b"a\nb\r\n".to_vec()), b"a\nb\r\n");
+        assert_eq!(
+            LineEndings::Binary.to_git(b"a\r\n".to_vec(), || None),
+            b"a\r\n"
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use crate::git::checkout::CheckoutError;
+use crate::git::object_id::ObjectId;
+use crate::git::objects::{GitObject, GitObjectType};
+use crate::git::repo::Repository;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::Write;
+
+/// Files in the git dir holding the state of a bisection.
+const BISECT_FILES: [&str; 6] = [
+    "BISECT_START",
+    "BISECT_LOG",
+    "BISECT_TERMS",
+    "BISECT_NAMES",
+    "BISECT_EXPECTED_REV",
+    "BISECT_ANCESTORS_OK",
+];
+
+/// How a tested commit behaved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BisectTerm {
+    /// Before the change that is searched for.
+    Good,
+    /// Has the change.
+    Bad,
+    /// Can't be tested, bisection avoids it.
+    Skip,
+}
+
+impl BisectTerm {
+    pub fn name(self) -> &'static str {
+        match self {
+            BisectTerm::Good => "good",
+            BisectTerm::Bad => "bad",
+            BisectTerm::Skip => "skip",
+        }
+    }
+}
+
+/// Where a bisection stands after a step.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BisectStep {
+    /// A bad and at least one good commit are needed to go on.
+    Waiting { bad: bool, good: usize },
+    /// [commit] is checked out for testing. [remaining] commits are left
+    /// to test after it, which takes about [steps] steps.
+    Testing {
+        commit: ObjectId,
+        remaining: usize,
+        steps: usize,
+    },
+    /// The first bad commit.
+    Found(ObjectId),
+    /// Only skipped commits are left to test, the first bad commit is one
+    /// of these.
+    OnlySkipped(Vec<ObjectId>),
+}
+
+#[derive(Debug)]
+pub enum BisectError {
+    /// No bisection was started.
+    NotBisecting,
+    /// The revision doesn't name a commit.
+    InvalidRevision(String),
+    /// No commit between the good ones and the bad one.
+    BadIsGood(ObjectId),
+    /// Checking out the next commit to test failed.
+    Checkout(CheckoutError),
+    IOError,
+}
+
+impl Display for BisectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BisectError::NotBisecting => write!(f, "We are not bisecting."),
+            BisectError::InvalidRevision(rev) => write!(f, "Bad rev input: {rev}"),
+            BisectError::BadIsGood(bad) => write!(
+                f,
+                "The bad commit {bad} is reachable from a good commit, nothing to bisect"
+            ),
+            BisectError::Checkout(err) => write!(f, "{err}"),
+            BisectError::IOError => write!(f, "failed to write the bisect state"),
+        }
+    }
+}
+
+impl Repository {
+    pub fn is_bisecting(&self) -> bool {
+        self.git_path("BISECT_START").is_file()
+    }
+
+    /// Start a new bisection, ending a running one. [revs] are the bad
+    /// commit followed by good ones, like git's `bisect start`.
+    ///
+    /// Returns the first commit to test once bad and good commits are known.
+    pub fn bisect_start(&self, revs: &[String]) -> Result<BisectStep, BisectError> {
+        let commits = revs
+            .iter()
+            .map(|rev| {
+                self.rev_parse(rev)
+                    .and_then(|id| self.object_peel(id, GitObjectType::Commit))
+                    .ok_or_else(|| BisectError::InvalidRevision(rev.to_string()))
+            })
+            .collect::<Result<Vec<ObjectId>, BisectError>>()?;
+        // A restarted bisection returns to where the first one started.
+        let start = match fs::read_to_string(self.git_path("BISECT_START")) {
+            Ok(start) => start.trim().to_string(),
+            Err(_) => match self.head_ref() {
+                Some(branch) => branch.trim_start_matches("refs/heads/").to_string(),
+                None => self.head().ok_or(BisectError::IOError)?.to_string(),
+            },
+        };
+        self.bisect_clean_state()?;
+        let write = |name: &str, data: &str| fs::write(self.git_path(name), data);
+        write("BISECT_START", &format!("{start}\n"))
+            .and_then(|()| write("BISECT_TERMS", "bad\ngood\n"))
+            .and_then(|()| write("BISECT_NAMES", "\n"))
+            .and_then(|()| write("BISECT_LOG", ""))
+            .map_err(|_| BisectError::IOError)?;
+
+        for (i, commit) in commits.into_iter().enumerate() {
+            let term = if i == 0 {
+                BisectTerm::Bad
+            } else {
+                BisectTerm::Good
+            };
+            self.bisect_write(term, commit, false)?;
+        }
+        let args = revs
+            .iter()
+            .map(|rev| format!(" '{}'", rev.replace('\'', "'\\''")))
+            .collect::<String>();
+        self.bisect_log_append(&format!("git bisect start{args}\n"))?;
+        self.bisect_next()
+    }
+
+    /// Record [commit] as [term]. Call [Repository::bisect_next] after
+    /// marking commits to get the next one to test.
+    pub fn bisect_mark(&self, term: BisectTerm, commit: ObjectId) -> Result<(), BisectError> {
+        if !self.is_bisecting() {
+            return Err(BisectError::NotBisecting);
+        }
+        self.bisect_write(term, commit, true)
+    }
+
+    /// Check out the commit that best halves the commits between the good
+    /// ones and the bad one, like git.
+    ///
+    /// The commit picked is the first (oldest first) with the most
+    /// candidates on both sides, or the first exactly halfway. Skipped
+    /// commits are avoided by picking another commit at a pseudo-random
+    /// distance from the best one.
+    pub fn bisect_next(&self) -> Result<BisectStep, BisectError> {
+        if !self.is_bisecting() {
+            return Err(BisectError::NotBisecting);
+        }
+        let mut bad = None;
+        let mut good = Vec::new();
+        let mut skipped = HashSet::new();
+        let refs = fs::read_dir(self.git_path("refs/bisect"))
+            .into_iter()
+            .flatten()
+            .flatten();
+        for entry in refs {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(id) = self.ref_resolve(&format!("refs/bisect/{name}")) else {
+                continue;
+            };
+            if name == "bad" {
+                bad = Some(id);
+            } else if name.starts_with("good-") {
+                good.push(id);
+            } else if name.starts_with("skip-") {
+                skipped.insert(id);
+            }
+        }
+        let Some(bad) = bad.filter(|_| !good.is_empty()) else {
+            return Ok(BisectStep::Waiting {
+                bad: bad.is_some(),
+                good: good.len(),
+            });
+        };
+
+        // Oldest first, with the parents among the candidates.
+        let mut list = self.walk_commits(vec![bad]).hide(good).collect::<Vec<_>>();
+        if list.is_empty() {
+            return Err(BisectError::BadIsGood(bad));
+        }
+        list.reverse();
+        let index = list
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect::<HashMap<ObjectId, usize>>();
+        let parents = list
+            .iter()
+            .map(|id| {
+                let parents = self.commit_info(id).map(|info| info.parents);
+                let parents = parents.unwrap_or_default().into_iter();
+                parents
+                    .filter_map(|parent| index.get(&parent).copied())
+                    .collect()
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        let all = list.len();
+        let (best, reaches) = if skipped.is_empty() {
+            let (best, weights) = find_bisection(&parents, false);
+            (list[best], weights[best])
+        } else {
+            let (_, weights) = find_bisection(&parents, true);
+            let distance = |i: usize| weights[i].min(all - weights[i]);
+            let mut sorted = (0..all).collect::<Vec<usize>>();
+            sorted.sort_by(|&a, &b| distance(b).cmp(&distance(a)).then(list[a].cmp(&list[b])));
+            let sorted = sorted.into_iter().map(|i| list[i]).collect::<Vec<_>>();
+            let (tried, rest): (Vec<ObjectId>, Vec<ObjectId>) =
+                sorted.iter().partition(|id| skipped.contains(id));
+            if rest.is_empty() {
+                return self.bisect_only_skipped(tried);
+            }
+            let best = if !skipped.contains(&sorted[0]) {
+                sorted[0]
+            } else {
+                let best = skip_away(&rest, bad);
+                if best == bad {
+                    return self.bisect_only_skipped(tried.into_iter().chain([bad]).collect());
+                }
+                best
+            };
+            (best, weights[index[&best]])
+        };
+        if best == bad {
+            let subject = self.commit_subject(bad);
+            self.bisect_log_append(&format!("# first bad commit: [{bad}] {subject}\n"))?;
+            return Ok(BisectStep::Found(bad));
+        }
+
+        self.checkout(&best.to_string())
+            .map_err(BisectError::Checkout)?;
+        Ok(BisectStep::Testing {
+            commit: best,
+            remaining: all - reaches - 1,
+            steps: estimate_bisect_steps(all),
+        })
+    }
+
+    /// End the bisection, checking out [commit] or else the branch or
+    /// commit it started from.
+    pub fn bisect_reset(&self, commit: Option<&str>) -> Result<(), BisectError> {
+        let start = fs::read_to_string(self.git_path("BISECT_START"))
+            .map_err(|_| BisectError::NotBisecting)?;
+        let target = commit.unwrap_or(start.trim());
+        self.checkout(target).map_err(BisectError::Checkout)?;
+        self.bisect_clean_state()
+    }
+
+    /// What happened in the bisection so far, as commands replaying it.
+    pub fn bisect_log(&self) -> Result<String, BisectError> {
+        if !self.is_bisecting() {
+            return Err(BisectError::NotBisecting);
+        }
+        fs::read_to_string(self.git_path("BISECT_LOG")).map_err(|_| BisectError::IOError)
+    }
+
+    fn bisect_only_skipped(&self, commits: Vec<ObjectId>) -> Result<BisectStep, BisectError> {
+        let mut log = "# only skipped commits left to test\n".to_string();
+        for commit in &commits {
+            let subject = self.commit_subject(*commit);
+            log.push_str(&format!(
+                "# possible first bad commit: [{commit}] {subject}\n"
+            ));
+        }
+        self.bisect_log_append(&log)?;
+        Ok(BisectStep::OnlySkipped(commits))
+    }
+
+    /// Store [term] for [commit] as ref and log it, with [command] also as
+    /// the command replaying it.
+    fn bisect_write(
+        &self,
+        term: BisectTerm,
+        commit: ObjectId,
+        command: bool,
+    ) -> Result<(), BisectError> {
+        let name = match term {
+            BisectTerm::Bad => "refs/bisect/bad".to_string(),
+            _ => format!("refs/bisect/{}-{commit}", term.name()),
+        };
+        self.ref_update(&name, commit).ok_or(BisectError::IOError)?;
+        let term = term.name();
+        let subject = self.commit_subject(commit);
+        let mut log = format!("# {term}: [{commit}] {subject}\n");
+        if command {
+            log.push_str(&format!("git bisect {term} {commit}\n"));
+        }
+        self.bisect_log_append(&log)
+    }
+
+    fn bisect_log_append(&self, text: &str) -> Result<(), BisectError> {
+        fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(self.git_path("BISECT_LOG"))
+            .and_then(|mut log| log.write_all(text.as_bytes()))
+            .map_err(|_| BisectError::IOError)
+    }
+
+    fn bisect_clean_state(&self) -> Result<(), BisectError> {
+        for name in BISECT_FILES {
+            let _ = fs::remove_file(self.git_path(name));
+        }
+        match fs::remove_dir_all(self.git_path("refs/bisect")) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(BisectError::IOError),
+            _ => Ok(()),
+        }
+    }
+
+    fn commit_subject(&self, id: ObjectId) -> String {
+        match self.object_read(id) {
+            Some(GitObject::Commit(commit)) => {
+                let message = commit.get_message().unwrap_or_default();
+                message.lines().next().unwrap_or_default().to_string()
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+/// Number of candidates reachable from each of the candidates with
+/// [parents] (oldest first) including itself, and the best one to test.
+///
+/// Follows git's `do_find_bisection`: merges are counted by walking their
+/// history, other commits add one to their parent, and without [find_all]
+/// the first commit found exactly halfway is returned right away.
+fn find_bisection(parents: &[Vec<usize>], find_all: bool) -> (usize, Vec<usize>) {
+    let nr = parents.len();
+    let halfway = |weight: usize| (2 * weight).abs_diff(nr) <= 1;
+    let mut weights = parents
+        .iter()
+        .map(|parents| parents.is_empty().then_some(1))
+        .collect::<Vec<Option<usize>>>();
+    let mut counted = weights.iter().filter(|weight| weight.is_some()).count();
+    for i in 0..nr {
+        if parents[i].len() < 2 {
+            continue;
+        }
+        let weight = count_distance(parents, i);
+        weights[i] = Some(weight);
+        if !find_all && halfway(weight) {
+            return (
+                i,
+                weights.into_iter().map(Option::unwrap_or_default).collect(),
+            );
+        }
+        counted += 1;
+    }
+    while counted < nr {
+        for i in 0..nr {
+            if weights[i].is_some() {
+                continue;
+            }
+            let Some(weight) = parents[i].iter().find_map(|&parent| weights[parent]) else {
+                continue;
+            };
+            weights[i] = Some(weight + 1);
+            counted += 1;
+            if !find_all && halfway(weight + 1) {
+                return (
+                    i,
+                    weights.into_iter().map(Option::unwrap_or_default).collect(),
+                );
+            }
+        }
+    }
+
+    let weights = weights
+        .into_iter()
+        .map(Option::unwrap_or_default)
+        .collect::<Vec<usize>>();
+    let mut best = 0;
+    let mut best_distance = None;
+    for (i, weight) in weights.iter().enumerate() {
+        let distance = (*weight).min(nr - weight);
+        if best_distance.is_none_or(|best| distance > best) {
+            best = i;
+            best_distance = Some(distance);
+        }
+    }
+    (best, weights)
+}
+
+/// Number of candidates reachable from [start] including itself.
+fn count_distance(parents: &[Vec<usize>], start: usize) -> usize {
+    let mut seen = HashSet::new();
+    let mut stack = vec![start];
+    while let Some(i) = stack.pop() {
+        if seen.insert(i) {
+            stack.extend(&parents[i]);
+        }
+    }
+    seen.len()
+}
+
+/// Pick a commit from [list] (best first, without skipped commits) at a
+/// pseudo-random distance from the best one, like git's `skip_away`.
+fn skip_away(list: &[ObjectId], bad: ObjectId) -> ObjectId {
+    const PRN_MODULO: u32 = 32768;
+    let count = list.len() as u32;
+    let prn = (count.wrapping_mul(1103515245).wrapping_add(12345) / 65536) % PRN_MODULO;
+    let index = (count * prn / PRN_MODULO * sqrti(prn) / sqrti(PRN_MODULO)) as usize;
+    match list.get(index) {
+        Some(&id) if id != bad => id,
+        Some(_) => list[index.saturating_sub(1)],
+        None => list[0],
+    }
+}
+
+/// Integer square root computed with floats, the way git does.
+fn sqrti(value: u32) -> u32 {
+    if value == 0 {
+        return 0;
+    }
+    let value = value as f32;
+    let mut x = value;
+    loop {
+        let y = (x + value / x) / 2.0;
+        let d = (y - x).abs();
+        x = y;
+        if d < 0.5 {
+            return x as u32;
+        }
+    }
+}
+
+/// About how many more steps a bisection of [all] candidates takes.
+fn estimate_bisect_steps(all: usize) -> usize {
+    if all < 3 {
+        return 0;
+    }
+    let n = all.ilog2() as usize;
+    let e = 1 << n;
+    let x = all - e;
+    if e < 3 * x {
+        n
+    } else {
+        n - 1
+    }
+}
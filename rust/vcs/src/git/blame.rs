@@ -0,0 +1,268 @@
+use crate::git::object_id::ObjectId;
+use crate::git::objects::{GitCommit, GitObject, GitObjectType};
+use crate::git::repo::Repository;
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Minimum share of matching lines for a file to count as renamed.
+const RENAME_SIMILARITY: f64 = 0.5;
+
+/// Origin of a single line of a blamed file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlameLine {
+    /// Commit that introduced the line.
+    pub commit: ObjectId,
+    /// Path of the file in [commit].
+    pub path: String,
+    /// 1-based line number in the file of [commit].
+    pub orig_line: usize,
+    /// 1-based line number in the blamed file.
+    pub final_line: usize,
+    /// Whether [commit] is a root commit, i.e. the line may be older.
+    pub boundary: bool,
+    /// First parent of [commit] containing the file and its path there.
+    pub previous: Option<(ObjectId, String)>,
+}
+
+/// Lines of a file at some commit together with their origins.
+pub struct Blame {
+    /// All lines of the blamed file including line endings.
+    pub lines: Vec<Vec<u8>>,
+    /// Origins of the requested lines, ordered by [BlameLine::final_line].
+    pub origins: Vec<BlameLine>,
+}
+
+/// Lines that still have to be attributed, as (index in the blamed file,
+/// index in the file version of the suspect commit).
+type Suspects = Vec<(usize, usize)>;
+
+impl Repository {
+    /// Attribute every line of [path] at commit [start] to the commit that
+    /// last changed it.
+    ///
+    /// Only lines in the 1-based inclusive [range] are tracked, the others
+    /// are missing from [Blame::origins].
+    pub fn blame(
+        &self,
+        start: ObjectId,
+        path: &str,
+        range: Option<(usize, usize)>,
+    ) -> Option<Blame> {
+        let blob = self.blob_at(start, path)?;
+        let lines = split_lines(&self.blob_data(blob)?);
+        let (first, last) = range.unwrap_or((1, lines.len()));
+        let tracked = (first.max(1) - 1..last.min(lines.len())).collect::<Vec<usize>>();
+
+        let mut queue = BinaryHeap::new();
+        let mut pending: HashMap<(ObjectId, String), (ObjectId, Suspects)> = HashMap::new();
+        let mut inserted = 0;
+        let mut push = |queue: &mut BinaryHeap<_>,
+                        pending: &mut HashMap<(ObjectId, String), (ObjectId, Suspects)>,
+                        commit: ObjectId,
+                        time: i64,
+                        path: String,
+                        blob: ObjectId,
+                        suspects: Suspects| {
+            let key = (commit, path);
+            match pending.get_mut(&key) {
+                Some((_, existing)) => existing.extend(suspects),
+                None => {
+                    queue.push((time, Reverse(inserted), key.clone()));
+                    inserted += 1;
+                    pending.insert(key, (blob, suspects));
+                }
+            }
+        };
+
+        let start_commit = self.read_commit(start)?;
+        push(
+            &mut queue,
+            &mut pending,
+            start,
+            commit_time(&start_commit),
+            path.to_string(),
+            blob,
+            tracked.iter().map(|i| (*i, *i)).collect(),
+        );
+
+        let mut origins: HashMap<usize, BlameLine> = HashMap::new();
+        while let Some((_, _, key)) = queue.pop() {
+            let (blob, mut suspects) = pending.remove(&key)?;
+            let (commit_id, path) = key;
+            let commit = self.read_commit(commit_id)?;
+            let parents = commit.get_parents();
+            let mut previous = None;
+            let mut data = None;
+            for parent_id in &parents {
+                if suspects.is_empty() {
+                    break;
+                }
+                let Some(parent) = self.read_commit(*parent_id) else {
+                    continue;
+                };
+                let Some((parent_path, parent_blob)) =
+                    self.find_in_parent(&commit, &parent, &path, blob)
+                else {
+                    continue;
+                };
+                if previous.is_none() {
+                    previous = Some((*parent_id, parent_path.clone()));
+                }
+                let passed = if parent_blob == blob {
+                    std::mem::take(&mut suspects)
+                } else {
+                    let data = match &data {
+                        Some(data) => data,
+                        None => data.insert(split_lines(&self.blob_data(blob)?)),
+                    };
+                    let parent_data = split_lines(&self.blob_data(parent_blob)?);
+                    let matches = matching_lines(&parent_data, data);
+                    let mut passed = Vec::new();
+                    suspects.retain(|(line, suspect_line)| match matches[*suspect_line] {
+                        Some(parent_line) => {
+                            passed.push((*line, parent_line));
+                            false
+                        }
+                        None => true,
+                    });
+                    passed
+                };
+                if !passed.is_empty() {
+                    let time = commit_time(&parent);
+                    push(
+                        &mut queue,
+                        &mut pending,
+                        *parent_id,
+                        time,
+                        parent_path,
+                        parent_blob,
+                        passed,
+                    );
+                }
+            }
+            for (line, suspect_line) in suspects {
+                origins.insert(
+                    line,
+                    BlameLine {
+                        commit: commit_id,
+                        path: path.clone(),
+                        orig_line: suspect_line + 1,
+                        final_line: line + 1,
+                        boundary: parents.is_empty(),
+                        previous: previous.clone(),
+                    },
+                );
+            }
+        }
+
+        let origins = tracked
+            .iter()
+            .map(|line| origins.remove(line))
+            .collect::<Option<Vec<BlameLine>>>()?;
+        Some(Blame { lines, origins })
+    }
+
+    /// Path and blob id of the file in [parent] that became [path] in
+    /// [commit], following renames.
+    fn find_in_parent(
+        &self,
+        commit: &GitCommit,
+        parent: &GitCommit,
+        path: &str,
+        blob: ObjectId,
+    ) -> Option<(String, ObjectId)> {
+        let parent_tree = parent.get_tree()?;
+        if let Some(id) = self.tree_lookup(parent_tree, path) {
+            return Some((path.to_string(), id));
+        }
+
+        // The file is new in [commit], look for a file of the parent that
+        // no longer exists and has mostly the same content.
+        let tree = self.tree_to_map(commit.get_tree()?.to_string(), String::new())?;
+        let parent_files = self.tree_to_map(parent_tree.to_string(), String::new())?;
+        let candidates = parent_files
+            .into_iter()
+            .filter(|(path, _)| !tree.contains_key(path))
+            .collect::<Vec<(String, ObjectId)>>();
+        if let Some(exact) = candidates.iter().find(|(_, id)| *id == blob) {
+            return Some(exact.clone());
+        }
+        let data = split_lines(&self.blob_data(blob)?);
+        let mut best: Option<(f64, String, ObjectId)> = None;
+        for (candidate, id) in candidates {
+            let Some(candidate_data) = self.blob_data(id) else {
+                continue;
+            };
+            let candidate_data = split_lines(&candidate_data);
+            let common = matching_lines(&candidate_data, &data)
+                .iter()
+                .filter(|line| line.is_some())
+                .count();
+            let similarity = 2.0 * common as f64 / (data.len() + candidate_data.len()) as f64;
+            if similarity >= RENAME_SIMILARITY
+                && best.as_ref().is_none_or(|(best, ..)| similarity > *best)
+            {
+                best = Some((similarity, candidate, id));
+            }
+        }
+        best.map(|(_, path, id)| (path, id))
+    }
+
+    /// Id of the blob at [path] in the tree of [commit].
+    fn blob_at(&self, commit: ObjectId, path: &str) -> Option<ObjectId> {
+        let tree = self.object_peel(commit, GitObjectType::Tree)?;
+        self.tree_lookup(tree, path)
+    }
+
+    fn read_commit(&self, id: ObjectId) -> Option<GitCommit> {
+        match self.object_read(id)? {
+            GitObject::Commit(commit) => Some(commit),
+            _ => None,
+        }
+    }
+}
+
+fn commit_time(commit: &GitCommit) -> i64 {
+    commit.get_commiter().map(|c| c.time).unwrap_or(0)
+}
+
+/// Split [data] into lines, keeping the line endings.
+fn split_lines(data: &[u8]) -> Vec<Vec<u8>> {
+    data.split_inclusive(|b| *b == b'\n')
+        .map(|line| line.to_vec())
+        .collect()
+}
+
+/// For every line of [new], the index of the same unchanged line in [old].
+fn matching_lines(old: &[Vec<u8>], new: &[Vec<u8>]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; new.len()];
+    for op in capture_diff_slices(Algorithm::Myers, old, new) {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        {
+            for i in 0..len {
+                matches[new_index + i] = Some(old_index + i);
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::blame::{matching_lines, split_lines};
+
+    #[test]
+    fn matches_unchanged_lines() {
+        let old = split_lines(b"a\nb\nc\nd");
+        let new = split_lines(b"a\nx\nc\nd\ne\n");
+        assert_eq!(
+            matching_lines(&old, &new),
+            vec![Some(0), None, Some(2), None, None]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::git::object_id::ObjectId;
+use crate::git::objects::GitObjectType;
+use crate::git::repo::Repository;
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+#[derive(Debug)]
+pub enum CheckoutError {
+    /// The revision doesn't name a commit.
+    InvalidRevision(String),
+    /// Bare repositories have nothing to check out into.
+    NoWorkTree,
+    /// Tracked files have staged or unstaged changes.
+    LocalChanges,
+    /// These untracked files would be overwritten.
+    UntrackedFiles(Vec<String>),
+    IOError,
+}
+
+impl Display for CheckoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckoutError::InvalidRevision(rev) => {
+                write!(f, "pathspec '{rev}' did not match any commit")
+            }
+            CheckoutError::NoWorkTree => write!(f, "this operation must be run in a work tree"),
+            CheckoutError::LocalChanges => write!(
+                f,
+                "Your local changes would be overwritten by checkout, commit or reset them first"
+            ),
+            CheckoutError::UntrackedFiles(paths) => write!(
+                f,
+                "The following untracked working tree files would be overwritten by checkout: {}",
+                paths.join(", ")
+            ),
+            CheckoutError::IOError => write!(f, "failed to check out"),
+        }
+    }
+}
+
+impl Repository {
+    /// Switch the work tree to [rev]: the branch if [rev] names one, else
+    /// the commit with a detached `HEAD`. Returns the checked out commit.
+    ///
+    /// Unlike git, any change to tracked files prevents the checkout instead
+    /// of being carried over. Untracked files are kept unless the new commit
+    /// has a file at their path. Runs the `post-checkout` hook.
+    pub fn checkout(&self, rev: &str) -> Result<ObjectId, CheckoutError> {
+        let invalid = || CheckoutError::InvalidRevision(rev.to_string());
+        let branch = format!("refs/heads/{rev}");
+        let (branch, commit) = match self.ref_resolve(&branch) {
+            Some(id) => (Some(branch), id),
+            None => (None, self.rev_parse(rev).ok_or_else(invalid)?),
+        };
+        let commit = self
+            .object_peel(commit, GitObjectType::Commit)
+            .ok_or_else(invalid)?;
+        let tree = self
+            .object_peel(commit, GitObjectType::Tree)
+            .ok_or_else(invalid)?;
+
+        let status = self.status().ok_or(CheckoutError::NoWorkTree)?;
+        if status.has_tracked_changes() {
+            return Err(CheckoutError::LocalChanges);
+        }
+        let files = self.tree_flatten(tree).ok_or(CheckoutError::IOError)?;
+        let overwritten = status
+            .untracked
+            .into_iter()
+            .filter(|untracked| {
+                files
+                    .iter()
+                    .any(|(path, _, _)| match untracked.strip_suffix('/') {
+                        Some(dir) => path.starts_with(untracked) || path == dir,
+                        None => path == untracked,
+                    })
+            })
+            .collect::<Vec<String>>();
+        if !overwritten.is_empty() {
+            return Err(CheckoutError::UntrackedFiles(overwritten));
+        }
+
+        let old_head = self.head();
+        self.read_tree(tree, true).ok_or(CheckoutError::IOError)?;
+        match branch {
+            Some(branch) => fs::write(self.git_path("HEAD"), format!("ref: {branch}\n"))
+                .map_err(|_| CheckoutError::IOError)?,
+            None => self
+                .ref_update("HEAD", commit)
+                .ok_or(CheckoutError::IOError)?,
+        }
+        // Like git, the outcome of the hook doesn't undo the checkout.
+        let old_head = old_head.map_or(self.null_id(), |id| id.to_string());
+        self.run_hook("post-checkout", &[&old_head, &commit.to_string(), "1"]);
+        Ok(commit)
+    }
+}
@@ -0,0 +1,178 @@
+use crate::git::ignore::IgnoreRules;
+use crate::git::repo::{is_git_dir, Repository};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Options of [Repository::clean].
+#[derive(Default)]
+pub struct CleanOptions {
+    /// Also remove untracked directories (`-d`).
+    pub directories: bool,
+    /// Don't use ignore rules, remove ignored files too (`-x`).
+    pub ignored: bool,
+    /// Only report what would be removed (`-n`).
+    pub dry_run: bool,
+    /// Limit cleaning to these files or directories, relative to the work
+    /// tree. Everything if empty.
+    pub paths: Vec<String>,
+}
+
+/// Where a path is relative to the paths given to `clean`.
+#[derive(PartialEq, Eq)]
+enum Scope {
+    Inside,
+    /// A parent directory of a selected path.
+    Ancestor,
+    Outside,
+}
+
+struct Cleaner<'a> {
+    work_tree: &'a Path,
+    options: &'a CleanOptions,
+    rules: IgnoreRules,
+    tracked: HashSet<String>,
+    /// Directories containing tracked files.
+    tracked_dirs: HashSet<String>,
+    found: Vec<String>,
+}
+
+impl Repository {
+    /// Remove untracked files from the work tree, returning their paths
+    /// sorted (directories with a trailing `/`).
+    ///
+    /// Ignored files are kept unless [CleanOptions::ignored] is set. Nested
+    /// repositories are never removed.
+    pub fn clean(&self, options: &CleanOptions) -> Option<Vec<String>> {
+        let work_tree = self.work_tree()?;
+        let tracked = self
+            .index()
+            .map(|index| {
+                index
+                    .iter()
+                    .map(|entry| entry.name().to_string_lossy().to_string())
+                    .collect::<HashSet<String>>()
+            })
+            .unwrap_or_default();
+        let tracked_dirs = tracked
+            .iter()
+            .flat_map(|path| {
+                path.match_indices('/')
+                    .map(|(i, _)| path[..i].to_string())
+                    .collect::<Vec<String>>()
+            })
+            .collect();
+        let mut cleaner = Cleaner {
+            work_tree,
+            options,
+            rules: self.ignore_rules()?,
+            tracked,
+            tracked_dirs,
+            found: Vec::new(),
+        };
+        cleaner.visit("")?;
+
+        let mut found = cleaner.found;
+        found.sort();
+        if !options.dry_run {
+            for path in &found {
+                let full_path = work_tree.join(path.trim_end_matches('/'));
+                let res = if path.ends_with('/') {
+                    fs::remove_dir_all(full_path)
+                } else {
+                    fs::remove_file(full_path)
+                };
+                res.ok()?;
+            }
+        }
+        Some(found)
+    }
+}
+
+impl Cleaner<'_> {
+    fn scope(&self, path: &str) -> Scope {
+        if self.options.paths.is_empty() {
+            return Scope::Inside;
+        }
+        let mut scope = Scope::Outside;
+        for spec in &self.options.paths {
+            let spec = spec.trim_end_matches('/');
+            if spec == "." || path == spec || path.starts_with(&format!("{spec}/")) {
+                return Scope::Inside;
+            }
+            if spec.starts_with(&format!("{path}/")) {
+                scope = Scope::Ancestor;
+            }
+        }
+        scope
+    }
+
+    /// Collect removable entries below the work tree directory [dir].
+    fn visit(&mut self, dir: &str) -> Option<()> {
+        let mut entries = fs::read_dir(self.work_tree.join(dir))
+            .ok()?
+            .flatten()
+            .collect::<Vec<fs::DirEntry>>();
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == ".git" {
+                continue;
+            }
+            let path = if dir.is_empty() {
+                name
+            } else {
+                format!("{dir}/{name}")
+            };
+            let scope = self.scope(&path);
+            if scope == Scope::Outside || self.tracked.contains(&path) {
+                continue;
+            }
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if !is_dir {
+                if scope == Scope::Inside
+                    && (self.options.ignored || !self.rules.is_ignored(&path, false))
+                {
+                    self.found.push(path);
+                }
+            } else if self.tracked_dirs.contains(&path) || scope == Scope::Ancestor {
+                self.visit(&path)?;
+            } else if self.options.directories && !self.is_repository(&path) {
+                if !self.options.ignored && self.rules.is_ignored(&path, true) {
+                    continue;
+                }
+                if self.all_removable(&path) {
+                    self.found.push(format!("{path}/"));
+                } else {
+                    self.visit(&path)?;
+                }
+            }
+        }
+        Some(())
+    }
+
+    /// Whether everything in the untracked directory [dir] can be removed,
+    /// i.e. it contains no ignored files (unless they are removed too) and no
+    /// nested repositories.
+    fn all_removable(&mut self, dir: &str) -> bool {
+        if self.is_repository(dir) {
+            return false;
+        }
+        let Ok(entries) = fs::read_dir(self.work_tree.join(dir)) else {
+            return false;
+        };
+        entries.flatten().all(|entry| {
+            let path = format!("{dir}/{}", entry.file_name().to_string_lossy());
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            (self.options.ignored || !self.rules.is_ignored(&path, is_dir))
+                && (!is_dir || self.all_removable(&path))
+        })
+    }
+
+    /// Whether [dir] is the work tree of another repository, with `.git`
+    /// being a `gitdir:` file or a directory with `HEAD` and `objects`.
+    fn is_repository(&self, dir: &str) -> bool {
+        let git = self.work_tree.join(dir).join(".git");
+        git.is_file() || is_git_dir(&git)
+    }
+}
@@ -0,0 +1,101 @@
+use crate::git::object_id::ObjectId;
+use crate::git::objects::{GitCommit, GitObject, GitObjectType};
+use crate::git::repo::Repository;
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+#[derive(Debug, Default)]
+pub struct CommitOptions {
+    /// Commit even if the tree is the same as the one of `HEAD`.
+    pub allow_empty: bool,
+    /// Skip the `pre-commit` and `commit-msg` hooks.
+    pub no_verify: bool,
+}
+
+#[derive(Debug)]
+pub enum CommitError {
+    /// `user.name` or `user.email` isn't configured.
+    UnknownIdentity,
+    /// The index has conflicted entries.
+    Unmerged,
+    /// Nothing changed compared to `HEAD`.
+    NothingToCommit,
+    /// The message is empty after removing whitespace.
+    EmptyMessage,
+    /// The hook with this name rejected the commit.
+    HookFailed(String),
+    IOError,
+}
+
+impl Display for CommitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommitError::UnknownIdentity => {
+                write!(f, "Author identity unknown, set user.name and user.email")
+            }
+            CommitError::Unmerged => write!(
+                f,
+                "Committing is not possible because you have unmerged files"
+            ),
+            CommitError::NothingToCommit => write!(f, "nothing to commit"),
+            CommitError::EmptyMessage => {
+                write!(f, "Aborting commit due to empty commit message")
+            }
+            CommitError::HookFailed(hook) => write!(f, "the {hook} hook rejected the commit"),
+            CommitError::IOError => write!(f, "failed to write the commit"),
+        }
+    }
+}
+
+impl Repository {
+    /// Record the index as a new commit on top of `HEAD` with [message] and
+    /// move the current branch (or the detached `HEAD`) to it.
+    ///
+    /// Runs the hooks like git: `pre-commit` before the tree is written (it
+    /// may still change the index), `prepare-commit-msg` and `commit-msg`
+    /// with the message in `COMMIT_EDITMSG` (they may edit it) and
+    /// `post-commit` afterwards.
+    pub fn commit(&self, message: &str, options: &CommitOptions) -> Result<ObjectId, CommitError> {
+        let (Some(author), Some(committer)) = (self.author_signature(), self.committer_signature())
+        else {
+            return Err(CommitError::UnknownIdentity);
+        };
+        let hook_failed = |hook: &str| CommitError::HookFailed(hook.to_string());
+        if !options.no_verify && !self.run_hook("pre-commit", &[]) {
+            return Err(hook_failed("pre-commit"));
+        }
+        let tree = self.write_tree().ok_or(CommitError::Unmerged)?;
+        let parents = self.head().into_iter().collect::<Vec<ObjectId>>();
+        let parent_tree = parents
+            .first()
+            .and_then(|parent| self.object_peel(*parent, GitObjectType::Tree));
+        if !options.allow_empty && parent_tree == Some(tree) {
+            return Err(CommitError::NothingToCommit);
+        }
+
+        let message_file = std::path::absolute(self.git_path("COMMIT_EDITMSG"))
+            .map_err(|_| CommitError::IOError)?;
+        fs::write(&message_file, format!("{}\n", message.trim_end()))
+            .map_err(|_| CommitError::IOError)?;
+        let message_path = message_file.to_string_lossy();
+        if !self.run_hook("prepare-commit-msg", &[&message_path, "message"]) {
+            return Err(hook_failed("prepare-commit-msg"));
+        }
+        if !options.no_verify && !self.run_hook("commit-msg", &[&message_path]) {
+            return Err(hook_failed("commit-msg"));
+        }
+        let message = fs::read_to_string(&message_file).map_err(|_| CommitError::IOError)?;
+        let message = message.trim_end();
+        if message.trim().is_empty() {
+            return Err(CommitError::EmptyMessage);
+        }
+
+        let commit = GitCommit::new(tree, parents, author, committer, format!("{message}\n"));
+        let id = self
+            .object_write(GitObject::Commit(commit))
+            .map_err(|_| CommitError::IOError)?;
+        self.head_update(id).ok_or(CommitError::IOError)?;
+        self.run_hook("post-commit", &[]);
+        Ok(id)
+    }
+}
@@ -0,0 +1,386 @@
+use crate::git::object_id::{ObjectFormat, ObjectId};
+use crate::git::objects::GitObject;
+use crate::git::repo::{write_locked, Repository};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const SIGNATURE: &[u8; 4] = b"CGPH";
+const VERSION: u8 = 1;
+const CHUNK_FANOUT: [u8; 4] = *b"OIDF";
+const CHUNK_LOOKUP: [u8; 4] = *b"OIDL";
+const CHUNK_DATA: [u8; 4] = *b"CDAT";
+const CHUNK_EXTRA_EDGES: [u8; 4] = *b"EDGE";
+/// Parent position meaning "no parent".
+const PARENT_NONE: u32 = 0x7000_0000;
+/// Set on the second parent position of octopus merges, the rest of the
+/// value indexes the extra edge list. Also marks the last extra edge.
+const PARENT_EXTRA: u32 = 0x8000_0000;
+/// Largest topological level that fits the 30 bits of the file.
+const GENERATION_MAX: u32 = 0x3fff_ffff;
+
+/// Generation of commits missing from the commit-graph: above all others.
+pub const GENERATION_INFINITY: u32 = u32::MAX;
+
+/// What history traversal needs to know about a commit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitInfo {
+    pub tree: ObjectId,
+    pub parents: Vec<ObjectId>,
+    /// Topological level: 1 for root commits, else one more than the
+    /// highest parent. [GENERATION_INFINITY] if unknown.
+    pub generation: u32,
+    /// Committer time in seconds since unix epoch.
+    pub time: i64,
+}
+
+/// The commit-graph file (`objects/info/commit-graph`): commits sorted by
+/// id with their root tree, parents, generation and committer time, so
+/// history can be walked without inflating commit objects.
+pub struct CommitGraph {
+    format: ObjectFormat,
+    ids: Vec<ObjectId>,
+    /// Contents of the `CDAT` chunk, one fixed size record per commit.
+    data: Vec<u8>,
+    /// Contents of the `EDGE` chunk for merges with more than two parents.
+    edges: Vec<u8>,
+}
+
+impl CommitGraph {
+    /// Load and check the commit-graph at [path].
+    pub(crate) fn open(path: &Path, format: ObjectFormat) -> io::Result<Self> {
+        let file = fs::read(path)?;
+        let raw_len = format.raw_len();
+        if file.len() < 8 + raw_len || &file[..4] != SIGNATURE || file[4] != VERSION {
+            return Err(invalid("unsupported header"));
+        }
+        if file[5] != hash_version(format) {
+            return Err(invalid("hash version"));
+        }
+        let (content, checksum) = file.split_at(file.len() - raw_len);
+        if format.hash(content).as_bytes() != checksum {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let chunk_count = file[6] as usize;
+        let mut chunks = HashMap::new();
+        for i in 0..chunk_count {
+            let entry = 8 + i * 12;
+            let next = entry + 12;
+            let (Some(id), Some(start), Some(end)) = (
+                file.get(entry..entry + 4),
+                file.get(entry + 4..entry + 12),
+                file.get(next + 4..next + 12),
+            ) else {
+                return Err(invalid("chunk table"));
+            };
+            let start = u64::from_be_bytes(start.try_into().unwrap()) as usize;
+            let end = u64::from_be_bytes(end.try_into().unwrap()) as usize;
+            if start > end || end > content.len() {
+                return Err(invalid("chunk offset"));
+            }
+            chunks.insert(<[u8; 4]>::try_from(id).unwrap(), &file[start..end]);
+        }
+        let (Some(fanout), Some(lookup), Some(data)) = (
+            chunks.get(&CHUNK_FANOUT),
+            chunks.get(&CHUNK_LOOKUP),
+            chunks.get(&CHUNK_DATA),
+        ) else {
+            return Err(invalid("missing chunk"));
+        };
+        if fanout.len() != 256 * 4 {
+            return Err(invalid("fanout"));
+        }
+        let count = read_u32(fanout, 255 * 4) as usize;
+        if lookup.len() != count * raw_len || data.len() != count * (raw_len + 16) {
+            return Err(invalid("chunk size"));
+        }
+        let ids = lookup
+            .chunks(raw_len)
+            .map(|id| ObjectId::from_bytes(id).ok_or_else(|| invalid("object id")))
+            .collect::<io::Result<Vec<ObjectId>>>()?;
+
+        Ok(CommitGraph {
+            format,
+            ids,
+            data: data.to_vec(),
+            edges: chunks
+                .get(&CHUNK_EXTRA_EDGES)
+                .map(|edges| edges.to_vec())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Number of commits in the graph.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Whether [id] is in the graph.
+    pub fn contains(&self, id: &ObjectId) -> bool {
+        self.ids.binary_search(id).is_ok()
+    }
+
+    /// Look up the commit [id].
+    pub fn commit(&self, id: &ObjectId) -> Option<CommitInfo> {
+        let pos = self.ids.binary_search(id).ok()?;
+        let raw_len = self.format.raw_len();
+        let record = &self.data[pos * (raw_len + 16)..(pos + 1) * (raw_len + 16)];
+        let tree = ObjectId::from_bytes(&record[..raw_len])?;
+
+        let mut parents = Vec::new();
+        let first = read_u32(record, raw_len);
+        let second = read_u32(record, raw_len + 4);
+        if first != PARENT_NONE {
+            parents.push(*self.ids.get(first as usize)?);
+        }
+        if second & PARENT_EXTRA != 0 {
+            let mut edge = (second & !PARENT_EXTRA) as usize;
+            loop {
+                let value = read_u32(self.edges.get(edge * 4..edge * 4 + 4)?, 0);
+                parents.push(*self.ids.get((value & !PARENT_EXTRA) as usize)?);
+                if value & PARENT_EXTRA != 0 {
+                    break;
+                }
+                edge += 1;
+            }
+        } else if second != PARENT_NONE {
+            parents.push(*self.ids.get(second as usize)?);
+        }
+
+        let high = read_u32(record, raw_len + 8);
+        let low = read_u32(record, raw_len + 12);
+        Some(CommitInfo {
+            tree,
+            parents,
+            generation: high >> 2,
+            time: (((high & 0x3) as i64) << 32) | low as i64,
+        })
+    }
+
+    /// Serialize [commits] as commit-graph file, computing generation
+    /// numbers. All parents must be part of [commits].
+    pub fn encode(format: ObjectFormat, commits: &HashMap<ObjectId, CommitInfo>) -> Vec<u8> {
+        let mut ids = commits.keys().copied().collect::<Vec<ObjectId>>();
+        ids.sort();
+        let positions = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i as u32))
+            .collect::<HashMap<ObjectId, u32>>();
+        let generations = generations(commits);
+
+        let mut fanout = Vec::with_capacity(256 * 4);
+        let mut count = 0;
+        for byte in 0..=255u8 {
+            count += ids[count..]
+                .iter()
+                .take_while(|id| id.as_bytes()[0] == byte)
+                .count();
+            fanout.extend_from_slice(&(count as u32).to_be_bytes());
+        }
+        let lookup = ids
+            .iter()
+            .flat_map(|id| id.as_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        let mut data = Vec::with_capacity(ids.len() * (format.raw_len() + 16));
+        let mut edges = Vec::new();
+        for id in &ids {
+            let commit = &commits[id];
+            let parent = |i: usize| commit.parents.get(i).map_or(PARENT_NONE, |p| positions[p]);
+            data.extend_from_slice(commit.tree.as_bytes());
+            data.extend_from_slice(&parent(0).to_be_bytes());
+            if commit.parents.len() > 2 {
+                let start = (edges.len() / 4) as u32;
+                data.extend_from_slice(&(PARENT_EXTRA | start).to_be_bytes());
+                for (i, extra) in commit.parents[1..].iter().enumerate() {
+                    let last = if i == commit.parents.len() - 2 {
+                        PARENT_EXTRA
+                    } else {
+                        0
+                    };
+                    edges.extend_from_slice(&(positions[extra] | last).to_be_bytes());
+                }
+            } else {
+                data.extend_from_slice(&parent(1).to_be_bytes());
+            }
+            let time = commit.time.clamp(0, (1 << 34) - 1) as u64;
+            let high = (generations[id] << 2) | (time >> 32) as u32;
+            data.extend_from_slice(&high.to_be_bytes());
+            data.extend_from_slice(&(time as u32).to_be_bytes());
+        }
+
+        let mut chunks = vec![
+            (CHUNK_FANOUT, fanout),
+            (CHUNK_LOOKUP, lookup),
+            (CHUNK_DATA, data),
+        ];
+        if !edges.is_empty() {
+            chunks.push((CHUNK_EXTRA_EDGES, edges));
+        }
+        let mut out = Vec::new();
+        out.extend_from_slice(SIGNATURE);
+        out.extend_from_slice(&[VERSION, hash_version(format), chunks.len() as u8, 0]);
+        let mut offset = (8 + (chunks.len() + 1) * 12) as u64;
+        for (id, chunk) in &chunks {
+            out.extend_from_slice(id);
+            out.extend_from_slice(&offset.to_be_bytes());
+            offset += chunk.len() as u64;
+        }
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&offset.to_be_bytes());
+        for (_, chunk) in chunks {
+            out.extend_from_slice(&chunk);
+        }
+        let checksum = format.hash(&out);
+        out.extend_from_slice(checksum.as_bytes());
+        out
+    }
+}
+
+/// Topological levels of [commits], iteratively to survive long histories.
+fn generations(commits: &HashMap<ObjectId, CommitInfo>) -> HashMap<ObjectId, u32> {
+    let mut generations = HashMap::with_capacity(commits.len());
+    for start in commits.keys() {
+        let mut stack = vec![*start];
+        while let Some(id) = stack.last().copied() {
+            if generations.contains_key(&id) {
+                stack.pop();
+                continue;
+            }
+            let parents = &commits[&id].parents;
+            let missing = parents
+                .iter()
+                .filter(|parent| !generations.contains_key(*parent))
+                .copied()
+                .collect::<Vec<ObjectId>>();
+            if missing.is_empty() {
+                let generation = parents
+                    .iter()
+                    .map(|parent| generations[parent])
+                    .max()
+                    .map_or(1, |max: u32| (max + 1).min(GENERATION_MAX));
+                generations.insert(id, generation);
+                stack.pop();
+            } else {
+                stack.extend(missing);
+            }
+        }
+    }
+    generations
+}
+
+impl Repository {
+    /// Parents, tree and committer time of the commit [id], from the
+    /// commit-graph if it has the commit.
+    pub fn commit_info(&self, id: &ObjectId) -> Option<CommitInfo> {
+        if let Some(info) = self.commit_graph().and_then(|graph| graph.commit(id)) {
+            return Some(info);
+        }
+        let GitObject::Commit(commit) = self.object_read(*id)? else {
+            return None;
+        };
+        Some(CommitInfo {
+            tree: commit.get_tree()?,
+            parents: commit.get_parents(),
+            generation: GENERATION_INFINITY,
+            time: commit.get_commiter().map_or(0, |committer| committer.time),
+        })
+    }
+
+    /// Write `objects/info/commit-graph` with all commits reachable from
+    /// refs and `HEAD`, returning how many it holds.
+    pub fn write_commit_graph(&self) -> Option<usize> {
+        let mut commits = HashMap::new();
+        let mut stack = self
+            .refs()
+            .into_iter()
+            .map(|(_, id)| id)
+            .chain(self.head())
+            .filter_map(|id| self.object_peel(id, crate::git::objects::GitObjectType::Commit))
+            .collect::<Vec<ObjectId>>();
+        let mut seen = HashSet::new();
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            let info = self.commit_info(&id)?;
+            stack.extend(info.parents.iter().copied());
+            commits.insert(id, info);
+        }
+
+        let data = CommitGraph::encode(self.object_format(), &commits);
+        let path = self.git_path("objects/info/commit-graph");
+        fs::create_dir_all(path.parent()?).ok()?;
+        write_locked(&path, &data).ok()?;
+        Some(commits.len())
+    }
+}
+
+/// Hash version byte of the file header.
+fn hash_version(format: ObjectFormat) -> u8 {
+    match format {
+        ObjectFormat::Sha1 => 1,
+        ObjectFormat::Sha256 => 2,
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid commit-graph: {what}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::commit_graph::{CommitGraph, CommitInfo};
+    use crate::git::object_id::{ObjectFormat, ObjectId};
+    use std::collections::HashMap;
+
+    #[test]
+    fn encode_round_trips() {
+        let format = ObjectFormat::Sha1;
+        let id = |n: u8| format.hash(&[n]);
+        let tree = id(0);
+        let info = |parents: Vec<ObjectId>, time| CommitInfo {
+            tree,
+            parents,
+            generation: 0,
+            time,
+        };
+        let commits = HashMap::from([
+            (id(1), info(vec![], 100)),
+            (id(2), info(vec![id(1)], 200)),
+            (id(3), info(vec![id(1)], 300)),
+            (id(4), info(vec![id(2), id(3)], 1 << 33)),
+            (id(5), info(vec![id(4), id(2), id(3)], 500)),
+        ]);
+        let data = CommitGraph::encode(format, &commits);
+
+        let dir = std::env::temp_dir().join(format!("vcs_commit_graph_{}", std::process::id()));
+        std::fs::write(&dir, &data).unwrap();
+        let graph = CommitGraph::open(&dir, format).unwrap();
+        std::fs::remove_file(&dir).unwrap();
+
+        assert_eq!(graph.len(), 5);
+        let expected_generations = [(1, 1), (2, 2), (3, 2), (4, 3), (5, 4)];
+        for (n, generation) in expected_generations {
+            let commit = graph.commit(&id(n)).unwrap();
+            assert_eq!(commit.parents, commits[&id(n)].parents);
+            assert_eq!(commit.time, commits[&id(n)].time);
+            assert_eq!(commit.tree, tree);
+            assert_eq!(commit.generation, generation);
+        }
+        assert!(graph.commit(&id(6)).is_none());
+    }
+}
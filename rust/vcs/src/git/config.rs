@@ -0,0 +1,448 @@
+use crate::git::repo::write_locked;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A git config file.
+///
+/// Changes are made to the lines of the file like `git config` does, so
+/// comments, the order of the sections and other values of multi-valued
+/// keys are kept when it is written back.
+///
+/// Like in git, section and key names are case-insensitive, only
+/// subsections are not. Lookups and changes accept any spelling.
+#[derive(Debug, Default)]
+pub struct ConfigFile {
+    path: PathBuf,
+    /// Lines of the file without line endings.
+    lines: Vec<String>,
+    /// Section headers in file order, with the index of their line.
+    sections: Vec<(String, usize)>,
+    /// Variables in file order.
+    entries: Vec<ConfigEntry>,
+}
+
+#[derive(Debug)]
+struct ConfigEntry {
+    /// Name of the section, with the subsection like `remote "origin"`.
+    /// Normalized with [normalize] like [key].
+    section: String,
+    key: String,
+    value: String,
+    /// Lines of the variable, more than one if continued with `\`.
+    lines: Range<usize>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// File is read protected, etc.
+    IOError,
+    /// A line is neither a section header, a variable nor a comment.
+    FormatError,
+}
+
+impl ConfigFile {
+    /// Open an existing file or create a reference to a new file.
+    pub fn open(path: PathBuf) -> Result<Self, ConfigError> {
+        if !path.exists() {
+            return Ok(ConfigFile {
+                path,
+                ..Self::default()
+            });
+        }
+        let data = fs::read_to_string(&path).map_err(|_| ConfigError::IOError)?;
+        let mut file = ConfigFile {
+            path,
+            lines: data.lines().map(str::to_string).collect(),
+            ..Self::default()
+        };
+        file.parse().ok_or(ConfigError::FormatError)?;
+        Ok(file)
+    }
+
+    fn parse(&mut self) -> Option<()> {
+        let mut section = None;
+        let mut i = 0;
+        while i < self.lines.len() {
+            let start = i;
+            let line = self.lines[i].trim_start();
+            i += 1;
+            let rest = if let Some(header) = line.strip_prefix('[') {
+                let (name, rest) = parse_section(header)?;
+                self.sections.push((name.clone(), start));
+                section = Some(name);
+                rest.trim_start()
+            } else {
+                line
+            };
+            if rest.is_empty() || rest.starts_with(['#', ';']) {
+                continue;
+            }
+            let key_len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+                .unwrap_or(rest.len());
+            let (key, mut value) = rest.split_at(key_len);
+            if key.is_empty() || !key.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return None;
+            }
+            value = value.trim_start();
+            // A key without value is a boolean set to true.
+            let value = match value.strip_prefix('=') {
+                Some(value) => {
+                    let mut value = value.to_string();
+                    loop {
+                        match parse_value(&value)? {
+                            Value::Done(value) => break value,
+                            Value::Continued(start) if i < self.lines.len() => {
+                                value = start + &self.lines[i];
+                                i += 1;
+                            }
+                            Value::Continued(_) => return None,
+                        }
+                    }
+                }
+                None if value.is_empty() || value.starts_with(['#', ';']) => "true".to_string(),
+                None => return None,
+            };
+            self.entries.push(ConfigEntry {
+                section: section.clone()?,
+                key: key.to_ascii_lowercase(),
+                value,
+                lines: start..i,
+            });
+        }
+        Some(())
+    }
+
+    /// Read the value of [key] in [section] if it exists, the last one if
+    /// it is set more than once.
+    pub fn get_string(&self, section: &str, key: &str) -> Option<&String> {
+        let (section, key) = normalize(section, key);
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.section == section && entry.key == key)
+            .map(|entry| &entry.value)
+    }
+
+    /// Read the value of [key] in [section] if it exists and parses.
+    pub fn get<F: FromStr>(&self, section: &str, key: &str) -> Option<F> {
+        self.get_string(section, key)?.parse::<F>().ok()
+    }
+
+    /// Names of the sections with variables, like `core` or
+    /// `remote "origin"`, in file order.
+    pub fn sections(&self) -> impl Iterator<Item = &str> {
+        let mut seen = Vec::new();
+        self.entries.iter().filter_map(move |entry| {
+            if seen.contains(&&entry.section) {
+                return None;
+            }
+            seen.push(&entry.section);
+            Some(entry.section.as_str())
+        })
+    }
+
+    /// Set [key] in [section] to [value].
+    ///
+    /// The last line setting it is replaced, else the variable is added at
+    /// the end of the last [section], which is appended if there is none.
+    pub fn set_str(&mut self, section: &str, key: &str, value: &str) {
+        let line = format!("\t{key} = {}", quote_value(value));
+        let (section, key) = normalize(section, key);
+        let (section, key) = (section.as_str(), key.as_str());
+        let existing = self
+            .entries
+            .iter()
+            .rposition(|entry| entry.section == section && entry.key == key);
+        if let Some(index) = existing {
+            let lines = self.entries[index].lines.clone();
+            self.splice(lines.clone(), vec![line]);
+            let entry = &mut self.entries[index];
+            entry.value = value.to_string();
+            entry.lines = lines.start..lines.start + 1;
+            return;
+        }
+
+        let header = self
+            .sections
+            .iter()
+            .rev()
+            .find(|(name, _)| name == section)
+            .map(|(_, line)| *line);
+        let at = match header {
+            Some(header) => self
+                .entries
+                .iter()
+                .filter(|entry| entry.lines.start > header && entry.section == section)
+                .map(|entry| entry.lines.end)
+                .max()
+                .unwrap_or(header + 1),
+            None => {
+                let at = self.lines.len();
+                self.lines.push(format!("[{section}]"));
+                self.sections.push((section.to_string(), at));
+                at + 1
+            }
+        };
+        self.splice(at..at, vec![line]);
+        // Keep the entries in file order.
+        let position = self
+            .entries
+            .iter()
+            .position(|entry| entry.lines.start >= at)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(
+            position,
+            ConfigEntry {
+                section: section.to_string(),
+                key: key.to_string(),
+                value: value.to_string(),
+                lines: at..at + 1,
+            },
+        );
+    }
+
+    /// Replace the lines in [range] with [lines], moving the positions of
+    /// the following sections and variables.
+    fn splice(&mut self, range: Range<usize>, lines: Vec<String>) {
+        let shift = |line: usize| line + lines.len() - range.len();
+        for (_, line) in &mut self.sections {
+            if *line >= range.end {
+                *line = shift(*line);
+            }
+        }
+        for entry in &mut self.entries {
+            if entry.lines.start >= range.end {
+                entry.lines = shift(entry.lines.start)..shift(entry.lines.end);
+            }
+        }
+        self.lines.splice(range, lines);
+    }
+
+    /// Save the file, through a lock file like git.
+    pub fn write(&self) -> io::Result<()> {
+        let mut data = self.lines.join("\n");
+        data.push('\n');
+        write_locked(&self.path, data.as_bytes())
+    }
+}
+
+/// Parse a section header after the `[`: `[section]`,
+/// `[section "subsection"]` or the deprecated `[section.subsection]`.
+/// Returns the name and the rest of the line.
+fn parse_section(header: &str) -> Option<(String, &str)> {
+    let name_len = header
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '.'))
+        .unwrap_or(header.len());
+    let (name, rest) = header.split_at(name_len);
+    if name.is_empty() {
+        return None;
+    }
+    if let Some(rest) = rest.strip_prefix(']') {
+        // The subsection of the deprecated syntax is case-insensitive too.
+        let name = name.to_ascii_lowercase();
+        return Some(match name.split_once('.') {
+            Some((name, subsection)) => (format!("{name} \"{subsection}\""), rest),
+            None => (name, rest),
+        });
+    }
+    let mut chars = rest.trim_start().strip_prefix('"')?.chars();
+    let mut subsection = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let rest = chars.as_str().strip_prefix(']')?;
+                let name = name.to_ascii_lowercase();
+                return Some((format!("{name} \"{subsection}\""), rest));
+            }
+            '\\' => subsection.push(chars.next()?),
+            c => subsection.push(c),
+        }
+    }
+    None
+}
+
+/// Lowercase the section and key name of a variable, keeping the case of
+/// the subsection.
+fn normalize(section: &str, key: &str) -> (String, String) {
+    let section = match section.split_once(' ') {
+        Some((name, subsection)) => format!("{} {subsection}", name.to_ascii_lowercase()),
+        None => section.to_ascii_lowercase(),
+    };
+    (section, key.to_ascii_lowercase())
+}
+
+enum Value {
+    Done(String),
+    /// The line ends with `\`, the value goes on in the next line. Holds
+    /// the raw text up to the `\`.
+    Continued(String),
+}
+
+/// Parse the raw text after `=`: surrounding whitespace is dropped, `#` and
+/// `;` start a comment and `"` quotes, both unless escaped.
+fn parse_value(raw: &str) -> Option<Value> {
+    let mut value = String::new();
+    // Length of [value] without unquoted trailing whitespace.
+    let mut len = 0;
+    let mut quoted = false;
+    let mut chars = raw.trim_start().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' | ';' if !quoted => break,
+            '\\' => match chars.next() {
+                None => {
+                    let end = raw.len() - 1;
+                    return Some(Value::Continued(raw[..end].to_string()));
+                }
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('b') => {
+                    value.pop();
+                }
+                Some(c @ ('"' | '\\')) => value.push(c),
+                Some(_) => return None,
+            },
+            c if c.is_whitespace() && !quoted => {
+                value.push(c);
+                continue;
+            }
+            c => value.push(c),
+        }
+        len = value.len();
+    }
+    if quoted {
+        return None;
+    }
+    value.truncate(len);
+    Some(Value::Done(value))
+}
+
+/// Quote [value] for writing if needed and escape it.
+fn quote_value(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    let needs_quotes = value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.contains(['#', ';']);
+    if needs_quotes {
+        format!("\"{escaped}\"")
+    } else {
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &str) -> ConfigFile {
+        let mut file = ConfigFile {
+            lines: data.lines().map(str::to_string).collect(),
+            ..ConfigFile::default()
+        };
+        file.parse().unwrap();
+        file
+    }
+
+    #[test]
+    fn reads_values() {
+        let file = parse(
+            "# comment\n[core]\n\tbare = false ; no\n\tflag\n\
+             [remote \"origin\"]\n\tfetch = a\n\tfetch = b\n\
+             [alias] msg = \"say \\\"hi\\\" # not a comment\" \n\
+             [branch.main]\n\tlong = one \\\n two\n",
+        );
+        assert_eq!(file.get::<bool>("core", "bare"), Some(false));
+        assert_eq!(file.get_string("core", "flag").unwrap(), "true");
+        assert_eq!(file.get_string("remote \"origin\"", "fetch").unwrap(), "b");
+        assert_eq!(
+            file.get_string("alias", "msg").unwrap(),
+            "say \"hi\" # not a comment"
+        );
+        assert_eq!(
+            file.get_string("branch \"main\"", "long").unwrap(),
+            "one  two"
+        );
+        assert_eq!(
+            file.sections().collect::<Vec<&str>>(),
+            ["core", "remote \"origin\"", "alias", "branch \"main\""]
+        );
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        let mut file = parse(
+            "[Core]\n\tfileMode = false\n[Branch.Main]\n\tremote = a\n\
+             [Remote \"Up\"]\n\tURL = x\n",
+        );
+        assert_eq!(file.get::<bool>("core", "filemode"), Some(false));
+        assert_eq!(file.get::<bool>("CORE", "FileMode"), Some(false));
+        assert_eq!(file.get_string("branch \"main\"", "remote").unwrap(), "a");
+        assert_eq!(file.get_string("remote \"Up\"", "url").unwrap(), "x");
+        assert_eq!(file.get_string("remote \"up\"", "url"), None);
+
+        file.set_str("core", "filemode", "true");
+        file.set_str("REMOTE \"Up\"", "Url", "y");
+        file.set_str("Extensions", "worktreeConfig", "true");
+        assert_eq!(
+            file.lines.join("\n"),
+            "[Core]\n\tfilemode = true\n[Branch.Main]\n\tremote = a\n\
+             [Remote \"Up\"]\n\tUrl = y\n[extensions]\n\tworktreeConfig = true"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for data in [
+            "key = outside",
+            "[core]\n\t= value",
+            "[core\n",
+            "[core]\n\tkey = \"open",
+        ] {
+            let mut file = ConfigFile {
+                lines: data.lines().map(str::to_string).collect(),
+                ..ConfigFile::default()
+            };
+            assert!(file.parse().is_none(), "{data:?}");
+        }
+    }
+
+    #[test]
+    fn sets_values_in_place() {
+        let mut file = parse(
+            "[core]\n\tbare = false\n# keep me\n[remote \"origin\"]\n\tfetch = a\n\t\
+             fetch = b\n\turl = long \\\n value\n[core]\n\tdup = 1\n",
+        );
+        file.set_str("remote \"origin\"", "url", "new");
+        file.set_str("core", "worktree", "../w");
+        file.set_str("core", "bare", "true");
+        file.set_str("extensions", "worktreeConfig", " spaced ");
+        assert_eq!(
+            file.lines.join("\n"),
+            "[core]\n\tbare = true\n# keep me\n[remote \"origin\"]\n\tfetch = a\n\t\
+             fetch = b\n\turl = new\n[core]\n\tdup = 1\n\tworktree = ../w\n\
+             [extensions]\n\tworktreeConfig = \" spaced \""
+        );
+        assert_eq!(file.get_string("remote \"origin\"", "fetch").unwrap(), "b");
+        assert_eq!(file.get_string("core", "worktree").unwrap(), "../w");
+        assert_eq!(
+            file.get_string("extensions", "worktreeConfig").unwrap(),
+            " spaced "
+        );
+    }
+}
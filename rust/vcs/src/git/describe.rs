@@ -0,0 +1,222 @@
+use crate::git::object_id::ObjectId;
+use crate::git::objects::{GitObject, GitObjectType};
+use crate::git::repo::Repository;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::{Display, Formatter};
+
+/// How many tags are considered before giving up on finding a closer one.
+const MAX_CANDIDATES: usize = 10;
+
+#[derive(Debug, Default)]
+pub struct DescribeOptions {
+    /// Also use lightweight tags, not only annotated ones.
+    pub tags: bool,
+    /// Always add the distance and commit id, even for a tagged commit.
+    pub long: bool,
+    /// Append this if tracked files in the work tree have changes.
+    pub dirty: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum DescribeError {
+    /// There are no tags at all.
+    NoNames,
+    /// No annotated tag is reachable, but lightweight tags are.
+    NoAnnotatedTags(ObjectId),
+    /// No tag is reachable.
+    NoTags(ObjectId),
+}
+
+impl Display for DescribeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DescribeError::NoNames => write!(f, "No names found, cannot describe anything."),
+            DescribeError::NoAnnotatedTags(id) => write!(
+                f,
+                "No annotated tags can describe '{id}'.\nHowever, there were unannotated tags: try --tags."
+            ),
+            DescribeError::NoTags(id) => write!(
+                f,
+                "No tags can describe '{id}'.\nTry --always, or create some tags."
+            ),
+        }
+    }
+}
+
+/// A tag pointing to a commit.
+struct TagName {
+    name: String,
+    annotated: bool,
+    /// Tagger time of annotated tags.
+    time: i64,
+}
+
+/// A tag found while walking the history and how far away it is.
+struct Candidate {
+    name: String,
+    depth: usize,
+    /// Bit set on the commits reachable from the tagged commit.
+    flag: u32,
+}
+
+impl Repository {
+    /// Name [commit] after the closest tag reachable from it, like
+    /// `v1.0-3-gabc1234` for three commits after `v1.0`, or just the tag if
+    /// it points to [commit].
+    ///
+    /// Like git, the history is walked newest first until all paths are
+    /// covered by the closest tag found, considering up to 10 tags. Among
+    /// tags of the same commit, annotated tags win over lightweight ones and
+    /// newer ones over older ones.
+    pub fn describe(
+        &self,
+        commit: ObjectId,
+        options: &DescribeOptions,
+    ) -> Result<String, DescribeError> {
+        let names = self.tag_names();
+        if names.is_empty() {
+            return Err(DescribeError::NoNames);
+        }
+        let usable = |name: &TagName| options.tags || name.annotated;
+        let dirty = options.dirty.as_ref().filter(|_| {
+            self.status()
+                .is_some_and(|status| status.has_tracked_changes())
+        });
+        let suffix = dirty.map_or("", |dirty| dirty.as_str());
+
+        if let Some(name) = names.get(&commit).filter(|name| usable(name)) {
+            if !options.long {
+                return Ok(format!("{}{suffix}", name.name));
+            }
+        }
+
+        let mut flags = HashMap::from([(commit, 0u32)]);
+        let mut times = HashMap::new();
+        let mut list = BinaryHeap::new();
+        let mut inserted = 0usize;
+        let mut push = |list: &mut BinaryHeap<_>, id: ObjectId| {
+            let time = *times
+                .entry(id)
+                .or_insert_with(|| self.commit_info(&id).map_or(0, |info| info.time));
+            list.push((time, Reverse(inserted), id));
+            inserted += 1;
+        };
+        push(&mut list, commit);
+        let mut candidates = Vec::<Candidate>::new();
+        let mut unannotated = false;
+        let mut annotated_count = 0;
+        let mut seen_commits = 0;
+        let mut gave_up_on = None;
+        while let Some((_, _, id)) = list.pop() {
+            seen_commits += 1;
+            if let Some(name) = names.get(&id) {
+                if !usable(name) {
+                    unannotated = true;
+                } else if candidates.len() < MAX_CANDIDATES {
+                    let flag = 1 << candidates.len();
+                    candidates.push(Candidate {
+                        name: name.name.clone(),
+                        depth: seen_commits - 1,
+                        flag,
+                    });
+                    *flags.entry(id).or_default() |= flag;
+                    if name.annotated {
+                        annotated_count += 1;
+                    }
+                } else {
+                    gave_up_on = Some(id);
+                    break;
+                }
+            }
+            let commit_flags = flags[&id];
+            for candidate in &mut candidates {
+                if commit_flags & candidate.flag == 0 {
+                    candidate.depth += 1;
+                }
+            }
+            // Stop once the last path is covered by the best candidates.
+            if annotated_count > 0 && list.is_empty() {
+                let best_depth = candidates.iter().map(|c| c.depth).min().unwrap_or(0);
+                let best = candidates
+                    .iter()
+                    .filter(|c| c.depth == best_depth)
+                    .fold(0, |flags, c| flags | c.flag);
+                if commit_flags & best == best {
+                    break;
+                }
+            }
+            if annotated_count == MAX_CANDIDATES {
+                break;
+            }
+            for parent in self.parents(id).unwrap_or_default() {
+                if !flags.contains_key(&parent) {
+                    push(&mut list, parent);
+                }
+                *flags.entry(parent).or_default() |= commit_flags;
+            }
+        }
+
+        if candidates.is_empty() {
+            return Err(match unannotated {
+                true => DescribeError::NoAnnotatedTags(commit),
+                false => DescribeError::NoTags(commit),
+            });
+        }
+        // Closest first, the first found among equally close ones.
+        candidates.sort_by_key(|candidate| candidate.depth);
+        if let Some(id) = gave_up_on {
+            push(&mut list, id);
+        }
+        let best = &mut candidates[0];
+        // Count the commits not reachable from the best tag that are left.
+        while let Some((_, _, id)) = list.pop() {
+            let commit_flags = flags[&id];
+            if commit_flags & best.flag != 0 {
+                if list.iter().all(|(_, _, id)| flags[id] & best.flag != 0) {
+                    break;
+                }
+            } else {
+                best.depth += 1;
+            }
+            for parent in self.parents(id).unwrap_or_default() {
+                if !flags.contains_key(&parent) {
+                    push(&mut list, parent);
+                }
+                *flags.entry(parent).or_default() |= commit_flags;
+            }
+        }
+
+        let short = &commit.to_string()[..7];
+        Ok(format!("{}-{}-g{short}{suffix}", best.name, best.depth))
+    }
+
+    /// The best tag of each tagged commit.
+    fn tag_names(&self) -> HashMap<ObjectId, TagName> {
+        let mut names: HashMap<ObjectId, TagName> = HashMap::new();
+        for (name, id) in self.refs() {
+            let Some(short) = name.strip_prefix("refs/tags/") else {
+                continue;
+            };
+            let Some(commit) = self.object_peel(id, GitObjectType::Commit) else {
+                continue;
+            };
+            let tagger = match self.object_read(id) {
+                Some(GitObject::Tag(tag)) => Some(tag.tagger().map_or(0, |tagger| tagger.time)),
+                _ => None,
+            };
+            let tag = TagName {
+                name: short.to_string(),
+                annotated: tagger.is_some(),
+                time: tagger.unwrap_or_default(),
+            };
+            match names.get(&commit) {
+                Some(old) if (old.annotated, old.time) >= (tag.annotated, tag.time) => {}
+                _ => {
+                    names.insert(commit, tag);
+                }
+            }
+        }
+        names
+    }
+}
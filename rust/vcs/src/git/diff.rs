@@ -0,0 +1,274 @@
+use crate::git::attributes::Attributes;
+use crate::git::index::GitIndex;
+use crate::git::object_id::ObjectId;
+use crate::git::objects::GitObjectType;
+use crate::git::repo::Repository;
+use crate::git::status::{work_tree_mode, WorkTreeChange};
+use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+
+/// Lines of unchanged context around changes.
+const CONTEXT_LINES: usize = 3;
+/// Longest function name shown in hunk headers, like git.
+const FUNCNAME_LEN: usize = 80;
+
+/// A file that differs between two states.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileDiff {
+    /// Path relative to the work tree.
+    pub path: String,
+    /// Mode and content before, [None] if the file was added.
+    pub old: Option<(String, ObjectId)>,
+    /// Mode and content after, [None] if the file was deleted.
+    pub new: Option<(String, ObjectId)>,
+}
+
+impl Repository {
+    /// Files that differ between `HEAD` and the index with [staged], else
+    /// between the index and the work tree, sorted by path.
+    pub fn diff_files(&self, staged: bool) -> Option<Vec<FileDiff>> {
+        if staged {
+            return Some(self.diff_head_index());
+        }
+        let index = self.index().unwrap_or_else(|| GitIndex::new(Vec::new()));
+        let staged = index
+            .iter()
+            .filter(|entry| entry.stage() == 0)
+            .map(|entry| {
+                (
+                    entry.name().to_path_buf(),
+                    (entry.mode_str(), *entry.hash()),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        let work_tree = self.work_tree()?;
+        let file_mode = self
+            .config_string("core", "fileMode")
+            .is_none_or(|enabled| enabled != "false");
+        let mut attributes = self.attributes();
+        let mut diffs = Vec::new();
+        for (path, change) in self.work_tree_changes()? {
+            let old = staged.get(&path).cloned();
+            let new = match change {
+                WorkTreeChange::Deleted => None,
+                WorkTreeChange::Modified => {
+                    let full_path = work_tree.join(&path);
+                    let old_mode = old.as_ref().map_or("100644", |(mode, _)| mode.as_str());
+                    if old_mode == "160000" {
+                        let head = Repository::new(full_path, None).ok()?.head()?;
+                        Some((old_mode.to_string(), head))
+                    } else {
+                        let metadata = full_path.symlink_metadata().ok()?;
+                        let mode = work_tree_mode(&metadata, old_mode, file_mode)?;
+                        let indexed = old.as_ref().map(|(_, id)| *id);
+                        let path = path.to_string_lossy();
+                        let data = self.work_tree_data(&mut attributes, &path, indexed)?;
+                        Some((mode, self.object_hash(GitObjectType::Blob, &data)))
+                    }
+                }
+            };
+            diffs.push(FileDiff {
+                path: path.to_string_lossy().to_string(),
+                old,
+                new,
+            });
+        }
+        Some(diffs)
+    }
+
+    fn diff_head_index(&self) -> Vec<FileDiff> {
+        let mut head = self
+            .head()
+            .and_then(|head| self.object_peel(head, GitObjectType::Tree))
+            .and_then(|tree| self.tree_flatten(tree))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(path, mode, id)| (path, (mode, id)))
+            .collect::<BTreeMap<String, (String, ObjectId)>>();
+        let index = self.index().unwrap_or_else(|| GitIndex::new(Vec::new()));
+        let mut diffs = Vec::new();
+        for entry in index.iter().filter(|entry| entry.stage() == 0) {
+            let path = entry.name().to_string_lossy().to_string();
+            let new = Some((entry.mode_str(), *entry.hash()));
+            let old = head.remove(&path);
+            if old != new {
+                diffs.push(FileDiff { path, old, new });
+            }
+        }
+        diffs.extend(head.into_iter().map(|(path, old)| FileDiff {
+            path,
+            old: Some(old),
+            new: None,
+        }));
+        diffs.sort_by(|a, b| a.path.cmp(&b.path));
+        diffs
+    }
+
+    /// [diff] as patch in git's format, with headers and hunks.
+    pub fn diff_patch(&self, diff: &FileDiff) -> Option<String> {
+        let path = &diff.path;
+        let mut out = format!("diff --git a/{path} b/{path}\n");
+        let zero = "0".repeat(7);
+        let short = |side: &Option<(String, ObjectId)>| {
+            side.as_ref()
+                .map_or(zero.clone(), |(_, id)| id.to_string()[..7].to_string())
+        };
+        match (&diff.old, &diff.new) {
+            (None, Some((mode, _))) => writeln!(out, "new file mode {mode}").ok()?,
+            (Some((mode, _)), None) => writeln!(out, "deleted file mode {mode}").ok()?,
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode != new_mode => {
+                writeln!(out, "old mode {old_mode}\nnew mode {new_mode}").ok()?
+            }
+            _ => {}
+        }
+        let old_id = diff.old.as_ref().map(|(_, id)| *id);
+        let new_id = diff.new.as_ref().map(|(_, id)| *id);
+        if old_id == new_id {
+            return Some(out);
+        }
+        write!(out, "index {}..{}", short(&diff.old), short(&diff.new)).ok()?;
+        match (&diff.old, &diff.new) {
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode == new_mode => {
+                writeln!(out, " {old_mode}").ok()?
+            }
+            _ => out.push('\n'),
+        }
+
+        let mut attributes = self.attributes();
+        let old = match &diff.old {
+            Some((mode, id)) => self.diff_data(&mut attributes, path, mode, *id, None)?,
+            None => Vec::new(),
+        };
+        let new = match &diff.new {
+            Some((mode, id)) => self.diff_data(&mut attributes, path, mode, *id, old_id)?,
+            None => Vec::new(),
+        };
+        let old_name = diff
+            .old
+            .as_ref()
+            .map_or("/dev/null".to_string(), |_| format!("a/{path}"));
+        let new_name = diff
+            .new
+            .as_ref()
+            .map_or("/dev/null".to_string(), |_| format!("b/{path}"));
+        if attributes.is_binary(path, &old) || attributes.is_binary(path, &new) {
+            writeln!(out, "Binary files {old_name} and {new_name} differ").ok()?;
+            return Some(out);
+        }
+        let hunks = unified_hunks(
+            &String::from_utf8_lossy(&old),
+            &String::from_utf8_lossy(&new),
+        );
+        if !hunks.is_empty() {
+            writeln!(out, "--- {old_name}\n+++ {new_name}").ok()?;
+            out.push_str(&hunks);
+        }
+        Some(out)
+    }
+
+    /// Content of the file [path] with [mode] and [id] as compared by diff:
+    /// the blob if it is stored, else the work tree file with that content
+    /// (see [Repository::work_tree_data]).
+    fn diff_data(
+        &self,
+        attributes: &mut Attributes,
+        path: &str,
+        mode: &str,
+        id: ObjectId,
+        indexed: Option<ObjectId>,
+    ) -> Option<Vec<u8>> {
+        if mode == "160000" {
+            return Some(format!("Subproject commit {id}\n").into_bytes());
+        }
+        self.blob_data(id)
+            .or_else(|| self.work_tree_data(attributes, path, indexed))
+    }
+
+    /// Content of the work tree file [path] as it would be stored, the
+    /// target of symlinks. Content is converted as [attributes] configure,
+    /// [indexed] is the blob in the index.
+    fn work_tree_data(
+        &self,
+        attributes: &mut Attributes,
+        path: &str,
+        indexed: Option<ObjectId>,
+    ) -> Option<Vec<u8>> {
+        let full_path = self.work_tree()?.join(path);
+        if full_path.is_symlink() {
+            let target = fs::read_link(full_path).ok()?;
+            return Some(target.to_string_lossy().as_bytes().to_vec());
+        }
+        let data = fs::read(full_path).ok()?;
+        let conversion = attributes.conversion(path);
+        self.convert_to_git(path, &conversion, data, indexed)
+    }
+}
+
+/// Hunks of a unified diff from [old] to [new] with git's hunk headers,
+/// including the function context after `@@`.
+fn unified_hunks(old: &str, new: &str) -> String {
+    let diff = TextDiff::from_lines(old, new);
+    let old_lines = old.split_inclusive('\n').collect::<Vec<&str>>();
+    let mut out = String::new();
+    for group in diff.grouped_ops(CONTEXT_LINES) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_start = first.old_range().start;
+        let old_len = last.old_range().end - old_start;
+        let new_start = first.new_range().start;
+        let new_len = last.new_range().end - new_start;
+        let _ = write!(
+            out,
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_len),
+            hunk_range(new_start, new_len)
+        );
+        if let Some(funcname) = funcname(&old_lines[..old_start]) {
+            let _ = write!(out, " {funcname}");
+        }
+        out.push('\n');
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                let sign = match change.tag() {
+                    ChangeTag::Equal => ' ',
+                    ChangeTag::Delete => '-',
+                    ChangeTag::Insert => '+',
+                };
+                out.push(sign);
+                out.push_str(change.value());
+                if change.missing_newline() {
+                    out.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+    }
+    out
+}
+
+/// `<start>,<len>` with 1-based [start], like git empty ranges start at the
+/// line before and a length of 1 is left out.
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+/// The last of [lines] starting like a function definition by git's default
+/// rule: a letter, `_` or `$`.
+fn funcname(lines: &[&str]) -> Option<String> {
+    let line = lines.iter().rev().find(|line| {
+        line.chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+    })?;
+    let mut end = line.len().min(FUNCNAME_LEN);
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(line[..end].trim_end().to_string())
+}
@@ -0,0 +1,311 @@
+use crate::git::repo::Repository;
+use log::warn;
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::thread;
+
+/// Longest data a pkt-line can hold.
+const MAX_PACKET_DATA: usize = 65516;
+
+/// Which way content goes through a filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterDirection {
+    /// From the work tree into the repository.
+    Clean,
+    /// From the repository into the work tree.
+    Smudge,
+}
+
+impl FilterDirection {
+    fn name(self) -> &'static str {
+        match self {
+            FilterDirection::Clean => "clean",
+            FilterDirection::Smudge => "smudge",
+        }
+    }
+}
+
+/// A filter driver configured as `filter.<name>` and selected with the
+/// `filter=<name>` attribute.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterDriver {
+    pub name: String,
+    /// Command filtering one file from stdin to stdout, `%f` is replaced
+    /// by the quoted path.
+    pub clean: Option<String>,
+    pub smudge: Option<String>,
+    /// Long-running command filtering all files, preferred over [clean]
+    /// and [smudge].
+    pub process: Option<String>,
+    /// Failing to filter is an error instead of passing the content on
+    /// unchanged.
+    pub required: bool,
+}
+
+/// Long-running filter processes by driver name, [None] if it failed.
+pub(crate) type FilterProcesses = HashMap<String, Option<FilterProcess>>;
+
+/// A long-running filter process talking git's filter protocol version 2
+/// over pkt-lines.
+pub(crate) struct FilterProcess {
+    child: Child,
+    /// Closed on drop to tell the filter to exit.
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+    capabilities: Vec<String>,
+}
+
+impl FilterProcess {
+    fn start(command: &str, dir: &std::path::Path) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().map(BufReader::new);
+        let (Some(stdin), Some(stdout)) = (stdin, stdout) else {
+            return Err(io::Error::other("filter has no pipes"));
+        };
+        let mut process = FilterProcess {
+            child,
+            stdin: Some(stdin),
+            stdout,
+            capabilities: Vec::new(),
+        };
+        process.handshake()?;
+        Ok(process)
+    }
+
+    fn handshake(&mut self) -> io::Result<()> {
+        self.write_text(&["git-filter-client", "version=2"])?;
+        let welcome = self.read_text()?;
+        if welcome.first().map(String::as_str) != Some("git-filter-server")
+            || !welcome.iter().any(|line| line == "version=2")
+        {
+            return Err(io::Error::other("unsupported filter protocol"));
+        }
+        self.write_text(&["capability=clean", "capability=smudge"])?;
+        self.capabilities = self
+            .read_text()?
+            .into_iter()
+            .filter_map(|line| Some(line.strip_prefix("capability=")?.to_string()))
+            .collect();
+        Ok(())
+    }
+
+    /// Filter [data] of [path] with [command]. [None] if the filter reports
+    /// an error, after `abort` the command isn't requested again.
+    fn filter(&mut self, command: &str, path: &str, data: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        if !self
+            .capabilities
+            .iter()
+            .any(|capability| capability == command)
+        {
+            return Ok(None);
+        }
+        let stdin = self.stdin.as_mut().ok_or(io::ErrorKind::BrokenPipe)?;
+        write_packet(stdin, format!("command={command}\n").as_bytes())?;
+        write_packet(stdin, format!("pathname={path}\n").as_bytes())?;
+        stdin.write_all(b"0000")?;
+        for chunk in data.chunks(MAX_PACKET_DATA) {
+            write_packet(stdin, chunk)?;
+        }
+        stdin.write_all(b"0000")?;
+        stdin.flush()?;
+
+        let mut status = status(&self.read_text()?);
+        let mut content = Vec::new();
+        if status.as_deref() == Some("success") {
+            while let Some(packet) = read_packet(&mut self.stdout)? {
+                content.extend_from_slice(&packet);
+            }
+            // An empty list keeps the status.
+            status = status_or(&self.read_text()?, status);
+        }
+        match status.as_deref() {
+            Some("success") => Ok(Some(content)),
+            Some("abort") => {
+                self.capabilities.retain(|capability| capability != command);
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Send [lines] as text packets followed by a flush.
+    fn write_text(&mut self, lines: &[&str]) -> io::Result<()> {
+        let stdin = self.stdin.as_mut().ok_or(io::ErrorKind::BrokenPipe)?;
+        for line in lines {
+            write_packet(stdin, format!("{line}\n").as_bytes())?;
+        }
+        stdin.write_all(b"0000")?;
+        stdin.flush()
+    }
+
+    /// Read text packets up to the next flush.
+    fn read_text(&mut self) -> io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        while let Some(packet) = read_packet(&mut self.stdout)? {
+            let line = String::from_utf8_lossy(&packet);
+            lines.push(line.trim_end_matches('\n').to_string());
+        }
+        Ok(lines)
+    }
+}
+
+impl Drop for FilterProcess {
+    fn drop(&mut self) {
+        self.stdin.take();
+        let _ = self.child.wait();
+    }
+}
+
+fn status(lines: &[String]) -> Option<String> {
+    status_or(lines, None)
+}
+
+/// The last `status=` of [lines], [previous] if there is none.
+fn status_or(lines: &[String], previous: Option<String>) -> Option<String> {
+    lines
+        .iter()
+        .rev()
+        .find_map(|line| Some(line.strip_prefix("status=")?.to_string()))
+        .or(previous)
+}
+
+fn write_packet(out: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    write!(out, "{:04x}", data.len() + 4)?;
+    out.write_all(data)
+}
+
+/// Read one pkt-line, [None] for a flush packet.
+fn read_packet(input: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    input.read_exact(&mut len)?;
+    let len = std::str::from_utf8(&len)
+        .ok()
+        .and_then(|len| usize::from_str_radix(len, 16).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid pkt-line length"))?;
+    if len == 0 {
+        return Ok(None);
+    }
+    let mut data = vec![0; len.saturating_sub(4)];
+    input.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+impl Repository {
+    /// The driver `filter.<name>`, [None] if it isn't configured.
+    pub fn filter_driver(&self, name: &str) -> Option<FilterDriver> {
+        let section = format!("filter \"{name}\"");
+        let driver = FilterDriver {
+            name: name.to_string(),
+            clean: self.config_string(&section, "clean"),
+            smudge: self.config_string(&section, "smudge"),
+            process: self.config_string(&section, "process"),
+            required: self
+                .config_string(&section, "required")
+                .is_some_and(|required| required == "true"),
+        };
+        let configured = driver.clean.is_some()
+            || driver.smudge.is_some()
+            || driver.process.is_some()
+            || driver.required;
+        configured.then_some(driver)
+    }
+
+    /// Run [data] of the file [path] through [driver] in [direction].
+    ///
+    /// Returns the data unchanged if the driver has no command for
+    /// [direction] or it fails, unless it is required. Long-running filter
+    /// processes are started on first use and kept until the repository is
+    /// dropped.
+    pub fn filter(
+        &self,
+        driver: &FilterDriver,
+        direction: FilterDirection,
+        path: &str,
+        data: Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        let dir = self.work_tree().unwrap_or(self.git_dir());
+        let filtered = if let Some(process) = &driver.process {
+            let mut processes = self.filter_processes.lock().ok()?;
+            let process =
+                processes
+                    .entry(driver.name.clone())
+                    .or_insert_with(|| match FilterProcess::start(process, dir) {
+                        Ok(process) => Some(process),
+                        Err(err) => {
+                            warn!("Failed to start filter '{}': {err}", driver.name);
+                            None
+                        }
+                    });
+            match process
+                .as_mut()
+                .map(|p| p.filter(direction.name(), path, &data))
+            {
+                Some(Ok(filtered)) => filtered,
+                Some(Err(err)) => {
+                    // The process is unusable after a protocol error.
+                    warn!("Filter '{}' failed: {err}", driver.name);
+                    *process = None;
+                    None
+                }
+                None => None,
+            }
+        } else {
+            let command = match direction {
+                FilterDirection::Clean => driver.clean.as_ref(),
+                FilterDirection::Smudge => driver.smudge.as_ref(),
+            };
+            let Some(command) = command else {
+                return (!driver.required).then_some(data);
+            };
+            run_filter(&command.replace("%f", &shell_quote(path)), dir, &data)
+        };
+        match filtered {
+            Some(filtered) => Some(filtered),
+            None if driver.required => {
+                warn!(
+                    "{path}: {} filter '{}' failed",
+                    direction.name(),
+                    driver.name
+                );
+                None
+            }
+            None => Some(data),
+        }
+    }
+}
+
+/// Run the shell [command] in [dir] with [data] on stdin, [None] if it
+/// fails.
+fn run_filter(command: &str, dir: &std::path::Path, data: &[u8]) -> Option<Vec<u8>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let mut stdin = child.stdin.take()?;
+    // Writing on another thread, the filter may not read everything before
+    // writing output.
+    let output = thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(data));
+        child.wait_with_output()
+    })
+    .ok()?;
+    output.status.success().then_some(output.stdout)
+}
+
+/// [value] in single quotes for `sh`.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
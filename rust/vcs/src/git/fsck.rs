@@ -0,0 +1,338 @@
+use crate::git::index::{GitIndex, GitIndexEntry};
+use crate::git::object_id::ObjectId;
+use crate::git::objects::{
+    BinSerializable, GitCommit, GitObjectType, GitTag, GitTree, GitTreeError,
+};
+use crate::git::repo::Repository;
+use flate2::read::ZlibDecoder;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+/// A problem found by [Repository::fsck].
+#[derive(Debug, PartialEq, Eq)]
+pub enum FsckError {
+    /// The object can't be read or has a malformed header.
+    Corrupt { id: ObjectId, reason: String },
+    /// The stored content hashes to a different id.
+    HashMismatch { id: ObjectId, actual: ObjectId },
+    /// A tree with malformed, invalid or unsorted entries.
+    BadTree { id: ObjectId, error: GitTreeError },
+    /// A commit or tag with missing or malformed headers.
+    BadObject {
+        obj_type: GitObjectType,
+        id: ObjectId,
+        reason: String,
+    },
+    /// [from] references [to], which doesn't exist or has another type.
+    BrokenLink {
+        from: (GitObjectType, ObjectId),
+        to: (GitObjectType, ObjectId),
+    },
+    /// A ref or the index points to an object that doesn't exist.
+    InvalidPointer { name: String, id: ObjectId },
+    /// The trailing checksum of a packfile doesn't match its contents.
+    BadPack { path: PathBuf },
+}
+
+impl Display for FsckError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FsckError::Corrupt { id, reason } => write!(f, "object {id} is corrupt: {reason}"),
+            FsckError::HashMismatch { id, actual } => {
+                write!(f, "hash mismatch for {id} (content hashes to {actual})")
+            }
+            FsckError::BadTree { id, error } => write!(f, "in tree {id}: {error}"),
+            FsckError::BadObject {
+                obj_type,
+                id,
+                reason,
+            } => write!(f, "in {} {id}: {reason}", obj_type.name()),
+            FsckError::BrokenLink { from, to } => write!(
+                f,
+                "broken link from {:>6} {}\n              to {:>6} {}",
+                from.0.name(),
+                from.1,
+                to.0.name(),
+                to.1
+            ),
+            FsckError::InvalidPointer { name, id } => {
+                write!(f, "{name}: invalid pointer {id}")
+            }
+            FsckError::BadPack { path } => {
+                write!(f, "{}: pack checksum mismatch", path.display())
+            }
+        }
+    }
+}
+
+/// Result of [Repository::fsck].
+#[derive(Default)]
+pub struct FsckReport {
+    pub errors: Vec<FsckError>,
+    /// Objects not reachable from any ref, `HEAD` or the index, sorted by id.
+    /// The flag tells whether the object is dangling, i.e. not referenced by
+    /// any other object either.
+    pub unreachable: Vec<(GitObjectType, ObjectId, bool)>,
+}
+
+impl Repository {
+    /// Check the integrity of all loose and packed objects and their links
+    /// and find objects that are no longer reachable.
+    pub fn fsck(&self) -> FsckReport {
+        let mut report = FsckReport::default();
+        let format = self.object_format();
+
+        // Only the type and the checked links of each object are kept, the
+        // contents are dropped once checked.
+        let mut objects = BTreeMap::new();
+        for (id, path) in self.loose_objects() {
+            let mut data = Vec::new();
+            let inflated =
+                fs::File::open(path).and_then(|file| ZlibDecoder::new(file).read_to_end(&mut data));
+            if inflated.is_err() {
+                // Leave data empty so the object is reported as corrupt.
+                data.clear();
+            }
+            match parse_loose(&data) {
+                Ok((obj_type, content)) => {
+                    let actual = format.hash(&data);
+                    if actual != id {
+                        report.errors.push(FsckError::HashMismatch { id, actual });
+                    }
+                    let links = check_object(self, id, obj_type, content.to_vec());
+                    objects.insert(id, (obj_type, links));
+                }
+                Err(reason) => report.errors.push(FsckError::Corrupt { id, reason }),
+            }
+        }
+        for pack in self.packs() {
+            match pack.verify_checksum() {
+                Ok(true) => {}
+                _ => report.errors.push(FsckError::BadPack {
+                    path: pack.path().to_path_buf(),
+                }),
+            }
+            for id in pack.ids() {
+                if objects.contains_key(id) {
+                    continue;
+                }
+                match self.object_open(*id).map(|mut reader| {
+                    let mut data = Vec::new();
+                    reader
+                        .read_to_end(&mut data)
+                        .map(|_| (reader.obj_type(), data))
+                }) {
+                    Some(Ok((obj_type, content))) => {
+                        let actual = self.object_hash(obj_type, &content);
+                        if actual != *id {
+                            report
+                                .errors
+                                .push(FsckError::HashMismatch { id: *id, actual });
+                        }
+                        let links = check_object(self, *id, obj_type, content);
+                        objects.insert(*id, (obj_type, links));
+                    }
+                    _ => report.errors.push(FsckError::Corrupt {
+                        id: *id,
+                        reason: "failed to read from pack".to_string(),
+                    }),
+                }
+            }
+        }
+
+        let types = objects
+            .iter()
+            .map(|(id, (obj_type, _))| (*id, *obj_type))
+            .collect::<HashMap<ObjectId, GitObjectType>>();
+        let mut links: HashMap<ObjectId, Vec<ObjectId>> = HashMap::new();
+        for (id, (obj_type, expected)) in objects {
+            let expected = match expected {
+                Ok(expected) => expected,
+                Err(err) => {
+                    report.errors.push(err);
+                    continue;
+                }
+            };
+            for (to_type, to) in expected {
+                if to_type == GitObjectType::Commit && obj_type == GitObjectType::Tree {
+                    // Submodule commits live in another repository.
+                    continue;
+                }
+                if types.get(&to) != Some(&to_type) {
+                    report.errors.push(FsckError::BrokenLink {
+                        from: (obj_type, id),
+                        to: (to_type, to),
+                    });
+                }
+                links.entry(id).or_default().push(to);
+            }
+        }
+
+        let mut roots = self
+            .refs()
+            .into_iter()
+            .chain(self.head().map(|id| ("HEAD".to_string(), id)))
+            .collect::<Vec<(String, ObjectId)>>();
+        if let Some(index) = self.index() {
+            roots.extend(
+                index
+                    .iter()
+                    .filter(|entry| !is_gitlink(entry))
+                    .map(|entry| (format!("index {}", entry.name().display()), *entry.hash())),
+            );
+        }
+        // `HEAD` and index of the other worktrees keep objects alive too.
+        for worktree in self.worktrees() {
+            if worktree.git_dir == self.git_dir() {
+                continue;
+            }
+            let prefix = match worktree.name {
+                Some(name) => format!("worktrees/{name}"),
+                None => "main-worktree".to_string(),
+            };
+            if let Some(head) = worktree.head {
+                roots.push((format!("{prefix}/HEAD"), head));
+            }
+            let index = fs::read(worktree.git_dir.join("index"))
+                .ok()
+                .and_then(|data| GitIndex::decode(&data, self.object_format()));
+            for entry in index
+                .iter()
+                .flat_map(|index| index.iter())
+                .filter(|entry| !is_gitlink(entry))
+            {
+                roots.push((
+                    format!("{prefix}/index {}", entry.name().display()),
+                    *entry.hash(),
+                ));
+            }
+        }
+        let mut reachable = HashSet::new();
+        let mut stack = Vec::new();
+        for (name, id) in roots {
+            if types.contains_key(&id) {
+                stack.push(id);
+            } else {
+                report.errors.push(FsckError::InvalidPointer { name, id });
+            }
+        }
+        while let Some(id) = stack.pop() {
+            if reachable.insert(id) {
+                stack.extend(links.get(&id).into_iter().flatten());
+            }
+        }
+
+        let referenced = links.values().flatten().collect::<HashSet<&ObjectId>>();
+        let mut unreachable = types
+            .iter()
+            .filter(|(id, _)| !reachable.contains(*id))
+            .map(|(id, obj_type)| (*obj_type, *id, !referenced.contains(id)))
+            .collect::<Vec<(GitObjectType, ObjectId, bool)>>();
+        unreachable.sort_by_key(|(_, id, _)| *id);
+        report.unreachable = unreachable;
+        report
+    }
+
+    /// Ids and paths of all loose objects.
+    fn loose_objects(&self) -> Vec<(ObjectId, PathBuf)> {
+        let mut objects = Vec::new();
+        let Ok(dirs) = fs::read_dir(self.common_dir().join("objects")) else {
+            return objects;
+        };
+        for dir in dirs.flatten() {
+            let prefix = dir.file_name().to_string_lossy().to_string();
+            if prefix.len() != 2 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+                continue;
+            }
+            let Ok(files) = fs::read_dir(dir.path()) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let name = format!("{prefix}{}", file.file_name().to_string_lossy());
+                if let Ok(id) = name.parse::<ObjectId>() {
+                    objects.push((id, file.path()));
+                }
+            }
+        }
+        objects
+    }
+}
+
+/// Submodule commits in the index live in another repository.
+fn is_gitlink(entry: &GitIndexEntry) -> bool {
+    entry.mode_str() == "160000"
+}
+
+/// Split an inflated loose object into its type and contents, checking the
+/// header.
+fn parse_loose(data: &[u8]) -> Result<(GitObjectType, &[u8]), String> {
+    let nul = data
+        .iter()
+        .position(|b| *b == 0x00)
+        .ok_or("missing header")?;
+    let header = std::str::from_utf8(&data[..nul]).map_err(|_| "invalid header")?;
+    let (obj_type, size) = header.split_once(' ').ok_or("invalid header")?;
+    let obj_type = GitObjectType::from_name(obj_type).ok_or("unknown type")?;
+    let content = &data[nul + 1..];
+    if size.parse::<usize>().ok() != Some(content.len()) {
+        return Err("size doesn't match header".to_string());
+    }
+    Ok((obj_type, content))
+}
+
+/// Validate a single object and return the objects it references with the
+/// type they must have.
+fn check_object(
+    repo: &Repository,
+    id: ObjectId,
+    obj_type: GitObjectType,
+    content: Vec<u8>,
+) -> Result<Vec<(GitObjectType, ObjectId)>, FsckError> {
+    let bad = |reason: &str| FsckError::BadObject {
+        obj_type,
+        id,
+        reason: reason.to_string(),
+    };
+    match obj_type {
+        GitObjectType::Blob => Ok(Vec::new()),
+        GitObjectType::Tree => {
+            let tree = GitTree::parse_checked(&content, repo.object_format())
+                .map_err(|error| FsckError::BadTree { id, error })?;
+            Ok(tree
+                .entries()
+                .iter()
+                .map(|entry| (entry.obj_type(), *entry.obj_hash()))
+                .collect())
+        }
+        GitObjectType::Commit => {
+            let commit = GitCommit::deserialize(content, repo.object_format());
+            let tree = commit.get_tree().ok_or_else(|| bad("missing tree"))?;
+            commit
+                .get_author()
+                .ok_or_else(|| bad("missing or bad author"))?;
+            commit
+                .get_commiter()
+                .ok_or_else(|| bad("missing or bad committer"))?;
+            Ok(std::iter::once((GitObjectType::Tree, tree))
+                .chain(
+                    commit
+                        .get_parents()
+                        .into_iter()
+                        .map(|parent| (GitObjectType::Commit, parent)),
+                )
+                .collect())
+        }
+        GitObjectType::Tag => {
+            let tag = GitTag::deserialize(content, repo.object_format());
+            let object = tag.object_hash().ok_or_else(|| bad("missing object"))?;
+            let target_type = tag
+                .target_type()
+                .and_then(|name| GitObjectType::from_name(&name))
+                .ok_or_else(|| bad("missing or bad type"))?;
+            Ok(vec![(target_type, object)])
+        }
+    }
+}
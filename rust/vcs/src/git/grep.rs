@@ -0,0 +1,234 @@
+use crate::git::attributes::{buffer_is_binary, AttributeValue};
+use crate::git::object_id::ObjectId;
+use crate::git::objects::GitObjectType;
+use crate::git::repo::Repository;
+use regex::bytes::{Regex, RegexBuilder};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[derive(Debug, Default)]
+pub struct GrepOptions {
+    /// Match letters regardless of case.
+    pub ignore_case: bool,
+    /// Only match whole words.
+    pub word: bool,
+    /// Stop at the first match in each file.
+    pub files_with_matches: bool,
+    /// Search the staged content instead of the work tree.
+    pub cached: bool,
+    /// Only search these files or directories, everything if empty.
+    pub paths: Vec<String>,
+}
+
+/// The matches in one file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GrepFile {
+    pub path: String,
+    /// Binary files only report that they match, without [lines].
+    pub binary: bool,
+    /// Matching lines without line ending and their numbers, from 1.
+    pub lines: Vec<(usize, Vec<u8>)>,
+}
+
+#[derive(Debug)]
+pub enum GrepError {
+    /// The pattern isn't a valid regular expression.
+    InvalidPattern(String),
+    /// The tree-ish doesn't name a tree.
+    InvalidTree(ObjectId),
+    /// Bare repositories have no work tree to search.
+    NoWorkTree,
+}
+
+impl Display for GrepError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrepError::InvalidPattern(err) => write!(f, "invalid pattern: {err}"),
+            GrepError::InvalidTree(id) => write!(f, "unable to read tree {id}"),
+            GrepError::NoWorkTree => write!(f, "this operation must be run in a work tree"),
+        }
+    }
+}
+
+/// Where the content of a searched file comes from.
+enum Source {
+    Blob(ObjectId),
+    WorkTree,
+}
+
+impl Repository {
+    /// Search the regular expression [pattern] in the files of [tree_ish],
+    /// or with [None] in the tracked files of the work tree (the index with
+    /// [GrepOptions::cached]). Returns the files with matches by path.
+    ///
+    /// Files are searched in parallel on as many threads as there are cores.
+    /// Like git, symlinks and submodules are not searched and files with
+    /// `-diff` or NUL bytes count as binary.
+    pub fn grep(
+        &self,
+        pattern: &str,
+        tree_ish: Option<ObjectId>,
+        options: &GrepOptions,
+    ) -> Result<Vec<GrepFile>, GrepError> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .map_err(|err| GrepError::InvalidPattern(err.to_string()))?;
+        let selected = |path: &str| {
+            options.paths.is_empty()
+                || options.paths.iter().any(|spec| {
+                    let spec = spec.trim_end_matches('/');
+                    spec == "." || path == spec || path.starts_with(&format!("{spec}/"))
+                })
+        };
+        let is_file = |mode: &str| mode == "100644" || mode == "100755";
+
+        let (files, mut attributes) = match tree_ish {
+            Some(id) => {
+                let entries = self
+                    .object_peel(id, GitObjectType::Tree)
+                    .and_then(|tree| self.tree_flatten(tree))
+                    .ok_or(GrepError::InvalidTree(id))?;
+                let files = entries
+                    .iter()
+                    .filter(|(path, mode, _)| is_file(mode) && selected(path))
+                    .map(|(path, _, id)| (path.clone(), Source::Blob(*id)))
+                    .collect::<Vec<_>>();
+                (files, self.checkout_attributes(&entries))
+            }
+            None => {
+                if !options.cached && self.work_tree().is_none() {
+                    return Err(GrepError::NoWorkTree);
+                }
+                let mut files = Vec::new();
+                for entry in self.index().iter().flat_map(|index| index.iter()) {
+                    let path = entry.name().to_string_lossy().to_string();
+                    // Conflicted files are searched once.
+                    let seen = files.last().is_some_and(|(last, _)| *last == path);
+                    if seen || !is_file(&entry.mode_str()) || !selected(&path) {
+                        continue;
+                    }
+                    let source = if options.cached {
+                        Source::Blob(*entry.hash())
+                    } else {
+                        Source::WorkTree
+                    };
+                    files.push((path, source));
+                }
+                (files, self.attributes())
+            }
+        };
+        // Attributes can't be shared between threads, look them up first.
+        let diff = files
+            .iter()
+            .map(|(path, _)| attributes.get(path, "diff"))
+            .collect::<Vec<Option<AttributeValue>>>();
+
+        let threads = thread::available_parallelism()
+            .map_or(1, |threads| threads.get())
+            .min(files.len());
+        let next = AtomicUsize::new(0);
+        let mut results = (0..files.len()).map(|_| None).collect::<Vec<_>>();
+        thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some((path, source)) = files.get(i) else {
+                                break;
+                            };
+                            let data = match source {
+                                Source::Blob(id) => self.blob_data(*id),
+                                Source::WorkTree => {
+                                    let work_tree = self.work_tree();
+                                    work_tree.and_then(|dir| fs::read(dir.join(path)).ok())
+                                }
+                            };
+                            let Some(data) = data else {
+                                continue;
+                            };
+                            let binary = match diff[i] {
+                                Some(AttributeValue::Unset) => true,
+                                Some(AttributeValue::Set) => false,
+                                _ => buffer_is_binary(&data),
+                            };
+                            let found = grep_data(&regex, path, &data, binary, options);
+                            done.push((i, found));
+                        }
+                        done
+                    })
+                })
+                .collect::<Vec<_>>();
+            for worker in workers {
+                for (i, found) in worker.join().unwrap_or_default() {
+                    results[i] = found;
+                }
+            }
+        });
+
+        Ok(results.into_iter().flatten().collect())
+    }
+}
+
+/// Search the lines of [data] of the file [path], [None] if nothing
+/// matches.
+fn grep_data(
+    regex: &Regex,
+    path: &str,
+    data: &[u8],
+    binary: bool,
+    options: &GrepOptions,
+) -> Option<GrepFile> {
+    if data.is_empty() {
+        return None;
+    }
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    let mut lines = Vec::new();
+    for (i, line) in data.split(|&byte| byte == b'\n').enumerate() {
+        if !is_match(regex, line, options.word) {
+            continue;
+        }
+        if !binary {
+            lines.push((i + 1, line.to_vec()));
+        }
+        if binary || options.files_with_matches {
+            return Some(GrepFile {
+                path: path.to_string(),
+                binary,
+                lines,
+            });
+        }
+    }
+    (!lines.is_empty()).then(|| GrepFile {
+        path: path.to_string(),
+        binary: false,
+        lines,
+    })
+}
+
+/// Whether [regex] matches [line], with [word] only where the match isn't
+/// next to a word character. Like git, a match that isn't a whole word is
+/// retried one byte after its start.
+fn is_match(regex: &Regex, line: &[u8], word: bool) -> bool {
+    if !word {
+        return regex.is_match(line);
+    }
+    let is_word = |byte: u8| byte.is_ascii_alphanumeric() || byte == b'_';
+    let mut start = 0;
+    while let Some(found) = regex.find_at(line, start) {
+        let before = found.start().checked_sub(1).map(|i| line[i]);
+        let after = line.get(found.end()).copied();
+        if !before.is_some_and(is_word) && !after.is_some_and(is_word) {
+            return true;
+        }
+        if found.start() >= line.len() {
+            break;
+        }
+        start = found.start() + 1;
+    }
+    false
+}
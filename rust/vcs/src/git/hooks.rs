@@ -0,0 +1,72 @@
+use crate::git::repo::{expand_home, Repository};
+use log::warn;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+impl Repository {
+    /// Directory the hooks are in: `core.hooksPath`, relative to the work
+    /// tree, or `hooks` in the common dir.
+    pub fn hooks_dir(&self) -> PathBuf {
+        match self.config_string("core", "hooksPath") {
+            Some(path) => {
+                let base = self.work_tree().unwrap_or(self.git_dir());
+                base.join(expand_home(&path))
+            }
+            None => self.common_dir().join("hooks"),
+        }
+    }
+
+    /// The all zero object id hooks get for missing commits.
+    pub(crate) fn null_id(&self) -> String {
+        "0".repeat(self.object_format().hex_len())
+    }
+
+    /// Run the hook [name] with [args] if it exists and is executable, in
+    /// the root of the work tree (the git dir for bare repositories) with
+    /// stdin closed and its output on stderr.
+    ///
+    /// Returns whether the hook succeeded, true if there is none.
+    ///
+    /// `pre-rebase` is never run, there is no rebase yet.
+    pub fn run_hook(&self, name: &str, args: &[&str]) -> bool {
+        // The hook runs in another directory, relative paths would break.
+        let Ok(hook) = std::path::absolute(self.hooks_dir().join(name)) else {
+            return true;
+        };
+        let index = std::path::absolute(self.git_path("index")).unwrap_or_default();
+        if !is_executable(&hook) {
+            if hook.is_file() {
+                warn!("The '{name}' hook was ignored because it's not set as executable");
+            }
+            return true;
+        }
+        let dir = self.work_tree().unwrap_or(self.git_dir());
+        let status = Command::new(&hook)
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_INDEX_FILE", index)
+            .stdin(Stdio::null())
+            .stdout(io::stderr())
+            .status();
+        match status {
+            Ok(status) => status.success(),
+            Err(err) => {
+                warn!("Failed to run the '{name}' hook: {err}");
+                false
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
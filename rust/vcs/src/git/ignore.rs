@@ -0,0 +1,105 @@
+use crate::git::repo::{expand_home, Repository};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Decides which untracked files are ignored, following `.gitignore` files
+/// in the work tree, `.git/info/exclude` and `core.excludesFile`.
+///
+/// `.gitignore` files are loaded lazily per directory, deeper files take
+/// precedence over higher ones.
+pub struct IgnoreRules {
+    work_tree: PathBuf,
+    /// Parsed `.gitignore` per directory (relative, `""` for the root).
+    dirs: HashMap<String, Option<Gitignore>>,
+    /// `info/exclude` and `core.excludesFile`, highest priority first.
+    global: Vec<Gitignore>,
+}
+
+impl IgnoreRules {
+    /// Whether [path] (relative to the work tree, `/` separated) or one of
+    /// its parent directories is ignored.
+    pub fn is_ignored(&mut self, path: &str, is_dir: bool) -> bool {
+        let parts = path.split('/').collect::<Vec<&str>>();
+        (1..=parts.len()).any(|len| {
+            let is_dir = len < parts.len() || is_dir;
+            self.matches(&parts[..len].join("/"), is_dir)
+        })
+    }
+
+    /// Whether [path] itself matches an ignore pattern.
+    fn matches(&mut self, path: &str, is_dir: bool) -> bool {
+        let full_path = self.work_tree.join(path);
+        let mut dir = path;
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            dir = parent;
+            if let Some(res) = self.dir_match(dir, &full_path, is_dir) {
+                return res;
+            }
+        }
+        if let Some(res) = self.dir_match("", &full_path, is_dir) {
+            return res;
+        }
+        for rules in &self.global {
+            match rules.matched(&full_path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+
+    /// Match against the `.gitignore` in [dir], [None] if it doesn't decide.
+    fn dir_match(&mut self, dir: &str, full_path: &Path, is_dir: bool) -> Option<bool> {
+        let work_tree = &self.work_tree;
+        let rules = self.dirs.entry(dir.to_string()).or_insert_with(|| {
+            let root = work_tree.join(dir);
+            let file = root.join(".gitignore");
+            if !file.is_file() {
+                return None;
+            }
+            let mut builder = GitignoreBuilder::new(root);
+            builder.add(file);
+            builder.build().ok()
+        });
+        match rules.as_ref()?.matched(full_path, is_dir) {
+            Match::Ignore(_) => Some(true),
+            Match::Whitelist(_) => Some(false),
+            Match::None => None,
+        }
+    }
+}
+
+impl Repository {
+    /// Ignore rules of the work tree, [None] for bare repositories.
+    pub fn ignore_rules(&self) -> Option<IgnoreRules> {
+        let work_tree = self.work_tree()?;
+        let mut global = Vec::new();
+        let mut files = vec![self.common_dir().join("info").join("exclude")];
+        match self.config_string("core", "excludesFile") {
+            Some(path) => files.push(expand_home(&path)),
+            None => {
+                let config = std::env::var_os("XDG_CONFIG_HOME")
+                    .map(PathBuf::from)
+                    .or_else(|| dirs::home_dir().map(|home| home.join(".config")));
+                if let Some(config) = config {
+                    files.push(config.join("git").join("ignore"));
+                }
+            }
+        }
+        for file in files.into_iter().filter(|file| file.is_file()) {
+            let mut builder = GitignoreBuilder::new(work_tree);
+            builder.add(file);
+            if let Ok(rules) = builder.build() {
+                global.push(rules);
+            }
+        }
+        Some(IgnoreRules {
+            work_tree: work_tree.to_path_buf(),
+            dirs: HashMap::new(),
+            global,
+        })
+    }
+}
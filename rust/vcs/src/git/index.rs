@@ -1,17 +1,23 @@
-use std::ops::Add;
-use std::path::PathBuf;
+use crate::git::object_id::{ObjectFormat, ObjectId};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::time;
 
 const DIR_CACHE_SIGNATURE: &[u8] = "DIRC".as_bytes();
 const SUPPORTED_INDEX_VERSION: u8 = 2;
+/// Version with a second flags field for entries with the extended flag.
+const EXTENDED_INDEX_VERSION: u8 = 3;
+const SKIP_WORKTREE_FLAG: u16 = 0b0100000000000000;
+const INTENT_TO_ADD_FLAG: u16 = 0b0010000000000000;
 
+#[derive(Clone)]
 pub struct GitIndex {
-    version: u32,
     entries: Vec<GitIndexEntry>,
 }
 
 /// A file entry in a git index.
-struct GitIndexEntry {
+#[derive(Clone)]
+pub struct GitIndexEntry {
     /// The last time a file's metadata changed.
     meta_changed_time: time::Duration,
     /// The last time a file's data changed.
@@ -31,49 +37,57 @@ struct GitIndexEntry {
     /// Size of this object, in bytes.
     fsize: u32,
     /// The object's SHA hash.
-    hash: String,
+    hash: ObjectId,
     flags: GitIndexEntryFlags,
     /// Full fs path of the object.
     name: PathBuf,
 }
 
+#[derive(Clone, Copy)]
 enum ObjectType {
     Regular, // b1000
     Symlink, // b1010
     GitLink, // b1110
 }
 
+#[derive(Clone)]
 struct GitIndexEntryFlags {
     flag_assume_valid: bool,
+    /// Whether the entry has the extended flags below (index version 3).
     flag_extended: bool,
     flag_stage: u16,
+    /// Not checked out because it is outside of the sparse checkout.
+    flag_skip_worktree: bool,
+    /// Added with `git add -N`, the content isn't staged yet.
+    flag_intent_to_add: bool,
     /// Stored in 12 bits so max is 0xFFF(4095). 0xFFF is interpreted as "at
     /// least".
     name_length: u16,
 }
 
 impl GitIndex {
-    pub fn decode(data: &[u8]) -> Option<Self> {
+    /// Decode an index file of a repository using the hash [format].
+    pub fn decode(data: &[u8], format: ObjectFormat) -> Option<Self> {
         let header = &data[..12];
         let header = {
             let signature = &header[..4]; // magic bytes
-            let version = read_int_u32(&header, 4);
-            let obj_count = read_int_u32(&header, 8);
+            let version = read_int_u32(header, 4);
+            let obj_count = read_int_u32(header, 8);
             (signature, version, obj_count)
         };
-        if (header.0 == DIR_CACHE_SIGNATURE) && (header.1 == SUPPORTED_INDEX_VERSION as u32) {
+        let supported = [SUPPORTED_INDEX_VERSION, EXTENDED_INDEX_VERSION].map(u32::from);
+        if (header.0 == DIR_CACHE_SIGNATURE) && supported.contains(&header.1) {
             let data = &data[12..];
             let mut entries = Vec::new();
             let mut idx = 0;
             for _ in 0..header.2 {
-                let ctime = read_time(data, 0);
-                let mtime = read_time(data, 8);
-                let dev = read_int_u32(data, 16);
-                let ino = read_int_u32(data, 20);
-                let unused = read_int_u32(data, 24);
-                //assert_eq!(unused, 0);
-
-                let mode = read_int_u16(data, 26);
+                let ctime = read_time(data, idx);
+                let mtime = read_time(data, idx + 8);
+                let dev = read_int_u32(data, idx + 16);
+                let ino = read_int_u32(data, idx + 20);
+                // 16 unused bits at idx + 24
+
+                let mode = read_int_u16(data, idx + 26);
                 let mode_type = (mode as u32) >> 12;
                 let mode_type = ObjectType::decode(mode_type);
                 let mode_perms = mode & 0b0000000111111111;
@@ -81,10 +95,10 @@ impl GitIndex {
                 let uid = read_int_u32(data, idx + 28);
                 let gid = read_int_u32(data, idx + 32);
                 let fsize = read_int_u32(data, idx + 36);
-                let hash = &data[idx + 40..idx + 60];
-                let hash = hash.iter().map(|byte| format!("{:x}", byte)).collect();
-                let flags = GitIndexEntryFlags::decode(data, idx + 60);
-                idx += 62;
+                let hash_end = idx + 40 + format.raw_len();
+                let hash = ObjectId::from_bytes(&data[idx + 40..hash_end])?;
+                let flags = GitIndexEntryFlags::decode(data, hash_end);
+                idx = hash_end + if flags.flag_extended { 4 } else { 2 };
 
                 let name: &[u8] = if flags.name_length < 0xFFF {
                     let len = flags.name_length as usize;
@@ -122,26 +136,230 @@ impl GitIndex {
                     name: PathBuf::from(name),
                 });
             }
-            Some(GitIndex {
-                version: header.1,
-                entries,
-            })
+            Some(GitIndex { entries })
         } else {
             // Unsupported format
             None
         }
     }
 
+    /// Index containing [entries], sorted the way git stores them.
+    pub fn new(entries: Vec<GitIndexEntry>) -> Self {
+        let mut entries = entries;
+        entries.sort_by(|a, b| {
+            (a.name.as_os_str().as_encoded_bytes(), a.stage())
+                .cmp(&(b.name.as_os_str().as_encoded_bytes(), b.stage()))
+        });
+        GitIndex { entries }
+    }
+
+    /// Encode as index file including the trailing checksum. Version 3 is
+    /// only used if an entry has extended flags, like git does.
+    pub fn encode(&self, format: ObjectFormat) -> Vec<u8> {
+        let mut out = DIR_CACHE_SIGNATURE.to_vec();
+        let version = match self.entries.iter().any(|entry| entry.flags.flag_extended) {
+            true => EXTENDED_INDEX_VERSION,
+            false => SUPPORTED_INDEX_VERSION,
+        };
+        out.extend((version as u32).to_be_bytes());
+        out.extend((self.entries.len() as u32).to_be_bytes());
+        for entry in &self.entries {
+            let start = out.len();
+            for time in [entry.meta_changed_time, entry.data_change_time] {
+                out.extend((time.as_secs() as u32).to_be_bytes());
+                out.extend(time.subsec_nanos().to_be_bytes());
+            }
+            out.extend(entry.dev.to_be_bytes());
+            out.extend(entry.ino.to_be_bytes());
+            let mode = (entry.mode_type.encode() << 12) | entry.mode_perms as u32;
+            out.extend(mode.to_be_bytes());
+            out.extend(entry.uid.to_be_bytes());
+            out.extend(entry.gid.to_be_bytes());
+            out.extend(entry.fsize.to_be_bytes());
+            out.extend(entry.hash.as_bytes());
+            let name = entry.name.as_os_str().as_encoded_bytes();
+            let flags = (entry.flags.flag_assume_valid as u16) << 15
+                | (entry.flags.flag_extended as u16) << 14
+                | (entry.stage() & 0b11) << 12
+                | name.len().min(0xFFF) as u16;
+            out.extend(flags.to_be_bytes());
+            if entry.flags.flag_extended {
+                let mut extended = 0;
+                if entry.flags.flag_skip_worktree {
+                    extended |= SKIP_WORKTREE_FLAG;
+                }
+                if entry.flags.flag_intent_to_add {
+                    extended |= INTENT_TO_ADD_FLAG;
+                }
+                out.extend(extended.to_be_bytes());
+            }
+            out.extend(name);
+            // At least one NUL, padded to a multiple of eight bytes.
+            let len = out.len() - start;
+            out.resize(start + 8 * (len / 8 + 1), 0x00);
+        }
+        let checksum = format.hash(&out);
+        out.extend(checksum.as_bytes());
+        out
+    }
+
+    /// All entries in the order they are stored (sorted by path and stage).
+    pub fn iter(&self) -> std::slice::Iter<'_, GitIndexEntry> {
+        self.entries.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, GitIndexEntry> {
+        self.entries.iter_mut()
+    }
+
     /// Get a list of full paths and hashes of index entries. (path, hash)
-    pub fn entries(&self) -> Vec<(String, String)> {
+    pub fn entries(&self) -> Vec<(String, ObjectId)> {
         self.entries
             .iter()
-            .map(|e| (e.name.to_str().unwrap().to_string(), e.hash.clone()))
+            .map(|e| (e.name.to_str().unwrap().to_string(), e.hash))
             .collect()
     }
 }
 
+impl GitIndexEntry {
+    /// Entry for the file at [name] (relative to the work tree) with content
+    /// [hash] and a tree [mode] like `100644`. Stat data is left empty until
+    /// [GitIndexEntry::update_stat] is called.
+    pub fn new(name: PathBuf, hash: ObjectId, mode: &str) -> Self {
+        let (mode_type, mode_perms) = match mode {
+            "120000" => (ObjectType::Symlink, 0),
+            "160000" => (ObjectType::GitLink, 0),
+            "100755" => (ObjectType::Regular, 0o755),
+            _ => (ObjectType::Regular, 0o644),
+        };
+        let name_length = name.as_os_str().len().min(0xFFF) as u16;
+        GitIndexEntry {
+            meta_changed_time: time::Duration::ZERO,
+            data_change_time: time::Duration::ZERO,
+            dev: 0,
+            ino: 0,
+            mode_type,
+            mode_perms,
+            uid: 0,
+            gid: 0,
+            fsize: 0,
+            hash,
+            flags: GitIndexEntryFlags {
+                flag_assume_valid: false,
+                flag_extended: false,
+                flag_stage: 0,
+                flag_skip_worktree: false,
+                flag_intent_to_add: false,
+                name_length,
+            },
+            name,
+        }
+    }
+
+    /// Remember the file system [metadata] of the work tree file so unchanged
+    /// files don't have to be hashed again.
+    pub fn update_stat(&mut self, metadata: &fs::Metadata) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let time = |secs: i64, nanos: i64| {
+                time::Duration::new(secs.max(0) as u64, nanos.clamp(0, 999_999_999) as u32)
+            };
+            self.meta_changed_time = time(metadata.ctime(), metadata.ctime_nsec());
+            self.data_change_time = time(metadata.mtime(), metadata.mtime_nsec());
+            self.dev = metadata.dev() as u32;
+            self.ino = metadata.ino() as u32;
+            self.uid = metadata.uid();
+            self.gid = metadata.gid();
+        }
+        #[cfg(not(unix))]
+        {
+            if let Ok(modified) = metadata.modified() {
+                self.data_change_time = modified
+                    .duration_since(time::UNIX_EPOCH)
+                    .unwrap_or_default();
+            }
+        }
+        self.fsize = metadata.len() as u32;
+    }
+
+    /// Whether the stat data of the work tree file [metadata] is still the
+    /// one recorded, so the file very likely didn't change.
+    pub fn stat_matches(&self, metadata: &fs::Metadata) -> bool {
+        let mut other = self.clone();
+        other.update_stat(metadata);
+        self.meta_changed_time == other.meta_changed_time
+            && self.data_change_time == other.data_change_time
+            && self.dev == other.dev
+            && self.ino == other.ino
+            && self.fsize == other.fsize
+    }
+
+    /// Whether the file could have been changed in the same second the
+    /// index written at [index_time] was, without the stat data showing it
+    /// ("racy git"). Such entries must be compared by content.
+    pub fn is_racy(&self, index_time: time::Duration) -> bool {
+        self.data_change_time.as_secs() >= index_time.as_secs()
+    }
+
+    /// Clear the recorded size so the stat data no longer matches and the
+    /// content is compared next time. Used for racy entries when the index is
+    /// written, like git does.
+    pub fn smudge(&mut self) {
+        self.fsize = 0;
+    }
+
+    /// Recorded size of the work tree file, 0 if unknown or smudged.
+    pub fn file_size(&self) -> u32 {
+        self.fsize
+    }
+
+    /// Path of the file relative to the work tree.
+    pub fn name(&self) -> &Path {
+        &self.name
+    }
+
+    /// The object's SHA hash.
+    pub fn hash(&self) -> &ObjectId {
+        &self.hash
+    }
+
+    /// Mode as stored in trees, like `100644`, `100755`, `120000` or
+    /// `160000`.
+    pub fn mode_str(&self) -> String {
+        match self.mode_type {
+            ObjectType::Regular => format!("100{:o}", self.mode_perms),
+            ObjectType::Symlink => "120000".to_string(),
+            ObjectType::GitLink => "160000".to_string(),
+        }
+    }
+
+    /// Merge stage, 0 unless the file is conflicted.
+    pub fn stage(&self) -> u16 {
+        self.flags.flag_stage
+    }
+
+    /// Whether the file is left out of the work tree by the sparse checkout
+    /// and must not be taken as deleted.
+    pub fn skip_worktree(&self) -> bool {
+        self.flags.flag_skip_worktree
+    }
+
+    pub fn set_skip_worktree(&mut self, skip: bool) {
+        self.flags.flag_skip_worktree = skip;
+        self.flags.flag_extended = skip || self.flags.flag_intent_to_add;
+    }
+}
+
 impl ObjectType {
+    fn encode(&self) -> u32 {
+        match self {
+            ObjectType::Regular => 0b1000,
+            ObjectType::Symlink => 0b1010,
+            ObjectType::GitLink => 0b1110,
+        }
+    }
+
     fn decode(data: u32) -> Self {
         match data {
             0b1000u32 => ObjectType::Regular,
@@ -157,13 +375,18 @@ impl GitIndexEntryFlags {
         let flags = read_int_u16(data, offset);
         let flag_assume_valid = (flags & 0b1000000000000000) != 0;
         let flag_extended = (flags & 0b0100000000000000) != 0;
-        assert!(!flag_extended);
-        let flag_stage = flags & 0b0011000000000000;
+        let flag_stage = (flags & 0b0011000000000000) >> 12;
         let name_length = flags & 0b0000111111111111;
+        let extended = match flag_extended {
+            true => read_int_u16(data, offset + 2),
+            false => 0,
+        };
         GitIndexEntryFlags {
             flag_assume_valid,
             flag_extended,
             flag_stage,
+            flag_skip_worktree: extended & SKIP_WORKTREE_FLAG != 0,
+            flag_intent_to_add: extended & INTENT_TO_ADD_FLAG != 0,
             name_length,
         }
     }
@@ -189,3 +412,77 @@ fn read_int_u16(data: &[u8], offset: usize) -> u16 {
     res.clone_from_slice(&data[offset..(offset + 2)]);
     u16::from_be_bytes(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::git::index::{GitIndex, GitIndexEntry};
+    use crate::git::object_id::{ObjectFormat, ObjectId};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn encode_round_trips() {
+        let id = "29ff16c9c14e2652b22f8b78bb08a5a07930c147"
+            .parse::<ObjectId>()
+            .unwrap();
+        let index = GitIndex::new(vec![
+            GitIndexEntry::new(PathBuf::from("dir/run.sh"), id, "100755"),
+            GitIndexEntry::new(PathBuf::from("a.txt"), id, "100644"),
+            GitIndexEntry::new(PathBuf::from("link"), id, "120000"),
+        ]);
+        let data = index.encode(ObjectFormat::Sha1);
+        assert_eq!(&data[..4], b"DIRC");
+        // 12 header, three entries padded to 8 bytes, 20 checksum
+        assert_eq!(data.len(), 12 + 72 + 80 + 72 + 20);
+
+        let decoded = GitIndex::decode(&data, ObjectFormat::Sha1).unwrap();
+        let entries = decoded
+            .iter()
+            .map(|entry| (entry.name().to_path_buf(), entry.mode_str(), *entry.hash()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("a.txt"), "100644".to_string(), id),
+                (PathBuf::from("dir/run.sh"), "100755".to_string(), id),
+                (PathBuf::from("link"), "120000".to_string(), id),
+            ]
+        );
+        assert_eq!(decoded.iter().next().unwrap().name(), Path::new("a.txt"));
+    }
+
+    #[test]
+    fn encodes_skip_worktree_as_version_3() {
+        let id = "29ff16c9c14e2652b22f8b78bb08a5a07930c147"
+            .parse::<ObjectId>()
+            .unwrap();
+        let mut skipped = GitIndexEntry::new(PathBuf::from("dir/a.txt"), id, "100644");
+        skipped.set_skip_worktree(true);
+        let index = GitIndex::new(vec![
+            skipped,
+            GitIndexEntry::new(PathBuf::from("b.txt"), id, "100644"),
+        ]);
+        let data = index.encode(ObjectFormat::Sha1);
+        assert_eq!(&data[4..8], &[0, 0, 0, 3]);
+        // The extended entry has two more bytes of flags.
+        assert_eq!(data.len(), 12 + 80 + 72 + 20);
+
+        let decoded = GitIndex::decode(&data, ObjectFormat::Sha1).unwrap();
+        let skipped = decoded
+            .iter()
+            .map(|entry| (entry.name().to_path_buf(), entry.skip_worktree()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            skipped,
+            vec![
+                (PathBuf::from("b.txt"), false),
+                (PathBuf::from("dir/a.txt"), true)
+            ]
+        );
+
+        let mut decoded = decoded;
+        decoded
+            .iter_mut()
+            .for_each(|entry| entry.set_skip_worktree(false));
+        assert_eq!(&decoded.encode(ObjectFormat::Sha1)[4..8], &[0, 0, 0, 2]);
+    }
+}
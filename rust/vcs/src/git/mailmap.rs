@@ -0,0 +1,167 @@
+use crate::git::repo::{expand_home, Repository};
+use std::collections::HashMap;
+use std::fs;
+
+/// Canonical names and emails for the identities in commits, read from
+/// `.mailmap` files.
+#[derive(Debug, Default)]
+pub struct Mailmap {
+    /// Entries by lowercase commit email.
+    entries: HashMap<String, MailmapEntry>,
+}
+
+#[derive(Debug, Default)]
+struct MailmapEntry {
+    /// Replacement for any name with this email.
+    name: Option<String>,
+    email: Option<String>,
+    /// Replacements only for these lowercase commit names.
+    names: HashMap<String, (Option<String>, Option<String>)>,
+}
+
+impl Mailmap {
+    /// Add the lines of a mailmap file, each one of
+    ///
+    /// ```text
+    /// Proper Name <commit@email>
+    /// <proper@email> <commit@email>
+    /// Proper Name <proper@email> <commit@email>
+    /// Proper Name <proper@email> Commit Name <commit@email>
+    /// ```
+    ///
+    /// Later lines override earlier ones, `#` starts a comment.
+    pub fn add(&mut self, data: &str) {
+        for line in data.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((name, email, rest)) = parse_name_and_email(line) else {
+                continue;
+            };
+            let (old_name, old_email) = match parse_name_and_email(rest) {
+                Some((old_name, old_email, _)) => (old_name, Some(old_email)),
+                None => (None, None),
+            };
+            match old_email {
+                Some(old_email) => self.add_mapping(name, Some(email), old_name, old_email),
+                None => self.add_mapping(name, None, old_name, email),
+            }
+        }
+    }
+
+    fn add_mapping(
+        &mut self,
+        name: Option<&str>,
+        email: Option<&str>,
+        old_name: Option<&str>,
+        old_email: &str,
+    ) {
+        let entry = self.entries.entry(old_email.to_lowercase()).or_default();
+        match old_name {
+            Some(old_name) => {
+                let mapping = (name.map(str::to_string), email.map(str::to_string));
+                entry.names.insert(old_name.to_lowercase(), mapping);
+            }
+            None => {
+                if let Some(name) = name {
+                    entry.name = Some(name.to_string());
+                }
+                if let Some(email) = email {
+                    entry.email = Some(email.to_string());
+                }
+            }
+        }
+    }
+
+    /// The canonical name and email for [name] and [email]. Emails and names
+    /// are matched regardless of case.
+    pub fn map(&self, name: &str, email: &str) -> (String, String) {
+        let Some(entry) = self.entries.get(&email.to_lowercase()) else {
+            return (name.to_string(), email.to_string());
+        };
+        let (new_name, new_email) = match entry.names.get(&name.to_lowercase()) {
+            Some((new_name, new_email)) => (new_name, new_email),
+            None => (&entry.name, &entry.email),
+        };
+        (
+            new_name.as_deref().unwrap_or(name).to_string(),
+            new_email.as_deref().unwrap_or(email).to_string(),
+        )
+    }
+}
+
+/// Split `Name <email>` off the start of [line], returning the trimmed
+/// name ([None] if empty), the email and the rest of [line].
+fn parse_name_and_email(line: &str) -> Option<(Option<&str>, &str, &str)> {
+    let (name, rest) = line.split_once('<')?;
+    let (email, rest) = rest.split_once('>')?;
+    let name = name.trim();
+    Some(((!name.is_empty()).then_some(name), email, rest))
+}
+
+impl Repository {
+    /// The mailmap from `.mailmap` in the work tree, the blob
+    /// `mailmap.blob` (`HEAD:.mailmap` in bare repositories) and the file
+    /// `mailmap.file`, in that order.
+    pub fn mailmap(&self) -> Mailmap {
+        let mut mailmap = Mailmap::default();
+        if let Some(work_tree) = self.work_tree() {
+            if let Ok(data) = fs::read_to_string(work_tree.join(".mailmap")) {
+                mailmap.add(&data);
+            }
+        }
+        let blob = self
+            .config_string("mailmap", "blob")
+            .or_else(|| self.is_bare().then(|| "HEAD:.mailmap".to_string()));
+        if let Some(data) = blob
+            .and_then(|blob| self.rev_parse(&blob))
+            .and_then(|id| self.blob_data(id))
+        {
+            mailmap.add(&String::from_utf8_lossy(&data));
+        }
+        if let Some(path) = self.config_string("mailmap", "file") {
+            if let Ok(data) = fs::read_to_string(expand_home(&path)) {
+                mailmap.add(&data);
+            }
+        }
+        mailmap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mailmap;
+
+    #[test]
+    fn maps_names_and_emails() {
+        let mut mailmap = Mailmap::default();
+        mailmap.add(
+            "# Comment\n\
+             Jane Doe <jane@example.com>\n\
+             <joe@example.com> <joe@old.example.com>\n\
+             Jo Proper <jo@example.com> Jo <JO@work.example.com>\n\
+             Jane Doe <jane@example.com> <jane@old.example.com>\n",
+        );
+        let map = |name, email| mailmap.map(name, email);
+        assert_eq!(
+            map("jane", "Jane@Example.com"),
+            ("Jane Doe".into(), "Jane@Example.com".into())
+        );
+        assert_eq!(
+            map("Joe", "joe@old.example.com"),
+            ("Joe".into(), "joe@example.com".into())
+        );
+        assert_eq!(
+            map("jo", "jo@work.example.com"),
+            ("Jo Proper".into(), "jo@example.com".into())
+        );
+        assert_eq!(
+            map("Someone", "jo@work.example.com"),
+            ("Someone".into(), "jo@work.example.com".into())
+        );
+        assert_eq!(
+            map("J", "jane@old.example.com"),
+            ("Jane Doe".into(), "jane@example.com".into())
+        );
+    }
+}
@@ -1,3 +1,33 @@
+pub mod archive;
+pub mod attributes;
+pub mod bisect;
+pub mod blame;
+pub mod checkout;
+pub mod clean;
+pub mod commit;
+pub mod commit_graph;
+pub mod config;
+pub mod describe;
+pub mod diff;
+pub mod filter;
+pub mod fsck;
+pub mod grep;
+pub mod hooks;
+pub mod ignore;
 pub mod index;
+pub mod mailmap;
+pub mod object_id;
+pub mod object_reader;
 pub mod objects;
+pub mod pack;
 pub mod repo;
+pub mod reset;
+pub mod rev;
+pub mod signature;
+pub mod sparse_checkout;
+pub mod stage;
+pub mod status;
+pub mod submodule;
+pub mod verify;
+pub mod walk;
+pub mod worktree;
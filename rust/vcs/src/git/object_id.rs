@@ -0,0 +1,198 @@
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+/// Hash algorithm used to name objects in a repository.
+///
+/// Configured through `extensions.objectformat`, SHA-1 if not set.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ObjectFormat {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl ObjectFormat {
+    /// Parse the value of `extensions.objectformat`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "sha1" => Some(ObjectFormat::Sha1),
+            "sha256" => Some(ObjectFormat::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Name as used in `extensions.objectformat`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectFormat::Sha1 => "sha1",
+            ObjectFormat::Sha256 => "sha256",
+        }
+    }
+
+    /// Length of a binary object id in bytes.
+    pub fn raw_len(&self) -> usize {
+        match self {
+            ObjectFormat::Sha1 => 20,
+            ObjectFormat::Sha256 => 32,
+        }
+    }
+
+    /// Length of a hex formatted object id.
+    pub fn hex_len(&self) -> usize {
+        self.raw_len() * 2
+    }
+
+    /// Hash [data] as is (no object header is added).
+    pub fn hash(&self, data: &[u8]) -> ObjectId {
+        match self {
+            ObjectFormat::Sha1 => ObjectId::Sha1(Sha1::digest(data).into()),
+            ObjectFormat::Sha256 => ObjectId::Sha256(Sha256::digest(data).into()),
+        }
+    }
+}
+
+/// Binary name of a git object.
+///
+/// Ordering is by bytes which is the same as ordering by the hex string.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ObjectId {
+    Sha1([u8; 20]),
+    Sha256([u8; 32]),
+}
+
+impl ObjectId {
+    /// Read a binary id, the format is determined by the length of [bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes.len() {
+            20 => Some(ObjectId::Sha1(bytes.try_into().ok()?)),
+            32 => Some(ObjectId::Sha256(bytes.try_into().ok()?)),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            ObjectId::Sha1(bytes) => bytes,
+            ObjectId::Sha256(bytes) => bytes,
+        }
+    }
+
+    pub fn format(&self) -> ObjectFormat {
+        match self {
+            ObjectId::Sha1(_) => ObjectFormat::Sha1,
+            ObjectId::Sha256(_) => ObjectFormat::Sha256,
+        }
+    }
+
+    /// Whether the hex representation starts with [prefix] (case insensitive).
+    pub fn starts_with_hex(&self, prefix: &str) -> bool {
+        self.to_string().starts_with(&prefix.to_lowercase())
+    }
+}
+
+impl Display for ObjectId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for byte in self.as_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for ObjectId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ObjectId({self})")
+    }
+}
+
+impl FromStr for ObjectId {
+    type Err = ObjectIdParseError;
+
+    /// Parse a full hex id of either format.
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        if hex.len() != ObjectFormat::Sha1.hex_len() && hex.len() != ObjectFormat::Sha256.hex_len()
+        {
+            return Err(ObjectIdParseError::InvalidLength(hex.len()));
+        }
+        let bytes = hex
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                let pair = std::str::from_utf8(pair).map_err(|_| ObjectIdParseError::InvalidHex)?;
+                u8::from_str_radix(pair, 16).map_err(|_| ObjectIdParseError::InvalidHex)
+            })
+            .collect::<Result<Vec<u8>, ObjectIdParseError>>()?;
+        ObjectId::from_bytes(&bytes).ok_or(ObjectIdParseError::InvalidLength(hex.len()))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ObjectIdParseError {
+    /// Hex string is neither 40 (SHA-1) nor 64 (SHA-256) chars long.
+    InvalidLength(usize),
+    /// Contains characters that are not hex digits.
+    InvalidHex,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::object_id::{ObjectFormat, ObjectId, ObjectIdParseError};
+
+    #[test]
+    fn formats_leading_zeros() {
+        let mut bytes = [0u8; 20];
+        bytes[0] = 0x0a;
+        bytes[19] = 0x01;
+        let id = ObjectId::Sha1(bytes);
+        assert_eq!(id.to_string(), "0a00000000000000000000000000000000000001");
+    }
+
+    #[test]
+    fn parses_both_formats() {
+        let sha1 = "0a5e6b2c8f3d1e4f5a6b7c8d9e0f1a2b3c4d5e6f";
+        let id = sha1.parse::<ObjectId>().unwrap();
+        assert_eq!(id.format(), ObjectFormat::Sha1);
+        assert_eq!(id.to_string(), sha1);
+
+        let sha256 = "0a5e6b2c8f3d1e4f5a6b7c8d9e0f1a2b3c4d5e6f0a5e6b2c8f3d1e4f5a6b7c8d";
+        let id = sha256.parse::<ObjectId>().unwrap();
+        assert_eq!(id.format(), ObjectFormat::Sha256);
+        assert_eq!(id.to_string(), sha256);
+
+        assert_eq!(
+            "0a5e".parse::<ObjectId>(),
+            Err(ObjectIdParseError::InvalidLength(4))
+        );
+        assert_eq!(
+            "za5e6b2c8f3d1e4f5a6b7c8d9e0f1a2b3c4d5e6f".parse::<ObjectId>(),
+            Err(ObjectIdParseError::InvalidHex)
+        );
+    }
+
+    #[test]
+    fn hashes_like_git() {
+        // `git hash-object -t blob /dev/null`
+        assert_eq!(
+            ObjectFormat::Sha1.hash(b"blob 0\0").to_string(),
+            "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
+        );
+        // `git --object-format=sha256 hash-object -t blob /dev/null`
+        assert_eq!(
+            ObjectFormat::Sha256.hash(b"blob 0\0").to_string(),
+            "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813"
+        );
+    }
+
+    #[test]
+    fn orders_by_bytes() {
+        let low = "0a00000000000000000000000000000000000000"
+            .parse::<ObjectId>()
+            .unwrap();
+        let high = "a000000000000000000000000000000000000000"
+            .parse::<ObjectId>()
+            .unwrap();
+        assert!(low < high);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::git::object_id::ObjectFormat;
+use crate::git::objects::{GitObject, GitObjectType};
+use flate2::read::ZlibDecoder;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Most bytes reserved up front for the contents, so a corrupt size in the
+/// header can't make the allocation fail.
+const MAX_PREALLOCATED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Streams the contents of an object.
+///
+/// The header is parsed when opening so type and size are known before any
+/// content is read. Reading yields the inflated contents without header.
+pub struct ObjectReader {
+    obj_type: GitObjectType,
+    size: u64,
+    data: Box<dyn Read + Send>,
+}
+
+impl ObjectReader {
+    /// Open the loose object file at [path] and parse its header.
+    pub(crate) fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut data = BufReader::new(ZlibDecoder::new(file));
+
+        let mut obj_type = Vec::new();
+        data.read_until(b' ', &mut obj_type)?;
+        let obj_type = obj_type
+            .strip_suffix(b" ")
+            .ok_or_else(|| invalid("header"))?;
+        let obj_type = std::str::from_utf8(obj_type).map_err(|_| invalid("type"))?;
+        let obj_type = GitObjectType::from_name(obj_type).ok_or_else(|| invalid("type"))?;
+
+        let mut size = Vec::new();
+        data.read_until(0x00, &mut size)?;
+        let size = size
+            .strip_suffix(&[0x00])
+            .ok_or_else(|| invalid("header"))?;
+        let size = std::str::from_utf8(size)
+            .ok()
+            .and_then(|size| size.parse::<u64>().ok())
+            .ok_or_else(|| invalid("size"))?;
+
+        Ok(ObjectReader {
+            obj_type,
+            size,
+            data: Box::new(data.take(size)),
+        })
+    }
+
+    /// Reader over contents already in memory, e.g. from a packfile.
+    pub(crate) fn from_data(obj_type: GitObjectType, data: Vec<u8>) -> Self {
+        ObjectReader {
+            obj_type,
+            size: data.len() as u64,
+            data: Box::new(io::Cursor::new(data)),
+        }
+    }
+
+    pub fn obj_type(&self) -> GitObjectType {
+        self.obj_type
+    }
+
+    /// Size of the contents in bytes as recorded in the header.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Read the remaining contents and parse them as [GitObject].
+    ///
+    /// Fails if the object is shorter than its header claims.
+    pub fn into_object(mut self, format: ObjectFormat) -> io::Result<GitObject> {
+        let mut data = Vec::with_capacity(self.size.min(MAX_PREALLOCATED_SIZE) as usize);
+        self.read_to_end(&mut data)?;
+        if data.len() as u64 != self.size {
+            return Err(invalid("truncated contents"));
+        }
+        GitObject::deserialize(self.obj_type, data, format)
+            .map_err(|error| invalid(&error.to_string()))
+    }
+}
+
+impl Read for ObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.data.read(buf)
+    }
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid loose object: {what}"),
+    )
+}
@@ -1,8 +1,12 @@
-use std::io::{Bytes, Error, Read};
+use crate::git::object_id::{ObjectFormat, ObjectId};
+use crate::git::signature::Signature;
+use std::borrow::Cow;
 
 pub(crate) trait BinSerializable {
     /// Read git object contents without header or compression.
-    fn deserialize(data: Vec<u8>) -> Self;
+    ///
+    /// [format] is the hash algorithm used for binary object ids.
+    fn deserialize(data: Vec<u8>, format: ObjectFormat) -> Self;
     fn serialize(self) -> Vec<u8>;
 }
 
@@ -14,7 +18,7 @@ pub enum GitObject {
 }
 
 /// Like [GitObject], but without data.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
 pub enum GitObjectType {
     Commit,
     Tree,
@@ -22,8 +26,53 @@ pub enum GitObjectType {
     Blob,
 }
 
+impl GitObjectType {
+    /// Name used in object headers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GitObjectType::Commit => "commit",
+            GitObjectType::Tree => "tree",
+            GitObjectType::Tag => "tag",
+            GitObjectType::Blob => "blob",
+        }
+    }
+
+    /// Parse the type name of an object header.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "commit" => Some(GitObjectType::Commit),
+            "tree" => Some(GitObjectType::Tree),
+            "tag" => Some(GitObjectType::Tag),
+            "blob" => Some(GitObjectType::Blob),
+            _ => None,
+        }
+    }
+
+    /// Header of a loose object: `<type> <len>\0`.
+    pub fn header(&self, len: usize) -> Vec<u8> {
+        format!("{} {len}\0", self.name()).into_bytes()
+    }
+}
+
 impl GitObject {
-    /// Serialize a git object including the header and compression.
+    /// Parse object contents (without header or compression) of type
+    /// [obj_type].
+    ///
+    /// Only trees can fail to parse, commits and tags are read leniently.
+    pub fn deserialize(
+        obj_type: GitObjectType,
+        data: Vec<u8>,
+        format: ObjectFormat,
+    ) -> Result<Self, GitTreeError> {
+        Ok(match obj_type {
+            GitObjectType::Commit => GitObject::Commit(GitCommit::deserialize(data, format)),
+            GitObjectType::Tree => GitObject::Tree(GitTree::parse(&data, format)?),
+            GitObjectType::Tag => GitObject::Tag(GitTag::deserialize(data, format)),
+            GitObjectType::Blob => GitObject::Blob(GitBlob::deserialize(data, format)),
+        })
+    }
+
+    /// Serialize the contents of a git object without header or compression.
     pub fn serialize(self) -> Vec<u8> {
         match self {
             GitObject::Commit(commit) => commit.serialize(),
@@ -32,6 +81,25 @@ impl GitObject {
             GitObject::Blob(blob) => blob.serialize(),
         }
     }
+
+    /// Serialize a git object with the `<type> <len>\0` header as it is
+    /// hashed and stored (before compression).
+    pub fn encode(self) -> Vec<u8> {
+        let obj_type = self.object_type();
+        let data = self.serialize();
+        let mut encoded = obj_type.header(data.len());
+        encoded.extend(data);
+        encoded
+    }
+
+    pub fn object_type(&self) -> GitObjectType {
+        match self {
+            GitObject::Commit(_) => GitObjectType::Commit,
+            GitObject::Tree(_) => GitObjectType::Tree,
+            GitObject::Tag(_) => GitObjectType::Tag,
+            GitObject::Blob(_) => GitObjectType::Blob,
+        }
+    }
 }
 
 /// Raw userdata.
@@ -40,13 +108,17 @@ pub struct GitBlob {
 }
 
 impl GitBlob {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
     pub fn data(&self) -> &Vec<u8> {
         &self.data
     }
 }
 
 impl BinSerializable for GitBlob {
-    fn deserialize(data: Vec<u8>) -> Self {
+    fn deserialize(data: Vec<u8>, _format: ObjectFormat) -> Self {
         Self { data }
     }
 
@@ -58,50 +130,72 @@ impl BinSerializable for GitBlob {
 // https://wyag.thb.lt/#orgfe2859f
 pub struct GitCommit {
     kvlm: Vec<(String, String)>,
+    /// Contents as stored, the signed payload is taken from these.
+    raw: Vec<u8>,
+    format: ObjectFormat,
 }
 
 impl GitCommit {
-    // TODO: constructor (no setters as immutable)
+    /// Create a commit of [tree] on top of [parents].
+    pub fn new(
+        tree: ObjectId,
+        parents: Vec<ObjectId>,
+        author: Signature,
+        committer: Signature,
+        message: String,
+    ) -> Self {
+        let mut kvlm = vec![("tree".to_string(), tree.to_string())];
+        for parent in parents {
+            kvlm.push(("parent".to_string(), parent.to_string()));
+        }
+        kvlm.push(("author".to_string(), author.to_string()));
+        kvlm.push(("committer".to_string(), committer.to_string()));
+        kvlm.push(("__message__".to_string(), message));
+        GitCommit {
+            raw: kvlm_serialize(kvlm.clone()),
+            kvlm,
+            format: tree.format(),
+        }
+    }
 
     /// Reference to a tree object.
-    pub fn get_tree(&self) -> Option<String> {
+    pub fn get_tree(&self) -> Option<ObjectId> {
         self.kvlm
             .iter()
             .filter(|(k, _)| k == "tree")
-            .map(|(_k, v)| v.trim_matches(|e| e == '\n').to_string())
-            .next()
+            .find_map(|(_k, v)| v.trim().parse().ok())
     }
     /// References to commits this commit is based on.
     ///
     /// - merge commits may have multiple
     /// - the first commit may have none
-    pub fn get_parents(&self) -> Vec<String> {
+    pub fn get_parents(&self) -> Vec<ObjectId> {
         self.kvlm
             .iter()
             .filter(|(k, _)| k == "parent")
-            .map(|(_k, v)| v.trim_matches(|e| e == '\n').to_string())
-            .collect::<Vec<String>>()
+            .filter_map(|(_k, v)| v.trim().parse().ok())
+            .collect::<Vec<ObjectId>>()
     }
-    /// Like: `Scott Chacon <schacon@gmail.com> 1243040974 -0700`
-    pub fn get_author(&self) -> Option<String> {
+    /// Stored like: `Scott Chacon <schacon@gmail.com> 1243040974 -0700`
+    pub fn get_author(&self) -> Option<Signature> {
         self.kvlm
             .iter()
             .filter(|(k, _)| k == "author")
-            .map(|(_k, v)| v.trim_matches(|e| e == '\n').to_string())
-            .next()
+            .find_map(|(_k, v)| v.parse().ok())
     }
-    pub fn get_commiter(&self) -> Option<String> {
+    pub fn get_commiter(&self) -> Option<Signature> {
         self.kvlm
             .iter()
             .filter(|(k, _)| k == "committer")
-            .map(|(_k, v)| v.trim_matches(|e| e == '\n').to_string())
-            .next()
+            .find_map(|(_k, v)| v.parse().ok())
     }
-    /// PGP signature of the object.
+    /// PGP signature of the object, from the `gpgsig` header in SHA-1
+    /// repositories and from `gpgsig-sha256` in SHA-256 ones.
     pub fn get_gpgsig(&self) -> Option<String> {
+        let header = signature_header(self.format);
         self.kvlm
             .iter()
-            .filter(|(k, _)| k == "gpgsig")
+            .filter(|(k, _)| k == header)
             .map(|(_k, v)| v.trim_matches(|e| e == '\n').to_string())
             .next()
     }
@@ -112,12 +206,51 @@ impl GitCommit {
             .map(|(_k, v)| v.trim_matches(|e| e == '\n').to_string())
             .next()
     }
+
+    /// Split a signed commit into the data that was signed (the commit
+    /// as stored without its signature headers) and the armored signature.
+    pub fn signed_payload(&self) -> Option<(Vec<u8>, String)> {
+        let signature = self.get_gpgsig()?;
+        let mut payload = Vec::with_capacity(self.raw.len());
+        let mut lines = self.raw.split_inclusive(|byte| *byte == b'\n');
+        let mut in_signature = false;
+        for line in lines.by_ref() {
+            if line == b"\n" {
+                payload.extend_from_slice(line);
+                break;
+            }
+            if !(in_signature && line.starts_with(b" ")) {
+                in_signature = SIGNATURE_HEADERS.iter().any(|header| {
+                    line.strip_prefix(header.as_bytes())
+                        .is_some_and(|rest| rest.starts_with(b" "))
+                });
+            }
+            if !in_signature {
+                payload.extend_from_slice(line);
+            }
+        }
+        lines.for_each(|line| payload.extend_from_slice(line));
+        Some((payload, format!("{signature}\n")))
+    }
+}
+
+/// Commit headers holding a signature, for each object format.
+const SIGNATURE_HEADERS: [&str; 2] = ["gpgsig", "gpgsig-sha256"];
+
+/// The commit header holding the signature in repositories using [format].
+fn signature_header(format: ObjectFormat) -> &'static str {
+    match format {
+        ObjectFormat::Sha1 => SIGNATURE_HEADERS[0],
+        ObjectFormat::Sha256 => SIGNATURE_HEADERS[1],
+    }
 }
 
 impl BinSerializable for GitCommit {
-    fn deserialize(data: Vec<u8>) -> Self {
+    fn deserialize(data: Vec<u8>, format: ObjectFormat) -> Self {
         GitCommit {
-            kvlm: kvlm_parse(data.bytes()),
+            kvlm: kvlm_parse(&data),
+            raw: data,
+            format,
         }
     }
 
@@ -129,24 +262,38 @@ impl BinSerializable for GitCommit {
 #[derive(Clone)]
 pub struct GitTag {
     kvlm: Vec<(String, String)>,
+    /// Contents as stored, the signed payload is taken from these.
+    raw: Vec<u8>,
 }
 
 impl GitTag {
-    pub fn new(obj_hash: String, tag_name: String, tagger: String, message: String) -> Self {
-        let mut kvlm = Vec::new();
-        kvlm.push(("object".to_string(), obj_hash));
-        kvlm.push(("type".to_string(), "commit".to_string()));
-        kvlm.push(("tag".to_string(), tag_name));
-        kvlm.push(("tagger".to_string(), tagger));
-        kvlm.push(("__message__".to_string(), message));
-        GitTag { kvlm }
+    pub fn new(obj_hash: ObjectId, tag_name: String, tagger: Signature, message: String) -> Self {
+        let kvlm = vec![
+            ("object".to_string(), obj_hash.to_string()),
+            ("type".to_string(), "commit".to_string()),
+            ("tag".to_string(), tag_name),
+            ("tagger".to_string(), tagger.to_string()),
+            ("__message__".to_string(), message),
+        ];
+        GitTag {
+            raw: kvlm_serialize(kvlm.clone()),
+            kvlm,
+        }
     }
 
-    pub fn object_hash(&self) -> Option<String> {
+    pub fn object_hash(&self) -> Option<ObjectId> {
         self.kvlm
             .iter()
             .filter(|(k, _)| k == "object")
-            .map(|(_k, v)| v.trim_matches(|e| e == '\n').to_string())
+            .find_map(|(_k, v)| v.trim().parse().ok())
+    }
+
+    /// Type name of the tagged object.
+    pub fn target_type(&self) -> Option<String> {
+        self.kvlm
+            .iter()
+            .filter(|(k, _)| k == "type")
+            .map(|(_k, v)| v.trim().to_string())
             .next()
     }
 
@@ -159,12 +306,11 @@ impl GitTag {
             .next()
     }
 
-    pub fn tagger(&self) -> Option<String> {
+    pub fn tagger(&self) -> Option<Signature> {
         self.kvlm
             .iter()
             .filter(|(k, _)| k == "tagger")
-            .map(|(_k, v)| v.trim_matches(|e| e == '\n').to_string())
-            .next()
+            .find_map(|(_k, v)| v.parse().ok())
     }
 
     pub fn get_message(&self) -> Option<String> {
@@ -174,12 +320,46 @@ impl GitTag {
             .map(|(_k, v)| v.trim_matches(|e| e == '\n').to_string())
             .next()
     }
+
+    /// Split a signed tag into the data that was signed (the tag as stored
+    /// up to the signature) and the armored signature appended to the
+    /// message.
+    pub fn signed_payload(&self) -> Option<(Vec<u8>, String)> {
+        let message = self
+            .raw
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .map(|i| i + 2)?;
+        let start = self.raw[message..]
+            .split_inclusive(|byte| *byte == b'\n')
+            .scan(message, |offset, line| {
+                let start = *offset;
+                *offset += line.len();
+                Some((start, line))
+            })
+            .find(|(_, line)| {
+                SIGNATURE_MARKERS
+                    .iter()
+                    .any(|marker| line.starts_with(marker.as_bytes()))
+            })
+            .map(|(start, _)| start)?;
+        let signature = String::from_utf8_lossy(&self.raw[start..]).to_string();
+        Some((self.raw[..start].to_vec(), signature))
+    }
 }
 
+/// Start of signatures appended to tag messages.
+const SIGNATURE_MARKERS: [&str; 3] = [
+    "-----BEGIN PGP SIGNATURE-----",
+    "-----BEGIN PGP MESSAGE-----",
+    "-----BEGIN SSH SIGNATURE-----",
+];
+
 impl BinSerializable for GitTag {
-    fn deserialize(data: Vec<u8>) -> Self {
+    fn deserialize(data: Vec<u8>, _format: ObjectFormat) -> Self {
         GitTag {
-            kvlm: kvlm_parse(data.bytes()),
+            kvlm: kvlm_parse(&data),
+            raw: data,
         }
     }
 
@@ -196,80 +376,235 @@ impl GitTree {
     pub fn entries(&self) -> &Vec<GitTreeEntry> {
         self.entries.as_ref()
     }
-}
 
-impl BinSerializable for GitTree {
-    fn deserialize(data: Vec<u8>) -> Self {
-        let mut data = data.bytes().peekable();
+    /// Parse tree contents, failing on entries that are cut off or have a
+    /// mode longer than 6 bytes. Names are kept as they are, bytes that
+    /// aren't UTF-8 included, so the tree serializes to the same object.
+    pub fn parse(data: &[u8], format: ObjectFormat) -> Result<Self, GitTreeError> {
         let mut entries = Vec::new();
-        while data.peek().is_some() {
-            entries.push(GitTreeEntry::parse(&mut data));
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (entry, next) = GitTreeEntry::parse(rest, format)?;
+            entries.push(entry);
+            rest = next;
         }
-        GitTree { entries }
+        Ok(GitTree { entries })
+    }
+
+    /// Parse tree contents like [GitTree::parse], but also fail on invalid
+    /// modes or names and entries not in git's order instead of accepting
+    /// them.
+    pub fn parse_checked(data: &[u8], format: ObjectFormat) -> Result<Self, GitTreeError> {
+        let tree = GitTree::parse(data, format)?;
+        for (i, entry) in tree.entries.iter().enumerate() {
+            let mode = entry.mode_str();
+            if !TREE_MODES.contains(&mode.as_str()) {
+                return Err(GitTreeError::InvalidMode(mode));
+            }
+            let name = entry.name();
+            if name.is_empty() || name == b"." || name == b".." || name.contains(&b'/') {
+                return Err(GitTreeError::InvalidName(entry.path().to_string()));
+            }
+            if let Some(last) = i.checked_sub(1).map(|last| &tree.entries[last]) {
+                if last.name == entry.name {
+                    return Err(GitTreeError::DuplicateName(entry.path().to_string()));
+                }
+                if last.sort_key() >= entry.sort_key() {
+                    return Err(GitTreeError::NotSorted(entry.path().to_string()));
+                }
+            }
+        }
+        Ok(tree)
     }
+}
 
-    fn serialize(self) -> Vec<u8> {
-        todo!()
+impl GitTree {
+    /// Serialize the contents of the tree without header or compression.
+    pub(crate) fn serialize(self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for entry in self.entries {
+            out.extend_from_slice(entry.mode_str().as_bytes());
+            out.push(b' ');
+            out.extend_from_slice(&entry.name);
+            out.push(0x00);
+            out.extend_from_slice(entry.obj_hash.as_bytes());
+        }
+        out
+    }
+}
+
+/// Modes git accepts in trees.
+const TREE_MODES: [&str; 5] = ["100644", "100755", "120000", "160000", "40000"];
+
+/// Collects entries for a new [GitTree].
+///
+/// Entries are validated on insertion and sorted the way git sorts them when
+/// building, so the serialized tree hashes the same as one written by git.
+#[derive(Default)]
+pub struct GitTreeBuilder {
+    entries: Vec<GitTreeEntry>,
+}
+
+impl GitTreeBuilder {
+    pub fn new() -> Self {
+        GitTreeBuilder::default()
+    }
+
+    /// Add an entry named [path] with a [mode] like `100644` or `40000`.
+    ///
+    /// [path] is a single file or directory name, not a path with slashes.
+    pub fn insert(
+        &mut self,
+        mode: &str,
+        path: String,
+        obj_hash: ObjectId,
+    ) -> Result<(), GitTreeError> {
+        let mode = if mode == "040000" { "40000" } else { mode };
+        if !TREE_MODES.contains(&mode) {
+            return Err(GitTreeError::InvalidMode(mode.to_string()));
+        }
+        if path.is_empty() || path == "." || path == ".." || path.contains(['/', '\0']) {
+            return Err(GitTreeError::InvalidName(path));
+        }
+        if self
+            .entries
+            .iter()
+            .any(|entry| entry.name == path.as_bytes())
+        {
+            return Err(GitTreeError::DuplicateName(path));
+        }
+        self.entries.push(GitTreeEntry::new(mode, path, obj_hash));
+        Ok(())
+    }
+
+    pub fn build(mut self) -> GitTree {
+        self.entries.sort_by_key(|entry| entry.sort_key());
+        GitTree {
+            entries: self.entries,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GitTreeError {
+    /// Mode is none of `100644`, `100755`, `120000`, `160000` or `40000`.
+    InvalidMode(String),
+    /// Empty, `.`, `..` or containing `/` or NUL.
+    InvalidName(String),
+    /// An entry with the same name was already added.
+    DuplicateName(String),
+    /// The entry comes before the previous one in git's tree order.
+    NotSorted(String),
+    /// The data ends in the middle of an entry.
+    Truncated,
+}
+
+impl std::fmt::Display for GitTreeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitTreeError::InvalidMode(mode) => write!(f, "invalid mode {mode}"),
+            GitTreeError::InvalidName(name) => write!(f, "invalid name '{name}'"),
+            GitTreeError::DuplicateName(name) => write!(f, "duplicate entry '{name}'"),
+            GitTreeError::NotSorted(name) => write!(f, "not properly sorted at '{name}'"),
+            GitTreeError::Truncated => write!(f, "truncated entry"),
+        }
     }
 }
 
 pub struct GitTreeEntry {
     /// Hash of a tree or a blob.
-    obj_hash: String,
+    obj_hash: ObjectId,
     /// File perm mode.
     mode: [u8; 6],
-    /// File or dir name.
-    path: String,
+    /// File or dir name, usually but not necessarily UTF-8.
+    name: Vec<u8>,
 }
 
 impl GitTreeEntry {
-    /// Decodes format: `[mode] space [path] 0x00 [sha-1]`.
-    fn parse(data: &mut impl Iterator<Item = Result<u8, Error>>) -> Self {
-        let mut mode: [u8; 6] = [0; 6];
-        let mut i = 0;
-        while let Some(Ok(byte)) = data.next() {
-            if byte == b' ' {
-                break;
-            }
-            println!("{}", char::from(byte));
-            if i >= mode.len() {
-                println!("{} >= {}", i, mode.len());
-                panic!("Incorrectly formatted git tree entry: mode too long")
-            }
-            mode[i] = byte;
-            i += 1;
+    /// Create an entry named [path] with a [mode] like `100644` or `40000`.
+    fn new(mode: &str, path: String, obj_hash: ObjectId) -> Self {
+        let mut mode_bytes = [0u8; 6];
+        for (i, byte) in mode.bytes().take(mode_bytes.len()).enumerate() {
+            mode_bytes[i] = byte;
         }
-
-        let mut path = String::new();
-        while let Some(Ok(byte)) = data.next() {
-            if byte == 0x00 {
-                break;
-            }
-            path.push(char::from(byte));
+        GitTreeEntry {
+            obj_hash,
+            mode: mode_bytes,
+            name: path.into_bytes(),
         }
+    }
 
-        let hash = data.take(40); // 2 chars per hex byte
-        let hash = hash.map(|e| e.unwrap());
-        let hash = String::from_utf8(hash.collect::<Vec<u8>>());
-
-        GitTreeEntry {
-            mode,
-            path,
-            obj_hash: hash.expect("invlaid hash"),
+    /// Decodes format: `[mode] space [path] 0x00 [binary object id]` from the
+    /// start of [data] and returns the entry and the data after it.
+    fn parse(data: &[u8], format: ObjectFormat) -> Result<(Self, &[u8]), GitTreeError> {
+        let space = data
+            .iter()
+            .position(|b| *b == b' ')
+            .ok_or(GitTreeError::Truncated)?;
+        let mut mode = [0u8; 6];
+        if space > mode.len() {
+            let mode = String::from_utf8_lossy(&data[..space]).to_string();
+            return Err(GitTreeError::InvalidMode(mode));
         }
+        mode[..space].copy_from_slice(&data[..space]);
+        let rest = &data[space + 1..];
+        let nul = rest
+            .iter()
+            .position(|b| *b == 0x00)
+            .ok_or(GitTreeError::Truncated)?;
+        let name = rest[..nul].to_vec();
+        let rest = &rest[nul + 1..];
+        if rest.len() < format.raw_len() {
+            return Err(GitTreeError::Truncated);
+        }
+        let obj_hash =
+            ObjectId::from_bytes(&rest[..format.raw_len()]).expect("length checked above");
+        let entry = GitTreeEntry {
+            obj_hash,
+            mode,
+            name,
+        };
+        Ok((entry, &rest[format.raw_len()..]))
     }
 
     /// Hash of a tree or a blob.
-    pub fn obj_hash(&self) -> &String {
+    pub fn obj_hash(&self) -> &ObjectId {
         &self.obj_hash
     }
     /// File perm mode.
     pub fn mode(&self) -> [u8; 6] {
         self.mode
     }
-    /// File or dir name.
-    pub fn path(&self) -> &String {
-        &self.path
+    /// File perm mode as written in the tree, like `100644` or `40000`.
+    pub fn mode_str(&self) -> String {
+        String::from_utf8_lossy(&self.mode)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+    /// Key for git's tree order: directories sort as if their name ended in
+    /// `/`, so `foo.txt` < `foo/` < `foo0`.
+    fn sort_key(&self) -> Vec<u8> {
+        let mut key = self.name.clone();
+        if self.obj_type() == GitObjectType::Tree {
+            key.push(b'/');
+        }
+        key
+    }
+    /// Type of the referenced object as implied by the mode.
+    pub fn obj_type(&self) -> GitObjectType {
+        match self.mode_str().as_str() {
+            "40000" | "040000" => GitObjectType::Tree,
+            "160000" => GitObjectType::Commit,
+            _ => GitObjectType::Blob,
+        }
+    }
+    /// File or dir name, bytes that aren't UTF-8 replaced.
+    pub fn path(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.name)
+    }
+    /// File or dir name as stored in the tree.
+    pub fn name(&self) -> &[u8] {
+        &self.name
     }
 }
 
@@ -277,20 +612,11 @@ impl GitTreeEntry {
 ///
 /// Returns keys and values in the order they were parsed and the message with a
 /// `__message__` key.
-fn kvlm_parse(mut raw: Bytes<&[u8]>) -> Vec<(String, String)> {
+fn kvlm_parse(raw: &[u8]) -> Vec<(String, String)> {
     // https://git-scm.com/book/en/v2/Git-Internals-Git-Objects#_git_commit_objects
-    let mut lines = Vec::new();
-    {
-        let mut line = String::new();
-        while let Some(Ok(byte)) = raw.next() {
-            line.push(char::from(byte));
-            if byte == b'\n' {
-                lines.push(line);
-                line = String::new();
-            }
-        }
-        lines.push(line);
-    }
+    let lines = raw
+        .split_inclusive(|byte| *byte == b'\n')
+        .map(|line| String::from_utf8_lossy(line).to_string());
 
     let mut kv_entries = Vec::new();
     let mut in_message_block = false;
@@ -300,7 +626,7 @@ fn kvlm_parse(mut raw: Bytes<&[u8]>) -> Vec<(String, String)> {
         for line in lines {
             if in_message_block {
                 value.push_str(line.as_str());
-            } else if line.starts_with(" ") || line.is_empty() {
+            } else if line.starts_with(' ') || line.is_empty() {
                 // Value continues in this line
                 value.push_str(line.as_str());
             } else if line == "\n" {
@@ -320,7 +646,7 @@ fn kvlm_parse(mut raw: Bytes<&[u8]>) -> Vec<(String, String)> {
                     value = String::new();
                     key = None;
                 }
-                if let Some((k, v)) = line.split_once(" ") {
+                if let Some((k, v)) = line.split_once(' ') {
                     key = Some(k.to_string());
                     assert!(value.is_empty());
                     value = v.to_string();
@@ -336,25 +662,34 @@ fn kvlm_parse(mut raw: Bytes<&[u8]>) -> Vec<(String, String)> {
     kv_entries
 }
 
+/// Inverse of [kvlm_parse].
+///
+/// Values may or may not end with the line break [kvlm_parse] keeps.
 fn kvlm_serialize(kvlm: Vec<(String, String)>) -> Vec<u8> {
-    // TODO: test
     let mut out = String::new();
+    let mut message = None;
     for (k, v) in kvlm {
         if k == "__message__" {
-            out.push_str(format!("\n{v}").as_str())
+            message = Some(v);
         } else {
-            let v = v.replace("\n", "\n ");
-            out.push_str(format!("{k} {v} \n").as_str());
+            let v = v.strip_suffix('\n').unwrap_or(&v).replace('\n', "\n ");
+            out.push_str(format!("{k} {v}\n").as_str());
         }
     }
-    out.as_bytes().to_vec()
+    if let Some(message) = message {
+        out.push('\n');
+        out.push_str(&message);
+    }
+    out.into_bytes()
 }
 
 #[cfg(test)]
 mod tests {
-    use std::io::Read;
-
-    use crate::git::objects::{kvlm_parse, BinSerializable, GitCommit, GitTree};
+    use crate::git::object_id::{ObjectFormat, ObjectId};
+    use crate::git::objects::{
+        kvlm_parse, kvlm_serialize, BinSerializable, GitCommit, GitObject, GitObjectType, GitTree,
+        GitTreeBuilder, GitTreeError,
+    };
 
     const SAMPLE_COMMIT: &str = "tree 29ff16c9c14e2652b22f8b78bb08a5a07930c147
 parent 206941306e8a8af65b66eaaaea388a7ae24d49a0
@@ -379,11 +714,11 @@ Create first draft";
 
     #[test]
     fn kvlm_parses() {
-        let parsed = kvlm_parse(SAMPLE_COMMIT.as_bytes().bytes());
+        let parsed = kvlm_parse(SAMPLE_COMMIT.as_bytes());
         // TODO: verify \n is wanted
-        assert_eq!(parsed.get(0).unwrap().0, "tree");
+        assert_eq!(parsed.first().unwrap().0, "tree");
         assert_eq!(
-            parsed.get(0).unwrap().1,
+            parsed.first().unwrap().1,
             "29ff16c9c14e2652b22f8b78bb08a5a07930c147\n"
         );
         assert_eq!(parsed.get(1).unwrap().0, "parent");
@@ -412,25 +747,39 @@ Create first draft";
         assert_eq!(parsed.get(5).unwrap().1, "Create first draft");
     }
 
+    #[test]
+    fn kvlm_round_trips() {
+        let parsed = kvlm_parse(SAMPLE_COMMIT.as_bytes());
+        assert_eq!(kvlm_serialize(parsed), SAMPLE_COMMIT.as_bytes());
+    }
+
+    #[test]
+    fn git_object_encode_adds_header() {
+        let commit = GitCommit::deserialize(SAMPLE_COMMIT.as_bytes().to_vec(), ObjectFormat::Sha1);
+        let encoded = GitObject::Commit(commit).encode();
+        let header = format!("commit {}\0", SAMPLE_COMMIT.len());
+        assert!(encoded.starts_with(header.as_bytes()));
+        assert_eq!(&encoded[header.len()..], SAMPLE_COMMIT.as_bytes());
+    }
+
     #[test]
     fn git_commit_deserialize() {
-        let commit = GitCommit::deserialize(SAMPLE_COMMIT.as_bytes().to_vec());
+        let commit = GitCommit::deserialize(SAMPLE_COMMIT.as_bytes().to_vec(), ObjectFormat::Sha1);
         assert_eq!(
-            commit.get_tree(),
+            commit.get_tree().map(|id| id.to_string()),
             Some(String::from("29ff16c9c14e2652b22f8b78bb08a5a07930c147"))
         );
         assert_eq!(
-            commit.get_parents().iter().next().unwrap().clone(),
+            commit.get_parents().first().unwrap().to_string(),
             String::from("206941306e8a8af65b66eaaaea388a7ae24d49a0")
         );
+        let author = commit.get_author().unwrap();
+        assert_eq!(author.name, "Thibault Polge");
+        assert_eq!(author.email, "thibault@thb.lt");
+        assert_eq!(author.time, 1527025023);
+        assert_eq!(author.tz_offset, 120);
         assert_eq!(
-            commit.get_author(),
-            Some(String::from(
-                "Thibault Polge <thibault@thb.lt> 1527025023 +0200"
-            ))
-        );
-        assert_eq!(
-            commit.get_commiter(),
+            commit.get_commiter().map(|c| c.to_string()),
             Some(String::from(
                 "Thibault Polge <thibault@thb.lt> 1527025044 +0200"
             ))
@@ -445,33 +794,175 @@ Create first draft";
         );
     }
 
+    #[test]
+    fn git_commit_signed_payload() {
+        let commit = GitCommit::deserialize(SAMPLE_COMMIT.as_bytes().to_vec(), ObjectFormat::Sha1);
+        let (payload, signature) = commit.signed_payload().unwrap();
+        let payload = String::from_utf8(payload).unwrap();
+        assert!(payload.starts_with("tree 29ff16c9c14e2652b22f8b78bb08a5a07930c147\n"));
+        assert!(payload.ends_with("+0200\n\nCreate first draft"));
+        assert!(!payload.contains("gpgsig"));
+        assert!(signature.starts_with("-----BEGIN PGP SIGNATURE-----\n\niQIz"));
+        assert!(signature.ends_with("-----END PGP SIGNATURE-----\n"));
+    }
+
+    #[test]
+    fn git_commit_signature_header_follows_format() {
+        let sha256 = SAMPLE_COMMIT.replace("gpgsig ", "gpgsig-sha256 ");
+        let commit = GitCommit::deserialize(sha256.as_bytes().to_vec(), ObjectFormat::Sha1);
+        assert!(commit.get_gpgsig().is_none());
+        let commit = GitCommit::deserialize(sha256.as_bytes().to_vec(), ObjectFormat::Sha256);
+        let (payload, _) = commit.signed_payload().unwrap();
+        let payload = String::from_utf8(payload).unwrap();
+        assert!(!payload.contains("gpgsig"));
+        assert!(payload.ends_with("+0200\n\nCreate first draft"));
+    }
+
     #[test]
     fn git_tree_deserialize() {
-        let mut txt = "100644 testfile\x0029ff16c9c14e2652b22f8b78bb08a5a07930c147"
-            .as_bytes()
-            .to_vec();
-        txt.append(
-            &mut "100645 some other test files.txt\x00206941306e8a8af65b66eaaaea388a7ae24d49a0"
-                .as_bytes()
-                .to_vec(),
-        );
-        let tree = GitTree::deserialize(txt);
+        let first = "29ff16c9c14e2652b22f8b78bb08a5a07930c147"
+            .parse::<ObjectId>()
+            .unwrap();
+        let second = "006941306e8a8af65b66eaaaea388a7ae24d49a0"
+            .parse::<ObjectId>()
+            .unwrap();
+        let mut txt = b"100644 testfile\x00".to_vec();
+        txt.extend_from_slice(first.as_bytes());
+        txt.extend_from_slice(b"100645 some other test files.txt\x00");
+        txt.extend_from_slice(second.as_bytes());
+        let tree = GitTree::parse(&txt, ObjectFormat::Sha1).unwrap();
 
         assert_eq!(tree.entries.len(), 2);
-        assert_eq!(tree.entries.get(0).unwrap().mode, "100644".as_bytes());
-        assert_eq!(tree.entries.get(0).unwrap().path, "testfile");
+        assert_eq!(tree.entries.first().unwrap().mode, "100644".as_bytes());
+        assert_eq!(tree.entries.first().unwrap().name, b"testfile");
+        assert_eq!(tree.entries.first().unwrap().obj_hash, first);
+        assert_eq!(tree.entries.get(1).unwrap().mode, "100645".as_bytes());
         assert_eq!(
-            tree.entries.get(0).unwrap().obj_hash,
-            "29ff16c9c14e2652b22f8b78bb08a5a07930c147"
+            tree.entries.get(1).unwrap().name,
+            b"some other test files.txt"
         );
-        assert_eq!(tree.entries.get(1).unwrap().mode, "100645".as_bytes());
         assert_eq!(
-            tree.entries.get(1).unwrap().path,
-            "some other test files.txt"
+            tree.entries.get(1).unwrap().obj_hash.to_string(),
+            "006941306e8a8af65b66eaaaea388a7ae24d49a0"
+        );
+    }
+
+    #[test]
+    fn git_tree_builder_sorts_like_git() {
+        let id = "29ff16c9c14e2652b22f8b78bb08a5a07930c147"
+            .parse::<ObjectId>()
+            .unwrap();
+        let mut builder = GitTreeBuilder::new();
+        for (mode, name) in [
+            ("100644", "foo0"),
+            ("40000", "foo"),
+            ("100644", "foo.txt"),
+            ("100755", "Bar"),
+        ] {
+            builder.insert(mode, name.to_string(), id).unwrap();
+        }
+        let tree = builder.build();
+        let names = tree
+            .entries()
+            .iter()
+            .map(|e| e.path().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(names, vec!["Bar", "foo.txt", "foo", "foo0"]);
+
+        let data = tree.serialize();
+        let tree = GitTree::parse(&data, ObjectFormat::Sha1).unwrap();
+        assert_eq!(tree.entries().len(), 4);
+        assert_eq!(tree.entries()[2].obj_type(), GitObjectType::Tree);
+    }
+
+    #[test]
+    fn git_tree_builder_validates() {
+        let id = "29ff16c9c14e2652b22f8b78bb08a5a07930c147"
+            .parse::<ObjectId>()
+            .unwrap();
+        let mut builder = GitTreeBuilder::new();
+        assert_eq!(
+            builder.insert("100645", "a".to_string(), id),
+            Err(GitTreeError::InvalidMode("100645".to_string()))
         );
         assert_eq!(
-            tree.entries.get(1).unwrap().obj_hash,
-            "206941306e8a8af65b66eaaaea388a7ae24d49a0"
+            builder.insert("100644", "a/b".to_string(), id),
+            Err(GitTreeError::InvalidName("a/b".to_string()))
+        );
+        assert_eq!(
+            builder.insert("100644", "..".to_string(), id),
+            Err(GitTreeError::InvalidName("..".to_string()))
+        );
+        builder.insert("100644", "a".to_string(), id).unwrap();
+        assert_eq!(
+            builder.insert("40000", "a".to_string(), id),
+            Err(GitTreeError::DuplicateName("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn git_tree_parse_checked() {
+        let entry = |mode: &str, name: &str| {
+            let mut data = format!("{mode} {name}\0").into_bytes();
+            data.extend([0xab; 20]);
+            data
+        };
+        let sorted = [entry("100644", "a.txt"), entry("40000", "a")].concat();
+        assert_eq!(
+            GitTree::parse_checked(&sorted, ObjectFormat::Sha1)
+                .unwrap()
+                .entries()
+                .len(),
+            2
+        );
+        let unsorted = [entry("40000", "a"), entry("100644", "a.txt")].concat();
+        assert_eq!(
+            GitTree::parse_checked(&unsorted, ObjectFormat::Sha1).err(),
+            Some(GitTreeError::NotSorted("a.txt".to_string()))
+        );
+        assert_eq!(
+            GitTree::parse_checked(&entry("100600", "a"), ObjectFormat::Sha1).err(),
+            Some(GitTreeError::InvalidMode("100600".to_string()))
+        );
+        assert_eq!(
+            GitTree::parse_checked(&sorted[..sorted.len() - 1], ObjectFormat::Sha1).err(),
+            Some(GitTreeError::Truncated)
+        );
+    }
+
+    #[test]
+    fn git_tree_parse_malformed() {
+        let mut data = b"100644 \xff\xfe.txt\0".to_vec();
+        data.extend([0xab; 20]);
+        let tree = GitTree::parse(&data, ObjectFormat::Sha1).unwrap();
+        assert_eq!(tree.entries()[0].name(), b"\xff\xfe.txt");
+        assert_eq!(tree.serialize(), data);
+
+        for malformed in [
+            &b"1006440 a\0"[..],
+            b"100644 a",
+            b"100644 a\0\xab\xab",
+            b"100644",
+        ] {
+            assert!(GitTree::parse(malformed, ObjectFormat::Sha1).is_err());
+        }
+        assert_eq!(
+            GitObject::deserialize(
+                GitObjectType::Tree,
+                b"100644 a".to_vec(),
+                ObjectFormat::Sha1
+            )
+            .err(),
+            Some(GitTreeError::Truncated)
+        );
+    }
+
+    #[test]
+    fn empty_tree_hash() {
+        let tree = GitObject::Tree(GitTreeBuilder::new().build());
+        assert_eq!(
+            ObjectFormat::Sha1.hash(&tree.encode()).to_string(),
+            "4b825dc642cb6eb9a060e54bf8d69288fbee4904"
         );
     }
 }
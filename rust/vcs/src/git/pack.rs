@@ -0,0 +1,432 @@
+use crate::git::object_id::{ObjectFormat, ObjectId};
+use crate::git::objects::GitObjectType;
+use flate2::read::ZlibDecoder;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const IDX_MAGIC: [u8; 4] = [0xff, b't', b'O', b'c'];
+const PACK_MAGIC: &[u8; 4] = b"PACK";
+/// Entry types of deltified objects.
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+/// Longest delta chain followed before giving up.
+const MAX_DELTA_DEPTH: usize = 10_000;
+/// Sizes in entry headers and deltas are untrusted, more than this is only
+/// allocated as the data actually arrives.
+const MAX_PREALLOCATED_SIZE: usize = 64 * 1024 * 1024;
+/// Total size of the cached delta bases, like git's default
+/// `core.deltaBaseCacheLimit`.
+const DELTA_BASE_CACHE_LIMIT: usize = 96 * 1024 * 1024;
+
+/// Looks up `REF_DELTA` bases that are not in the pack itself.
+pub type ExternalBase<'a> = &'a dyn Fn(&ObjectId) -> Option<(GitObjectType, Vec<u8>)>;
+
+/// A packfile (`objects/pack/pack-<id>.pack`) with its version 2 index.
+pub struct Pack {
+    pack_path: PathBuf,
+    format: ObjectFormat,
+    /// Object ids sorted as in the index.
+    ids: Vec<ObjectId>,
+    /// Offsets of the objects in the pack, same order as [ids].
+    offsets: Vec<u64>,
+    /// The pack, opened on first read and kept open.
+    file: Mutex<Option<BufReader<File>>>,
+    /// Objects that deltas were recently applied to, by offset.
+    delta_bases: Mutex<DeltaBaseCache>,
+}
+
+/// Where a delta chain ends.
+enum ChainBase {
+    /// An object at an offset in the pack.
+    Packed(u64, GitObjectType, Arc<Vec<u8>>),
+    /// A `REF_DELTA` base that isn't in the pack.
+    External(ObjectId),
+}
+
+/// Resolved objects by their offset in the pack, the oldest ones are dropped
+/// once they take more than [DELTA_BASE_CACHE_LIMIT] bytes.
+#[derive(Default)]
+struct DeltaBaseCache {
+    objects: HashMap<u64, (GitObjectType, Arc<Vec<u8>>)>,
+    /// Offsets in [objects], oldest first.
+    order: VecDeque<u64>,
+    size: usize,
+}
+
+impl DeltaBaseCache {
+    fn get(&self, offset: u64) -> Option<(GitObjectType, Arc<Vec<u8>>)> {
+        self.objects.get(&offset).cloned()
+    }
+
+    fn insert(&mut self, offset: u64, obj_type: GitObjectType, data: Arc<Vec<u8>>) {
+        if data.len() > DELTA_BASE_CACHE_LIMIT || self.objects.contains_key(&offset) {
+            return;
+        }
+        self.size += data.len();
+        self.objects.insert(offset, (obj_type, data));
+        self.order.push_back(offset);
+        while self.size > DELTA_BASE_CACHE_LIMIT {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some((_, data)) = self.objects.remove(&oldest) {
+                self.size -= data.len();
+            }
+        }
+    }
+}
+
+impl Pack {
+    /// Load the index at [idx_path]. The pack is expected next to it with
+    /// the `.pack` extension.
+    pub(crate) fn open(idx_path: &Path, format: ObjectFormat) -> io::Result<Self> {
+        let data = std::fs::read(idx_path)?;
+        let raw_len = format.raw_len();
+        if data.len() < 8 + 256 * 4 || data[..4] != IDX_MAGIC || read_u32(&data, 4) != 2 {
+            return Err(invalid("unsupported index version"));
+        }
+        let count = read_u32(&data, 8 + 255 * 4) as usize;
+        let ids_start = 8 + 256 * 4;
+        let large_start = count
+            .checked_mul(raw_len + 8)
+            .and_then(|len| len.checked_add(ids_start))
+            .filter(|start| data.len() >= start.saturating_add(2 * raw_len))
+            .ok_or_else(|| invalid("truncated index"))?;
+        let offsets_start = large_start - count * 4;
+
+        let ids = (0..count)
+            .map(|i| {
+                let start = ids_start + i * raw_len;
+                ObjectId::from_bytes(&data[start..start + raw_len])
+                    .ok_or_else(|| invalid("object id"))
+            })
+            .collect::<io::Result<Vec<ObjectId>>>()?;
+        let offsets = (0..count)
+            .map(|i| {
+                let offset = read_u32(&data, offsets_start + i * 4);
+                if offset & 0x8000_0000 == 0 {
+                    return Ok(offset as u64);
+                }
+                let large = large_start + (offset & 0x7fff_ffff) as usize * 8;
+                data.get(large..large + 8)
+                    .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+                    .ok_or_else(|| invalid("large offset"))
+            })
+            .collect::<io::Result<Vec<u64>>>()?;
+
+        Ok(Pack {
+            pack_path: idx_path.with_extension("pack"),
+            format,
+            ids,
+            offsets,
+            file: Mutex::new(None),
+            delta_bases: Mutex::new(DeltaBaseCache::default()),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.pack_path
+    }
+
+    /// All objects in the pack, sorted by id.
+    pub fn ids(&self) -> &[ObjectId] {
+        &self.ids
+    }
+
+    /// Offset of [id] in the pack.
+    pub fn find(&self, id: &ObjectId) -> Option<u64> {
+        let i = self.ids.binary_search(id).ok()?;
+        Some(self.offsets[i])
+    }
+
+    /// Read the object [id], resolving deltas.
+    ///
+    /// Bases of `REF_DELTA` entries outside this pack are looked up with
+    /// [external].
+    pub fn read(
+        &self,
+        id: &ObjectId,
+        external: ExternalBase,
+    ) -> io::Result<Option<(GitObjectType, Vec<u8>)>> {
+        match self.find(id) {
+            Some(offset) => self.read_at(offset, external).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read_at(&self, offset: u64, external: ExternalBase) -> io::Result<(GitObjectType, Vec<u8>)> {
+        // Deltas with the offset of the object they produce, the requested
+        // one first.
+        let mut deltas = Vec::new();
+        let (obj_type, base) = match self.read_chain(offset, &mut deltas)? {
+            ChainBase::Packed(offset, obj_type, base) => {
+                if !deltas.is_empty() {
+                    self.cache_delta_base(offset, obj_type, &base);
+                }
+                (obj_type, base)
+            }
+            // Looked up without holding the pack, the base may be in another
+            // pack with bases in this one.
+            ChainBase::External(id) => {
+                let (obj_type, base) = external(&id).ok_or_else(|| invalid("missing base"))?;
+                (obj_type, Arc::new(base))
+            }
+        };
+        let mut data = base;
+        while let Some((offset, delta)) = deltas.pop() {
+            data = Arc::new(apply_delta(&data, &delta)?);
+            if !deltas.is_empty() {
+                self.cache_delta_base(offset, obj_type, &data);
+            }
+        }
+        Ok((obj_type, Arc::unwrap_or_clone(data)))
+    }
+
+    /// Follow the deltas starting at [offset] to their base, collecting them
+    /// in [deltas].
+    fn read_chain(
+        &self,
+        mut offset: u64,
+        deltas: &mut Vec<(u64, Vec<u8>)>,
+    ) -> io::Result<ChainBase> {
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        let file = match &mut *file {
+            Some(file) => file,
+            None => file.insert(BufReader::new(File::open(&self.pack_path)?)),
+        };
+        loop {
+            if deltas.len() > MAX_DELTA_DEPTH {
+                return Err(invalid("delta chain too long"));
+            }
+            let cached = self
+                .delta_bases
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .get(offset);
+            if let Some((obj_type, data)) = cached {
+                return Ok(ChainBase::Packed(offset, obj_type, data));
+            }
+            file.seek(SeekFrom::Start(offset))?;
+            let (entry_type, size) = read_entry_header(file)?;
+            match entry_type {
+                OFS_DELTA => {
+                    let base = offset
+                        .checked_sub(read_base_offset(file)?)
+                        .ok_or_else(|| invalid("delta base offset"))?;
+                    deltas.push((offset, inflate(file, size)?));
+                    offset = base;
+                }
+                REF_DELTA => {
+                    let mut base = vec![0; self.format.raw_len()];
+                    file.read_exact(&mut base)?;
+                    let base = ObjectId::from_bytes(&base).ok_or_else(|| invalid("base id"))?;
+                    deltas.push((offset, inflate(file, size)?));
+                    match self.find(&base) {
+                        Some(base) => offset = base,
+                        None => return Ok(ChainBase::External(base)),
+                    }
+                }
+                _ => {
+                    let data = Arc::new(inflate(file, size)?);
+                    return Ok(ChainBase::Packed(offset, object_type(entry_type)?, data));
+                }
+            }
+        }
+    }
+
+    fn cache_delta_base(&self, offset: u64, obj_type: GitObjectType, data: &Arc<Vec<u8>>) {
+        self.delta_bases
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(offset, obj_type, data.clone());
+    }
+
+    /// Check the trailing checksum of the pack and its header.
+    pub fn verify_checksum(&self) -> io::Result<bool> {
+        let data = std::fs::read(&self.pack_path)?;
+        let raw_len = self.format.raw_len();
+        if data.len() < 12 + raw_len || &data[..4] != PACK_MAGIC {
+            return Ok(false);
+        }
+        let (content, checksum) = data.split_at(data.len() - raw_len);
+        let count = read_u32(&data, 8) as usize;
+        Ok(count == self.ids.len() && self.format.hash(content).as_bytes() == checksum)
+    }
+}
+
+/// Read the type and inflated size of a pack entry.
+fn read_entry_header(file: &mut impl Read) -> io::Result<(u8, usize)> {
+    let mut byte = read_byte(file)?;
+    let entry_type = (byte >> 4) & 0x7;
+    let mut size = (byte & 0x0f) as usize;
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+        byte = read_byte(file)?;
+        size = add_bits(size, byte & 0x7f, shift).ok_or_else(|| invalid("entry size"))?;
+        shift += 7;
+    }
+    Ok((entry_type, size))
+}
+
+/// [value] with [bits] shifted by [shift] added, [None] if they don't fit.
+fn add_bits(value: usize, bits: u8, shift: u32) -> Option<usize> {
+    if shift >= usize::BITS {
+        return None;
+    }
+    let bits = bits as usize;
+    let shifted = bits << shift;
+    (shifted >> shift == bits).then_some(value | shifted)
+}
+
+/// Read the negative offset of an `OFS_DELTA` base.
+fn read_base_offset(file: &mut impl Read) -> io::Result<u64> {
+    let mut byte = read_byte(file)?;
+    let mut offset = (byte & 0x7f) as u64;
+    while byte & 0x80 != 0 {
+        byte = read_byte(file)?;
+        offset = offset
+            .checked_add(1)
+            .filter(|offset| offset.leading_zeros() >= 7)
+            .map(|offset| (offset << 7) | (byte & 0x7f) as u64)
+            .ok_or_else(|| invalid("delta base offset"))?;
+    }
+    Ok(offset)
+}
+
+fn inflate(file: &mut impl Read, size: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size.min(MAX_PREALLOCATED_SIZE));
+    ZlibDecoder::new(file)
+        .take(size as u64)
+        .read_to_end(&mut data)?;
+    if data.len() != size {
+        return Err(invalid("truncated entry"));
+    }
+    Ok(data)
+}
+
+fn object_type(entry_type: u8) -> io::Result<GitObjectType> {
+    match entry_type {
+        1 => Ok(GitObjectType::Commit),
+        2 => Ok(GitObjectType::Tree),
+        3 => Ok(GitObjectType::Blob),
+        4 => Ok(GitObjectType::Tag),
+        _ => Err(invalid("entry type")),
+    }
+}
+
+/// Rebuild an object from its [base] and a git delta: the sizes of base and
+/// result followed by copy (from base) and insert (literal) instructions.
+fn apply_delta(base: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    let mut delta = delta;
+    let base_size = read_varint(&mut delta)?;
+    let size = read_varint(&mut delta)?;
+    if base_size != base.len() {
+        return Err(invalid("delta base size"));
+    }
+    let mut out = Vec::with_capacity(size.min(MAX_PREALLOCATED_SIZE));
+    while let Some((&op, rest)) = delta.split_first() {
+        delta = rest;
+        if op & 0x80 != 0 {
+            let mut offset = 0usize;
+            let mut len = 0usize;
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    offset |= (read_byte(&mut delta)? as usize) << (8 * i);
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    len |= (read_byte(&mut delta)? as usize) << (8 * i);
+                }
+            }
+            if len == 0 {
+                len = 0x10000;
+            }
+            let chunk = offset
+                .checked_add(len)
+                .and_then(|end| base.get(offset..end))
+                .ok_or_else(|| invalid("delta copy"))?;
+            out.extend_from_slice(chunk);
+        } else if op != 0 {
+            let len = op as usize;
+            if delta.len() < len {
+                return Err(invalid("delta insert"));
+            }
+            out.extend_from_slice(&delta[..len]);
+            delta = &delta[len..];
+        } else {
+            return Err(invalid("delta opcode"));
+        }
+    }
+    if out.len() != size {
+        return Err(invalid("delta result size"));
+    }
+    Ok(out)
+}
+
+fn read_varint(data: &mut &[u8]) -> io::Result<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = read_byte(data)?;
+        value = add_bits(value, byte & 0x7f, shift).ok_or_else(|| invalid("delta size"))?;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn read_byte(reader: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid packfile: {what}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::pack::{apply_delta, read_base_offset, read_entry_header};
+
+    #[test]
+    fn applies_delta() {
+        let base = b"hello world";
+        // base size 11, result size 13, copy 6 bytes from 0, insert "there!!"
+        let delta = [11, 13, 0x90, 6, 7, b't', b'h', b'e', b'r', b'e', b'!', b'!'];
+        assert_eq!(apply_delta(base, &delta).unwrap(), b"hello there!!");
+        assert!(apply_delta(b"short", &delta).is_err());
+    }
+
+    #[test]
+    fn rejects_overlong_sizes() {
+        let header = [[0xb0].as_slice(), &[0xff; 10], &[0x01]].concat();
+        assert!(read_entry_header(&mut header.as_slice()).is_err());
+        assert_eq!(
+            read_entry_header(&mut [0x95, 0x01].as_slice()).unwrap(),
+            (1, 0x15)
+        );
+
+        let offset = [[0xff; 10].as_slice(), &[0x7f]].concat();
+        assert!(read_base_offset(&mut offset.as_slice()).is_err());
+
+        // Claims a result of 2^63 bytes, which must not be allocated.
+        let delta = [[11].as_slice(), &[0x80; 9], &[0x01, 0x05, b'h']].concat();
+        assert!(apply_delta(b"hello world", &delta).is_err());
+        let delta = [[11].as_slice(), &[0xff; 10], &[0x01]].concat();
+        assert!(apply_delta(b"hello world", &delta).is_err());
+    }
+}
@@ -1,54 +1,210 @@
+use crate::git::attributes::{Attributes, LineEndings};
+use crate::git::commit_graph::CommitGraph;
+use crate::git::config::{ConfigError, ConfigFile};
+use crate::git::filter::FilterProcesses;
 use crate::git::index::GitIndex;
-use crate::git::objects::{
-    BinSerializable, GitBlob, GitCommit, GitObject, GitObjectType, GitTag, GitTree,
-};
-use iniconf::{IniFile, IniFileOpenError};
+use crate::git::object_id::{ObjectFormat, ObjectId};
+use crate::git::object_reader::ObjectReader;
+use crate::git::objects::{GitObject, GitObjectType, GitTag, GitTree, GitTreeBuilder};
+use crate::git::pack::Pack;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use log::warn;
-use sha1::{Digest, Sha1};
-use std::collections::BTreeMap;
-use std::fmt::format;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::hash::Hash;
-use std::io::Read;
+use std::io;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const MAX_REF_RESOLVE_DEPTH: u8 = 100;
+/// Makes the names of temporary object files unique within the process.
+static TMP_OBJECT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Format version 1 adds the `extensions` section (used for `objectformat`).
+const SUPPORTED_REPOSITORY_FORMAT_VERSION: u8 = 1;
 
 pub struct Repository {
-    /// Where the files meant to be in version control live.
-    work_tree: PathBuf,
-    /// Storage of vcs data
+    /// Where the files meant to be in version control live, [None] for bare
+    /// repositories.
+    work_tree: Option<PathBuf>,
+    /// Storage of vcs data, `.git/worktrees/<name>` for linked worktrees.
     git_dir: PathBuf,
+    /// Data shared by all worktrees: objects, refs and config. Same as
+    /// [git_dir] for the main worktree.
+    common_dir: PathBuf,
 
     config: RepoConfig,
+    /// Per-worktree `config.worktree` in [git_dir], only used with
+    /// `extensions.worktreeConfig`. Overrides [config].
+    worktree_config: Option<ConfigFile>,
+    /// User wide configuration, highest priority first.
+    global_config: Vec<ConfigFile>,
+    /// Packfiles in `objects/pack`, loaded on first use.
+    packs: OnceLock<Vec<Pack>>,
+    /// `objects/info/commit-graph`, loaded on first use.
+    commit_graph: OnceLock<Option<CommitGraph>>,
+    /// Long-running filter processes, started on first use.
+    pub(crate) filter_processes: Mutex<FilterProcesses>,
 }
 
 impl Repository {
-    /// Load an existing repository at [path].
+    /// Load an existing repository with its work tree at [path].
     ///
     /// [force] (default false) ignores missing directories and always returns
     /// [Ok].
     pub fn new(path: PathBuf, force: Option<bool>) -> Result<Self, RepositoryLoadError> {
-        let force = force.unwrap_or(false);
+        let dot_git = path.join(".git");
+        let git_dir = if dot_git.is_file() {
+            read_gitdir_file(&dot_git).ok_or(RepositoryLoadError::NotAGitRepository)?
+        } else {
+            dot_git
+        };
+        Self::load(git_dir, Some(path), force.unwrap_or(false))
+    }
+
+    /// Load the bare repository stored in [git_dir].
+    pub fn open_bare(git_dir: PathBuf) -> Result<Self, RepositoryLoadError> {
+        Self::load(git_dir, None, false)
+    }
+
+    /// Find the repository containing the directory [start] like git does.
+    ///
+    /// `$GIT_DIR` names the git dir directly, the work tree then is
+    /// `$GIT_WORK_TREE`, `core.worktree` or [start] unless the repository is
+    /// bare. Otherwise [start] and its parents are searched for a `.git`
+    /// directory or file, or a bare repository. The search doesn't enter
+    /// `$GIT_CEILING_DIRECTORIES` and stops at filesystem boundaries unless
+    /// `$GIT_DISCOVERY_ACROSS_FILESYSTEM` is set.
+    pub fn discover(start: &Path) -> Result<Self, RepositoryLoadError> {
+        let env_path = |name: &str| {
+            std::env::var_os(name)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+        let start = fs::canonicalize(start).map_err(|_| RepositoryLoadError::NotAGitRepository)?;
+        let work_tree = env_path("GIT_WORK_TREE").map(|path| start.join(path));
+
+        if let Some(git_dir) = env_path("GIT_DIR") {
+            let git_dir = start.join(git_dir);
+            let git_dir = if git_dir.is_file() {
+                read_gitdir_file(&git_dir).ok_or(RepositoryLoadError::NotAGitRepository)?
+            } else {
+                git_dir
+            };
+            let mut repo = Self::load(git_dir, None, false)?;
+            repo.work_tree = work_tree
+                .or_else(|| repo.configured_work_tree())
+                .or_else(|| (!repo.config.bare).then_some(start));
+            return Ok(repo);
+        }
+
+        let ceilings = std::env::var_os("GIT_CEILING_DIRECTORIES")
+            .map(|dirs| {
+                std::env::split_paths(&dirs)
+                    .filter(|dir| dir.is_absolute())
+                    .map(|dir| fs::canonicalize(&dir).unwrap_or(dir))
+                    .collect::<Vec<PathBuf>>()
+            })
+            .unwrap_or_default();
+        let across_filesystems = std::env::var("GIT_DISCOVERY_ACROSS_FILESYSTEM")
+            .is_ok_and(|value| ["1", "true", "yes", "on"].contains(&value.to_lowercase().as_str()));
+        let device = device_of(&start);
+
+        let mut dir = start.as_path();
+        loop {
+            let dot_git = dir.join(".git");
+            let repo = if dot_git.is_file() {
+                let git_dir =
+                    read_gitdir_file(&dot_git).ok_or(RepositoryLoadError::NotAGitRepository)?;
+                Some(Self::load(git_dir, Some(dir.to_path_buf()), false)?)
+            } else if is_git_dir(&dot_git) {
+                let mut repo = Self::load(dot_git, Some(dir.to_path_buf()), false)?;
+                if repo.config.bare {
+                    repo.work_tree = None;
+                }
+                Some(repo)
+            } else if is_git_dir(dir) {
+                Some(Self::load(dir.to_path_buf(), None, false)?)
+            } else {
+                None
+            };
+            if let Some(mut repo) = repo {
+                if let Some(work_tree) = work_tree.or_else(|| repo.configured_work_tree()) {
+                    repo.work_tree = Some(work_tree);
+                }
+                return Ok(repo);
+            }
+
+            let Some(parent) = dir.parent() else {
+                break;
+            };
+            // Ceiling directories themselves are only checked when starting there.
+            let at_ceiling = ceilings
+                .iter()
+                .any(|ceiling| ceiling == parent || ceiling == dir);
+            if at_ceiling || !across_filesystems && device_of(parent) != device {
+                break;
+            }
+            dir = parent;
+        }
+        Err(RepositoryLoadError::NotAGitRepository)
+    }
+
+    /// Load the repository stored in [git_dir] with an optional [work_tree].
+    fn load(
+        git_dir: PathBuf,
+        work_tree: Option<PathBuf>,
+        force: bool,
+    ) -> Result<Self, RepositoryLoadError> {
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common_dir) => {
+                let common_dir = git_dir.join(common_dir.trim());
+                fs::canonicalize(&common_dir).unwrap_or(common_dir)
+            }
+            Err(_) => git_dir.clone(),
+        };
 
         let mut instance = Repository {
-            work_tree: path.clone(),
-            git_dir: path.join(".git"),
+            work_tree,
+            git_dir,
+            common_dir,
             config: RepoConfig::default(),
+            worktree_config: None,
+            global_config: global_config_paths()
+                .into_iter()
+                .filter_map(|path| ConfigFile::open(path).ok())
+                .collect(),
+            packs: OnceLock::new(),
+            commit_graph: OnceLock::new(),
+            filter_processes: Mutex::new(HashMap::new()),
         };
 
         if instance.git_dir.is_dir() || force {
-            let path = instance.git_dir.join("config");
+            let path = instance.common_dir.join("config");
             if path.is_file() || force {
                 instance.config =
                     RepoConfig::read(path).expect("IO is possible as per check above");
-                if instance.config.repository_format_version <= 0 || force {
-                    Ok(instance)
-                } else {
+                let worktree_config = instance
+                    .config
+                    .file
+                    .get_string("extensions", "worktreeConfig");
+                if worktree_config.is_some_and(|enabled| enabled == "true") {
+                    let path = instance.git_dir.join("config.worktree");
+                    instance.worktree_config = ConfigFile::open(path).ok();
+                }
+                if instance.config.repository_format_version > SUPPORTED_REPOSITORY_FORMAT_VERSION
+                    && !force
+                {
                     Err(RepositoryLoadError::UnsupportedRepositoryFormatVersion {
                         actual: instance.config.repository_format_version,
-                        supported: 0,
+                        supported: SUPPORTED_REPOSITORY_FORMAT_VERSION,
                     })
+                } else if instance.config.object_format.is_none() && !force {
+                    Err(RepositoryLoadError::UnsupportedObjectFormat)
+                } else {
+                    Ok(instance)
                 }
             } else {
                 Err(RepositoryLoadError::ConfigurationFileMissing)
@@ -58,22 +214,46 @@ impl Repository {
         }
     }
 
-    pub fn init(path: PathBuf) -> Result<Self, RepositoryInitError> {
-        let mut repo = Self::new(path, Some(true)).ok().expect("Force is passed");
-        if repo.work_tree.is_file() || repo.work_tree.is_symlink() {
+    /// `core.worktree` of the repository, relative to the git dir.
+    fn configured_work_tree(&self) -> Option<PathBuf> {
+        let path = self.config.file.get_string("core", "worktree")?;
+        let path = self.git_dir.join(path);
+        Some(fs::canonicalize(&path).unwrap_or(path))
+    }
+
+    /// Create a new repository at [path] that names objects using [format].
+    pub fn init(path: PathBuf, format: ObjectFormat) -> Result<Self, RepositoryInitError> {
+        let git_dir = path.join(".git");
+        Self::init_at(git_dir, Some(path), format)
+    }
+
+    /// Create a new bare repository, without work tree, in [path].
+    pub fn init_bare(path: PathBuf, format: ObjectFormat) -> Result<Self, RepositoryInitError> {
+        Self::init_at(path, None, format)
+    }
+
+    pub(crate) fn init_at(
+        git_dir: PathBuf,
+        work_tree: Option<PathBuf>,
+        format: ObjectFormat,
+    ) -> Result<Self, RepositoryInitError> {
+        let mut repo = Self::load(git_dir, work_tree, true).expect("Force is passed");
+        if repo
+            .work_tree
+            .as_ref()
+            .is_some_and(|path| path.is_file() || path.is_symlink())
+        {
             Err(RepositoryInitError::NotADirectory)
         } else if repo.git_dir.read_dir().is_ok_and(|dir| dir.count() > 0) {
             Err(RepositoryInitError::AlreadyInitialized)
+        } else if Self::init_fs(&mut repo, format).is_none() {
+            Err(RepositoryInitError::IOError)
         } else {
-            if Self::init_fs(&mut repo).is_none() {
-                Err(RepositoryInitError::IOError)
-            } else {
-                Ok(repo)
-            }
+            Ok(repo)
         }
     }
 
-    fn init_fs(repo: &mut Repository) -> Option<()> {
+    fn init_fs(repo: &mut Repository, format: ObjectFormat) -> Option<()> {
         if !repo.git_dir.exists() {
             fs::create_dir_all(&repo.git_dir).ok()?;
         }
@@ -95,6 +275,11 @@ impl Repository {
 
         let config = repo.repo_path(vec!["config"], Some(false), Some(true))?;
         repo.config = RepoConfig::read(config)?;
+        repo.config.object_format = Some(format);
+        repo.config.bare = repo.work_tree.is_none();
+        if format != ObjectFormat::Sha1 {
+            repo.config.repository_format_version = 1;
+        }
         repo.config.write()?;
 
         Some(())
@@ -124,7 +309,16 @@ impl Repository {
             &path_list
         };
 
-        let mut res_path = self.git_dir.clone();
+        let name = path_list
+            .iter()
+            .map(|path| path.as_ref().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let mut res_path = if is_per_worktree(&name) {
+            self.git_dir.clone()
+        } else {
+            self.common_dir.clone()
+        };
         for path in dir_path_list {
             res_path = res_path.join(path)
         }
@@ -154,53 +348,129 @@ impl Repository {
         }
     }
 
+    /// Read the value of [key] in [section] of the worktree or repository
+    /// config, falling back to the global config. Section and key names are
+    /// case-insensitive like in git.
+    pub fn config_string(&self, section: &str, key: &str) -> Option<String> {
+        self.worktree_config
+            .as_ref()
+            .and_then(|file| file.get_string(section, key))
+            .or_else(|| self.config.file.get_string(section, key))
+            .or_else(|| {
+                self.global_config
+                    .iter()
+                    .find_map(|file| file.get_string(section, key))
+            })
+            .cloned()
+    }
+
+    /// Set [key] in [section] of the repository config and save it.
+    pub fn config_set(&mut self, section: &str, key: &str, value: &str) -> Option<()> {
+        self.config.file.set_str(section, key, value);
+        self.config.file.write().ok()
+    }
+
+    /// Set [key] in [section] of the config of this worktree only and save
+    /// it. Like git, `extensions.worktreeConfig` is enabled if needed.
+    pub fn config_set_worktree(&mut self, section: &str, key: &str, value: &str) -> Option<()> {
+        if self.worktree_config.is_none() {
+            let path = self.git_dir.join("config.worktree");
+            self.worktree_config = Some(ConfigFile::open(path).ok()?);
+            self.config_set("extensions", "worktreeConfig", "true")?;
+        }
+        let file = self.worktree_config.as_mut()?;
+        file.set_str(section, key, value);
+        file.write().ok()
+    }
+
+    /// Directory holding the repository data, usually `.git`.
+    ///
+    /// For linked worktrees this only holds `HEAD`, the index and other
+    /// per-worktree files, see [Repository::common_dir].
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    /// Directory holding the data shared by all worktrees.
+    pub fn common_dir(&self) -> &Path {
+        &self.common_dir
+    }
+
+    /// Path of [name] (like `HEAD`, `index` or `refs/heads/master`) in the
+    /// per-worktree or common git dir.
+    pub(crate) fn git_path(&self, name: &str) -> PathBuf {
+        if is_per_worktree(name) {
+            self.git_dir.join(name)
+        } else {
+            self.common_dir.join(name)
+        }
+    }
+
+    /// Hash algorithm used to name objects in this repository.
+    pub fn object_format(&self) -> ObjectFormat {
+        self.config.object_format.unwrap_or_default()
+    }
+
+    /// Resolve [name] (`HEAD`, a ref name like `master` or `tags/v1`, a full
+    /// or an abbreviated object id) to an existing object.
     pub fn object_find(&self, name: String) -> ObjectRefResult {
         let name = name.trim();
+        let format = self.object_format();
         let obj_hash = if name == "HEAD" {
             if let Some(head) = self.head() {
                 ObjectRefResult::Ok(head)
             } else {
                 ObjectRefResult::NoResult
             }
-        } else if name.len() == 6 {
-            let path = self.repo_path(vec!["objects", &name[0..2]], None, None);
-            if let Some(path) = path {
-                let mut matching = Vec::new();
+        } else if let Some(id) = self.ref_dwim(name) {
+            ObjectRefResult::Ok(id)
+        } else if !name.chars().all(|c| c.is_ascii_hexdigit()) {
+            ObjectRefResult::NotARef
+        } else if name.len() == format.hex_len() {
+            match name.parse::<ObjectId>() {
+                Ok(id) => ObjectRefResult::Ok(id),
+                Err(_) => ObjectRefResult::NotARef,
+            }
+        } else if name.len() >= 4 && name.len() < format.hex_len() {
+            let name = name.to_lowercase();
+            let mut matching = Vec::new();
+            if let Some(path) = self.repo_path(vec!["objects", &name[0..2]], None, None) {
                 if let Ok(dir) = fs::read_dir(path) {
-                    for entry in dir {
-                        if let Ok(entry) = entry {
-                            let entry = entry.path();
-                            if entry.starts_with(&name[2..6]) {
-                                matching.push(entry);
-                            }
+                    for entry in dir.flatten() {
+                        let file_name = entry.file_name();
+                        let file_name = file_name.to_string_lossy();
+                        if file_name.starts_with(&name[2..]) {
+                            matching.push(format!("{}{}", &name[0..2], file_name));
                         }
                     }
                 }
+            }
 
-                if matching.len() == 1 {
-                    ObjectRefResult::Ok(matching.first().unwrap().to_str().unwrap().to_string())
-                } else if matching.len() < 1 {
-                    ObjectRefResult::NoResult
-                } else {
-                    // if matching.count() > 1 {
-                    ObjectRefResult::TooManyResults
+            for pack in self.packs() {
+                for id in pack.ids() {
+                    let hex = id.to_string();
+                    if hex.starts_with(&name) && !matching.contains(&hex) {
+                        matching.push(hex);
+                    }
                 }
-            } else {
+            }
+
+            if matching.len() == 1 {
+                match matching.first().unwrap().parse::<ObjectId>() {
+                    Ok(id) => ObjectRefResult::Ok(id),
+                    Err(_) => ObjectRefResult::NoResult,
+                }
+            } else if matching.is_empty() {
                 ObjectRefResult::NoResult
+            } else {
+                ObjectRefResult::TooManyResults
             }
-        } else if name.len() == 20 {
-            ObjectRefResult::Ok(name.to_string())
         } else {
             ObjectRefResult::NotARef
         };
 
         if let ObjectRefResult::Ok(obj_hash) = obj_hash {
-            let path = self.repo_path(
-                vec!["objects", &obj_hash[0..2], &obj_hash[2..obj_hash.len()]],
-                None,
-                Some(true),
-            );
-            if path.is_some_and(|p| p.is_file()) {
+            if self.object_exists(&obj_hash) {
                 ObjectRefResult::Ok(obj_hash)
             } else {
                 ObjectRefResult::PointsToDeletedRef
@@ -210,121 +480,250 @@ impl Repository {
         }
     }
 
-    fn head(&self) -> Option<String> {
+    /// Whether [id] is stored as loose or packed object.
+    pub fn object_exists(&self, id: &ObjectId) -> bool {
+        self.object_path(id).is_some_and(|p| p.is_file())
+            || self.packs().iter().any(|pack| pack.find(id).is_some())
+    }
+
+    /// Packfiles of the repository.
+    pub fn packs(&self) -> &[Pack] {
+        self.packs.get_or_init(|| {
+            let Some(dir) = self.repo_path(vec!["objects", "pack"], None, Some(true)) else {
+                return Vec::new();
+            };
+            let Ok(entries) = fs::read_dir(dir) else {
+                return Vec::new();
+            };
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+                .filter_map(|path| match Pack::open(&path, self.object_format()) {
+                    Ok(pack) => Some(pack),
+                    Err(err) => {
+                        warn!("Failed to open pack index {}: {err}", path.display());
+                        None
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// The commit-graph of the repository, [None] if there is none or
+    /// `core.commitGraph` is false.
+    pub fn commit_graph(&self) -> Option<&CommitGraph> {
+        self.commit_graph
+            .get_or_init(|| {
+                if self
+                    .config_string("core", "commitGraph")
+                    .is_some_and(|enabled| enabled == "false")
+                {
+                    return None;
+                }
+                let path = self.git_path("objects/info/commit-graph");
+                match CommitGraph::open(&path, self.object_format()) {
+                    Ok(graph) => Some(graph),
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                    Err(err) => {
+                        warn!("Failed to open {}: {err}", path.display());
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+
+    /// Read the type and contents of a packed object.
+    fn packed_object_read(&self, id: &ObjectId) -> Option<(GitObjectType, Vec<u8>)> {
+        let external = |base: &ObjectId| {
+            let mut reader = self.object_open(*base)?;
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).ok()?;
+            Some((reader.obj_type(), data))
+        };
+        for pack in self.packs() {
+            match pack.read(id, &external) {
+                Ok(Some(obj)) => return Some(obj),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(
+                        "Failed to read object {id} from {}: {err}",
+                        pack.path().display()
+                    );
+                    return None;
+                }
+            }
+        }
+        None
+    }
+
+    /// Path of the loose object file of [id].
+    fn object_path(&self, id: &ObjectId) -> Option<PathBuf> {
+        let hex = id.to_string();
+        self.repo_path(vec!["objects", &hex[..2], &hex[2..]], None, Some(true))
+    }
+
+    pub(crate) fn head(&self) -> Option<ObjectId> {
         let obj_ref = self.repo_path(vec!["HEAD"], None, Some(true))?;
         let obj_ref = fs::read_to_string(obj_ref).ok()?;
+        let obj_ref = obj_ref.trim();
         if let Some(obj_ref) = obj_ref.strip_prefix("ref: ") {
-            let obj_ref = self.ref_resolve(&obj_ref.to_string())?;
-            Some(obj_ref)
             // object_ref can only be full ref
-        } else if let ObjectRefResult::Ok(hash) = self.object_find(obj_ref) {
-            Some(hash)
+            self.ref_resolve(obj_ref)
         } else {
-            None
+            obj_ref.parse().ok()
         }
     }
 
     /// Load a git object by hash.
-    pub fn object_read(&self, sha: String) -> Option<GitObject> {
-        // let sha: String = sha.iter().map(|byte| format!("{:x}", byte)).collect();
-        let path = self.repo_path(
-            vec!["objects", &sha[0..2], &sha[2..sha.len()]],
-            None,
-            Some(true),
-        );
-        if path.as_ref().is_some_and(|p| p.is_file()) {
-            if let Ok(data) = fs::read(path.unwrap()) {
-                let data = flate2::read::ZlibDecoder::new(&data[..]);
-                let mut data = data.bytes();
-                let mut obj_type = String::new();
-                while let Some(Ok(byte)) = data.next() {
-                    if byte == b' ' {
-                        break;
-                    }
-                    obj_type.push(char::from(byte));
-                }
+    ///
+    /// Reads the whole object into memory, use [Repository::object_open] for
+    /// large blobs.
+    pub fn object_read(&self, sha: ObjectId) -> Option<GitObject> {
+        let reader = self.object_open(sha)?;
+        match reader.into_object(self.object_format()) {
+            Ok(obj) => Some(obj),
+            Err(err) => {
+                warn!("Failed to read object {sha}: {err}");
+                None
+            }
+        }
+    }
 
-                let mut obj_len = String::new();
-                while let Some(Ok(byte)) = data.next() {
-                    if byte == 0x00 {
-                        break;
-                    }
-                    obj_len.push(char::from(byte));
-                }
-                let obj_len = obj_len
-                    .parse::<u64>()
-                    .expect("GitObject doesn't contain size");
-
-                let remaining_bits: Vec<u8> = data.map(|e| e.unwrap()).collect();
-                assert_eq!(obj_len as usize, remaining_bits.len());
-
-                let obj = match obj_type.as_str() {
-                    "commit" => GitObject::Commit(GitCommit::deserialize(remaining_bits)),
-                    "tree" => GitObject::Tree(GitTree::deserialize(remaining_bits)),
-                    "tag" => GitObject::Tag(GitTag::deserialize(remaining_bits)),
-                    "blob" => GitObject::Blob(GitBlob::deserialize(remaining_bits)),
-                    _ => panic!("Unknown type {obj_type} for object {sha}"),
-                };
-                return Some(obj);
+    /// Content of the blob [id], [None] if it is missing or not a blob.
+    pub fn blob_data(&self, id: ObjectId) -> Option<Vec<u8>> {
+        match self.object_read(id)? {
+            GitObject::Blob(blob) => Some(blob.data().clone()),
+            _ => None,
+        }
+    }
+
+    /// Open a git object by hash for streaming its contents.
+    pub fn object_open(&self, sha: ObjectId) -> Option<ObjectReader> {
+        let Some(path) = self.object_path(&sha).filter(|path| path.is_file()) else {
+            let (obj_type, data) = self.packed_object_read(&sha)?;
+            return Some(ObjectReader::from_data(obj_type, data));
+        };
+        match ObjectReader::open(&path) {
+            Ok(reader) => Some(reader),
+            Err(err) => {
+                warn!("Failed to open object {sha}: {err}");
+                None
             }
         }
+    }
+
+    /// Follow tags and commits from [sha] until an object of [target] type is
+    /// reached (`<rev>^{<type>}` in git).
+    ///
+    /// Commits peel to their tree, tags to the tagged object.
+    pub fn object_peel(&self, sha: ObjectId, target: GitObjectType) -> Option<ObjectId> {
+        let mut sha = sha;
+        for _ in 0..MAX_REF_RESOLVE_DEPTH {
+            let reader = self.object_open(sha)?;
+            if reader.obj_type() == target {
+                return Some(sha);
+            }
+            sha = match reader.into_object(self.object_format()).ok()? {
+                GitObject::Tag(tag) => tag.object_hash()?,
+                GitObject::Commit(commit) if target == GitObjectType::Tree => commit.get_tree()?,
+                _ => return None,
+            };
+        }
         None
     }
 
     /// Store a git object in the repo data and return its hash.
-    pub fn object_write(&self, obj: GitObject) -> String {
-        let obj = obj.serialize();
-        let mut hasher = Sha1::new();
-        hasher.update(&obj);
-        let sha = hasher.finalize();
-        let sha: String = sha.iter().map(|byte| format!("{:x}", byte)).collect();
-
-        let path = self.repo_path(
-            vec!["objects", &sha[..2], &sha[2..]],
-            Some(true),
-            Some(true),
-        );
-        if let Some(path) = path {
-            if !path.exists() {
-                fs::write(path, obj).unwrap();
+    pub fn object_write(&self, obj: GitObject) -> io::Result<ObjectId> {
+        let obj_type = obj.object_type();
+        self.object_write_raw(obj_type, &obj.serialize())
+    }
+
+    /// Store [data] as a loose object of [obj_type] and return its hash.
+    ///
+    /// The object is prefixed with the `<type> <len>\0` header, hashed and
+    /// zlib compressed the same way git does it. It is written to a new
+    /// temporary file first, so readers never see a partially written object
+    /// and concurrent writers don't get in each other's way.
+    pub fn object_write_raw(&self, obj_type: GitObjectType, data: &[u8]) -> io::Result<ObjectId> {
+        let mut obj = obj_type.header(data.len());
+        obj.extend_from_slice(data);
+        let sha = self.object_format().hash(&obj);
+        let hex = sha.to_string();
+
+        let dir = self.common_dir.join("objects").join(&hex[..2]);
+        let path = dir.join(&hex[2..]);
+        if path.exists() {
+            return Ok(sha);
+        }
+        fs::create_dir_all(&dir)?;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&obj)?;
+        let obj = encoder.finish()?;
+
+        let (tmp_path, mut file) = loop {
+            let count = TMP_OBJECT_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let name = format!("tmp_obj_{}_{count}", std::process::id());
+            let tmp_path = path.with_file_name(name);
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp_path)
+            {
+                Ok(file) => break (tmp_path, file),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
             }
+        };
+        let written = file
+            .write_all(&obj)
+            .and_then(|_| fs::rename(&tmp_path, path));
+        if let Err(err) = written {
+            let _ = fs::remove_file(tmp_path);
+            return Err(err);
         }
+        Ok(sha)
+    }
 
-        sha
+    /// Compute the hash [data] would have as object of [obj_type] without
+    /// storing it.
+    pub fn object_hash(&self, obj_type: GitObjectType, data: &[u8]) -> ObjectId {
+        let mut obj = obj_type.header(data.len());
+        obj.extend_from_slice(data);
+        self.object_format().hash(&obj)
     }
 
     /// Store a file at [path] in the repo.
-    pub fn hash_object(&self, path: PathBuf, format: GitObjectType) -> Option<String> {
+    pub fn hash_object(&self, path: PathBuf, format: GitObjectType) -> Option<ObjectId> {
         let data = fs::read(path).ok()?;
-        let data = match format {
-            GitObjectType::Commit => todo!(),
-            GitObjectType::Tree => todo!(),
-            GitObjectType::Tag => todo!(),
-            GitObjectType::Blob => GitObject::Blob(GitBlob::deserialize(data)),
-        };
-        Some(self.object_write(data))
+        self.object_write_raw(format, &data).ok()
     }
 
     /// Checks out a git tree to an empty (except git dir) work tree.
     pub fn tree_checkout(&self, tree: GitTree) -> bool {
-        let mut dir = self
-            .work_tree
-            .read_dir()
-            .expect("Work tree no longer exists");
+        let work_tree = self
+            .work_tree()
+            .expect("Bare repositories have no work tree");
+        let dir = work_tree.read_dir().expect("Work tree no longer exists");
         assert!(dir.count() <= 1, "Work tree no empty");
 
-        self.tree_checkout_inner(tree, &self.work_tree).is_some()
+        self.tree_checkout_inner(tree, work_tree).is_some()
     }
-    fn tree_checkout_inner(&self, tree: GitTree, path: &PathBuf) -> Option<()> {
+    fn tree_checkout_inner(&self, tree: GitTree, path: &Path) -> Option<()> {
         for entry in tree.entries() {
-            let path = path.join(entry.path());
-            match self.object_read(entry.obj_hash().clone()) {
-                Some(GitObject::Tree(tree)) => {
-                    fs::create_dir(&path).ok()?;
-                    self.tree_checkout_inner(tree, &path)?;
+            let path = path.join(&*entry.path());
+            let mut reader = self.object_open(*entry.obj_hash())?;
+            match reader.obj_type() {
+                GitObjectType::Tree => {
+                    if let GitObject::Tree(tree) = reader.into_object(self.object_format()).ok()? {
+                        fs::create_dir(&path).ok()?;
+                        self.tree_checkout_inner(tree, &path)?;
+                    }
                 }
-                Some(GitObject::Blob(blob)) => {
-                    fs::write(&path, blob.data()).ok()?;
+                GitObjectType::Blob => {
+                    let mut file = fs::File::create(&path).ok()?;
+                    io::copy(&mut reader, &mut file).ok()?;
                 }
                 _ => {}
             }
@@ -332,39 +731,155 @@ impl Repository {
         Some(())
     }
 
-    /// Resolve a git ref (file path after ref) to a full object hash.
-    fn ref_resolve(&self, git_ref: &String) -> Option<String> {
+    /// Resolve a git ref (path relative to the git dir like
+    /// `refs/heads/master`) to a full object hash.
+    pub(crate) fn ref_resolve(&self, git_ref: &str) -> Option<ObjectId> {
         self.ref_resolve_inner(git_ref, 0)
     }
 
-    /// Resolve a git ref (file path after ref) to a full object hash.
-    fn ref_resolve_inner(&self, git_ref: &String, depth: u8) -> Option<String> {
+    /// Resolve a git ref (path relative to the git dir like
+    /// `refs/heads/master`) to a full object hash.
+    fn ref_resolve_inner(&self, git_ref: &str, depth: u8) -> Option<ObjectId> {
         if depth > MAX_REF_RESOLVE_DEPTH {
             panic!("ref_resolve_inner failed to resolve ref: MAX_REF_RESOLVE_DEPTH exceeded");
         }
 
-        let mut git_ref = git_ref
-            .split("/")
-            .map(|e| e.to_string())
-            .collect::<Vec<String>>();
-        let mut path = vec!["ref".to_string()];
-        path.append(&mut git_ref);
-        let path = self.repo_path(path, None, Some(true))?;
-
-        let mut data = fs::read_to_string(path).ok()?;
-        if let Some(stripped) = data.strip_suffix("\n") {
-            data = stripped.to_string();
+        let git_ref = git_ref.trim();
+        if git_ref
+            .split('/')
+            .any(|part| part.is_empty() || part == "..")
+        {
+            return None;
         }
+        let path = git_ref.split('/').collect::<Vec<&str>>();
+        let data = match self.repo_path(path, None, Some(true)) {
+            Some(path) if path.is_file() => fs::read_to_string(path).ok()?,
+            _ => return self.packed_ref(git_ref),
+        };
+        let data = data.trim();
 
-        if data.starts_with("ref: ") {
-            self.ref_resolve_inner(&data[5..].to_string(), depth + 1)
+        if let Some(next_ref) = data.strip_prefix("ref: ") {
+            self.ref_resolve_inner(next_ref, depth + 1)
         } else {
-            Some(data)
+            data.parse().ok()
+        }
+    }
+
+    /// Look up [git_ref] in the `packed-refs` file.
+    fn packed_ref(&self, git_ref: &str) -> Option<ObjectId> {
+        let path = self.repo_path(vec!["packed-refs"], None, Some(true))?;
+        let data = fs::read_to_string(path).ok()?;
+        data.lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .filter_map(|line| line.split_once(' '))
+            .find(|(_, name)| *name == git_ref)
+            .and_then(|(id, _)| id.parse().ok())
+    }
+
+    /// All refs below `refs/` with the objects they point to, sorted by
+    /// name. Loose refs take precedence over packed ones.
+    pub fn refs(&self) -> Vec<(String, ObjectId)> {
+        let mut refs = BTreeMap::new();
+        if let Some(data) = self
+            .repo_path(vec!["packed-refs"], None, Some(true))
+            .and_then(|path| fs::read_to_string(path).ok())
+        {
+            for (id, name) in data
+                .lines()
+                .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+                .filter_map(|line| line.split_once(' '))
+            {
+                if let Ok(id) = id.parse() {
+                    refs.insert(name.to_string(), id);
+                }
+            }
+        }
+
+        let mut dirs = vec!["refs".to_string()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = fs::read_dir(self.common_dir.join(&dir))
+                .into_iter()
+                .flatten()
+                .flatten()
+                .collect::<Vec<_>>();
+            if self.git_dir != self.common_dir {
+                entries.extend(
+                    fs::read_dir(self.git_dir.join(&dir))
+                        .into_iter()
+                        .flatten()
+                        .flatten(),
+                );
+            }
+            for entry in entries {
+                let name = format!("{dir}/{}", entry.file_name().to_string_lossy());
+                if entry.path().is_dir() {
+                    if !dirs.contains(&name) {
+                        dirs.push(name);
+                    }
+                } else if !entry
+                    .path()
+                    .starts_with(self.git_path(&name).parent().unwrap())
+                {
+                    // Per-worktree ref of the main worktree seen from a linked one.
+                    continue;
+                } else if let Some(id) = self.ref_resolve(&name) {
+                    refs.insert(name, id);
+                }
+            }
+        }
+        refs.into_iter().collect()
+    }
+
+    /// Resolve a short ref [name] the way git does: `<name>`,
+    /// `refs/<name>`, `refs/tags/<name>`, `refs/heads/<name>`,
+    /// `refs/remotes/<name>` and `refs/remotes/<name>/HEAD` are tried in order.
+    fn ref_dwim(&self, name: &str) -> Option<ObjectId> {
+        // Only special refs like `ORIG_HEAD` are looked up directly in the git dir.
+        let is_special = name.chars().all(|c| c.is_ascii_uppercase() || c == '_');
+        if !name.is_empty() && (is_special || name.starts_with("refs/")) {
+            if let Some(id) = self.ref_resolve(name) {
+                return Some(id);
+            }
+        }
+        [
+            format!("refs/{name}"),
+            format!("refs/tags/{name}"),
+            format!("refs/heads/{name}"),
+            format!("refs/remotes/{name}"),
+            format!("refs/remotes/{name}/HEAD"),
+        ]
+        .iter()
+        .find_map(|candidate| self.ref_resolve(candidate))
+    }
+
+    /// Full name of the branch `HEAD` points to, like `refs/heads/master`,
+    /// or [None] if `HEAD` is detached.
+    pub fn head_ref(&self) -> Option<String> {
+        let head = fs::read_to_string(self.git_path("HEAD")).ok()?;
+        head.trim().strip_prefix("ref: ").map(str::to_string)
+    }
+
+    /// Point the ref [name] (like `refs/heads/master` or `ORIG_HEAD`) to
+    /// [id], creating directories as needed.
+    pub fn ref_update(&self, name: &str, id: ObjectId) -> Option<()> {
+        if name.split('/').any(|part| part.is_empty() || part == "..") {
+            return None;
+        }
+        let path = self.git_path(name);
+        fs::create_dir_all(path.parent()?).ok()?;
+        write_locked(&path, format!("{id}\n").as_bytes()).ok()
+    }
+
+    /// Move the current branch, or `HEAD` itself if detached, to [id].
+    pub fn head_update(&self, id: ObjectId) -> Option<()> {
+        match self.head_ref() {
+            Some(branch) => self.ref_update(&branch, id),
+            None => self.ref_update("HEAD", id),
         }
     }
 
     /// Create a [name]d reference to an object [hash].
-    fn ref_create(&self, name: String, hash: String) -> Option<()> {
+    fn ref_create(&self, name: String, hash: ObjectId) -> Option<()> {
         let path = self.repo_path(vec!["refs".to_string(), name], None, Some(true))?;
         fs::write(path, format!("{hash}\n")).ok()
     }
@@ -375,78 +890,194 @@ impl Repository {
     pub fn create_tag(&self, tag: GitTag) -> Option<()> {
         let path = PathBuf::from("tags");
         let path = path.join(tag.tag()?);
-        let tag_ref = self.object_write(GitObject::Tag(tag));
+        let tag_ref = self.object_write(GitObject::Tag(tag)).ok()?;
 
         self.ref_create(path.to_str()?.to_string(), tag_ref)
     }
 
-    pub fn status(&self) -> Option<GitStatus> {
+    /// Load the staging area.
+    pub fn index(&self) -> Option<GitIndex> {
         let index = self.repo_path(vec!["index"], None, Some(true))?;
         let index = fs::read(index).ok()?;
-        let index = GitIndex::decode(index.as_slice())?;
-        let res = if let ObjectRefResult::Ok(head) = self.object_find("HEAD".to_string()) {
-            if let Some(head) = self.tree_to_map("HEAD".to_string(), String::new()) {
-                let mut added = Vec::new();
-                let mut deleted = head.clone();
-                let mut modified = Vec::new();
-                let mut entries = index.entries();
-                for index_entry in entries {
-                    if head.get(&index_entry.0).is_some() {
-                        if head
-                            .get(&index_entry.0)
-                            .is_some_and(|e| e != &index_entry.1)
-                        {
-                            modified.push(index_entry.0.clone());
-                        }
-                        deleted.remove(&index_entry.0);
-                    } else {
-                        added.push(index_entry.0);
-                    }
+        GitIndex::decode(index.as_slice(), self.object_format())
+    }
+
+    /// Replace the index file with [index].
+    ///
+    /// The new index is written to `index.lock` first and renamed, so other
+    /// readers never see a partially written file. Fails if another process
+    /// holds the lock.
+    ///
+    /// Like git, entries whose file was modified in the same second the
+    /// index is written are smudged, so a later change in that second isn't
+    /// hidden by matching stat data once the index is rewritten.
+    pub fn index_write(&self, index: &GitIndex) -> Option<()> {
+        let path = self.git_path("index");
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+        let mut index = index.clone();
+        for entry in index.iter_mut() {
+            if entry.is_racy(now) {
+                entry.smudge();
+            }
+        }
+        write_locked(&path, &index.encode(self.object_format())).ok()
+    }
+
+    /// Store the staged files as tree objects and return the root tree.
+    ///
+    /// Fails if the index contains unmerged entries.
+    pub fn write_tree(&self) -> Option<ObjectId> {
+        let index_path = self.repo_path(vec!["index"], None, Some(true))?;
+        let mut entries = Vec::new();
+        if index_path.is_file() {
+            for entry in self.index()?.iter() {
+                if entry.stage() != 0 {
+                    return None;
                 }
-                Some((added, deleted, modified))
-            } else {
-                None
+                entries.push((
+                    entry.name().to_str()?.to_string(),
+                    entry.mode_str(),
+                    *entry.hash(),
+                ));
             }
-        } else {
-            None
-        };
+        }
+        self.write_tree_inner(&entries)
+    }
 
-        if let Some((added, deleted, modified)) = res {
-            Some(GitStatus {
-                active_branch: self.get_active_branch().unwrap_or("HEAD".to_string()),
-                added,
-                modified,
-                deleted: deleted.keys().map(|e| e.clone()).collect::<Vec<String>>(),
-            })
-        } else {
-            None
+    /// Write a tree of [entries] (path relative to the tree, mode, hash) and
+    /// their subdirectories.
+    fn write_tree_inner(&self, entries: &[(String, String, ObjectId)]) -> Option<ObjectId> {
+        let mut tree = GitTreeBuilder::new();
+        let mut subdirs: BTreeMap<&str, Vec<(String, String, ObjectId)>> = BTreeMap::new();
+        for (path, mode, hash) in entries {
+            if let Some((dir, rest)) = path.split_once('/') {
+                subdirs
+                    .entry(dir)
+                    .or_default()
+                    .push((rest.to_string(), mode.clone(), *hash));
+            } else {
+                tree.insert(mode, path.clone(), *hash).ok()?;
+            }
         }
+        for (dir, entries) in subdirs {
+            let hash = self.write_tree_inner(&entries)?;
+            tree.insert("40000", dir.to_string(), hash).ok()?;
+        }
+        self.object_write(GitObject::Tree(tree.build())).ok()
     }
 
-    fn get_active_branch(&self) -> Option<String> {
+    pub(crate) fn get_active_branch(&self) -> Option<String> {
         let head = self.repo_path(vec!["HEAD"], None, Some(true))?;
         let head = fs::read_to_string(head).ok()?;
         let head = head.strip_prefix("ref: refs/heads/")?;
-        Some(head.to_string())
+        Some(head.trim_end().to_string())
+    }
+
+    /// All non-tree entries below [tree] as (path, mode, id), in tree order.
+    pub fn tree_flatten(&self, tree: ObjectId) -> Option<Vec<(String, String, ObjectId)>> {
+        let mut entries = Vec::new();
+        self.tree_flatten_inner(tree, "", &mut entries)?;
+        Some(entries)
+    }
+
+    fn tree_flatten_inner(
+        &self,
+        tree: ObjectId,
+        prefix: &str,
+        entries: &mut Vec<(String, String, ObjectId)>,
+    ) -> Option<()> {
+        let GitObject::Tree(tree) = self.object_read(tree)? else {
+            return None;
+        };
+        for entry in tree.entries() {
+            let path = format!("{prefix}{}", entry.path());
+            if entry.obj_type() == GitObjectType::Tree {
+                self.tree_flatten_inner(*entry.obj_hash(), &format!("{path}/"), entries)?;
+            } else {
+                entries.push((path, entry.mode_str(), *entry.obj_hash()));
+            }
+        }
+        Some(())
+    }
+
+    /// Write the blob [id] to [path] in the work tree with file [mode],
+    /// replacing what is there and creating parent directories. Content is
+    /// converted as [attributes] configure.
+    pub fn checkout_file(
+        &self,
+        attributes: &mut Attributes,
+        path: &str,
+        mode: &str,
+        id: ObjectId,
+    ) -> Option<()> {
+        let conversion = attributes.conversion(path);
+        let name = path;
+        let path = self.work_tree()?.join(path);
+        fs::create_dir_all(path.parent()?).ok()?;
+        if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(&path).ok()?;
+        } else if path.symlink_metadata().is_ok() {
+            fs::remove_file(&path).ok()?;
+        }
+        if mode == "160000" {
+            // Submodules are checked out separately.
+            return fs::create_dir_all(path).ok();
+        }
+        let mut reader = self.object_open(id)?;
+        #[cfg(unix)]
+        if mode == "120000" {
+            let mut target = Vec::new();
+            reader.read_to_end(&mut target).ok()?;
+            let target = String::from_utf8(target).ok()?;
+            return std::os::unix::fs::symlink(target, path).ok();
+        }
+        let mut file = fs::File::create(&path).ok()?;
+        if conversion.filter.is_none() && conversion.line_endings == LineEndings::Binary {
+            io::copy(&mut reader, &mut file).ok()?;
+        } else {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).ok()?;
+            file.write_all(&self.convert_to_work_tree(name, &conversion, data)?)
+                .ok()?;
+        }
+        #[cfg(unix)]
+        if mode == "100755" {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).ok()?;
+        }
+        Some(())
+    }
+
+    /// Root of the work tree, [None] for bare repositories.
+    pub fn work_tree(&self) -> Option<&Path> {
+        self.work_tree.as_deref()
+    }
+
+    pub fn is_bare(&self) -> bool {
+        self.work_tree.is_none()
     }
 
     /// Creates a map from a tree with the full file path as key and the hash as value.
-    fn tree_to_map(&self, tree_ref: String, prefix: String) -> Option<BTreeMap<String, String>> {
-        let mut ret: BTreeMap<String, String> = BTreeMap::new();
+    pub(crate) fn tree_to_map(
+        &self,
+        tree_ref: String,
+        prefix: String,
+    ) -> Option<BTreeMap<String, ObjectId>> {
+        let mut ret: BTreeMap<String, ObjectId> = BTreeMap::new();
         if let ObjectRefResult::Ok(tree) = self.object_find(tree_ref) {
             if let Some(GitObject::Tree(tree)) = self.object_read(tree) {
                 for leaf in tree.entries() {
                     let path = PathBuf::from(&prefix);
-                    let path = path.join(leaf.path());
+                    let path = path.join(&*leaf.path());
                     let path = path.to_str()?.to_string();
 
-                    match self.object_read(leaf.obj_hash().to_string()) {
-                        Some(GitObject::Tree(..)) => {
-                            let mut res = self.tree_to_map(leaf.obj_hash().clone(), path)?;
+                    match leaf.obj_type() {
+                        GitObjectType::Tree => {
+                            let mut res = self.tree_to_map(leaf.obj_hash().to_string(), path)?;
                             ret.append(&mut res);
                         }
-                        Some(GitObject::Blob(..)) => {
-                            ret.insert(path, leaf.obj_hash().clone());
+                        GitObjectType::Blob => {
+                            ret.insert(path, *leaf.obj_hash());
                         }
                         _ => {}
                     }
@@ -461,6 +1092,94 @@ impl Repository {
     }
 }
 
+/// Whether [name] (relative to the git dir) belongs to a single worktree
+/// rather than being shared by all of them: `HEAD`, the index, pseudo refs
+/// like `ORIG_HEAD` and the `refs/bisect/` and `refs/worktree/` namespaces.
+fn is_per_worktree(name: &str) -> bool {
+    let is_pseudo_ref = name.chars().all(|c| c.is_ascii_uppercase() || c == '_');
+    is_pseudo_ref
+        || ["index", "logs/HEAD", "info/sparse-checkout"].contains(&name)
+        || ["refs/bisect/", "refs/worktree/", "refs/rewritten/"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+/// Whether [path] looks like a git dir: a `HEAD` file and an `objects`
+/// directory, or a `commondir` file for linked worktrees.
+pub(crate) fn is_git_dir(path: &Path) -> bool {
+    path.join("HEAD").is_file()
+        && (path.join("objects").is_dir() || path.join("commondir").is_file())
+}
+
+/// Device of the filesystem holding [path], [None] where unknown.
+fn device_of(path: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(path).ok().map(|metadata| metadata.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Replace the file at [path] with [data] like git: written to `<path>.lock`
+/// first, which must not exist yet, and renamed. The lock is removed again
+/// on failure.
+pub(crate) fn write_locked(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    let lock = path.with_file_name(name);
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock)?;
+    let written = file.write_all(data).and_then(|_| fs::rename(&lock, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&lock);
+    }
+    written
+}
+
+/// Read the git dir named by a `.git` file (`gitdir: <path>`), relative
+/// paths are resolved against the directory of the file.
+fn read_gitdir_file(path: &Path) -> Option<PathBuf> {
+    let data = fs::read_to_string(path).ok()?;
+    let git_dir = data.trim_end().strip_prefix("gitdir: ")?;
+    Some(path.parent()?.join(git_dir))
+}
+
+/// Resolve a leading `~/` in a configured [path] to the home directory.
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Locations of the user wide config: `$GIT_CONFIG_GLOBAL` or
+/// `~/.gitconfig` and `$XDG_CONFIG_HOME/git/config`.
+fn global_config_paths() -> Vec<PathBuf> {
+    if let Some(path) = std::env::var_os("GIT_CONFIG_GLOBAL") {
+        return vec![PathBuf::from(path)];
+    }
+    let mut paths = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        paths.push(home.join(".gitconfig"));
+    }
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(config) => paths.push(PathBuf::from(config).join("git").join("config")),
+        None => {
+            if let Some(home) = dirs::home_dir() {
+                paths.push(home.join(".config").join("git").join("config"));
+            }
+        }
+    }
+    paths
+}
+
 #[derive(Debug)]
 pub enum RepositoryLoadError {
     NotAGitRepository,
@@ -471,6 +1190,8 @@ pub enum RepositoryLoadError {
         /// Highest version supported by the program.
         supported: u8,
     },
+    /// `extensions.objectformat` names an unknown hash algorithm.
+    UnsupportedObjectFormat,
 }
 
 #[derive(Debug)]
@@ -481,7 +1202,7 @@ pub enum RepositoryInitError {
 }
 
 struct RepoConfig {
-    file: IniFile,
+    file: ConfigFile,
     ///  The version of the gitdir format.
     ///
     /// - 0 means the initial format
@@ -491,7 +1212,9 @@ struct RepoConfig {
     file_mode: bool,
     /// Indicates whether this repository has a worktree.
     bare: bool,
-    // Always assume worktree is at `../`.
+    /// Hash algorithm of the repository, [None] if the configured one is
+    /// unknown.
+    object_format: Option<ObjectFormat>,
 }
 impl RepoConfig {
     /// Reads repo config from [path].
@@ -502,25 +1225,28 @@ impl RepoConfig {
             fs::create_dir_all(path.parent().unwrap()).ok()?;
         }
         if !(path.is_dir() || path.is_symlink()) {
-            let x = IniFile::open(path.clone());
-            println!("{:#?}", &x);
-            match x {
+            match ConfigFile::open(path.clone()) {
                 Ok(file) => {
                     let version = file.get::<u8>("core", "repositoryformatversion");
                     let mode = file.get::<bool>("core", "filemode");
                     let bare = file.get::<bool>("core", "bare");
+                    let object_format = match file.get_string("extensions", "objectformat") {
+                        Some(name) => ObjectFormat::from_name(name),
+                        None => Some(ObjectFormat::default()),
+                    };
                     Some(Self {
                         file,
                         repository_format_version: version
                             .unwrap_or(Self::default().repository_format_version),
                         file_mode: mode.unwrap_or(Self::default().file_mode),
                         bare: bare.unwrap_or(Self::default().bare),
+                        object_format,
                     })
                 }
-                Err(IniFileOpenError::FormatError) => {
+                Err(ConfigError::FormatError) => {
                     warn!("Overriding repo config as it is badly formatted");
                     if fs::remove_file(&path).is_ok() {
-                        if let Ok(file) = IniFile::open(path) {
+                        if let Ok(file) = ConfigFile::open(path) {
                             Some(Self {
                                 file,
                                 ..Self::default()
@@ -532,7 +1258,7 @@ impl RepoConfig {
                         None
                     }
                 }
-                Err(IniFileOpenError::IOError) => {
+                Err(ConfigError::IOError) => {
                     warn!("Couldn't open ini file: IOError");
                     None
                 }
@@ -553,6 +1279,10 @@ impl RepoConfig {
             .set_str("core", "filemode", format!("{}", self.file_mode).as_str());
         self.file
             .set_str("core", "bare", format!("{}", self.bare).as_str());
+        if let Some(format) = self.object_format.filter(|f| f != &ObjectFormat::Sha1) {
+            self.file
+                .set_str("extensions", "objectformat", format.name());
+        }
 
         self.file.write().ok()
     }
@@ -560,29 +1290,19 @@ impl RepoConfig {
 impl Default for RepoConfig {
     fn default() -> Self {
         RepoConfig {
-            file: IniFile::default(),
+            file: ConfigFile::default(),
             repository_format_version: 0,
             file_mode: false,
             bare: false,
+            object_format: Some(ObjectFormat::default()),
         }
     }
 }
 
 pub enum ObjectRefResult {
-    Ok(String),
+    Ok(ObjectId),
     NoResult,
     TooManyResults,
     PointsToDeletedRef,
     NotARef,
 }
-
-pub struct GitStatus {
-    active_branch: String,
-    /// File names of new files.
-    added: Vec<String>,
-    /// File names of modified files.
-    modified: Vec<String>,
-    /// File names of removed files.
-    deleted: Vec<String>,
-    // TODO: changes not staged for commit
-}
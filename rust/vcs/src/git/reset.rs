@@ -0,0 +1,168 @@
+use crate::git::index::{GitIndex, GitIndexEntry};
+use crate::git::object_id::ObjectId;
+use crate::git::objects::GitObjectType;
+use crate::git::repo::Repository;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What `reset` updates besides the current branch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ResetMode {
+    /// Only move the branch.
+    Soft,
+    /// Also make the index match the commit.
+    Mixed,
+    /// Also make the index and the tracked files of the work tree match.
+    Hard,
+}
+
+impl Repository {
+    /// Move the current branch to the commit [target] and, depending on
+    /// [mode], reset the index and work tree to its tree.
+    ///
+    /// The previous `HEAD` is saved as `ORIG_HEAD`. Untracked files are never
+    /// touched. Bare repositories only support [ResetMode::Soft].
+    pub fn reset(&self, target: ObjectId, mode: ResetMode) -> Option<()> {
+        if mode != ResetMode::Soft && self.is_bare() {
+            return None;
+        }
+        let target = self.object_peel(target, GitObjectType::Commit)?;
+        let tree = self.object_peel(target, GitObjectType::Tree)?;
+        let head = self.head();
+        if mode != ResetMode::Soft {
+            self.read_tree(tree, mode == ResetMode::Hard)?;
+        }
+        if let Some(head) = head {
+            self.ref_update("ORIG_HEAD", head)?;
+        }
+        self.head_update(target)
+    }
+
+    /// Make the index match [tree], and with [update_work_tree] the tracked
+    /// files of the work tree too, discarding their changes. Files tracked
+    /// before but not in [tree] are deleted from the work tree.
+    ///
+    /// Files outside of the sparse checkout are marked to be skipped instead
+    /// of being checked out.
+    pub(crate) fn read_tree(&self, tree: ObjectId, update_work_tree: bool) -> Option<()> {
+        let old = self.index_map();
+        let sparse = self.sparse_checkout();
+        let entries = self.tree_flatten(tree)?;
+        let dirty = match update_work_tree {
+            true => self
+                .work_tree_changes()?
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect::<HashSet<PathBuf>>(),
+            false => HashSet::new(),
+        };
+        if update_work_tree {
+            let new_paths = entries
+                .iter()
+                .map(|(path, _, _)| PathBuf::from(path))
+                .collect::<HashSet<PathBuf>>();
+            for path in old.keys().filter(|path| !new_paths.contains(*path)) {
+                self.remove_work_tree_file(self.work_tree()?, path);
+            }
+        }
+
+        let mut attributes = self.checkout_attributes(&entries);
+        let mut new = Vec::new();
+        for (path, mode_str, id) in &entries {
+            let old_entry = old.get(Path::new(path));
+            let unchanged = old_entry.is_some_and(|old| {
+                old.stage() == 0 && old.hash() == id && old.mode_str() == *mode_str
+            });
+            let mut entry = match old_entry {
+                Some(old) if unchanged => old.clone(),
+                _ => GitIndexEntry::new(PathBuf::from(path), *id, mode_str),
+            };
+            if sparse.as_ref().is_some_and(|sparse| !sparse.includes(path)) {
+                let full_path = self.work_tree()?.join(path);
+                // Without updating the work tree a file that is there stays.
+                if update_work_tree && old_entry.is_some_and(|old| !old.skip_worktree()) {
+                    self.remove_work_tree_file(self.work_tree()?, Path::new(path));
+                }
+                entry.set_skip_worktree(update_work_tree || !full_path.exists());
+                new.push(entry);
+                continue;
+            }
+            entry.set_skip_worktree(false);
+            if update_work_tree {
+                let full_path = self.work_tree()?.join(path);
+                if !unchanged || dirty.contains(Path::new(path)) || !full_path.exists() {
+                    self.checkout_file(&mut attributes, path, mode_str, *id)?;
+                }
+                if let Ok(metadata) = full_path.symlink_metadata() {
+                    entry.update_stat(&metadata);
+                }
+            }
+            new.push(entry);
+        }
+        self.index_write(&GitIndex::new(new))
+    }
+
+    /// Reset the index entries of [paths] (files or directories relative to
+    /// the work tree) to their state in [target], leaving `HEAD` and the work
+    /// tree alone.
+    pub fn reset_paths(&self, target: ObjectId, paths: &[String]) -> Option<()> {
+        let tree = self.object_peel(target, GitObjectType::Tree)?;
+        let selected = |path: &Path| {
+            paths.iter().any(|spec| {
+                let spec = spec.trim_end_matches('/');
+                spec == "." || path.starts_with(spec)
+            })
+        };
+        let index = self.index().unwrap_or_else(|| GitIndex::new(Vec::new()));
+        // Entries of other paths stay as they are, conflicts included.
+        let mut new = index
+            .iter()
+            .filter(|entry| !selected(entry.name()))
+            .cloned()
+            .collect::<Vec<GitIndexEntry>>();
+        let old = index
+            .iter()
+            .filter(|entry| entry.stage() == 0 && selected(entry.name()))
+            .map(|entry| (entry.name().to_path_buf(), entry))
+            .collect::<HashMap<PathBuf, &GitIndexEntry>>();
+        for (path, mode, id) in self.tree_flatten(tree)? {
+            let path = PathBuf::from(path);
+            if !selected(&path) {
+                continue;
+            }
+            new.push(match old.get(&path) {
+                Some(old) if *old.hash() == id && old.mode_str() == mode => (*old).clone(),
+                _ => GitIndexEntry::new(path, id, &mode),
+            });
+        }
+        self.index_write(&GitIndex::new(new))
+    }
+
+    /// Entries of the index by path, conflicted files only once.
+    fn index_map(&self) -> HashMap<PathBuf, GitIndexEntry> {
+        self.index()
+            .map(|index| {
+                index
+                    .iter()
+                    .map(|entry| (entry.name().to_path_buf(), entry.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Delete a tracked file and the directories it leaves empty.
+    pub(crate) fn remove_work_tree_file(&self, work_tree: &Path, path: &Path) {
+        let full_path = work_tree.join(path);
+        if fs::remove_file(&full_path).is_err() {
+            return;
+        }
+        let mut dir = full_path.parent();
+        while let Some(current) = dir {
+            if current == work_tree || fs::remove_dir(current).is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::git::object_id::ObjectId;
+use crate::git::objects::{GitObject, GitObjectType};
+use crate::git::repo::{ObjectRefResult, Repository};
+
+impl Repository {
+    /// Resolve a revision expression to an object.
+    ///
+    /// Supports names accepted by [Repository::object_find] followed by any
+    /// number of `^`, `^<n>`, `~`, `~<n>`, `^{<type>}` and `^{}` suffixes, as
+    /// well as `<rev>:<path>` for entries of a tree and `:<path>` for staged
+    /// files.
+    pub fn rev_parse(&self, rev: &str) -> Option<ObjectId> {
+        let rev = rev.trim();
+        if let Some((rev, path)) = rev.split_once(':') {
+            return if rev.is_empty() {
+                self.index()?
+                    .iter()
+                    .find(|entry| entry.stage() == 0 && entry.name().to_str() == Some(path))
+                    .map(|entry| *entry.hash())
+            } else {
+                let tree = self.object_peel(self.rev_parse(rev)?, GitObjectType::Tree)?;
+                self.tree_lookup(tree, path)
+            };
+        }
+
+        let base_end = rev.find(['^', '~']).unwrap_or(rev.len());
+        let mut id = match self.object_find(rev[..base_end].to_string()) {
+            ObjectRefResult::Ok(id) => id,
+            _ => return None,
+        };
+
+        let mut rest = &rev[base_end..];
+        while !rest.is_empty() {
+            if let Some(peel) = rest.strip_prefix("^{") {
+                let end = peel.find('}')?;
+                id = match &peel[..end] {
+                    "" => self.peel_tags(id)?,
+                    "object" => id,
+                    obj_type => self.object_peel(id, GitObjectType::from_name(obj_type)?)?,
+                };
+                rest = &peel[end + 1..];
+            } else if let Some(suffix) = rest.strip_prefix('^') {
+                let (n, remaining) = parse_count(suffix);
+                let commit = self.object_peel(id, GitObjectType::Commit)?;
+                id = if n == 0 {
+                    commit
+                } else {
+                    *self.parents(commit)?.get(n - 1)?
+                };
+                rest = remaining;
+            } else if let Some(suffix) = rest.strip_prefix('~') {
+                let (n, remaining) = parse_count(suffix);
+                id = self.object_peel(id, GitObjectType::Commit)?;
+                for _ in 0..n {
+                    id = *self.parents(id)?.first()?;
+                }
+                rest = remaining;
+            } else {
+                return None;
+            }
+        }
+        Some(id)
+    }
+
+    /// Find the object at [path] (`/` separated) below [tree].
+    pub fn tree_lookup(&self, tree: ObjectId, path: &str) -> Option<ObjectId> {
+        let mut id = tree;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let GitObject::Tree(tree) = self.object_read(id)? else {
+                return None;
+            };
+            id = *tree
+                .entries()
+                .iter()
+                .find(|entry| entry.path() == name)?
+                .obj_hash();
+        }
+        Some(id)
+    }
+
+    /// Parents of the commit [id].
+    pub(crate) fn parents(&self, id: ObjectId) -> Option<Vec<ObjectId>> {
+        match self.object_read(id)? {
+            GitObject::Commit(commit) => Some(commit.get_parents()),
+            _ => None,
+        }
+    }
+
+    /// Follow tags until a non-tag object is reached.
+    fn peel_tags(&self, id: ObjectId) -> Option<ObjectId> {
+        let mut id = id;
+        while let GitObject::Tag(tag) = self.object_read(id)? {
+            id = tag.object_hash()?;
+        }
+        Some(id)
+    }
+}
+
+/// Parse the optional count after `^` or `~`, which defaults to 1.
+fn parse_count(suffix: &str) -> (usize, &str) {
+    let digits = suffix.chars().take_while(|c| c.is_ascii_digit()).count();
+    let n = suffix[..digits].parse().unwrap_or(1);
+    (n, &suffix[digits..])
+}
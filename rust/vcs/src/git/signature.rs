@@ -0,0 +1,258 @@
+use crate::git::repo::Repository;
+use chrono::{DateTime, FixedOffset, Local};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Identity and time of an author, committer or tagger.
+///
+/// Stored in objects like `Scott Chacon <schacon@gmail.com> 1243040974 -0700`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// Seconds since unix epoch.
+    pub time: i64,
+    /// Offset of the local time zone from UTC in minutes.
+    pub tz_offset: i32,
+}
+
+impl Signature {
+    /// Signature of [name] and [email] at the current local time.
+    pub fn now(name: String, email: String) -> Self {
+        let now = Local::now();
+        Signature {
+            name,
+            email,
+            time: now.timestamp(),
+            tz_offset: now.offset().local_minus_utc() / 60,
+        }
+    }
+
+    /// Parse a date as accepted in `GIT_AUTHOR_DATE`: `<unix> <tz>`,
+    /// `@<unix> <tz>`, RFC 2822 or ISO 8601.
+    pub fn parse_date(date: &str) -> Option<(i64, i32)> {
+        let date = date.trim();
+        if let Some((time, tz)) = date.trim_start_matches('@').split_once(' ') {
+            if let (Ok(time), Some(tz)) = (time.parse::<i64>(), parse_tz(tz)) {
+                return Some((time, tz));
+            }
+        }
+        if let Ok(time) = date.trim_start_matches('@').parse::<i64>() {
+            return Some((time, 0));
+        }
+        let parsed = DateTime::parse_from_rfc3339(date)
+            .or_else(|_| DateTime::parse_from_rfc2822(date))
+            .or_else(|_| DateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S %z"))
+            .ok()?;
+        Some((parsed.timestamp(), parsed.offset().local_minus_utc() / 60))
+    }
+
+    /// Date in git's default format, like `Tue May 22 23:37:03 2018 +0200`.
+    pub fn date_string(&self) -> String {
+        self.format_date("%a %b %-d %H:%M:%S %Y %z")
+    }
+
+    /// Format the time in the signature's time zone with a chrono format
+    /// string.
+    pub fn format_date(&self, format: &str) -> String {
+        let offset = FixedOffset::east_opt(self.tz_offset * 60)
+            .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+        match DateTime::from_timestamp(self.time, 0) {
+            Some(date) => date.with_timezone(&offset).format(format).to_string(),
+            None => format!("{} {}", self.time, self.tz_string()),
+        }
+    }
+
+    /// Time zone formatted like `+0200` or `-0730`.
+    pub fn tz_string(&self) -> String {
+        let sign = if self.tz_offset < 0 { '-' } else { '+' };
+        let offset = self.tz_offset.abs();
+        format!("{sign}{:02}{:02}", offset / 60, offset % 60)
+    }
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name,
+            self.email,
+            self.time,
+            self.tz_string()
+        )
+    }
+}
+
+impl FromStr for Signature {
+    type Err = SignatureParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, rest) = s.split_once('<').ok_or(SignatureParseError::MissingEmail)?;
+        let (email, date) = rest
+            .rsplit_once('>')
+            .ok_or(SignatureParseError::MissingEmail)?;
+        let mut date = date.split_whitespace();
+        let time = date
+            .next()
+            .and_then(|time| time.parse::<i64>().ok())
+            .ok_or(SignatureParseError::InvalidDate)?;
+        let tz_offset = date
+            .next()
+            .and_then(parse_tz)
+            .ok_or(SignatureParseError::InvalidDate)?;
+        Ok(Signature {
+            name: name.trim().to_string(),
+            email: email.to_string(),
+            time,
+            tz_offset,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureParseError {
+    /// No `<email>` part.
+    MissingEmail,
+    /// Time or time zone after the email are missing or malformed.
+    InvalidDate,
+}
+
+/// Parse `+HHMM` or `-HHMM` into minutes.
+fn parse_tz(tz: &str) -> Option<i32> {
+    let (sign, digits) = match tz.as_bytes().first()? {
+        b'+' => (1, &tz[1..]),
+        b'-' => (-1, &tz[1..]),
+        _ => return None,
+    };
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours = digits[..2].parse::<i32>().ok()?;
+    let minutes = digits[2..].parse::<i32>().ok()?;
+    Some(sign * (hours * 60 + minutes))
+}
+
+impl Repository {
+    /// Signature for the author of new commits.
+    ///
+    /// `GIT_AUTHOR_NAME`, `GIT_AUTHOR_EMAIL` and `GIT_AUTHOR_DATE` override
+    /// `user.name`, `user.email` and the current time.
+    pub fn author_signature(&self) -> Option<Signature> {
+        self.signature("AUTHOR")
+    }
+
+    /// Signature for the committer of new commits and tagger of new tags.
+    ///
+    /// `GIT_COMMITTER_NAME`, `GIT_COMMITTER_EMAIL` and `GIT_COMMITTER_DATE`
+    /// override `user.name`, `user.email` and the current time.
+    pub fn committer_signature(&self) -> Option<Signature> {
+        self.signature("COMMITTER")
+    }
+
+    fn signature(&self, role: &str) -> Option<Signature> {
+        let env = |key: &str| std::env::var(format!("GIT_{role}_{key}")).ok();
+        let name = env("NAME").or_else(|| self.config_string("user", "name"))?;
+        let email = env("EMAIL")
+            .or_else(|| self.config_string("user", "email"))
+            .or_else(|| std::env::var("EMAIL").ok())?;
+        let mut signature = Signature::now(name, email);
+        if let Some(date) = env("DATE") {
+            (signature.time, signature.tz_offset) = Signature::parse_date(&date)?;
+        }
+        Some(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::signature::{Signature, SignatureParseError};
+
+    #[test]
+    fn parses_and_formats() {
+        let raw = "Scott Chacon <schacon@gmail.com> 1243040974 -0700";
+        let signature = raw.parse::<Signature>().unwrap();
+        assert_eq!(signature.name, "Scott Chacon");
+        assert_eq!(signature.email, "schacon@gmail.com");
+        assert_eq!(signature.time, 1243040974);
+        assert_eq!(signature.tz_offset, -420);
+        assert_eq!(signature.to_string(), raw);
+        assert_eq!(signature.date_string(), "Fri May 22 18:09:34 2009 -0700");
+        assert_eq!(
+            signature.format_date("%Y-%m-%d %H:%M:%S %z"),
+            "2009-05-22 18:09:34 -0700"
+        );
+
+        let raw = "Half Hour <hh@example.com> 0 +0530";
+        assert_eq!(raw.parse::<Signature>().unwrap().tz_offset, 330);
+        assert_eq!(raw.parse::<Signature>().unwrap().to_string(), raw);
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert_eq!(
+            "No Email 1243040974 -0700".parse::<Signature>(),
+            Err(SignatureParseError::MissingEmail)
+        );
+        assert_eq!(
+            "Name <email> yesterday".parse::<Signature>(),
+            Err(SignatureParseError::InvalidDate)
+        );
+    }
+
+    #[test]
+    fn handles_edge_cases() {
+        let raw = "Just Behind <jb@example.com> 1527025023 -0030";
+        let signature = raw.parse::<Signature>().unwrap();
+        assert_eq!(signature.tz_offset, -30);
+        assert_eq!(signature.to_string(), raw);
+        assert_eq!(signature.date_string(), "Tue May 22 21:07:03 2018 -0030");
+
+        for malformed in [
+            "Name <email> 1527025023",
+            "Name <email> 1527025023 +02",
+            "Name <email> 1527025023 0200",
+            "Name <email>",
+        ] {
+            assert_eq!(
+                malformed.parse::<Signature>(),
+                Err(SignatureParseError::InvalidDate),
+                "{malformed}"
+            );
+        }
+        assert_eq!(
+            "Name email> 0 +0000".parse::<Signature>(),
+            Err(SignatureParseError::MissingEmail)
+        );
+
+        // Too far in the future for a calendar date, still printable.
+        let far = "Far <far@example.com> 9223372036854775807 +0100"
+            .parse::<Signature>()
+            .unwrap();
+        assert_eq!(far.date_string(), "9223372036854775807 +0100");
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(
+            Signature::parse_date("1527025023 +0200"),
+            Some((1527025023, 120))
+        );
+        assert_eq!(
+            Signature::parse_date("@1527025023 -0100"),
+            Some((1527025023, -60))
+        );
+        assert_eq!(
+            Signature::parse_date("2018-05-22T23:37:03+02:00"),
+            Some((1527025023, 120))
+        );
+        assert_eq!(Signature::parse_date("@1527025023"), Some((1527025023, 0)));
+        assert_eq!(
+            Signature::parse_date("Tue, 22 May 2018 23:37:03 +0200"),
+            Some((1527025023, 120))
+        );
+        assert_eq!(Signature::parse_date("1527025023 +2"), None);
+        assert_eq!(Signature::parse_date("not a date"), None);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::git::repo::Repository;
+use crate::git::status::WorkTreeChange;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+/// Directories checked out by a cone mode sparse checkout, stored as
+/// patterns in `info/sparse-checkout`.
+///
+/// Files at the top level are always checked out, as are all files below
+/// the directories and the files directly in their parent directories.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SparseCheckout {
+    /// Directories without leading or trailing `/`, none inside another.
+    dirs: BTreeSet<String>,
+}
+
+#[derive(Debug)]
+pub enum SparseCheckoutError {
+    /// Bare repositories have nothing to check out.
+    NoWorkTree,
+    /// The sparse checkout isn't enabled.
+    NotSparse,
+    IOError,
+}
+
+impl Display for SparseCheckoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SparseCheckoutError::NoWorkTree => {
+                write!(f, "this operation must be run in a work tree")
+            }
+            SparseCheckoutError::NotSparse => write!(f, "this worktree is not sparse"),
+            SparseCheckoutError::IOError => write!(f, "failed to update the sparse checkout"),
+        }
+    }
+}
+
+impl SparseCheckout {
+    /// Sparse checkout of [dirs] (relative to the work tree).
+    pub fn new(dirs: &[String]) -> Self {
+        let mut sparse = SparseCheckout::default();
+        sparse.add(dirs);
+        sparse
+    }
+
+    /// Parse the cone mode patterns of `info/sparse-checkout`:
+    ///
+    /// ```text
+    /// /*
+    /// !/*/
+    /// /parent/
+    /// !/parent/*/
+    /// /parent/dir/
+    /// ```
+    ///
+    /// Directories excluded again with `!/<dir>/*/` are only parents.
+    pub fn parse(data: &str) -> Self {
+        let mut included = Vec::new();
+        let mut parents = BTreeSet::new();
+        for line in data.lines().map(str::trim) {
+            if let Some(parent) = line.strip_prefix('!') {
+                if let Some(parent) = parent.strip_suffix("/*/") {
+                    parents.insert(parent.trim_start_matches('/'));
+                }
+            } else if line.starts_with('/') && line.ends_with('/') {
+                included.push(line.trim_matches('/'));
+            }
+        }
+        let dirs = included
+            .into_iter()
+            .filter(|dir| !dir.is_empty() && !parents.contains(dir))
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        SparseCheckout::new(&dirs)
+    }
+
+    /// The patterns for `info/sparse-checkout`, the parent directories first
+    /// like git writes them.
+    pub fn encode(&self) -> String {
+        let mut parents = BTreeSet::new();
+        for dir in &self.dirs {
+            let mut parent = dir.as_str();
+            while let Some((rest, _)) = parent.rsplit_once('/') {
+                parents.insert(rest);
+                parent = rest;
+            }
+        }
+        let mut out = "/*\n!/*/\n".to_string();
+        for parent in parents {
+            out.push_str(&format!("/{parent}/\n!/{parent}/*/\n"));
+        }
+        for dir in &self.dirs {
+            out.push_str(&format!("/{dir}/\n"));
+        }
+        out
+    }
+
+    /// The checked out directories, sorted.
+    pub fn dirs(&self) -> impl Iterator<Item = &str> {
+        self.dirs.iter().map(String::as_str)
+    }
+
+    /// Also check out [dirs]. Directories inside others are dropped.
+    pub fn add(&mut self, dirs: &[String]) {
+        for dir in dirs {
+            let dir = dir.trim_matches('/');
+            if !dir.is_empty() {
+                self.dirs.insert(dir.to_string());
+            }
+        }
+        let dirs = std::mem::take(&mut self.dirs);
+        for dir in &dirs {
+            let nested = dirs.iter().any(|other| {
+                dir.strip_prefix(other.as_str())
+                    .is_some_and(|r| r.starts_with('/'))
+            });
+            if !nested {
+                self.dirs.insert(dir.clone());
+            }
+        }
+    }
+
+    /// Whether the file at [path] (relative to the work tree) is checked
+    /// out.
+    pub fn includes(&self, path: &str) -> bool {
+        let Some((dir, _)) = path.rsplit_once('/') else {
+            return true;
+        };
+        self.dirs.iter().any(|included| {
+            let inside = |a: &str, b: &str| a.strip_prefix(b).is_some_and(|r| r.starts_with('/'));
+            dir == included || inside(dir, included) || inside(included, dir)
+        })
+    }
+}
+
+impl Repository {
+    /// The sparse checkout of this worktree, [None] unless
+    /// `core.sparseCheckout` is enabled.
+    pub fn sparse_checkout(&self) -> Option<SparseCheckout> {
+        if self.config_string("core", "sparseCheckout")? != "true" {
+            return None;
+        }
+        let data = fs::read_to_string(self.git_path("info/sparse-checkout")).unwrap_or_default();
+        Some(SparseCheckout::parse(&data))
+    }
+
+    /// Enable the sparse checkout, with only the top level files unless
+    /// `info/sparse-checkout` already has patterns.
+    ///
+    /// Returns the files that have changes and are kept although they are
+    /// outside of the sparse checkout.
+    pub fn sparse_checkout_init(&mut self) -> Result<Vec<String>, SparseCheckoutError> {
+        let path = self.git_path("info/sparse-checkout");
+        let sparse = match fs::read_to_string(&path) {
+            Ok(data) => SparseCheckout::parse(&data),
+            Err(_) => SparseCheckout::default(),
+        };
+        self.sparse_checkout_set(&sparse)
+    }
+
+    /// Only check out the top level files and [sparse], enabling the sparse
+    /// checkout if needed. Files outside of it are removed from the work
+    /// tree and marked to be skipped in the index, files inside it that
+    /// were skipped are checked out.
+    ///
+    /// Returns the files that have changes and are kept although they are
+    /// outside of the sparse checkout.
+    pub fn sparse_checkout_set(
+        &mut self,
+        sparse: &SparseCheckout,
+    ) -> Result<Vec<String>, SparseCheckoutError> {
+        let path = self.git_path("info/sparse-checkout");
+        let io_error = |_| SparseCheckoutError::IOError;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        fs::write(path, sparse.encode()).map_err(io_error)?;
+        self.sparse_checkout_config(true)?;
+        self.sparse_checkout_apply(Some(sparse))
+    }
+
+    /// Check out [dirs] too.
+    ///
+    /// Returns the files that have changes and are kept although they are
+    /// outside of the sparse checkout.
+    pub fn sparse_checkout_add(
+        &mut self,
+        dirs: &[String],
+    ) -> Result<Vec<String>, SparseCheckoutError> {
+        let mut sparse = self
+            .sparse_checkout()
+            .ok_or(SparseCheckoutError::NotSparse)?;
+        sparse.add(dirs);
+        self.sparse_checkout_set(&sparse)
+    }
+
+    /// Check out all files again and disable the sparse checkout. Like git,
+    /// `info/sparse-checkout` is kept for a later [Repository::sparse_checkout_init].
+    pub fn sparse_checkout_disable(&mut self) -> Result<(), SparseCheckoutError> {
+        self.sparse_checkout_apply(None)?;
+        self.sparse_checkout_config(false)
+    }
+
+    /// Like git, each worktree has its own sparse checkout config.
+    fn sparse_checkout_config(&mut self, enabled: bool) -> Result<(), SparseCheckoutError> {
+        let value = if enabled { "true" } else { "false" };
+        self.config_set_worktree("core", "sparseCheckout", value)
+            .and_then(|_| self.config_set_worktree("core", "sparseCheckoutCone", value))
+            .ok_or(SparseCheckoutError::IOError)
+    }
+
+    /// Make the work tree and the skip-worktree bits of the index match
+    /// [sparse], everything is checked out for [None]. Returns the files
+    /// with changes that are kept.
+    fn sparse_checkout_apply(
+        &self,
+        sparse: Option<&SparseCheckout>,
+    ) -> Result<Vec<String>, SparseCheckoutError> {
+        let work_tree = self.work_tree().ok_or(SparseCheckoutError::NoWorkTree)?;
+        let changes = self
+            .work_tree_changes()
+            .ok_or(SparseCheckoutError::NoWorkTree)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let Some(mut index) = self.index() else {
+            return Ok(Vec::new());
+        };
+        let files = index
+            .iter()
+            .map(|entry| {
+                let path = entry.name().to_string_lossy().to_string();
+                (path, entry.mode_str(), *entry.hash())
+            })
+            .collect::<Vec<_>>();
+        let mut attributes = self.checkout_attributes(&files);
+        let mut kept = Vec::new();
+        for entry in index.iter_mut().filter(|entry| entry.stage() == 0) {
+            let path = entry.name().to_string_lossy().to_string();
+            let included = sparse.is_none_or(|sparse| sparse.includes(&path));
+            if included && entry.skip_worktree() {
+                self.checkout_file(&mut attributes, &path, &entry.mode_str(), *entry.hash())
+                    .ok_or(SparseCheckoutError::IOError)?;
+                if let Ok(metadata) = work_tree.join(&path).symlink_metadata() {
+                    entry.update_stat(&metadata);
+                }
+                entry.set_skip_worktree(false);
+            } else if !included && !entry.skip_worktree() {
+                if changes.get(entry.name()) == Some(&WorkTreeChange::Modified) {
+                    kept.push(path);
+                    continue;
+                }
+                self.remove_work_tree_file(work_tree, entry.name());
+                entry.set_skip_worktree(true);
+            }
+        }
+        self.index_write(&index)
+            .ok_or(SparseCheckoutError::IOError)?;
+        Ok(kept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SparseCheckout;
+
+    #[test]
+    fn encodes_cone_patterns() {
+        let dirs = ["a/b/c/", "/e", "a/b/c/d"].map(str::to_string);
+        let mut sparse = SparseCheckout::new(&dirs);
+        let patterns = "/*\n!/*/\n/a/\n!/a/*/\n/a/b/\n!/a/b/*/\n/a/b/c/\n/e/\n";
+        assert_eq!(sparse.encode(), patterns);
+        assert_eq!(SparseCheckout::parse(patterns), sparse);
+        assert!(sparse.includes("top.txt"));
+        assert!(sparse.includes("a/b/file.txt"));
+        assert!(sparse.includes("a/b/c/d/file.txt"));
+        assert!(!sparse.includes("a/x/file.txt"));
+        assert!(!sparse.includes("ab/file.txt"));
+
+        sparse.add(&["a/b".to_string()]);
+        assert_eq!(sparse.dirs().collect::<Vec<_>>(), ["a/b", "e"]);
+        assert!(sparse.includes("a/b/x/file.txt"));
+    }
+}
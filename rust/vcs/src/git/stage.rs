@@ -0,0 +1,135 @@
+use crate::git::clean::CleanOptions;
+use crate::git::ignore::IgnoreRules;
+use crate::git::index::{GitIndex, GitIndexEntry};
+use crate::git::objects::GitObjectType;
+use crate::git::repo::Repository;
+use crate::git::status::{work_tree_mode, WorkTreeChange};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+impl Repository {
+    /// Stage the work tree state of [paths] (files or directories relative
+    /// to the work tree, `.` for everything) like `git add --all`.
+    ///
+    /// Changed files are stored as blobs, deleted ones are removed from the
+    /// index and untracked files that aren't ignored are added. Returns the
+    /// staged paths, [None] for bare repositories.
+    pub fn stage(&self, paths: &[String]) -> Option<Vec<PathBuf>> {
+        let work_tree = self.work_tree()?;
+        let selected = |path: &Path| {
+            paths.iter().any(|spec| {
+                let spec = spec.trim_end_matches('/');
+                spec == "." || path.starts_with(spec)
+            })
+        };
+        let changes = self
+            .work_tree_changes()?
+            .into_iter()
+            .filter(|(path, _)| selected(path))
+            .collect::<HashMap<PathBuf, WorkTreeChange>>();
+        let index = self.index().unwrap_or_else(|| GitIndex::new(Vec::new()));
+
+        let mut staged = Vec::new();
+        let mut entries = Vec::new();
+        let mut old_entries = HashMap::new();
+        for entry in index.iter() {
+            let path = entry.name().to_path_buf();
+            // Conflicted files are resolved by staging them.
+            let conflicted = entry.stage() != 0 && selected(&path);
+            match changes.get(&path) {
+                _ if conflicted && work_tree.join(&path).symlink_metadata().is_err() => {
+                    staged.push(path)
+                }
+                _ if conflicted => {
+                    old_entries.insert(path, (entry.mode_str(), *entry.hash()));
+                }
+                None => entries.push(entry.clone()),
+                Some(WorkTreeChange::Deleted) => staged.push(path),
+                Some(WorkTreeChange::Modified) => {
+                    old_entries.insert(path, (entry.mode_str(), *entry.hash()));
+                }
+            }
+        }
+
+        let mut to_add = old_entries.keys().cloned().collect::<BTreeSet<PathBuf>>();
+        let untracked = CleanOptions {
+            directories: true,
+            ignored: false,
+            dry_run: true,
+            paths: paths.to_vec(),
+        };
+        let mut rules = self.ignore_rules()?;
+        for path in self.clean(&untracked)? {
+            match path.strip_suffix('/') {
+                Some(dir) => untracked_files(work_tree, dir, &mut rules, &mut to_add),
+                None => {
+                    to_add.insert(PathBuf::from(path));
+                }
+            }
+        }
+
+        let file_mode = self
+            .config_string("core", "fileMode")
+            .is_none_or(|enabled| enabled != "false");
+        let mut attributes = self.attributes();
+        for path in to_add {
+            let full_path = work_tree.join(&path);
+            let metadata = full_path.symlink_metadata().ok()?;
+            let old = old_entries.get(&path);
+            let old_mode = old.map_or("100644", |(mode, _)| mode.as_str());
+            let entry = if old_mode == "160000" || metadata.is_dir() {
+                // A checked out submodule records its `HEAD`.
+                let head = Repository::new(full_path, None).ok()?.head()?;
+                GitIndexEntry::new(path.clone(), head, "160000")
+            } else {
+                let mode = work_tree_mode(&metadata, old_mode, file_mode)?;
+                let data = if mode == "120000" {
+                    let target = fs::read_link(&full_path).ok()?;
+                    target.to_string_lossy().as_bytes().to_vec()
+                } else {
+                    let name = path.to_string_lossy();
+                    let conversion = attributes.conversion(&name);
+                    let data = fs::read(&full_path).ok()?;
+                    self.convert_to_git(&name, &conversion, data, old.map(|(_, id)| *id))?
+                };
+                let id = self.object_write_raw(GitObjectType::Blob, &data).ok()?;
+                let mut entry = GitIndexEntry::new(path.clone(), id, &mode);
+                entry.update_stat(&metadata);
+                entry
+            };
+            entries.push(entry);
+            staged.push(path);
+        }
+        self.index_write(&GitIndex::new(entries))?;
+        staged.sort();
+        staged.dedup();
+        Some(staged)
+    }
+}
+
+/// Add the files below the untracked directory [dir] that aren't ignored to
+/// [files], skipping nested repositories.
+fn untracked_files(
+    work_tree: &Path,
+    dir: &str,
+    rules: &mut IgnoreRules,
+    files: &mut BTreeSet<PathBuf>,
+) {
+    let Ok(entries) = fs::read_dir(work_tree.join(dir)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = format!("{dir}/{name}");
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if name == ".git" || rules.is_ignored(&path, is_dir) {
+            continue;
+        }
+        if !is_dir {
+            files.insert(PathBuf::from(path));
+        } else if !entry.path().join(".git").exists() {
+            untracked_files(work_tree, &path, rules, files);
+        }
+    }
+}
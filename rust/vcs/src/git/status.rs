@@ -0,0 +1,306 @@
+use crate::git::attributes::Conversion;
+use crate::git::clean::CleanOptions;
+use crate::git::index::GitIndex;
+use crate::git::object_id::ObjectId;
+use crate::git::objects::GitObjectType;
+use crate::git::repo::Repository;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How a tracked file in the work tree differs from the index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkTreeChange {
+    /// Content, type or executable bit changed.
+    Modified,
+    Deleted,
+}
+
+/// Changes in the index and the work tree, paths are relative to the work
+/// tree and sorted.
+#[derive(Debug, Default)]
+pub struct GitStatus {
+    /// Checked out branch without `refs/heads/`, `HEAD` if detached.
+    pub active_branch: String,
+    /// File names of new files.
+    pub added: Vec<String>,
+    /// File names of modified files.
+    pub modified: Vec<String>,
+    /// File names of removed files.
+    pub deleted: Vec<String>,
+    /// Tracked files changed in the work tree but not staged.
+    pub unstaged_modified: Vec<String>,
+    /// Tracked files removed from the work tree but not staged.
+    pub unstaged_deleted: Vec<String>,
+    /// Files that are neither tracked nor ignored. Untracked directories
+    /// are listed once with a trailing `/`.
+    pub untracked: Vec<String>,
+    /// Share of the tracked files that are checked out in a sparse
+    /// checkout, rounded up to whole percents. [None] if not sparse.
+    pub sparse_percentage: Option<usize>,
+}
+
+impl GitStatus {
+    /// Whether there is nothing to commit and no untracked file.
+    pub fn is_clean(&self) -> bool {
+        !self.has_tracked_changes() && self.untracked.is_empty()
+    }
+
+    /// Whether tracked files have staged or unstaged changes.
+    pub fn has_tracked_changes(&self) -> bool {
+        !self.added.is_empty()
+            || !self.modified.is_empty()
+            || !self.deleted.is_empty()
+            || !self.unstaged_modified.is_empty()
+            || !self.unstaged_deleted.is_empty()
+    }
+}
+
+impl Repository {
+    /// Compare `HEAD` with the index and the index with the work tree.
+    ///
+    /// Returns [None] for bare repositories.
+    pub fn status(&self) -> Option<GitStatus> {
+        let work_tree_changes = self.work_tree_changes()?;
+        let index = self.index().unwrap_or_else(|| GitIndex::new(Vec::new()));
+        let mut head = self
+            .head()
+            .and_then(|head| self.object_peel(head, GitObjectType::Tree))
+            .and_then(|tree| self.tree_flatten(tree))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(path, mode, id)| (path, (mode, id)))
+            .collect::<HashMap<String, (String, ObjectId)>>();
+
+        let mut status = GitStatus {
+            active_branch: self.get_active_branch().unwrap_or("HEAD".to_string()),
+            ..GitStatus::default()
+        };
+        for entry in index.iter().filter(|entry| entry.stage() == 0) {
+            let path = entry.name().to_string_lossy().to_string();
+            match head.remove(&path) {
+                None => status.added.push(path),
+                Some((mode, id)) if mode != entry.mode_str() || id != *entry.hash() => {
+                    status.modified.push(path)
+                }
+                Some(_) => {}
+            }
+        }
+        status.deleted = head.into_keys().collect();
+        status.deleted.sort();
+        let total = index.iter().count();
+        if self.sparse_checkout().is_some() && total > 0 {
+            let skipped = index.iter().filter(|entry| entry.skip_worktree()).count();
+            status.sparse_percentage = Some(100 - 100 * skipped / total);
+        }
+
+        for (path, change) in work_tree_changes {
+            let path = path.to_string_lossy().to_string();
+            match change {
+                WorkTreeChange::Modified => status.unstaged_modified.push(path),
+                WorkTreeChange::Deleted => status.unstaged_deleted.push(path),
+            }
+        }
+        let untracked = CleanOptions {
+            directories: true,
+            ignored: false,
+            dry_run: true,
+            paths: Vec::new(),
+        };
+        // Unlike clean, status doesn't show directories without files.
+        let work_tree = self.work_tree()?;
+        status.untracked = self
+            .clean(&untracked)?
+            .into_iter()
+            .filter(|path| !path.ends_with('/') || has_files(&work_tree.join(path)))
+            .collect();
+        Some(status)
+    }
+
+    /// Tracked files whose work tree state differs from the index, in index
+    /// order. Files outside of the sparse checkout are left out.
+    ///
+    /// Files whose stat data (times, device, inode and size) matches the
+    /// index are taken as unchanged unless they were modified in the same
+    /// second the index was written. All others are hashed, spread over the
+    /// available cores. The index is only read, the stat data of files found
+    /// unchanged is refreshed by the commands that write it.
+    ///
+    /// Returns [None] for bare repositories.
+    pub fn work_tree_changes(&self) -> Option<Vec<(PathBuf, WorkTreeChange)>> {
+        let work_tree = self.work_tree()?;
+        let Some(index) = self.index() else {
+            return Some(Vec::new());
+        };
+        let index_time = fs::metadata(self.git_path("index"))
+            .and_then(|metadata| metadata.modified())
+            .map(since_epoch)
+            .unwrap_or_default();
+        let file_mode = self
+            .config_string("core", "fileMode")
+            .is_none_or(|enabled| enabled != "false");
+        let empty_blob = self.object_hash(GitObjectType::Blob, &[]);
+
+        let mut changes = Vec::new();
+        let mut to_hash = Vec::new();
+        for entry in index.iter() {
+            if entry.stage() != 0 || entry.skip_worktree() {
+                continue;
+            }
+            let path = work_tree.join(entry.name());
+            let mode = entry.mode_str();
+            if mode == "160000" {
+                // Submodules are changed if another commit is checked out.
+                let changed = path.join(".git").exists()
+                    && Repository::new(path, None)
+                        .is_ok_and(|sub| sub.head() != Some(*entry.hash()));
+                if changed {
+                    changes.push((entry.name().to_path_buf(), WorkTreeChange::Modified));
+                }
+                continue;
+            }
+            let Ok(metadata) = path.symlink_metadata() else {
+                changes.push((entry.name().to_path_buf(), WorkTreeChange::Deleted));
+                continue;
+            };
+            match work_tree_mode(&metadata, &mode, file_mode) {
+                None => {
+                    changes.push((entry.name().to_path_buf(), WorkTreeChange::Deleted));
+                    continue;
+                }
+                Some(work_tree_mode) if work_tree_mode != mode => {
+                    changes.push((entry.name().to_path_buf(), WorkTreeChange::Modified));
+                    continue;
+                }
+                Some(_) => {}
+            }
+            // A size of 0 for content that isn't empty means the entry was
+            // smudged because it was racy.
+            let smudged = entry.file_size() == 0 && *entry.hash() != empty_blob;
+            if entry.stat_matches(&metadata) && !entry.is_racy(index_time) && !smudged {
+                continue;
+            }
+            let name = entry.name().to_string_lossy().to_string();
+            to_hash.push((entry.name(), path, name, *entry.hash()));
+        }
+
+        let mut attributes = (!to_hash.is_empty()).then(|| self.attributes());
+        let files = to_hash
+            .iter()
+            .map(|(_, path, name, id)| {
+                let conversion = attributes.as_mut()?.conversion(name);
+                Some((path.as_path(), name.as_str(), conversion, *id))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let hashes = hash_files(self, &files);
+        for ((entry_name, _, _, expected), id) in to_hash.into_iter().zip(hashes) {
+            if id != Some(expected) {
+                changes.push((entry_name.to_path_buf(), WorkTreeChange::Modified));
+            }
+        }
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        Some(changes)
+    }
+}
+
+/// Tree mode of the work tree file with [metadata], [None] for directories.
+///
+/// Without [file_mode] (`core.fileMode = false`) the executable bit is taken
+/// from [index_mode].
+pub(crate) fn work_tree_mode(
+    metadata: &fs::Metadata,
+    index_mode: &str,
+    file_mode: bool,
+) -> Option<String> {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        return Some("120000".to_string());
+    }
+    if !file_type.is_file() {
+        return None;
+    }
+    if !file_mode && index_mode.starts_with("100") {
+        return Some(index_mode.to_string());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return Some("100755".to_string());
+        }
+    }
+    Some("100644".to_string())
+}
+
+/// Blob ids of the [files] (path, name in the index, conversion and indexed
+/// blob) as they would be stored, symlinks hash their target. Hashed on as
+/// many threads as there are cores, [None] for files that can't be read.
+fn hash_files(
+    repo: &Repository,
+    files: &[(&Path, &str, Conversion, ObjectId)],
+) -> Vec<Option<ObjectId>> {
+    let threads = thread::available_parallelism()
+        .map_or(1, |threads| threads.get())
+        .min(files.len());
+    let next = AtomicUsize::new(0);
+    let mut hashes = vec![None; files.len()];
+    thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((path, name, conversion, indexed)) = files.get(i) else {
+                            break;
+                        };
+                        let id = hash_file(repo, path, name, conversion, *indexed);
+                        done.push((i, id));
+                    }
+                    done
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            for (i, id) in worker.join().unwrap_or_default() {
+                hashes[i] = id;
+            }
+        }
+    });
+    hashes
+}
+
+fn hash_file(
+    repo: &Repository,
+    path: &Path,
+    name: &str,
+    conversion: &Conversion,
+    indexed: ObjectId,
+) -> Option<ObjectId> {
+    let data = if path.is_symlink() {
+        fs::read_link(path)
+            .ok()?
+            .to_string_lossy()
+            .as_bytes()
+            .to_vec()
+    } else {
+        repo.convert_to_git(name, conversion, fs::read(path).ok()?, Some(indexed))?
+    };
+    Some(repo.object_hash(GitObjectType::Blob, &data))
+}
+
+/// Whether there is a file anywhere below the directory [dir].
+fn has_files(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| entry.file_type().is_ok_and(|t| !t.is_dir()) || has_files(&entry.path()))
+    })
+}
+
+fn since_epoch(time: SystemTime) -> Duration {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default()
+}
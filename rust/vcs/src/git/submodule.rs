@@ -0,0 +1,343 @@
+use crate::git::config::ConfigFile;
+use crate::git::object_id::ObjectId;
+use crate::git::repo::Repository;
+use crate::git::reset::ResetMode;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A submodule declared in `.gitmodules`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Submodule {
+    pub name: String,
+    /// Location in the work tree, relative to its root.
+    pub path: String,
+    /// Where to clone from as written in `.gitmodules`, possibly relative
+    /// to the superproject's remote.
+    pub url: String,
+}
+
+/// State of a submodule as reported by `submodule status`.
+#[derive(Debug)]
+pub struct SubmoduleStatus {
+    pub submodule: Submodule,
+    /// Commit recorded in the index of the superproject.
+    pub recorded: ObjectId,
+    /// Commit checked out in the submodule, [None] if it isn't cloned.
+    pub checked_out: Option<ObjectId>,
+}
+
+#[derive(Debug)]
+pub enum SubmoduleError {
+    /// `submodule.<name>.url` isn't set in the config, see
+    /// [Repository::submodule_init].
+    NotInitialized(String),
+    /// The url doesn't point to a local repository.
+    UnsupportedUrl(String),
+    /// The submodule doesn't contain the recorded commit.
+    MissingCommit(ObjectId),
+    /// The submodule path has files but isn't a repository.
+    NotEmpty(String),
+    /// Checking out would overwrite changes in the submodule.
+    LocalChanges(String),
+    IOError,
+}
+
+impl Display for SubmoduleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmoduleError::NotInitialized(path) => {
+                write!(f, "Submodule path '{path}' not initialized")
+            }
+            SubmoduleError::UnsupportedUrl(url) => {
+                write!(f, "'{url}' is not a local repository")
+            }
+            SubmoduleError::MissingCommit(id) => {
+                write!(f, "Fetched in submodule, but it did not contain {id}")
+            }
+            SubmoduleError::NotEmpty(path) => {
+                write!(f, "'{path}' already exists and is not an empty directory")
+            }
+            SubmoduleError::LocalChanges(path) => write!(
+                f,
+                "Your local changes in submodule path '{path}' would be overwritten by checkout"
+            ),
+            SubmoduleError::IOError => write!(f, "failed to write the submodule"),
+        }
+    }
+}
+
+impl Repository {
+    /// Submodules declared in the `.gitmodules` file of the work tree,
+    /// sorted by path. Entries without path or url are skipped.
+    pub fn submodules(&self) -> Vec<Submodule> {
+        let Some(work_tree) = self.work_tree() else {
+            return Vec::new();
+        };
+        let path = work_tree.join(".gitmodules");
+        let Ok(file) = ConfigFile::open(path) else {
+            return Vec::new();
+        };
+        let mut submodules = file
+            .sections()
+            .filter_map(|section| {
+                let name = section.strip_prefix("submodule \"")?.strip_suffix('"')?;
+                Some(Submodule {
+                    name: name.to_string(),
+                    path: file.get_string(section, "path")?.clone(),
+                    url: file.get_string(section, "url")?.clone(),
+                })
+            })
+            .collect::<Vec<Submodule>>();
+        submodules.sort_by(|a, b| a.path.cmp(&b.path));
+        submodules
+    }
+
+    /// Status of the submodules recorded in the index (mode `160000`), in
+    /// index order.
+    pub fn submodule_status(&self) -> Vec<SubmoduleStatus> {
+        let Some(index) = self.index() else {
+            return Vec::new();
+        };
+        let submodules = self.submodules();
+        index
+            .iter()
+            .filter(|entry| entry.mode_str() == "160000" && entry.stage() == 0)
+            .map(|entry| {
+                let path = entry.name().to_string_lossy().to_string();
+                let submodule = submodules
+                    .iter()
+                    .find(|submodule| submodule.path == path)
+                    .cloned()
+                    .unwrap_or_else(|| Submodule {
+                        name: path.clone(),
+                        path: path.clone(),
+                        url: String::new(),
+                    });
+                let checked_out = self.submodule_repo(&submodule).and_then(|sub| sub.head());
+                SubmoduleStatus {
+                    submodule,
+                    recorded: *entry.hash(),
+                    checked_out,
+                }
+            })
+            .collect()
+    }
+
+    /// The cloned repository of [submodule], [None] if it isn't checked out.
+    pub fn submodule_repo(&self, submodule: &Submodule) -> Option<Repository> {
+        let path = self.work_tree()?.join(&submodule.path);
+        if !path.join(".git").exists() {
+            return None;
+        }
+        Repository::new(path, None).ok()
+    }
+
+    /// Copy the url of [submodule] to the repository config, resolving
+    /// relative urls against the `origin` remote or the work tree.
+    ///
+    /// Returns the url, [None] if it was already initialized.
+    pub fn submodule_init(&mut self, submodule: &Submodule) -> Option<String> {
+        let section = format!("submodule \"{}\"", submodule.name);
+        if self.config_string(&section, "url").is_some() {
+            return None;
+        }
+        let url = if submodule.url.starts_with("./") || submodule.url.starts_with("../") {
+            let base = self
+                .config_string("remote \"origin\"", "url")
+                .or_else(|| Some(self.work_tree()?.to_string_lossy().to_string()))?;
+            resolve_url(&base, &submodule.url)
+        } else {
+            submodule.url.clone()
+        };
+        self.config_set(&section, "active", "true")?;
+        self.config_set(&section, "url", &url)?;
+        Some(url)
+    }
+
+    /// Check out the commit recorded for [submodule], cloning it into
+    /// `.git/modules/<name>` first if needed.
+    ///
+    /// Only local paths and `file://` urls are supported. Returns the commit
+    /// if it was checked out, [None] if it already was.
+    pub fn submodule_update(
+        &self,
+        submodule: &Submodule,
+    ) -> Result<Option<ObjectId>, SubmoduleError> {
+        let work_tree = self.work_tree().ok_or(SubmoduleError::IOError)?;
+        let recorded = self
+            .index()
+            .and_then(|index| {
+                index
+                    .iter()
+                    .find(|entry| {
+                        entry.mode_str() == "160000" && entry.name() == Path::new(&submodule.path)
+                    })
+                    .map(|entry| *entry.hash())
+            })
+            .ok_or(SubmoduleError::IOError)?;
+        let section = format!("submodule \"{}\"", submodule.name);
+        let url = self
+            .config_string(&section, "url")
+            .ok_or_else(|| SubmoduleError::NotInitialized(submodule.path.clone()))?;
+        let source_path = PathBuf::from(url.strip_prefix("file://").unwrap_or(&url));
+        let source = if source_path.join(".git").exists() {
+            Repository::new(source_path, None)
+        } else {
+            Repository::open_bare(source_path)
+        }
+        .map_err(|_| SubmoduleError::UnsupportedUrl(url.clone()))?;
+
+        let (mut sub, cloned) = match self.submodule_repo(submodule) {
+            Some(sub) => (sub, false),
+            None => (
+                self.submodule_clone(submodule, &url, &source, work_tree)?,
+                true,
+            ),
+        };
+        if !sub.object_exists(&recorded) {
+            copy_objects(&source, &sub).map_err(|_| SubmoduleError::IOError)?;
+            // Reopen to pick up the new packs.
+            sub = self
+                .submodule_repo(submodule)
+                .ok_or(SubmoduleError::IOError)?;
+            if !sub.object_exists(&recorded) {
+                return Err(SubmoduleError::MissingCommit(recorded));
+            }
+        }
+        if !cloned && sub.head() == Some(recorded) {
+            return Ok(None);
+        }
+        if !cloned && sub.has_changes() {
+            return Err(SubmoduleError::LocalChanges(submodule.path.clone()));
+        }
+        // Detach first so no branch is moved.
+        sub.ref_update("HEAD", recorded)
+            .and_then(|_| sub.reset(recorded, ResetMode::Hard))
+            .ok_or(SubmoduleError::IOError)?;
+        Ok(Some(recorded))
+    }
+
+    /// Clone [source] into `.git/modules/<name>` with its work tree at the
+    /// submodule path, the way `git clone --separate-git-dir` lays it out.
+    fn submodule_clone(
+        &self,
+        submodule: &Submodule,
+        url: &str,
+        source: &Repository,
+        work_tree: &Path,
+    ) -> Result<Repository, SubmoduleError> {
+        let path = work_tree.join(&submodule.path);
+        if path.read_dir().is_ok_and(|mut dir| dir.next().is_some()) {
+            return Err(SubmoduleError::NotEmpty(submodule.path.clone()));
+        }
+        fs::create_dir_all(&path).map_err(|_| SubmoduleError::IOError)?;
+        let git_dir = self.common_dir().join("modules").join(&submodule.name);
+        let mut sub = Repository::init_at(git_dir, Some(path.clone()), source.object_format())
+            .map_err(|_| SubmoduleError::IOError)?;
+
+        // Both links are relative so the superproject can be moved.
+        let up = |path: &str| "../".repeat(path.split('/').count());
+        let dot_git = format!(
+            "gitdir: {}.git/modules/{}\n",
+            up(&submodule.path),
+            submodule.name
+        );
+        let core_worktree = format!("../../{}{}", up(&submodule.name), submodule.path);
+        let mut write = || -> Option<()> {
+            fs::write(path.join(".git"), &dot_git).ok()?;
+            sub.config_set("core", "worktree", &core_worktree)?;
+            sub.config_set("remote \"origin\"", "url", url)?;
+            sub.config_set(
+                "remote \"origin\"",
+                "fetch",
+                "+refs/heads/*:refs/remotes/origin/*",
+            )
+        };
+        write().ok_or(SubmoduleError::IOError)?;
+        copy_objects(source, &sub).map_err(|_| SubmoduleError::IOError)?;
+
+        for (name, id) in source.refs() {
+            let name = match name.strip_prefix("refs/heads/") {
+                Some(branch) => format!("refs/remotes/origin/{branch}"),
+                None if name.starts_with("refs/tags/") => name,
+                None => continue,
+            };
+            sub.ref_update(&name, id).ok_or(SubmoduleError::IOError)?;
+        }
+        // Like a clone, start with a local branch for the remote `HEAD`.
+        if let (Some(head), Some(id)) = (source.head_ref(), source.head()) {
+            let branch = head.strip_prefix("refs/heads/").unwrap_or(&head);
+            sub.ref_update(&head, id).ok_or(SubmoduleError::IOError)?;
+            fs::write(
+                sub.git_path("refs/remotes/origin/HEAD"),
+                format!("ref: refs/remotes/origin/{branch}\n"),
+            )
+            .map_err(|_| SubmoduleError::IOError)?;
+        }
+        Repository::new(path, None).map_err(|_| SubmoduleError::IOError)
+    }
+}
+
+/// Resolve a submodule [url] starting with `./` or `../` against [base],
+/// each `../` removing one component of [base].
+fn resolve_url(base: &str, url: &str) -> String {
+    let mut base = base.trim_end_matches('/').to_string();
+    let mut url = url;
+    loop {
+        if let Some(rest) = url.strip_prefix("./") {
+            url = rest;
+        } else if let Some(rest) = url.strip_prefix("../") {
+            url = rest;
+            match base.rfind('/') {
+                Some(i) => base.truncate(i),
+                None => base.clear(),
+            }
+        } else {
+            break;
+        }
+    }
+    if base.is_empty() {
+        url.to_string()
+    } else {
+        format!("{base}/{url}")
+    }
+}
+
+/// Copy the loose objects and packs of [source] that [target] lacks.
+fn copy_objects(source: &Repository, target: &Repository) -> std::io::Result<()> {
+    let mut dirs = vec![PathBuf::new()];
+    let from = source.common_dir().join("objects");
+    let to = target.common_dir().join("objects");
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(from.join(&dir))? {
+            let entry = entry?;
+            let path = dir.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                fs::create_dir_all(to.join(&path))?;
+                dirs.push(path);
+            } else if path.starts_with("info") {
+                // Alternates and similar files only make sense for the source.
+                continue;
+            } else if !to.join(&path).exists() {
+                fs::copy(entry.path(), to.join(&path))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::submodule::resolve_url;
+
+    #[test]
+    fn resolves_relative_urls() {
+        assert_eq!(resolve_url("/srv/super", "../lib"), "/srv/lib");
+        assert_eq!(resolve_url("/srv/super/", "./lib"), "/srv/super/lib");
+        assert_eq!(
+            resolve_url("file:///srv/a/super", "../../b/lib"),
+            "file:///srv/b/lib"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use crate::git::repo::{expand_home, Repository};
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+const SSH_SIGNATURE_MARKER: &str = "-----BEGIN SSH SIGNATURE-----";
+/// Namespace git uses for SSH signatures of commits and tags.
+const SSH_NAMESPACE: &str = "git";
+
+/// Outcome of checking a commit or tag signature.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// Valid signature by a trusted key.
+    Good { signer: String },
+    /// The signature doesn't match the signed data.
+    Bad,
+    /// Valid signature, but by a key that is not known or not allowed.
+    UnknownKey { key: String },
+    /// Verification could not be run, e.g. the verifying program is missing.
+    Error(String),
+}
+
+impl Verification {
+    pub fn is_good(&self) -> bool {
+        matches!(self, Verification::Good { .. })
+    }
+}
+
+impl std::fmt::Display for Verification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verification::Good { signer } => write!(f, "Good signature from {signer}"),
+            Verification::Bad => write!(f, "BAD signature"),
+            Verification::UnknownKey { key } => {
+                write!(f, "Can't check signature: unknown key {key}")
+            }
+            Verification::Error(err) => write!(f, "Can't check signature: {err}"),
+        }
+    }
+}
+
+impl Repository {
+    /// Check that [signature] was made over [payload].
+    ///
+    /// SSH signatures are verified with `ssh-keygen` against
+    /// `gpg.ssh.allowedSignersFile`, OpenPGP signatures with `gpg` (or
+    /// `gpg.program`) and its keyring.
+    pub fn verify_signature(&self, payload: &[u8], signature: &str) -> Verification {
+        let sig_file = match self.write_signature_file(signature) {
+            Ok(sig_file) => sig_file,
+            Err(err) => return Verification::Error(err.to_string()),
+        };
+        let res = if signature.contains(SSH_SIGNATURE_MARKER) {
+            self.verify_ssh(payload, &sig_file)
+        } else {
+            self.verify_gpg(payload, &sig_file)
+        };
+        let _ = fs::remove_file(sig_file);
+        res
+    }
+
+    /// Write [signature] to a new file in the git directory for the
+    /// verifying program to read. Existing files are never reused, so
+    /// nobody else can swap in another signature.
+    fn write_signature_file(&self, signature: &str) -> io::Result<PathBuf> {
+        for n in 0.. {
+            let path = self.git_path(&format!("vcs_signature_{}_{n}", process::id()));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    if let Err(err) = file.write_all(signature.as_bytes()) {
+                        let _ = fs::remove_file(&path);
+                        return Err(err);
+                    }
+                    return Ok(path);
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err),
+            }
+        }
+        unreachable!("ran out of signature file names")
+    }
+
+    fn verify_ssh(&self, payload: &[u8], sig_file: &Path) -> Verification {
+        let program = self
+            .config_string("gpg \"ssh\"", "program")
+            .unwrap_or("ssh-keygen".to_string());
+        let sig_file = sig_file.to_string_lossy().to_string();
+
+        if let Some(allowed) = self.config_string("gpg \"ssh\"", "allowedSignersFile") {
+            let allowed = expand_home(&allowed).to_string_lossy().to_string();
+            let principals = match run(
+                &program,
+                &["-Y", "find-principals", "-f", &allowed, "-s", &sig_file],
+                None,
+            ) {
+                Ok((true, out)) => out,
+                Ok((false, _)) => String::new(),
+                Err(err) => return Verification::Error(err),
+            };
+            if let Some(principal) = principals.lines().next() {
+                let args = [
+                    "-Y",
+                    "verify",
+                    "-f",
+                    &allowed,
+                    "-I",
+                    principal,
+                    "-n",
+                    SSH_NAMESPACE,
+                    "-s",
+                    &sig_file,
+                ];
+                return match run(&program, &args, Some(payload)) {
+                    Ok((true, _)) => Verification::Good {
+                        signer: principal.to_string(),
+                    },
+                    Ok((false, _)) => Verification::Bad,
+                    Err(err) => Verification::Error(err),
+                };
+            }
+        }
+
+        // No allowed signer matched, check whether the signature itself is
+        // valid to tell bad signatures from unknown keys.
+        let args = [
+            "-Y",
+            "check-novalidate",
+            "-n",
+            SSH_NAMESPACE,
+            "-s",
+            &sig_file,
+        ];
+        match run(&program, &args, Some(payload)) {
+            Ok((true, out)) => Verification::UnknownKey {
+                key: out
+                    .split_whitespace()
+                    .last()
+                    .unwrap_or_default()
+                    .to_string(),
+            },
+            Ok((false, _)) => Verification::Bad,
+            Err(err) => Verification::Error(err),
+        }
+    }
+
+    fn verify_gpg(&self, payload: &[u8], sig_file: &Path) -> Verification {
+        let program = self
+            .config_string("gpg", "program")
+            .unwrap_or("gpg".to_string());
+        let sig_file = sig_file.to_string_lossy().to_string();
+        let args = ["--status-fd=1", "--verify", &sig_file, "-"];
+        match run(&program, &args, Some(payload)) {
+            Ok((_, status)) => parse_gpg_status(&status),
+            Err(err) => Verification::Error(err),
+        }
+    }
+}
+
+/// Interpret the `--status-fd` output of `gpg --verify`.
+fn parse_gpg_status(status: &str) -> Verification {
+    let mut res = Verification::Error("gpg reported no signature".to_string());
+    for line in status.lines() {
+        let Some(line) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let (keyword, args) = line.split_once(' ').unwrap_or((line, ""));
+        match keyword {
+            "GOODSIG" => {
+                let signer = args.split_once(' ').map(|(_, uid)| uid).unwrap_or(args);
+                res = Verification::Good {
+                    signer: signer.to_string(),
+                };
+            }
+            "BADSIG" | "EXPSIG" | "EXPKEYSIG" | "REVKEYSIG" => return Verification::Bad,
+            "ERRSIG" | "NO_PUBKEY" => {
+                let key = args.split_whitespace().next().unwrap_or_default();
+                return Verification::UnknownKey {
+                    key: key.to_string(),
+                };
+            }
+            _ => {}
+        }
+    }
+    res
+}
+
+/// Run [program] with [args] writing [stdin] to it. Returns whether it exited
+/// successfully and its stdout.
+fn run(program: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<(bool, String), String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("failed to run {program}: {err}"))?;
+    if let Some(stdin) = stdin {
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(stdin)
+            .map_err(|err| err.to_string())?;
+    }
+    drop(child.stdin.take());
+    let out = child.wait_with_output().map_err(|err| err.to_string())?;
+    Ok((
+        out.status.success(),
+        String::from_utf8_lossy(&out.stdout).to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::verify::{parse_gpg_status, Verification};
+
+    #[test]
+    fn parses_gpg_status() {
+        let good = "[GNUPG:] NEWSIG\n\
+            [GNUPG:] GOODSIG 1A2B3C4D5E6F7A8B Jane Doe <jane@example.com>\n\
+            [GNUPG:] VALIDSIG ABCDEF\n";
+        assert_eq!(
+            parse_gpg_status(good),
+            Verification::Good {
+                signer: "Jane Doe <jane@example.com>".to_string()
+            }
+        );
+        let bad = "[GNUPG:] BADSIG 1A2B3C4D5E6F7A8B Jane Doe <jane@example.com>\n";
+        assert_eq!(parse_gpg_status(bad), Verification::Bad);
+        let unknown = "[GNUPG:] ERRSIG 1A2B3C4D5E6F7A8B 22 8 00 1527025023 9 -\n\
+            [GNUPG:] NO_PUBKEY 1A2B3C4D5E6F7A8B\n";
+        assert_eq!(
+            parse_gpg_status(unknown),
+            Verification::UnknownKey {
+                key: "1A2B3C4D5E6F7A8B".to_string()
+            }
+        );
+    }
+}
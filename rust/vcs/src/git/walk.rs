@@ -0,0 +1,246 @@
+use crate::git::commit_graph::{CommitInfo, GENERATION_INFINITY};
+use crate::git::object_id::ObjectId;
+use crate::git::repo::Repository;
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Iterates over the commits reachable from a set of starting points, newest
+/// committer time first (like `git log`).
+///
+/// Parents and times come from the commit-graph when there is one, so the
+/// commits themselves are only read for commits missing from it.
+pub struct CommitWalk<'a> {
+    repo: &'a Repository,
+    /// Commits to visit ordered by committer time, then by insertion order.
+    queue: BinaryHeap<(i64, Reverse<usize>, ObjectId)>,
+    /// Number of commits pushed to [queue] so far.
+    inserted: usize,
+    /// Parents of the commits in [queue].
+    pending: HashMap<ObjectId, Vec<ObjectId>>,
+    seen: HashSet<ObjectId>,
+    first_parent: bool,
+}
+
+impl<'a> CommitWalk<'a> {
+    /// Only follow the first parent of merge commits.
+    pub fn first_parent(mut self, first_parent: bool) -> Self {
+        self.first_parent = first_parent;
+        self
+    }
+
+    /// Exclude [ids] and everything reachable from them (`^<rev>` in git).
+    pub fn hide(mut self, ids: Vec<ObjectId>) -> Self {
+        let mut hidden = HashSet::new();
+        let mut stack = ids;
+        while let Some(id) = stack.pop() {
+            if !hidden.insert(id) {
+                continue;
+            }
+            if let Some(commit) = self.repo.commit_info(&id) {
+                stack.extend(commit.parents);
+            }
+        }
+        self.pending.retain(|id, _| !hidden.contains(id));
+        self.queue.retain(|(_, _, id)| !hidden.contains(id));
+        self.seen.extend(hidden);
+        self
+    }
+
+    fn push(&mut self, id: ObjectId) {
+        if !self.seen.insert(id) {
+            return;
+        }
+        if let Some(commit) = self.repo.commit_info(&id) {
+            self.queue.push((commit.time, Reverse(self.inserted), id));
+            self.inserted += 1;
+            self.pending.insert(id, commit.parents);
+        }
+    }
+}
+
+impl Iterator for CommitWalk<'_> {
+    type Item = ObjectId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, _, id) = self.queue.pop()?;
+        let mut parents = self.pending.remove(&id)?;
+        if self.first_parent {
+            parents.truncate(1);
+        }
+        for parent in parents {
+            self.push(parent);
+        }
+        Some(id)
+    }
+}
+
+const PARENT1: u8 = 1;
+const PARENT2: u8 = 2;
+/// Reachable from a common ancestor, so no longer a merge base candidate.
+const STALE: u8 = 4;
+const RESULT: u8 = 8;
+
+impl Repository {
+    /// Walk the history starting at the commits [starts].
+    pub fn walk_commits(&self, starts: Vec<ObjectId>) -> CommitWalk<'_> {
+        let mut walk = CommitWalk {
+            repo: self,
+            queue: BinaryHeap::new(),
+            inserted: 0,
+            pending: HashMap::new(),
+            seen: HashSet::new(),
+            first_parent: false,
+        };
+        for start in starts {
+            walk.push(start);
+        }
+        walk
+    }
+
+    /// Best common ancestors of [one] and [two]: common ancestors that
+    /// aren't reachable from another common ancestor, newest first.
+    ///
+    /// Like git, commits are visited by generation number, then by committer
+    /// time, so with a commit-graph the walk stops as early as possible.
+    pub fn merge_bases(&self, one: ObjectId, two: ObjectId) -> Vec<ObjectId> {
+        if one == two {
+            return vec![one];
+        }
+        let mut walk = MergeBaseWalk {
+            repo: self,
+            infos: HashMap::new(),
+            flags: HashMap::new(),
+            queue: BinaryHeap::new(),
+            queued: HashMap::new(),
+            inserted: 0,
+            non_stale: 0,
+        };
+        walk.paint(one, PARENT1);
+        walk.paint(two, PARENT2);
+
+        let mut results = Vec::new();
+        while walk.non_stale > 0 {
+            let Some(id) = walk.pop() else {
+                break;
+            };
+            let mut flags = walk.flags[&id] & (PARENT1 | PARENT2 | STALE);
+            if flags == PARENT1 | PARENT2 {
+                if walk.flags[&id] & RESULT == 0 {
+                    walk.flags.insert(id, walk.flags[&id] | RESULT);
+                    results.push(id);
+                }
+                flags |= STALE;
+            }
+            for parent in walk.infos[&id].parents.clone() {
+                if walk.flags.get(&parent).is_some_and(|f| f & flags == flags) {
+                    continue;
+                }
+                walk.paint(parent, flags);
+            }
+        }
+
+        results.retain(|id| walk.flags[id] & STALE == 0);
+        results.sort_by_key(|id| Reverse(walk.infos[id].time));
+        if results.len() < 2 {
+            return results;
+        }
+        // Drop candidates reachable from another one.
+        let redundant = results
+            .iter()
+            .filter(|id| {
+                let others = results.iter().filter(|other| other != id).copied();
+                self.reaches(others.collect(), **id, &mut walk.infos)
+            })
+            .copied()
+            .collect::<HashSet<ObjectId>>();
+        results.retain(|id| !redundant.contains(id));
+        results
+    }
+
+    /// Whether [target] is reachable from one of [starts], skipping commits
+    /// whose generation number shows they can't reach it.
+    fn reaches(
+        &self,
+        starts: Vec<ObjectId>,
+        target: ObjectId,
+        infos: &mut HashMap<ObjectId, CommitInfo>,
+    ) -> bool {
+        let generation = |infos: &mut HashMap<ObjectId, CommitInfo>, id: ObjectId| {
+            if let Entry::Vacant(entry) = infos.entry(id) {
+                entry.insert(self.commit_info(&id)?);
+            }
+            Some(infos[&id].generation)
+        };
+        let Some(min_generation) = generation(infos, target) else {
+            return false;
+        };
+        let mut seen = HashSet::new();
+        let mut stack = starts;
+        while let Some(id) = stack.pop() {
+            if id == target {
+                return true;
+            }
+            if !seen.insert(id) {
+                continue;
+            }
+            match generation(infos, id) {
+                Some(gen) if gen == GENERATION_INFINITY || gen > min_generation => {
+                    stack.extend(infos[&id].parents.iter().copied());
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+}
+
+/// State of the merge base search: commits are painted with the sides
+/// ([PARENT1], [PARENT2]) they are reachable from.
+struct MergeBaseWalk<'a> {
+    repo: &'a Repository,
+    infos: HashMap<ObjectId, CommitInfo>,
+    flags: HashMap<ObjectId, u8>,
+    /// Commits to visit, highest generation and newest first.
+    queue: BinaryHeap<(u32, i64, Reverse<usize>, ObjectId)>,
+    /// How often each commit is in [queue].
+    queued: HashMap<ObjectId, usize>,
+    inserted: usize,
+    /// Entries of [queue] that aren't stale, the walk ends without any.
+    non_stale: usize,
+}
+
+impl MergeBaseWalk<'_> {
+    /// Add [flags] to [id] and queue it.
+    fn paint(&mut self, id: ObjectId, flags: u8) {
+        if !self.infos.contains_key(&id) {
+            let Some(info) = self.repo.commit_info(&id) else {
+                return;
+            };
+            self.infos.insert(id, info);
+        }
+        let old = self.flags.get(&id).copied().unwrap_or_default();
+        let new = old | flags;
+        self.flags.insert(id, new);
+        if old & STALE == 0 && new & STALE != 0 {
+            self.non_stale -= self.queued.get(&id).copied().unwrap_or_default();
+        }
+        if new & STALE == 0 {
+            self.non_stale += 1;
+        }
+        let info = &self.infos[&id];
+        self.queue
+            .push((info.generation, info.time, Reverse(self.inserted), id));
+        self.inserted += 1;
+        *self.queued.entry(id).or_default() += 1;
+    }
+
+    fn pop(&mut self) -> Option<ObjectId> {
+        let (_, _, _, id) = self.queue.pop()?;
+        *self.queued.get_mut(&id)? -= 1;
+        if self.flags[&id] & STALE == 0 {
+            self.non_stale -= 1;
+        }
+        Some(id)
+    }
+}
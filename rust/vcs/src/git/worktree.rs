@@ -0,0 +1,248 @@
+use crate::git::object_id::ObjectId;
+use crate::git::repo::Repository;
+use crate::git::reset::ResetMode;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A work tree attached to the repository.
+#[derive(Debug)]
+pub struct Worktree {
+    /// Root of the work tree.
+    pub path: PathBuf,
+    /// Name of the administrative directory in `.git/worktrees`, [None] for
+    /// the main worktree.
+    pub name: Option<String>,
+    /// Per-worktree git dir holding `HEAD` and the index.
+    pub git_dir: PathBuf,
+    /// Commit checked out, [None] on an unborn branch.
+    pub head: Option<ObjectId>,
+    /// Full name of the checked out branch, [None] if `HEAD` is detached.
+    pub branch: Option<String>,
+    /// Whether this is the git dir of a bare repository, without files.
+    pub bare: bool,
+}
+
+impl Worktree {
+    pub fn is_main(&self) -> bool {
+        self.name.is_none()
+    }
+}
+
+#[derive(Debug)]
+pub enum WorktreeError {
+    /// The target directory exists and is not empty.
+    AlreadyExists(PathBuf),
+    /// The branch is the `HEAD` of another worktree.
+    BranchCheckedOut { branch: String, path: PathBuf },
+    /// No worktree with the given path or name.
+    NotAWorktree(String),
+    /// The main worktree can't be removed.
+    MainWorktree(String),
+    /// The worktree has changes that would be lost.
+    Dirty(String),
+    /// Writing the worktree failed.
+    IOError,
+}
+
+impl Display for WorktreeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorktreeError::AlreadyExists(path) => write!(f, "'{}' already exists", path.display()),
+            WorktreeError::BranchCheckedOut { branch, path } => {
+                let branch = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+                write!(
+                    f,
+                    "'{branch}' is already checked out at '{}'",
+                    path.display()
+                )
+            }
+            WorktreeError::NotAWorktree(name) => write!(f, "'{name}' is not a working tree"),
+            WorktreeError::MainWorktree(name) => write!(f, "'{name}' is a main working tree"),
+            WorktreeError::Dirty(name) => write!(
+                f,
+                "'{name}' contains modified or untracked files, use --force to delete it"
+            ),
+            WorktreeError::IOError => write!(f, "failed to write the worktree"),
+        }
+    }
+}
+
+impl Repository {
+    /// The main worktree followed by the linked ones sorted by path.
+    pub fn worktrees(&self) -> Vec<Worktree> {
+        let common_dir = self.common_dir();
+        let common_path = fs::canonicalize(common_dir).unwrap_or(common_dir.to_path_buf());
+        let bare = self
+            .config_string("core", "bare")
+            .is_some_and(|bare| bare == "true");
+        let main_path = match common_path.parent() {
+            Some(parent) if !bare => parent.to_path_buf(),
+            _ => common_path.clone(),
+        };
+        let mut main = self.worktree_at(main_path, None, common_dir);
+        main.bare = bare;
+        let mut worktrees = vec![main];
+
+        let mut linked = fs::read_dir(common_dir.join("worktrees"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| {
+                let git_dir = entry.path();
+                let name = entry.file_name().to_string_lossy().to_string();
+                // `gitdir` names the `.git` file inside the worktree.
+                let path = fs::read_to_string(git_dir.join("gitdir"))
+                    .ok()
+                    .and_then(|path| Some(Path::new(path.trim_end()).parent()?.to_path_buf()))
+                    .unwrap_or_default();
+                self.worktree_at(path, Some(name), &git_dir)
+            })
+            .collect::<Vec<Worktree>>();
+        linked.sort_by(|a, b| a.path.cmp(&b.path));
+        worktrees.extend(linked);
+        worktrees
+    }
+
+    fn worktree_at(&self, path: PathBuf, name: Option<String>, git_dir: &Path) -> Worktree {
+        let head = fs::read_to_string(git_dir.join("HEAD")).unwrap_or_default();
+        let head = head.trim();
+        let (head, branch) = match head.strip_prefix("ref: ") {
+            Some(branch) => (self.ref_resolve(branch), Some(branch.to_string())),
+            None => (head.parse().ok(), None),
+        };
+        Worktree {
+            path,
+            name,
+            git_dir: git_dir.to_path_buf(),
+            head,
+            branch,
+            bare: false,
+        }
+    }
+
+    /// Check out [commit] in a new worktree at [path].
+    ///
+    /// With [branch] (a full ref name) the branch is checked out, created at
+    /// [commit] if it doesn't exist yet. Otherwise `HEAD` is detached.
+    pub fn worktree_add(
+        &self,
+        path: &Path,
+        commit: ObjectId,
+        branch: Option<&str>,
+    ) -> Result<Worktree, WorktreeError> {
+        if path.read_dir().is_ok_and(|mut dir| dir.next().is_some())
+            || path.exists() && !path.is_dir()
+        {
+            return Err(WorktreeError::AlreadyExists(path.to_path_buf()));
+        }
+        if let Some(branch) = branch {
+            if let Some(other) = self
+                .worktrees()
+                .into_iter()
+                .find(|worktree| worktree.branch.as_deref() == Some(branch))
+            {
+                return Err(WorktreeError::BranchCheckedOut {
+                    branch: branch.to_string(),
+                    path: other.path,
+                });
+            }
+        }
+
+        fs::create_dir_all(path).map_err(|_| WorktreeError::IOError)?;
+        let path = fs::canonicalize(path).map_err(|_| WorktreeError::IOError)?;
+        let base_name = path
+            .file_name()
+            .map(|name| {
+                name.to_string_lossy()
+                    .replace(|c: char| c.is_whitespace(), "-")
+            })
+            .unwrap_or_else(|| "worktree".to_string());
+        let worktrees_dir = self.common_dir().join("worktrees");
+        let mut name = base_name.clone();
+        let mut counter = 0;
+        while worktrees_dir.join(&name).exists() {
+            counter += 1;
+            name = format!("{base_name}{counter}");
+        }
+        let git_dir = worktrees_dir.join(&name);
+        fs::create_dir_all(&git_dir).map_err(|_| WorktreeError::IOError)?;
+        let git_dir = fs::canonicalize(git_dir).map_err(|_| WorktreeError::IOError)?;
+        let head = match branch {
+            Some(branch) => format!("ref: {branch}\n"),
+            None => format!("{commit}\n"),
+        };
+        let write = || -> std::io::Result<()> {
+            fs::write(git_dir.join("commondir"), "../..\n")?;
+            fs::write(
+                git_dir.join("gitdir"),
+                format!("{}\n", path.join(".git").display()),
+            )?;
+            fs::write(git_dir.join("HEAD"), head)?;
+            fs::write(
+                path.join(".git"),
+                format!("gitdir: {}\n", git_dir.display()),
+            )
+        };
+        write().map_err(|_| WorktreeError::IOError)?;
+
+        if let Some(branch) = branch {
+            if self.ref_resolve(branch).is_none() {
+                self.ref_update(branch, commit)
+                    .ok_or(WorktreeError::IOError)?;
+            }
+        }
+        let worktree = Repository::new(path.clone(), None).map_err(|_| WorktreeError::IOError)?;
+        let commit = worktree.head().unwrap_or(commit);
+        worktree
+            .reset(commit, ResetMode::Hard)
+            .ok_or(WorktreeError::IOError)?;
+        worktree.run_hook(
+            "post-checkout",
+            &[&self.null_id(), &commit.to_string(), "1"],
+        );
+        Ok(self.worktree_at(path, Some(name), &git_dir))
+    }
+
+    /// Delete the linked worktree named [name] (its path or the name of its
+    /// administrative directory) including its files.
+    ///
+    /// Unless [force] is set, worktrees with modified or untracked files are
+    /// kept.
+    pub fn worktree_remove(&self, name: &str, force: bool) -> Result<(), WorktreeError> {
+        let path = fs::canonicalize(name).ok();
+        let worktree = self
+            .worktrees()
+            .into_iter()
+            .find(|worktree| {
+                path.as_ref().is_some_and(|path| *path == worktree.path)
+                    || worktree.name.as_deref() == Some(name)
+            })
+            .ok_or_else(|| WorktreeError::NotAWorktree(name.to_string()))?;
+        if worktree.is_main() {
+            return Err(WorktreeError::MainWorktree(name.to_string()));
+        }
+        if !force && worktree.path.exists() {
+            let repo = Repository::new(worktree.path.clone(), None)
+                .map_err(|_| WorktreeError::NotAWorktree(name.to_string()))?;
+            if repo.has_changes() {
+                return Err(WorktreeError::Dirty(name.to_string()));
+            }
+        }
+
+        if worktree.path.exists() {
+            fs::remove_dir_all(&worktree.path).map_err(|_| WorktreeError::IOError)?;
+        }
+        fs::remove_dir_all(&worktree.git_dir).map_err(|_| WorktreeError::IOError)?;
+        // Like git, don't leave an empty `worktrees` directory behind.
+        let _ = fs::remove_dir(self.common_dir().join("worktrees"));
+        Ok(())
+    }
+
+    /// Whether the index differs from `HEAD`, tracked files differ from the
+    /// index or there are untracked files that aren't ignored.
+    pub(crate) fn has_changes(&self) -> bool {
+        self.status().is_some_and(|status| !status.is_clean())
+    }
+}
@@ -1,8 +1,284 @@
+//! Simple API for apps embedding the library, e.g. through
+//! flutter_rust_bridge.
+//!
+//! Every function takes the path of a directory inside the repository,
+//! which is discovered like git does, and returns owned, serializable data.
+//! Paths of files are relative to the root of the work tree.
+
+use crate::git::commit::CommitOptions;
+use crate::git::objects::{GitObject, GitObjectType};
 use crate::git::repo::Repository;
+use crate::git::signature::Signature;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
 
 pub mod git;
 
-// TODO: simple wrapper api
-pub fn git_checkout(repository: Repository, commit_hash: String) -> bool {
-    false
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VcsError {
+    /// The path isn't inside a repository.
+    NotARepository(String),
+    /// The revision doesn't name a commit.
+    InvalidRevision(String),
+    /// The operation needs a work tree but the repository is bare.
+    NoWorkTree,
+    /// The operation was refused or failed, with the reason.
+    Failed(String),
+}
+
+impl Display for VcsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VcsError::NotARepository(path) => {
+                write!(f, "not a git repository (or any parent): {path}")
+            }
+            VcsError::InvalidRevision(rev) => write!(f, "unknown revision '{rev}'"),
+            VcsError::NoWorkTree => write!(f, "this operation must be run in a work tree"),
+            VcsError::Failed(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for VcsError {}
+
+/// Where a repository is and what is checked out.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepositoryInfo {
+    /// Root of the work tree, [None] for bare repositories.
+    pub work_tree: Option<String>,
+    pub git_dir: String,
+    /// Checked out branch without `refs/heads/`, [None] if `HEAD` is
+    /// detached.
+    pub branch: Option<String>,
+    /// Checked out commit, [None] before the first commit.
+    pub head: Option<String>,
+}
+
+/// How a file changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileState {
+    Added,
+    Modified,
+    Deleted,
+    Untracked,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStatus {
+    /// Untracked directories end with `/`.
+    pub path: String,
+    /// Change between `HEAD` and the index.
+    pub staged: Option<FileState>,
+    /// Change between the index and the work tree.
+    pub unstaged: Option<FileState>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    pub repository: RepositoryInfo,
+    /// Changed files sorted by path, untracked ones last.
+    pub files: Vec<FileStatus>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Person {
+    pub name: String,
+    pub email: String,
+    /// Seconds since unix epoch.
+    pub time: i64,
+    /// Offset of the time zone from UTC in minutes.
+    pub tz_offset: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitSummary {
+    pub id: String,
+    pub parents: Vec<String>,
+    pub author: Person,
+    pub committer: Person,
+    /// First line of the message.
+    pub summary: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilePatch {
+    pub path: String,
+    pub state: FileState,
+    /// Unified diff in git's format.
+    pub patch: String,
+}
+
+/// Find the repository containing [path].
+pub fn open(path: String) -> Result<RepositoryInfo, VcsError> {
+    let repo = discover(&path)?;
+    Ok(info(&repo))
+}
+
+/// Staged, unstaged and untracked changes.
+pub fn status(path: String) -> Result<Status, VcsError> {
+    let repo = discover(&path)?;
+    let status = repo.status().ok_or(VcsError::NoWorkTree)?;
+    let mut files = BTreeMap::<String, FileStatus>::new();
+    let mut set = |paths: Vec<String>, state: FileState, staged: bool| {
+        for path in paths {
+            let file = files.entry(path.clone()).or_insert(FileStatus {
+                path,
+                staged: None,
+                unstaged: None,
+            });
+            if staged {
+                file.staged = Some(state);
+            } else {
+                file.unstaged = Some(state);
+            }
+        }
+    };
+    set(status.added, FileState::Added, true);
+    set(status.modified, FileState::Modified, true);
+    set(status.deleted, FileState::Deleted, true);
+    set(status.unstaged_modified, FileState::Modified, false);
+    set(status.unstaged_deleted, FileState::Deleted, false);
+
+    let mut files = files.into_values().collect::<Vec<FileStatus>>();
+    files.extend(status.untracked.into_iter().map(|path| FileStatus {
+        path,
+        staged: None,
+        unstaged: Some(FileState::Untracked),
+    }));
+    Ok(Status {
+        repository: info(&repo),
+        files,
+    })
+}
+
+/// History of [rev] (`HEAD` if [None]), newest first, at most [max_count]
+/// commits. Empty before the first commit.
+pub fn log(
+    path: String,
+    rev: Option<String>,
+    max_count: Option<u32>,
+) -> Result<Vec<CommitSummary>, VcsError> {
+    let repo = discover(&path)?;
+    let start = match rev {
+        Some(rev) => Some(resolve_commit(&repo, &rev)?),
+        None => repo.rev_parse("HEAD"),
+    };
+    let max_count = max_count.map_or(usize::MAX, |max| max as usize);
+    let mut commits = Vec::new();
+    for id in repo
+        .walk_commits(start.into_iter().collect())
+        .take(max_count)
+    {
+        let Some(GitObject::Commit(commit)) = repo.object_read(id) else {
+            return Err(VcsError::Failed(format!("unable to read commit {id}")));
+        };
+        let message = commit.get_message().unwrap_or_default();
+        let unknown = || Signature {
+            name: String::new(),
+            email: String::new(),
+            time: 0,
+            tz_offset: 0,
+        };
+        commits.push(CommitSummary {
+            id: id.to_string(),
+            parents: commit
+                .get_parents()
+                .iter()
+                .map(|id| id.to_string())
+                .collect(),
+            author: person(commit.get_author().unwrap_or_else(unknown)),
+            committer: person(commit.get_commiter().unwrap_or_else(unknown)),
+            summary: message.lines().next().unwrap_or_default().to_string(),
+            message,
+        });
+    }
+    Ok(commits)
+}
+
+/// Patches of the unstaged changes, of the staged ones with [staged].
+pub fn diff(path: String, staged: bool) -> Result<Vec<FilePatch>, VcsError> {
+    let repo = discover(&path)?;
+    let diffs = repo.diff_files(staged).ok_or(VcsError::NoWorkTree)?;
+    diffs
+        .into_iter()
+        .map(|diff| {
+            let patch = repo
+                .diff_patch(&diff)
+                .ok_or_else(|| VcsError::Failed(format!("unable to read {}", diff.path)))?;
+            let state = match (&diff.old, &diff.new) {
+                (None, _) => FileState::Added,
+                (_, None) => FileState::Deleted,
+                _ => FileState::Modified,
+            };
+            Ok(FilePatch {
+                path: diff.path,
+                state,
+                patch,
+            })
+        })
+        .collect()
+}
+
+/// Stage the changes of [paths] (files or directories, `.` for all),
+/// including deletions and untracked files. Returns the staged paths.
+pub fn stage(path: String, paths: Vec<String>) -> Result<Vec<String>, VcsError> {
+    let repo = discover(&path)?;
+    let staged = repo.stage(&paths).ok_or(VcsError::NoWorkTree)?;
+    Ok(staged
+        .into_iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect())
+}
+
+/// Commit the staged changes with [message], returning the new commit id.
+pub fn commit(path: String, message: String) -> Result<String, VcsError> {
+    let repo = discover(&path)?;
+    repo.commit(&message, &CommitOptions::default())
+        .map(|id| id.to_string())
+        .map_err(|err| VcsError::Failed(err.to_string()))
+}
+
+/// Check out the branch or commit [rev], returning the commit id.
+pub fn checkout(path: String, rev: String) -> Result<String, VcsError> {
+    let repo = discover(&path)?;
+    resolve_commit(&repo, &rev)?;
+    repo.checkout(&rev)
+        .map(|id| id.to_string())
+        .map_err(|err| VcsError::Failed(err.to_string()))
+}
+
+fn discover(path: &str) -> Result<Repository, VcsError> {
+    Repository::discover(Path::new(path)).map_err(|_| VcsError::NotARepository(path.to_string()))
+}
+
+fn info(repo: &Repository) -> RepositoryInfo {
+    let branch = repo
+        .head_ref()
+        .and_then(|head| Some(head.strip_prefix("refs/heads/")?.to_string()));
+    RepositoryInfo {
+        work_tree: repo
+            .work_tree()
+            .map(|path| path.to_string_lossy().to_string()),
+        git_dir: repo.git_dir().to_string_lossy().to_string(),
+        branch,
+        head: repo.rev_parse("HEAD").map(|id| id.to_string()),
+    }
+}
+
+fn resolve_commit(repo: &Repository, rev: &str) -> Result<git::object_id::ObjectId, VcsError> {
+    repo.rev_parse(rev)
+        .and_then(|id| repo.object_peel(id, GitObjectType::Commit))
+        .ok_or_else(|| VcsError::InvalidRevision(rev.to_string()))
+}
+
+fn person(signature: Signature) -> Person {
+    Person {
+        name: signature.name,
+        email: signature.email,
+        time: signature.time,
+        tz_offset: signature.tz_offset,
+    }
 }
@@ -1,48 +1,337 @@
-use clap::{Parser, Subcommand};
-use std::ffi::CString;
-use std::io;
-use std::io::Write;
+use clap::{ArgGroup, Parser, Subcommand};
+use commands::archive::archive;
+use commands::bisect::{bisect_log, bisect_mark, bisect_reset, bisect_run, bisect_start};
+use commands::blame::{blame, BlameFormat};
+use commands::cat_file::{cat_file, cat_file_batch, CatFileMode};
+use commands::checkout::checkout;
+use commands::clean::clean;
+use commands::commit::commit;
+use commands::commit_graph::commit_graph_write;
+use commands::commit_tree::{commit_tree, write_tree};
+use commands::describe::describe;
+use commands::diff::diff;
+use commands::fsck::fsck;
+use commands::grep::grep;
+use commands::log::{log, LogOptions};
+use commands::ls_files::ls_files;
+use commands::ls_tree::ls_tree;
+use commands::merge_base::merge_base;
+use commands::open_repo;
+use commands::reset::reset;
+use commands::shortlog::{shortlog, ShortlogOptions};
+use commands::sparse_checkout::{
+    sparse_checkout_add, sparse_checkout_disable, sparse_checkout_init, sparse_checkout_list,
+    sparse_checkout_set,
+};
+use commands::status::status;
+use commands::submodule::{submodule_init, submodule_status, submodule_update};
+use commands::verify::{verify_commit, verify_tag};
+use commands::worktree::{worktree_add, worktree_list, worktree_remove};
 use std::path::PathBuf;
-use vcs::git::objects::{GitObject, GitObjectType};
-use vcs::git::repo::{ObjectRefResult, Repository, RepositoryLoadError};
+use std::process::ExitCode;
+use vcs::git::archive::ArchiveFormat;
+use vcs::git::bisect::BisectTerm;
+use vcs::git::clean::CleanOptions;
+use vcs::git::commit::CommitOptions;
+use vcs::git::describe::DescribeOptions;
+use vcs::git::grep::GrepOptions;
+use vcs::git::object_id::ObjectFormat;
+use vcs::git::objects::GitObjectType;
+use vcs::git::repo::Repository;
+use vcs::git::reset::ResetMode;
 
-fn main() {
+mod commands;
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
-    match cli.command {
-        None => println!("Unrecognized command"),
-        Some(Commands::Init { path }) => {
-            Repository::init(path.parse().unwrap()).unwrap();
-        }
-        Some(Commands::CatFile { obj_type, object }) => {
-            let repo = Repository::new(PathBuf::from(cli.repo_path), None);
-            match repo {
-                Err(err) => eprintln!("{:?}", err),
-                Ok(repo) => {
-                    let obj = if let ObjectRefResult::Ok(obj_ref) = repo.object_find(object) {
-                        repo.object_read(obj_ref)
-                    } else {
-                        None
-                    };
-                    match obj {
-                        None => println!("No object found"),
-                        Some(obj) => {
-                            io::stdout().lock().write(&*obj.serialize()).unwrap();
-                            io::stdout().lock().flush().unwrap();
+    let success = match cli.command {
+        None => {
+            println!("Unrecognized command");
+            false
+        }
+        Some(Commands::Init {
+            path,
+            bare,
+            object_format,
+        }) => {
+            let format = ObjectFormat::from_name(&object_format).expect("Unknown object format");
+            if bare {
+                Repository::init_bare(path.parse().unwrap(), format).unwrap();
+            } else {
+                Repository::init(path.parse().unwrap(), format).unwrap();
+            }
+            true
+        }
+        Some(Commands::CatFile {
+            show_type,
+            show_size,
+            exists,
+            pretty,
+            batch,
+            batch_check,
+            args,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            if batch || batch_check {
+                return cat_file_batch(&repo, batch);
+            }
+            let (mode, object) = match (show_type, show_size, exists, pretty, args.as_slice()) {
+                (true, _, _, _, [object]) => (CatFileMode::Type, object),
+                (_, true, _, _, [object]) => (CatFileMode::Size, object),
+                (_, _, true, _, [object]) => (CatFileMode::Exists, object),
+                (_, _, _, true, [object]) => (CatFileMode::Pretty, object),
+                (false, false, false, false, [obj_type, object]) => {
+                    match GitObjectType::from_name(obj_type) {
+                        Some(obj_type) => (CatFileMode::Raw(obj_type), object),
+                        None => {
+                            eprintln!("Invalid object type {obj_type}");
+                            return false;
                         }
                     }
                 }
-            }
-        }
+                _ => {
+                    eprintln!("Usage: cat-file (-t | -s | -e | -p | <type>) <object>");
+                    return false;
+                }
+            };
+            cat_file(&repo, mode, object)
+        }),
         Some(Commands::HashObject { obj_type, path }) => {
-            let repo = Repository::new(PathBuf::from(cli.repo_path), None);
-            match repo {
-                Err(err) => eprintln!("{:?}", err),
-                Ok(repo) => {
-                    let sha = repo.hash_object(PathBuf::from(path), obj_type);
-                    println!("{:?}", sha);
+            open_repo(&cli.repo_path).is_some_and(|repo| {
+                match repo.hash_object(PathBuf::from(path), obj_type) {
+                    Some(sha) => {
+                        println!("{sha}");
+                        true
+                    }
+                    None => {
+                        eprintln!("Failed to hash object");
+                        false
+                    }
                 }
-            }
+            })
+        }
+        Some(Commands::LsTree {
+            recursive,
+            name_only,
+            tree_ish,
+        }) => open_repo(&cli.repo_path)
+            .is_some_and(|repo| ls_tree(&repo, &tree_ish, recursive, name_only)),
+        Some(Commands::LsFiles { stage }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| ls_files(&repo, stage))
+        }
+        Some(Commands::WriteTree) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| write_tree(&repo))
+        }
+        Some(Commands::CommitTree {
+            tree,
+            parents,
+            message,
+        }) => open_repo(&cli.repo_path)
+            .is_some_and(|repo| commit_tree(&repo, &tree, &parents, &message)),
+        Some(Commands::Log {
+            max_count,
+            oneline,
+            show_signature,
+            revs,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            let options = LogOptions {
+                max_count,
+                oneline,
+                show_signature,
+            };
+            log(&repo, &revs, options)
+        }),
+        Some(Commands::Shortlog {
+            summary,
+            numbered,
+            email,
+            revs,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            let options = ShortlogOptions {
+                summary,
+                numbered,
+                email,
+            };
+            shortlog(&repo, &revs, &options)
+        }),
+        Some(Commands::Describe {
+            tags,
+            long,
+            dirty,
+            commit_ish,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            let options = DescribeOptions { tags, long, dirty };
+            describe(&repo, commit_ish.as_deref(), &options)
+        }),
+        Some(Commands::Blame {
+            range,
+            porcelain,
+            line_porcelain,
+            args,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            let format = if line_porcelain {
+                BlameFormat::LinePorcelain
+            } else if porcelain {
+                BlameFormat::Porcelain
+            } else {
+                BlameFormat::Default
+            };
+            let (rev, path) = match args.as_slice() {
+                [path] => ("HEAD", path),
+                [rev, path] => (rev.as_str(), path),
+                _ => unreachable!("clap limits the number of arguments"),
+            };
+            blame(&repo, rev, path, range.as_deref(), format)
+        }),
+        Some(Commands::MergeBase { all, one, two }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| merge_base(&repo, &one, &two, all))
+        }
+        Some(Commands::CommitGraph { command }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| match command {
+                CommitGraphCommand::Write => commit_graph_write(&repo),
+            })
+        }
+        Some(Commands::Fsck { unreachable }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| fsck(&repo, unreachable))
+        }
+        Some(Commands::Reset {
+            soft,
+            hard,
+            mixed: _,
+            commit,
+            paths,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            let mode = if soft {
+                ResetMode::Soft
+            } else if hard {
+                ResetMode::Hard
+            } else {
+                ResetMode::Mixed
+            };
+            reset(&repo, mode, commit.as_deref(), &paths)
+        }),
+        Some(Commands::Clean {
+            dry_run,
+            force,
+            directories,
+            ignored,
+            paths,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            let options = CleanOptions {
+                directories,
+                ignored,
+                dry_run,
+                paths,
+            };
+            clean(&repo, options, force)
+        }),
+        Some(Commands::Commit {
+            message,
+            allow_empty,
+            no_verify,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            let options = CommitOptions {
+                allow_empty,
+                no_verify,
+            };
+            commit(&repo, &message, &options)
+        }),
+        Some(Commands::Checkout { rev }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| checkout(&repo, &rev))
+        }
+        Some(Commands::Diff { cached }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| diff(&repo, cached))
+        }
+        Some(Commands::Grep {
+            line_number,
+            ignore_case,
+            word_regexp,
+            files_with_matches,
+            cached,
+            pattern,
+            tree_ish,
+            paths,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            let options = GrepOptions {
+                ignore_case,
+                word: word_regexp,
+                files_with_matches,
+                cached,
+                paths,
+            };
+            grep(&repo, &pattern, tree_ish.as_deref(), line_number, &options)
+        }),
+        Some(Commands::Status { short, porcelain }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| status(&repo, short || porcelain))
+        }
+        Some(Commands::Archive {
+            format,
+            prefix,
+            output,
+            tree_ish,
+            paths,
+        }) => open_repo(&cli.repo_path).is_some_and(|repo| {
+            archive(&repo, &tree_ish, format, prefix, output.as_deref(), paths)
+        }),
+        Some(Commands::Bisect { command }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| match command {
+                BisectCommand::Start { revs } => bisect_start(&repo, &revs),
+                BisectCommand::Bad { rev } => bisect_mark(&repo, BisectTerm::Bad, rev.as_slice()),
+                BisectCommand::Good { revs } => bisect_mark(&repo, BisectTerm::Good, &revs),
+                BisectCommand::Skip { revs } => bisect_mark(&repo, BisectTerm::Skip, &revs),
+                BisectCommand::Reset { commit } => bisect_reset(&repo, commit.as_deref()),
+                BisectCommand::Log => bisect_log(&repo),
+                BisectCommand::Run { command } => bisect_run(&repo, &command),
+            })
+        }
+        Some(Commands::VerifyCommit { commits }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| verify_commit(&repo, &commits))
         }
+        Some(Commands::VerifyTag { tags }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| verify_tag(&repo, &tags))
+        }
+        Some(Commands::Submodule { command }) => {
+            open_repo(&cli.repo_path).is_some_and(|mut repo| match command {
+                SubmoduleCommand::Status { paths } => submodule_status(&repo, &paths),
+                SubmoduleCommand::Init { paths } => submodule_init(&mut repo, &paths),
+                SubmoduleCommand::Update { init, paths } => {
+                    submodule_update(&mut repo, init, &paths)
+                }
+            })
+        }
+        Some(Commands::SparseCheckout { command }) => {
+            open_repo(&cli.repo_path).is_some_and(|mut repo| match command {
+                SparseCheckoutCommand::Init => sparse_checkout_init(&mut repo),
+                SparseCheckoutCommand::Set { dirs } => sparse_checkout_set(&mut repo, &dirs),
+                SparseCheckoutCommand::Add { dirs } => sparse_checkout_add(&mut repo, &dirs),
+                SparseCheckoutCommand::List => sparse_checkout_list(&repo),
+                SparseCheckoutCommand::Disable => sparse_checkout_disable(&mut repo),
+            })
+        }
+        Some(Commands::Worktree { command }) => {
+            open_repo(&cli.repo_path).is_some_and(|repo| match command {
+                WorktreeCommand::Add {
+                    new_branch,
+                    detach,
+                    path,
+                    commit_ish,
+                } => worktree_add(
+                    &repo,
+                    &path,
+                    commit_ish.as_deref(),
+                    new_branch.as_deref(),
+                    detach,
+                ),
+                WorktreeCommand::List { porcelain } => worktree_list(&repo, porcelain),
+                WorktreeCommand::Remove { force, worktree } => {
+                    worktree_remove(&repo, &worktree, force)
+                }
+            })
+        }
+    };
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
     }
 }
 
@@ -61,17 +350,41 @@ enum Commands {
         /// Where to create the repository.
         #[arg(default_value = ".")]
         path: String,
+        /// Create a repository without work tree.
+        #[arg(long)]
+        bare: bool,
+        /// Hash algorithm used for object ids (sha1 or sha256).
+        #[arg(long, value_name = "FORMAT", default_value = "sha1")]
+        object_format: String,
     },
     /// Provide content of repository objects
-    #[command(subcommand_value_name = "cat-file")]
+    #[command(name = "cat-file")]
+    #[command(group(ArgGroup::new("mode").args(["show_type", "show_size", "exists", "pretty", "batch", "batch_check"])))]
     CatFile {
-        #[arg(value_enum, value_name = "TYPE")]
-        obj_type: GitObjectType,
-        #[arg(value_name = "OBJECT")]
-        object: String,
+        /// Show the object type.
+        #[arg(short = 't')]
+        show_type: bool,
+        /// Show the object size.
+        #[arg(short = 's')]
+        show_size: bool,
+        /// Exit with zero status if the object exists.
+        #[arg(short = 'e')]
+        exists: bool,
+        /// Pretty-print the object's content.
+        #[arg(short = 'p')]
+        pretty: bool,
+        /// Print id, type, size and contents of objects named on stdin.
+        #[arg(long)]
+        batch: bool,
+        /// Print id, type and size of objects named on stdin.
+        #[arg(long)]
+        batch_check: bool,
+        /// `<type> <object>` or `<object>` when a mode is given.
+        #[arg(value_name = "OBJECT", num_args = 0..=2)]
+        args: Vec<String>,
     },
     /// Compute object ID and optionally creates a blob from a file
-    #[command(subcommand_value_name = "cat-file")]
+    #[command(name = "hash-object")]
     HashObject {
         /// Specify the type.
         #[arg(value_enum, long, short, value_name = "TYPE", default_value = "blob")]
@@ -79,4 +392,386 @@ enum Commands {
         // Read object from <file>
         path: String,
     },
+    /// List the contents of a tree object
+    #[command(name = "ls-tree")]
+    LsTree {
+        /// Recurse into sub-trees.
+        #[arg(short = 'r')]
+        recursive: bool,
+        /// List only file names.
+        #[arg(long)]
+        name_only: bool,
+        #[arg(value_name = "TREE-ISH")]
+        tree_ish: String,
+    },
+    /// Show information about files in the index
+    #[command(name = "ls-files")]
+    LsFiles {
+        /// Show mode, object name and stage of staged files.
+        #[arg(short, long)]
+        stage: bool,
+    },
+    /// Create a tree object from the current index
+    #[command(name = "write-tree")]
+    WriteTree,
+    /// Create a new commit object
+    #[command(name = "commit-tree")]
+    CommitTree {
+        #[arg(value_name = "TREE")]
+        tree: String,
+        /// Id of a parent commit object.
+        #[arg(short = 'p', value_name = "PARENT")]
+        parents: Vec<String>,
+        /// A paragraph in the commit log message.
+        #[arg(short = 'm', value_name = "MESSAGE")]
+        message: Vec<String>,
+    },
+    /// Show commit logs
+    Log {
+        /// Limit the number of commits to output.
+        #[arg(short = 'n', long, value_name = "NUMBER")]
+        max_count: Option<usize>,
+        /// Show each commit as a single line.
+        #[arg(long)]
+        oneline: bool,
+        /// Check the signature of each signed commit.
+        #[arg(long)]
+        show_signature: bool,
+        /// Commits to start from, `^<rev>` excludes a commit's history.
+        #[arg(value_name = "REVISION")]
+        revs: Vec<String>,
+    },
+    /// Summarize the commit history grouped by author
+    Shortlog {
+        /// Only print the number of commits per author.
+        #[arg(short, long)]
+        summary: bool,
+        /// Sort authors by their number of commits instead of by name.
+        #[arg(short, long)]
+        numbered: bool,
+        /// Show the email address of each author.
+        #[arg(short, long)]
+        email: bool,
+        /// Commits to start from, `^<rev>` excludes a commit's history.
+        #[arg(value_name = "REVISION")]
+        revs: Vec<String>,
+    },
+    /// Name a commit after the closest reachable tag
+    Describe {
+        /// Also use lightweight tags.
+        #[arg(long)]
+        tags: bool,
+        /// Always show the distance and abbreviated commit id.
+        #[arg(long)]
+        long: bool,
+        /// Describe HEAD and append a mark ("-dirty" by default) if tracked
+        /// files have changes.
+        #[arg(long, value_name = "MARK", num_args = 0..=1, default_missing_value = "-dirty")]
+        #[arg(conflicts_with = "commit_ish")]
+        dirty: Option<String>,
+        #[arg(value_name = "COMMIT-ISH")]
+        commit_ish: Option<String>,
+    },
+    /// Show what revision and author last modified each line of a file
+    Blame {
+        /// Only annotate lines `<start>,<end>` (or `<start>,+<count>`).
+        #[arg(short = 'L', value_name = "RANGE")]
+        range: Option<String>,
+        /// Show in a format designed for machine consumption.
+        #[arg(short = 'p', long)]
+        porcelain: bool,
+        /// Like --porcelain, but repeat commit information for every line.
+        #[arg(long)]
+        line_porcelain: bool,
+        /// `[<rev>] <file>`
+        #[arg(value_name = "FILE", num_args = 1..=2, required = true)]
+        args: Vec<String>,
+    },
+    /// Find the best common ancestors of two commits
+    #[command(name = "merge-base")]
+    MergeBase {
+        /// Print all best common ancestors instead of one.
+        #[arg(short, long)]
+        all: bool,
+        #[arg(value_name = "COMMIT")]
+        one: String,
+        #[arg(value_name = "COMMIT")]
+        two: String,
+    },
+    /// Write the commit-graph file speeding up history traversal
+    #[command(name = "commit-graph")]
+    CommitGraph {
+        #[command(subcommand)]
+        command: CommitGraphCommand,
+    },
+    /// Verify the connectivity and validity of the objects in the database
+    Fsck {
+        /// Print all objects that exist but aren't reachable from any ref.
+        #[arg(long)]
+        unreachable: bool,
+    },
+    /// Reset current HEAD to the specified state
+    #[command(group(ArgGroup::new("mode").args(["soft", "mixed", "hard"])))]
+    Reset {
+        /// Only move the current branch.
+        #[arg(long)]
+        soft: bool,
+        /// Also reset the index (default).
+        #[arg(long)]
+        mixed: bool,
+        /// Also reset the index and the tracked files in the work tree.
+        #[arg(long)]
+        hard: bool,
+        #[arg(value_name = "COMMIT")]
+        commit: Option<String>,
+        /// Only reset the index entries of these paths.
+        #[arg(last = true, value_name = "PATHS")]
+        paths: Vec<String>,
+    },
+    /// Remove untracked files from the working tree
+    Clean {
+        /// Don't remove anything, just show what would be done.
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        /// Actually remove the files.
+        #[arg(short, long)]
+        force: bool,
+        /// Also remove untracked directories.
+        #[arg(short = 'd')]
+        directories: bool,
+        /// Don't use the ignore rules, remove ignored files too.
+        #[arg(short = 'x')]
+        ignored: bool,
+        #[arg(value_name = "PATHS")]
+        paths: Vec<String>,
+    },
+    /// Show changes between the index and the working tree
+    Diff {
+        /// Show the changes staged for the next commit instead.
+        #[arg(long, visible_alias = "staged")]
+        cached: bool,
+    },
+    /// Record the staged changes as a new commit
+    Commit {
+        /// The message, given multiple times the paragraphs of it.
+        #[arg(short, long, value_name = "MSG", required = true)]
+        message: Vec<String>,
+        /// Commit even if nothing changed.
+        #[arg(long)]
+        allow_empty: bool,
+        /// Skip the pre-commit and commit-msg hooks.
+        #[arg(short = 'n', long)]
+        no_verify: bool,
+    },
+    /// Switch to a branch or commit
+    Checkout {
+        #[arg(value_name = "BRANCH")]
+        rev: String,
+    },
+    /// Print lines matching a pattern in tracked files or a tree
+    Grep {
+        /// Prefix lines with their line number.
+        #[arg(short = 'n', long)]
+        line_number: bool,
+        /// Match letters regardless of case.
+        #[arg(short, long)]
+        ignore_case: bool,
+        /// Only match whole words.
+        #[arg(short, long)]
+        word_regexp: bool,
+        /// Only print the names of files with matches.
+        #[arg(short = 'l', long, visible_alias = "name-only")]
+        files_with_matches: bool,
+        /// Search the staged content instead of the work tree.
+        #[arg(long, conflicts_with = "tree_ish")]
+        cached: bool,
+        /// Regular expression to search for.
+        pattern: String,
+        /// Search the files of this tree instead of the work tree.
+        #[arg(value_name = "TREE-ISH")]
+        tree_ish: Option<String>,
+        /// Only search these files or directories.
+        #[arg(value_name = "PATHS", last = true)]
+        paths: Vec<String>,
+    },
+    /// Show the working tree status
+    Status {
+        /// Give the output in the short format.
+        #[arg(short, long)]
+        short: bool,
+        /// Like --short, in a format stable across versions for scripts.
+        #[arg(long)]
+        porcelain: bool,
+    },
+    /// Create an archive of the files of a tree
+    Archive {
+        /// Format of the archive, by default implied by --output or tar.
+        #[arg(long, value_enum)]
+        format: Option<ArchiveFormat>,
+        /// Prepend this to every path, ending with `/` for a directory.
+        #[arg(long, value_name = "PREFIX")]
+        prefix: Option<String>,
+        /// Write the archive to this file instead of stdout.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+        #[arg(value_name = "TREE-ISH")]
+        tree_ish: String,
+        /// Only include these files or directories.
+        #[arg(value_name = "PATHS")]
+        paths: Vec<String>,
+    },
+    /// Find the commit that introduced a change by binary search
+    Bisect {
+        #[command(subcommand)]
+        command: BisectCommand,
+    },
+    /// Check the signature of commits
+    #[command(name = "verify-commit")]
+    VerifyCommit {
+        #[arg(value_name = "COMMIT", required = true)]
+        commits: Vec<String>,
+    },
+    /// Check the signature of tags
+    #[command(name = "verify-tag")]
+    VerifyTag {
+        #[arg(value_name = "TAG", required = true)]
+        tags: Vec<String>,
+    },
+    /// Initialize, update or inspect submodules
+    Submodule {
+        #[command(subcommand)]
+        command: SubmoduleCommand,
+    },
+    /// Only check out some directories of the work tree
+    #[command(name = "sparse-checkout")]
+    SparseCheckout {
+        #[command(subcommand)]
+        command: SparseCheckoutCommand,
+    },
+    /// Manage multiple working trees
+    Worktree {
+        #[command(subcommand)]
+        command: WorktreeCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum CommitGraphCommand {
+    /// Write the commit-graph for the commits reachable from refs
+    Write,
+}
+
+#[derive(Subcommand)]
+enum BisectCommand {
+    /// Start bisecting, optionally with the bad and good commits
+    Start {
+        #[arg(value_name = "BAD [GOOD...]")]
+        revs: Vec<String>,
+    },
+    /// Mark a commit (HEAD by default) as having the change
+    Bad {
+        #[arg(value_name = "REV")]
+        rev: Option<String>,
+    },
+    /// Mark commits (HEAD by default) as being before the change
+    Good {
+        #[arg(value_name = "REV")]
+        revs: Vec<String>,
+    },
+    /// Mark commits (HEAD by default) as untestable
+    Skip {
+        #[arg(value_name = "REV")]
+        revs: Vec<String>,
+    },
+    /// End bisecting and check out where it started or <COMMIT>
+    Reset {
+        #[arg(value_name = "COMMIT")]
+        commit: Option<String>,
+    },
+    /// Show the steps of the bisection so far
+    Log,
+    /// Bisect automatically by running a command on each commit to test
+    Run {
+        /// Exits with 0 for good, 125 to skip and 1-127 (but 125) for bad.
+        #[arg(
+            value_name = "CMD",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SparseCheckoutCommand {
+    /// Enable the sparse checkout with the top level files or the
+    /// directories checked out before
+    Init,
+    /// Only check out the top level files and these directories
+    Set {
+        #[arg(value_name = "DIR")]
+        dirs: Vec<String>,
+    },
+    /// Check out these directories too
+    Add {
+        #[arg(value_name = "DIR", required = true)]
+        dirs: Vec<String>,
+    },
+    /// Show the checked out directories
+    List,
+    /// Check out all files again
+    Disable,
+}
+
+#[derive(Subcommand)]
+enum SubmoduleCommand {
+    /// Show the status of the submodules
+    Status {
+        #[arg(value_name = "PATH")]
+        paths: Vec<String>,
+    },
+    /// Register the submodule urls in the repository config
+    Init {
+        #[arg(value_name = "PATH")]
+        paths: Vec<String>,
+    },
+    /// Clone missing submodules and check out the recorded commits
+    Update {
+        /// Initialize uninitialized submodules first.
+        #[arg(long)]
+        init: bool,
+        #[arg(value_name = "PATH")]
+        paths: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WorktreeCommand {
+    /// Create a new working tree at <PATH>
+    Add {
+        /// Create a new branch with this name.
+        #[arg(short = 'b', value_name = "NEW-BRANCH", conflicts_with = "detach")]
+        new_branch: Option<String>,
+        /// Detach HEAD in the new working tree.
+        #[arg(long)]
+        detach: bool,
+        path: String,
+        #[arg(value_name = "COMMIT-ISH")]
+        commit_ish: Option<String>,
+    },
+    /// List the working trees
+    List {
+        /// Machine readable output.
+        #[arg(long)]
+        porcelain: bool,
+    },
+    /// Remove a working tree
+    Remove {
+        /// Remove even with modified or untracked files.
+        #[arg(short, long)]
+        force: bool,
+        #[arg(value_name = "WORKTREE")]
+        worktree: String,
+    },
 }
@@ -0,0 +1,181 @@
+mod common;
+
+use common::{git, git_repo};
+use std::fs;
+use vcs::{FileState, FileStatus, VcsError};
+
+#[test]
+fn facade_round_trip() {
+    let dir = git_repo("api", "sha1");
+    git(&dir.path, &["config", "user.name", "App"]);
+    git(&dir.path, &["config", "user.email", "app@example.com"]);
+    let path = dir.path.join("dir").to_string_lossy().to_string();
+
+    let info = vcs::open(path.clone()).unwrap();
+    assert_eq!(info.branch.as_deref(), Some("master"));
+    let first = git(&dir.path, &["rev-parse", "HEAD"]).trim().to_string();
+    assert_eq!(info.head.as_deref(), Some(first.as_str()));
+
+    fs::write(dir.path.join("file.txt"), "hello\nworld\n").unwrap();
+    fs::write(dir.path.join("new.txt"), "new\n").unwrap();
+    fs::remove_file(dir.path.join("dir").join("nested.txt")).unwrap();
+    let status = vcs::status(path.clone()).unwrap();
+    let file = |path: &str, staged, unstaged| FileStatus {
+        path: path.to_string(),
+        staged,
+        unstaged,
+    };
+    assert_eq!(
+        status.files,
+        vec![
+            file("dir/nested.txt", None, Some(FileState::Deleted)),
+            file("file.txt", None, Some(FileState::Modified)),
+            file("new.txt", None, Some(FileState::Untracked)),
+        ]
+    );
+    let patches = vcs::diff(path.clone(), false).unwrap();
+    assert_eq!(patches.len(), 2);
+    assert_eq!(patches[1].patch, git(&dir.path, &["diff", "file.txt"]));
+
+    let staged = vcs::stage(path.clone(), vec![".".to_string()]).unwrap();
+    assert_eq!(staged, vec!["dir/nested.txt", "file.txt", "new.txt"]);
+    assert_eq!(
+        git(&dir.path, &["status", "--porcelain"]),
+        "D  dir/nested.txt\nM  file.txt\nA  new.txt\n"
+    );
+    let staged_patches = vcs::diff(path.clone(), true).unwrap();
+    let states = staged_patches
+        .iter()
+        .map(|patch| patch.state)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        states,
+        [FileState::Deleted, FileState::Modified, FileState::Added]
+    );
+
+    let second = vcs::commit(path.clone(), "Second\n\nBody".to_string()).unwrap();
+    assert_eq!(git(&dir.path, &["rev-parse", "HEAD"]).trim(), second);
+    assert_eq!(git(&dir.path, &["status", "--porcelain"]), "");
+    assert!(matches!(
+        vcs::commit(path.clone(), "Empty".to_string()),
+        Err(VcsError::Failed(_))
+    ));
+
+    let log = vcs::log(path.clone(), None, None).unwrap();
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0].id, second);
+    assert_eq!(log[0].parents, vec![first.clone()]);
+    assert_eq!(log[0].summary, "Second");
+    assert_eq!(log[0].author.name, "App");
+    assert_eq!(vcs::log(path.clone(), None, Some(1)).unwrap().len(), 1);
+
+    // Detach at the first commit, then go back to the branch.
+    let root = dir.path.to_string_lossy().to_string();
+    assert_eq!(vcs::checkout(root.clone(), first.clone()).unwrap(), first);
+    assert!(dir.path.join("dir").join("nested.txt").exists());
+    assert!(!dir.path.join("new.txt").exists());
+    assert_eq!(vcs::open(root.clone()).unwrap().branch, None);
+    vcs::checkout(root.clone(), "master".to_string()).unwrap();
+    assert_eq!(git(&dir.path, &["status", "--porcelain"]), "");
+    assert_eq!(
+        vcs::open(root.clone()).unwrap().branch.as_deref(),
+        Some("master")
+    );
+
+    fs::write(dir.path.join("file.txt"), "local\n").unwrap();
+    assert!(vcs::checkout(root.clone(), first).is_err());
+    assert_eq!(
+        vcs::checkout(root.clone(), "nope".to_string()),
+        Err(VcsError::InvalidRevision("nope".to_string()))
+    );
+
+    let status = vcs::status(root).unwrap();
+    let json = serde_json::to_string(&status).unwrap();
+    assert_eq!(serde_json::from_str::<vcs::Status>(&json).unwrap(), status);
+}
+
+#[test]
+fn reports_missing_repository() {
+    let dir = common::TmpDir::new("api_missing");
+    let path = dir.path.to_string_lossy().to_string();
+    assert_eq!(vcs::open(path.clone()), Err(VcsError::NotARepository(path)));
+}
+
+#[test]
+fn facade_edge_cases() {
+    // Before the first commit there is a branch but no history.
+    let dir = common::TmpDir::new("api_empty");
+    git(&dir.path, &["init", "-q", "-b", "master"]);
+    git(&dir.path, &["config", "user.name", "App"]);
+    git(&dir.path, &["config", "user.email", "app@example.com"]);
+    let path = dir.path.to_string_lossy().to_string();
+    let info = vcs::open(path.clone()).unwrap();
+    assert_eq!(info.branch.as_deref(), Some("master"));
+    assert_eq!(info.head, None);
+    assert_eq!(vcs::log(path.clone(), None, None).unwrap(), Vec::new());
+    assert_eq!(
+        vcs::log(path.clone(), Some("HEAD".to_string()), None),
+        Err(VcsError::InvalidRevision("HEAD".to_string()))
+    );
+
+    // Ignored files and paths outside the selection aren't staged.
+    fs::write(dir.path.join(".gitignore"), "*.log\n").unwrap();
+    fs::write(dir.path.join("debug.log"), "ignored\n").unwrap();
+    fs::create_dir(dir.path.join("src")).unwrap();
+    fs::write(dir.path.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.path.join("notes.txt"), "notes\n").unwrap();
+    let staged = vcs::stage(path.clone(), vec!["src/".to_string()]).unwrap();
+    assert_eq!(staged, vec!["src/main.rs"]);
+    assert_eq!(
+        git(&dir.path, &["status", "--porcelain"]),
+        "A  src/main.rs\n?? .gitignore\n?? notes.txt\n"
+    );
+    assert_eq!(
+        vcs::stage(path.clone(), vec!["missing".to_string()]).unwrap(),
+        Vec::<String>::new()
+    );
+
+    let root = vcs::commit(path.clone(), "Root".to_string()).unwrap();
+    let log = vcs::log(path.clone(), None, None).unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].id, root);
+    assert!(log[0].parents.is_empty());
+    assert_eq!(
+        vcs::log(path.clone(), Some("nope".to_string()), None),
+        Err(VcsError::InvalidRevision("nope".to_string()))
+    );
+
+    // Bare repositories have history but nothing to stage or compare.
+    let bare = common::TmpDir::new("api_bare");
+    git(&bare.path, &["clone", "-q", "--bare", &path, "."]);
+    let bare_path = bare.path.to_string_lossy().to_string();
+    assert_eq!(vcs::open(bare_path.clone()).unwrap().work_tree, None);
+    assert_eq!(vcs::log(bare_path.clone(), None, None).unwrap(), log);
+    assert_eq!(vcs::status(bare_path.clone()), Err(VcsError::NoWorkTree));
+    assert_eq!(
+        vcs::diff(bare_path.clone(), false),
+        Err(VcsError::NoWorkTree)
+    );
+    assert_eq!(
+        vcs::stage(bare_path, vec![".".to_string()]),
+        Err(VcsError::NoWorkTree)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn diff_ignores_modes_without_filemode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = git_repo("api_filemode", "sha1");
+    let path = dir.path.to_string_lossy().to_string();
+    let file = dir.path.join("file.txt");
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o755)).unwrap();
+    let patches = vcs::diff(path.clone(), false).unwrap();
+    assert_eq!(patches.len(), 1);
+    assert!(patches[0].patch.contains("new mode 100755"));
+
+    git(&dir.path, &["config", "core.filemode", "false"]);
+    assert_eq!(vcs::diff(path.clone(), false).unwrap(), Vec::new());
+    assert_eq!(vcs::status(path).unwrap().files, Vec::new());
+}
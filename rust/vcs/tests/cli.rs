@@ -0,0 +1,2196 @@
+mod common;
+
+use common::{git, git_output, git_repo, vcs, vcs_env, vcs_output, TmpDir};
+use std::fs;
+
+#[test]
+fn cat_file_matches_git() {
+    let dir = git_repo("cat_file", "sha1");
+    git(&dir.path, &["tag", "-a", "v1", "-m", "Release"]);
+    let tree = git(&dir.path, &["rev-parse", "HEAD^{tree}"]);
+    let tree = tree.trim();
+
+    for args in [
+        ["-t", "HEAD"],
+        ["-s", "HEAD"],
+        ["-t", "v1"],
+        ["-p", "v1"],
+        ["-p", tree],
+        ["-p", "master"],
+        ["tree", "HEAD"],
+    ] {
+        let mut git_args = vec!["cat-file"];
+        git_args.extend(args);
+        assert_eq!(vcs(&dir.path, &git_args, ""), git(&dir.path, &git_args));
+    }
+}
+
+#[test]
+fn cat_file_batch_matches_git() {
+    let dir = git_repo("cat_file_batch", "sha256");
+    let names = "HEAD\nmaster\nmissing-name\n";
+
+    let expected = std::process::Command::new("git")
+        .args(["cat-file", "--batch-check"])
+        .current_dir(&dir.path)
+        .stdin(std::fs::File::open(write_stdin(&dir.path, names)).unwrap())
+        .output()
+        .unwrap();
+    assert_eq!(
+        vcs(&dir.path, &["cat-file", "--batch-check"], names),
+        String::from_utf8(expected.stdout).unwrap()
+    );
+
+    let out = vcs(&dir.path, &["cat-file", "--batch"], "HEAD\n");
+    let head = git(&dir.path, &["rev-parse", "HEAD"]);
+    assert!(out.starts_with(&format!("{} commit ", head.trim())));
+    assert!(out.ends_with("Body with ünïcode\n\n"));
+}
+
+#[test]
+fn cat_file_errors_match_git() {
+    let dir = git_repo("cat_file_errors", "sha1");
+    let missing = "0123456789012345678901234567890123456789";
+    for args in [
+        vec!["-e", "HEAD"],
+        vec!["-e", missing],
+        vec!["-t", missing],
+        vec!["-p", "no-such-ref"],
+        vec!["-s", "HEAD:file.txt"],
+        vec!["-s", "HEAD:missing.txt"],
+        vec!["blob", "HEAD"],
+    ] {
+        let mut args = args;
+        args.insert(0, "cat-file");
+        let expected = git_output(&dir.path, &args);
+        let actual = vcs_output(&dir.path, &args, "", &[]);
+        assert_eq!(
+            actual.status.success(),
+            expected.status.success(),
+            "{args:?}"
+        );
+        assert_eq!(actual.stdout, expected.stdout, "{args:?}");
+    }
+
+    let names = format!("{missing}\nHEAD:file.txt\n\n");
+    let expected = std::process::Command::new("git")
+        .args(["cat-file", "--batch"])
+        .current_dir(&dir.path)
+        .stdin(std::fs::File::open(write_stdin(&dir.path, &names)).unwrap())
+        .output()
+        .unwrap();
+    assert_eq!(
+        vcs(&dir.path, &["cat-file", "--batch"], &names),
+        String::from_utf8(expected.stdout).unwrap()
+    );
+}
+
+fn write_stdin(dir: &std::path::Path, data: &str) -> std::path::PathBuf {
+    let path = dir.join(".git").join("stdin");
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+#[test]
+fn plumbing_matches_git() {
+    let dir = git_repo("plumbing", "sha1");
+    std::fs::write(dir.path.join("dir.txt"), "sorts before dir/\n").unwrap();
+    std::fs::write(dir.path.join("dir-a"), "sorts before dir/\n").unwrap();
+    git(&dir.path, &["add", "."]);
+
+    for args in [
+        vec!["ls-tree", "HEAD"],
+        vec!["ls-tree", "-r", "HEAD"],
+        vec!["ls-tree", "-r", "--name-only", "HEAD"],
+        vec!["ls-tree", "HEAD:dir"],
+        vec!["ls-files"],
+        vec!["ls-files", "--stage"],
+        vec!["write-tree"],
+    ] {
+        assert_eq!(vcs(&dir.path, &args, ""), git(&dir.path, &args), "{args:?}");
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn plumbing_errors_match_git() {
+    let dir = conflicted_repo("plumbing_errors");
+    std::os::unix::fs::symlink("file.txt", dir.path.join("link")).unwrap();
+    let missing = "0123456789012345678901234567890123456789";
+    git(
+        &dir.path,
+        &[
+            "update-index",
+            "--add",
+            "--cacheinfo",
+            &format!("160000,{missing},sub"),
+        ],
+    );
+    git(&dir.path, &["add", "link"]);
+
+    for args in [
+        vec!["ls-files", "--stage"],
+        vec!["ls-tree", "HEAD:file.txt"],
+        vec!["ls-tree", "no-such-ref"],
+        vec!["write-tree"],
+        vec!["commit-tree", "HEAD:file.txt", "-m", "Blob"],
+        vec!["commit-tree", "HEAD^{tree}", "-p", missing, "-m", "Missing"],
+    ] {
+        let expected = git_output(&dir.path, &args);
+        let actual = vcs_output(&dir.path, &args, "", &[]);
+        assert_eq!(
+            actual.status.success(),
+            expected.status.success(),
+            "{args:?}"
+        );
+        assert_eq!(actual.stdout, expected.stdout, "{args:?}");
+    }
+
+    // Same identity and dates as git gets, so the commit must be identical.
+    git(&dir.path, &["checkout", "-q", "-f", "master"]);
+    let env = [
+        ("GIT_AUTHOR_NAME", "Jöhn Doe"),
+        ("GIT_AUTHOR_EMAIL", "john@example.com"),
+        ("GIT_AUTHOR_DATE", "1527025023 +0200"),
+        ("GIT_COMMITTER_NAME", "Jöhn Doe"),
+        ("GIT_COMMITTER_EMAIL", "john@example.com"),
+        ("GIT_COMMITTER_DATE", "1527025044 +0200"),
+    ];
+    let args = [
+        "commit-tree",
+        "HEAD^{tree}",
+        "-p",
+        "HEAD",
+        "-p",
+        "other",
+        "-m",
+        "First",
+        "-m",
+        "Second",
+    ];
+    assert_eq!(vcs_env(&dir.path, &args, "", &env), git(&dir.path, &args));
+    let tree = vcs(&dir.path, &["write-tree"], "");
+    assert_eq!(tree, git(&dir.path, &["write-tree"]));
+}
+
+#[test]
+fn commit_tree_creates_commit() {
+    let dir = git_repo("commit_tree", "sha1");
+    git(&dir.path, &["config", "user.name", "Jane"]);
+    git(&dir.path, &["config", "user.email", "jane@example.com"]);
+    let tree = vcs(&dir.path, &["write-tree"], "");
+    let commit = vcs(
+        &dir.path,
+        &["commit-tree", tree.trim(), "-p", "HEAD", "-m", "Second"],
+        "",
+    );
+
+    git(&dir.path, &["fsck", "--strict"]);
+    assert_eq!(
+        git(
+            &dir.path,
+            &["log", "--format=%s|%an|%T|%P", "-1", commit.trim()]
+        ),
+        format!(
+            "Second|Jane|{}|{}",
+            tree.trim(),
+            git(&dir.path, &["rev-parse", "HEAD"])
+        )
+    );
+}
+
+#[test]
+fn commit_tree_honours_identity_env() {
+    let dir = git_repo("commit_tree_env", "sha1");
+    git(&dir.path, &["config", "user.name", "Jane"]);
+    git(&dir.path, &["config", "user.email", "jane@example.com"]);
+    let commit = vcs_env(
+        &dir.path,
+        &["commit-tree", "HEAD^{tree}", "-m", "Env"],
+        "",
+        &[
+            ("GIT_AUTHOR_NAME", "Author Override"),
+            ("GIT_AUTHOR_DATE", "1527025023 -0130"),
+            ("GIT_COMMITTER_EMAIL", "committer@example.com"),
+        ],
+    );
+    assert_eq!(
+        git(
+            &dir.path,
+            &[
+                "log",
+                "--format=%an <%ae> %ad|%cn <%ce>",
+                "--date=raw",
+                "-1",
+                commit.trim()
+            ]
+        ),
+        "Author Override <jane@example.com> 1527025023 -0130|Jane <committer@example.com>\n"
+    );
+}
+
+#[test]
+fn log_matches_git() {
+    let dir = git_repo("log", "sha1");
+    fs::write(dir.path.join("file.txt"), "second\n").unwrap();
+    git(&dir.path, &["commit", "-q", "-a", "-m", "Second"]);
+    git(&dir.path, &["checkout", "-q", "-b", "side", "HEAD~1"]);
+    fs::write(dir.path.join("side.txt"), "side\n").unwrap();
+    git(&dir.path, &["add", "side.txt"]);
+    git(&dir.path, &["commit", "-q", "-m", "Side"]);
+    git(&dir.path, &["checkout", "-q", "master"]);
+    git(
+        &dir.path,
+        &["merge", "-q", "--no-ff", "-m", "Merge side", "side"],
+    );
+
+    for args in [
+        vec!["log"],
+        vec!["log", "-n", "2"],
+        vec!["log", "side", "^master~1"],
+    ] {
+        assert_eq!(vcs(&dir.path, &args, ""), git(&dir.path, &args), "{args:?}");
+    }
+    assert_eq!(
+        vcs(&dir.path, &["log", "--oneline", "-n", "1"], ""),
+        git(
+            &dir.path,
+            &["log", "--oneline", "--no-decorate", "--abbrev=7", "-n", "1"]
+        )
+    );
+}
+
+#[test]
+fn blame_matches_git() {
+    let dir = git_repo("blame", "sha1");
+    fs::write(dir.path.join("file.txt"), "hello\nsecond\nthird\n").unwrap();
+    git(&dir.path, &["commit", "-q", "-a", "-m", "Grow"]);
+    git(&dir.path, &["mv", "file.txt", "renamed.txt"]);
+    git(&dir.path, &["commit", "-q", "-m", "Rename"]);
+    git(&dir.path, &["checkout", "-q", "-b", "side"]);
+    fs::write(dir.path.join("renamed.txt"), "hello\nsecond\nthird\nside\n").unwrap();
+    git(&dir.path, &["commit", "-q", "-a", "-m", "Side"]);
+    git(&dir.path, &["checkout", "-q", "master"]);
+    fs::write(dir.path.join("renamed.txt"), "HELLO\nsecond\nthird\n").unwrap();
+    git(&dir.path, &["commit", "-q", "-a", "-m", "Shout"]);
+    git(&dir.path, &["merge", "-q", "-m", "Merge side", "side"]);
+
+    for args in [
+        vec!["blame", "renamed.txt"],
+        vec!["blame", "-L", "2,+2", "renamed.txt"],
+        vec!["blame", "--porcelain", "renamed.txt"],
+        vec!["blame", "--line-porcelain", "renamed.txt"],
+        vec!["blame", "HEAD~3", "file.txt"],
+    ] {
+        assert_eq!(vcs(&dir.path, &args, ""), git(&dir.path, &args), "{args:?}");
+    }
+}
+
+#[test]
+fn blame_edge_cases_match_git() {
+    let dir = git_repo("blame_edges", "sha1");
+    fs::write(dir.path.join("empty.txt"), "").unwrap();
+    fs::write(dir.path.join("no_newline.txt"), "first\nlast").unwrap();
+    git(&dir.path, &["add", "."]);
+    git(&dir.path, &["commit", "-q", "-m", "Edges"]);
+    fs::write(dir.path.join("no_newline.txt"), "first\nmiddle\nlast").unwrap();
+    git(&dir.path, &["commit", "-q", "-a", "-m", "Middle"]);
+
+    for args in [
+        vec!["blame", "empty.txt"],
+        vec!["blame", "no_newline.txt"],
+        vec!["blame", "--porcelain", "no_newline.txt"],
+        vec!["blame", "-L", "3,3", "no_newline.txt"],
+        vec!["blame", "-L", "2", "no_newline.txt"],
+        vec!["blame", "-L", "5,6", "no_newline.txt"],
+        vec!["blame", "missing.txt"],
+        vec!["blame", "no-such-rev", "file.txt"],
+        vec!["blame", "HEAD~1", "dir"],
+    ] {
+        let expected = git_output(&dir.path, &args);
+        let actual = vcs_output(&dir.path, &args, "", &[]);
+        assert_eq!(
+            actual.status.success(),
+            expected.status.success(),
+            "{args:?}"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&actual.stdout),
+            String::from_utf8_lossy(&expected.stdout),
+            "{args:?}"
+        );
+    }
+}
+
+/// Repository with two commits and uncommitted changes in the work tree.
+fn dirty_repo(name: &str) -> common::TmpDir {
+    let dir = git_repo(name, "sha1");
+    fs::write(dir.path.join("file.txt"), "changed\n").unwrap();
+    fs::write(dir.path.join("dir").join("added.txt"), "added\n").unwrap();
+    git(&dir.path, &["rm", "-q", "dir/nested.txt"]);
+    git(&dir.path, &["add", "."]);
+    git(&dir.path, &["commit", "-q", "-m", "Second"]);
+    fs::write(dir.path.join("file.txt"), "dirty\n").unwrap();
+    fs::write(dir.path.join("untracked.txt"), "untracked\n").unwrap();
+    dir
+}
+
+/// Commits, index and work tree status of the repository in [dir].
+fn repo_state(dir: &std::path::Path) -> String {
+    [
+        git(dir, &["rev-parse", "HEAD", "ORIG_HEAD"]),
+        git(dir, &["ls-files", "--stage"]),
+        git(dir, &["status", "--porcelain", "--untracked-files=all"]),
+    ]
+    .concat()
+}
+
+#[test]
+fn reset_matches_git() {
+    for mode in ["--soft", "--mixed", "--hard"] {
+        let expected = dirty_repo("reset_git");
+        git(&expected.path, &["reset", "-q", mode, "HEAD~1"]);
+        let actual = dirty_repo("reset_vcs");
+        vcs(&actual.path, &["reset", mode, "HEAD~1"], "");
+        assert_eq!(
+            repo_state(&actual.path),
+            repo_state(&expected.path),
+            "{mode}"
+        );
+    }
+
+    let expected = dirty_repo("reset_paths_git");
+    git(&expected.path, &["add", "-A"]);
+    git(&expected.path, &["reset", "-q", "HEAD~1", "--", "dir"]);
+    let actual = dirty_repo("reset_paths_vcs");
+    git(&actual.path, &["add", "-A"]);
+    vcs(&actual.path, &["reset", "HEAD~1", "--", "dir"], "");
+    assert_eq!(
+        [
+            git(&actual.path, &["ls-files", "--stage"]),
+            git(&actual.path, &["status", "--porcelain"])
+        ],
+        [
+            git(&expected.path, &["ls-files", "--stage"]),
+            git(&expected.path, &["status", "--porcelain"])
+        ]
+    );
+}
+
+/// Repository with a conflicted merge of `file.txt` and a staged change in
+/// `dir`.
+fn conflicted_repo(name: &str) -> common::TmpDir {
+    let dir = git_repo(name, "sha1");
+    git(&dir.path, &["checkout", "-q", "-b", "other"]);
+    fs::write(dir.path.join("file.txt"), "other\n").unwrap();
+    git(&dir.path, &["commit", "-q", "-a", "-m", "Other"]);
+    git(&dir.path, &["checkout", "-q", "master"]);
+    fs::write(dir.path.join("file.txt"), "master\n").unwrap();
+    git(&dir.path, &["commit", "-q", "-a", "-m", "Master"]);
+    std::process::Command::new("git")
+        .args(["merge", "-q", "other"])
+        .current_dir(&dir.path)
+        .output()
+        .unwrap();
+    fs::write(dir.path.join("dir").join("nested.txt"), "staged\n").unwrap();
+    git(&dir.path, &["add", "dir"]);
+    dir
+}
+
+#[test]
+fn reset_edge_cases_match_git() {
+    // Local edits of files unchanged between HEAD and the target.
+    let dirty = |name: &str| {
+        let dir = dirty_repo(name);
+        fs::write(dir.path.join("dir").join("added.txt"), "edited\n").unwrap();
+        dir
+    };
+    let expected = dirty("reset_dirty_git");
+    git(&expected.path, &["reset", "-q", "--hard"]);
+    let actual = dirty("reset_dirty_vcs");
+    vcs(&actual.path, &["reset", "--hard"], "");
+    assert_eq!(repo_state(&actual.path), repo_state(&expected.path));
+    assert_eq!(
+        fs::read_to_string(actual.path.join("dir").join("added.txt")).unwrap(),
+        "added\n"
+    );
+
+    // Conflicts of other paths survive a reset of some paths.
+    for paths in [vec!["dir"], vec!["file.txt"]] {
+        let expected = conflicted_repo("reset_conflict_git");
+        let mut args = vec!["reset", "-q", "HEAD", "--"];
+        args.extend(&paths);
+        git(&expected.path, &args);
+        let actual = conflicted_repo("reset_conflict_vcs");
+        args.remove(1);
+        vcs(&actual.path, &args, "");
+        assert_eq!(
+            git(&actual.path, &["ls-files", "--stage"]),
+            git(&expected.path, &["ls-files", "--stage"]),
+            "{paths:?}"
+        );
+    }
+
+    let expected = dirty_repo("reset_unstaged_git");
+    fs::remove_file(expected.path.join("dir").join("added.txt")).unwrap();
+    let actual = dirty_repo("reset_unstaged_vcs");
+    fs::remove_file(actual.path.join("dir").join("added.txt")).unwrap();
+    assert_eq!(
+        vcs(&actual.path, &["reset", "HEAD~1"], ""),
+        git(&expected.path, &["reset", "HEAD~1"])
+    );
+}
+
+#[test]
+fn reset_fails_on_held_lock() {
+    let dir = dirty_repo("reset_lock");
+    let lock = dir.path.join(".git").join("index.lock");
+    fs::write(&lock, "held").unwrap();
+    let index = fs::read(dir.path.join(".git").join("index")).unwrap();
+    let head = git(&dir.path, &["rev-parse", "HEAD"]);
+    let out = vcs_output(&dir.path, &["reset", "--mixed", "HEAD~1"], "", &[]);
+    assert!(!out.status.success());
+    assert_eq!(fs::read_to_string(&lock).unwrap(), "held");
+    assert_eq!(
+        fs::read(dir.path.join(".git").join("index")).unwrap(),
+        index
+    );
+    assert_eq!(git(&dir.path, &["rev-parse", "HEAD"]), head);
+}
+
+#[test]
+fn clean_matches_git() {
+    let dir = git_repo("clean", "sha1");
+    fs::write(dir.path.join(".gitignore"), "*.log\n").unwrap();
+    git(&dir.path, &["add", ".gitignore"]);
+    git(&dir.path, &["commit", "-q", "-m", "Ignore logs"]);
+    for (path, content) in [
+        ("top.txt", "top"),
+        ("dir/untracked.txt", "untracked"),
+        ("dir/ignored.log", "ignored"),
+        ("new/file.txt", "new"),
+        ("new/ignored.log", "ignored"),
+        ("logs/only.log", "ignored"),
+        ("broken/.git/HEAD", "not a repository"),
+    ] {
+        let path = dir.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    fs::create_dir(dir.path.join("nested")).unwrap();
+    git(&dir.path.join("nested"), &["init", "-q"]);
+
+    for args in [
+        vec!["clean", "-n"],
+        vec!["clean", "-n", "-d"],
+        vec!["clean", "-n", "-x"],
+        vec!["clean", "-n", "-d", "-x"],
+        vec!["clean", "-n", "-d", "new", "dir/untracked.txt"],
+    ] {
+        assert_eq!(vcs(&dir.path, &args, ""), git(&dir.path, &args), "{args:?}");
+    }
+    assert!(!vcs_output(&dir.path, &["clean"], "", &[]).status.success());
+
+    vcs(&dir.path, &["clean", "-f", "-d"], "");
+    assert_eq!(
+        git(&dir.path, &["status", "--porcelain", "--ignored"]),
+        "?? nested/\n!! dir/ignored.log\n!! logs/\n!! new/\n"
+    );
+}
+
+#[test]
+fn clean_and_reset_errors_match_git() {
+    let untracked = |name: &str| {
+        let dir = git_repo(name, "sha1");
+        fs::write(dir.path.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.path.join("debug.log"), "ignored\n").unwrap();
+        fs::create_dir(dir.path.join("new")).unwrap();
+        fs::write(dir.path.join("new").join("file.txt"), "new\n").unwrap();
+        dir
+    };
+    let state = |dir: &std::path::Path| {
+        git(
+            dir,
+            &[
+                "status",
+                "--porcelain",
+                "--ignored",
+                "--untracked-files=all",
+            ],
+        )
+    };
+    for args in [
+        vec!["clean", "-f", "-x"],
+        vec!["clean", "-f", "-d", "-x", "new"],
+    ] {
+        let expected = untracked("clean_x_git");
+        git(&expected.path, &args);
+        let actual = untracked("clean_x_vcs");
+        vcs(&actual.path, &args, "");
+        assert_eq!(state(&actual.path), state(&expected.path), "{args:?}");
+    }
+
+    let expected = untracked("clean_no_force_git");
+    git(&expected.path, &["config", "clean.requireForce", "false"]);
+    let dir = untracked("clean_no_force_vcs");
+    git(&dir.path, &["config", "clean.requireForce", "false"]);
+    assert_eq!(
+        vcs(&dir.path, &["clean", "-d"], ""),
+        git(&expected.path, &["clean", "-d"])
+    );
+
+    for args in [
+        vec!["reset", "--soft", "HEAD", "--", "file.txt"],
+        vec!["reset", "--hard", "HEAD", "--", "file.txt"],
+        vec!["reset", "no-such-rev"],
+        vec!["reset", "HEAD:file.txt"],
+    ] {
+        assert_eq!(
+            vcs_output(&dir.path, &args, "", &[]).status.success(),
+            git_output(&dir.path, &args).status.success(),
+            "{args:?}"
+        );
+    }
+
+    let bare = TmpDir::new("reset_bare");
+    git(
+        &bare.path,
+        &["clone", "-q", "--bare", dir.path.to_str().unwrap(), "."],
+    );
+    for args in [
+        vec!["reset", "--hard", "HEAD"],
+        vec!["reset", "--mixed", "HEAD"],
+        vec!["reset", "--soft", "HEAD"],
+        vec!["clean", "-f"],
+    ] {
+        assert_eq!(
+            vcs_output(&bare.path, &args, "", &[]).status.success(),
+            git_output(&bare.path, &args).status.success(),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn worktree_matches_git() {
+    let dir = git_repo("worktree", "sha1");
+    let main = fs::canonicalize(&dir.path).unwrap();
+    let linked_dir = TmpDir::new("worktree_linked");
+    let linked = fs::canonicalize(&linked_dir.path).unwrap();
+    let feature = linked.join("feature");
+    let detached = linked.join("detached");
+    let feature_str = feature.to_str().unwrap();
+    let detached_str = detached.to_str().unwrap();
+
+    vcs(&main, &["worktree", "add", feature_str], "");
+    vcs(&main, &["worktree", "add", "--detach", detached_str], "");
+    let out = vcs_output(&main, &["worktree", "add", "../other", "master"], "", &[]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("is already checked out"));
+
+    let list = ["worktree", "list", "--porcelain"];
+    assert_eq!(vcs(&main, &list, ""), git(&main, &list));
+    assert_eq!(vcs(&feature, &list, ""), git(&feature, &list));
+    assert_eq!(git(&feature, &["status", "--porcelain"]), "");
+    assert_eq!(
+        git(&feature, &["rev-parse", "--abbrev-ref", "HEAD"]),
+        "feature\n"
+    );
+
+    // Commits in a linked worktree move its own branch only.
+    fs::write(feature.join("file.txt"), "changed\n").unwrap();
+    git(&feature, &["commit", "-q", "-am", "Change"]);
+    let log = ["log", "--oneline"];
+    assert_eq!(vcs(&feature, &log, ""), git(&feature, &log));
+    assert_eq!(vcs(&main, &log, ""), git(&main, &log));
+
+    fs::write(detached.join("untracked.txt"), "new\n").unwrap();
+    let remove = ["worktree", "remove", detached_str];
+    assert!(!vcs_output(&main, &remove, "", &[]).status.success());
+    vcs(&main, &["worktree", "remove", "--force", detached_str], "");
+    vcs(&main, &["worktree", "remove", "feature"], "");
+    assert!(!feature.exists() && !detached.exists());
+    assert_eq!(
+        git(&main, &list),
+        format!(
+            "worktree {}\nHEAD {}branch refs/heads/master\n\n",
+            main.display(),
+            git(&main, &["rev-parse", "HEAD"])
+        )
+    );
+    assert!(!main.join(".git").join("worktrees").exists());
+}
+
+#[test]
+fn worktree_errors_match_git() {
+    let dir = git_repo("worktree_errors", "sha1");
+    let main = fs::canonicalize(&dir.path).unwrap();
+    let linked_dir = TmpDir::new("worktree_errors_linked");
+    let linked = fs::canonicalize(&linked_dir.path).unwrap();
+    let occupied = linked.join("occupied");
+    fs::create_dir(&occupied).unwrap();
+    fs::write(occupied.join("file.txt"), "in the way\n").unwrap();
+    let occupied = occupied.to_str().unwrap();
+    let fresh = linked.join("fresh");
+    let fresh = fresh.to_str().unwrap();
+
+    for args in [
+        vec!["worktree", "add", occupied],
+        vec!["worktree", "add", fresh, "no-such-rev"],
+        vec!["worktree", "add", "-b", "master", fresh],
+        vec!["worktree", "remove", main.to_str().unwrap()],
+        vec!["worktree", "remove", "no-such-worktree"],
+    ] {
+        assert_eq!(
+            vcs_output(&main, &args, "", &[]).status.success(),
+            git_output(&main, &args).status.success(),
+            "{args:?}"
+        );
+    }
+    assert_eq!(
+        fs::read_to_string(linked.join("occupied").join("file.txt")).unwrap(),
+        "in the way\n"
+    );
+    assert!(!main.join(".git").join("worktrees").exists());
+
+    // A new branch at an older commit, and a modified file blocks removal.
+    fs::write(main.join("file.txt"), "second\n").unwrap();
+    git(&main, &["commit", "-q", "-am", "Second"]);
+    vcs(
+        &main,
+        &["worktree", "add", "-b", "old", fresh, "HEAD~1"],
+        "",
+    );
+    assert_eq!(
+        git(&linked.join("fresh"), &["rev-parse", "HEAD"]),
+        git(&main, &["rev-parse", "HEAD~1"])
+    );
+    assert_eq!(
+        git(&main, &["rev-parse", "old"]),
+        git(&main, &["rev-parse", "HEAD~1"])
+    );
+    fs::write(linked.join("fresh").join("file.txt"), "modified\n").unwrap();
+    assert!(!vcs_output(&main, &["worktree", "remove", fresh], "", &[])
+        .status
+        .success());
+    assert!(linked.join("fresh").join("file.txt").exists());
+}
+
+#[test]
+fn discovers_repositories_like_git() {
+    let dir = git_repo("discover", "sha1");
+    let nested = dir.path.join("dir");
+    let log = ["log", "--oneline"];
+    assert_eq!(vcs(&nested, &log, ""), git(&dir.path, &log));
+    assert_eq!(
+        vcs(&nested, &["ls-files"], ""),
+        git(&dir.path, &["ls-files"])
+    );
+
+    let ceiling = dir.path.to_str().unwrap();
+    let out = vcs_output(&nested, &log, "", &[("GIT_CEILING_DIRECTORIES", ceiling)]);
+    assert!(!out.status.success());
+    assert_eq!(
+        vcs_env(&dir.path, &log, "", &[("GIT_CEILING_DIRECTORIES", ceiling)]),
+        git(&dir.path, &log)
+    );
+
+    // A bare clone is found from inside its git dir and has no work tree.
+    let bare = TmpDir::new("discover_bare");
+    let bare_dir = bare.path.join("repo.git");
+    git(
+        &bare.path,
+        &[
+            "clone",
+            "-q",
+            "--bare",
+            dir.path.to_str().unwrap(),
+            "repo.git",
+        ],
+    );
+    assert_eq!(vcs(&bare_dir.join("refs"), &log, ""), git(&dir.path, &log));
+    assert!(!vcs_output(&bare_dir, &["reset", "--hard"], "", &[])
+        .status
+        .success());
+    let list = ["worktree", "list", "--porcelain"];
+    assert_eq!(vcs(&bare_dir, &list, ""), git(&bare_dir, &list));
+
+    // GIT_DIR and GIT_WORK_TREE override discovery.
+    let work_tree = bare.path.join("work");
+    fs::create_dir(&work_tree).unwrap();
+    let envs = [
+        ("GIT_DIR", bare_dir.to_str().unwrap()),
+        ("GIT_WORK_TREE", work_tree.to_str().unwrap()),
+    ];
+    vcs_env(&bare.path, &["reset", "--hard"], "", &envs);
+    assert_eq!(
+        fs::read_to_string(work_tree.join("dir").join("nested.txt")).unwrap(),
+        "nested\n"
+    );
+    assert_eq!(
+        vcs_env(&bare.path, &log, "", &envs[..1]),
+        git(&dir.path, &log)
+    );
+
+    vcs(&bare.path, &["init", "--bare", "new.git"], "");
+    assert_eq!(
+        git(
+            &bare.path.join("new.git"),
+            &["rev-parse", "--is-bare-repository"]
+        ),
+        "true\n"
+    );
+}
+
+#[test]
+fn discovery_edge_cases_match_git() {
+    let dir = git_repo("discover_edges", "sha1");
+    let log = ["log", "--oneline"];
+    let expected = git(&dir.path, &log);
+
+    // A `.git` file with a relative path to the git dir.
+    let separate = TmpDir::new("discover_separate");
+    let work = separate.path.join("work");
+    fs::create_dir(&work).unwrap();
+    fs::rename(dir.path.join(".git"), separate.path.join("repo.git")).unwrap();
+    fs::write(work.join(".git"), "gitdir: ../repo.git\n").unwrap();
+    assert_eq!(vcs(&work, &log, ""), expected);
+
+    // core.worktree in a git dir found through GIT_DIR.
+    let git_dir = separate.path.join("repo.git");
+    git(
+        &separate.path,
+        &[
+            "--git-dir",
+            git_dir.to_str().unwrap(),
+            "config",
+            "core.worktree",
+            work.to_str().unwrap(),
+        ],
+    );
+    let envs = [("GIT_DIR", git_dir.to_str().unwrap())];
+    vcs_env(&separate.path, &["reset", "--hard"], "", &envs);
+    assert_eq!(
+        fs::read_to_string(work.join("file.txt")).unwrap(),
+        "hello\n"
+    );
+
+    let broken = TmpDir::new("discover_broken");
+    fs::write(broken.path.join(".git"), "not a gitdir line\n").unwrap();
+    let missing = broken.path.join("missing.git");
+    for (cwd, envs) in [
+        (&broken.path, vec![]),
+        (&broken.path, vec![("GIT_DIR", missing.to_str().unwrap())]),
+        (&separate.path, vec![]),
+    ] {
+        let out = vcs_output(cwd, &log, "", &envs);
+        assert!(!out.status.success(), "{cwd:?} {envs:?}");
+    }
+}
+
+#[test]
+fn submodule_matches_git() {
+    let lib = git_repo("submodule_lib", "sha1");
+    fs::write(lib.path.join("file.txt"), "second\n").unwrap();
+    git(&lib.path, &["commit", "-q", "-am", "Second"]);
+    let root = TmpDir::new("submodule");
+    let superproject = root.path.join("super");
+    fs::create_dir(&superproject).unwrap();
+    git(&superproject, &["init", "-q", "-b", "master"]);
+    let lib_path = lib.path.to_str().unwrap();
+    git(
+        &superproject,
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "-q",
+            lib_path,
+            "deps/lib",
+        ],
+    );
+    git(&superproject, &["commit", "-q", "-m", "Add lib"]);
+    git(&root.path, &["clone", "-q", "super", "clone"]);
+    let clone = root.path.join("clone");
+    let sub = clone.join("deps").join("lib");
+
+    let status = ["submodule", "status"];
+    assert_eq!(vcs(&clone, &status, ""), git(&clone, &status));
+    vcs(&clone, &["submodule", "update"], "");
+    assert!(
+        !sub.join(".git").exists(),
+        "uninitialized submodules are skipped"
+    );
+
+    vcs(&clone, &["submodule", "update", "--init"], "");
+    assert_eq!(
+        git(&clone, &["config", "submodule.deps/lib.url"]),
+        format!("{lib_path}\n")
+    );
+    assert_eq!(vcs(&clone, &status, ""), git(&clone, &status));
+    assert_eq!(git(&clone, &["status", "--porcelain"]), "");
+    assert_eq!(git(&sub, &["status", "--porcelain"]), "");
+    assert_eq!(
+        git(&sub, &["rev-parse", "HEAD"]),
+        git(&lib.path, &["rev-parse", "HEAD"])
+    );
+    assert_eq!(
+        fs::read_to_string(sub.join("file.txt")).unwrap(),
+        "second\n"
+    );
+
+    git(&sub, &["checkout", "-q", "HEAD~1"]);
+    assert!(vcs(&clone, &status, "").starts_with('+'));
+    assert_eq!(vcs(&clone, &status, ""), git(&clone, &status));
+    vcs(&clone, &["submodule", "update", "deps"], "");
+    assert_eq!(vcs(&clone, &status, ""), git(&clone, &status));
+    assert_eq!(git(&clone, &["status", "--porcelain"]), "");
+}
+
+#[test]
+fn submodule_init_keeps_config() {
+    let lib = git_repo("submodule_config_lib", "sha1");
+    let root = TmpDir::new("submodule_config");
+    let superproject = root.path.join("super");
+    fs::create_dir(&superproject).unwrap();
+    git(&superproject, &["init", "-q", "-b", "master"]);
+    git(
+        &superproject,
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "-q",
+            lib.path.to_str().unwrap(),
+            "deps/lib",
+        ],
+    );
+    git(&superproject, &["commit", "-q", "-m", "Add lib"]);
+    git(&root.path, &["clone", "-q", "super", "clone"]);
+    let clone = root.path.join("clone");
+    let fetch = ["config", "--get-all", "remote.origin.fetch"];
+    git(
+        &clone,
+        &[
+            "config",
+            "--add",
+            "remote.origin.fetch",
+            "+refs/tags/*:refs/tags/*",
+        ],
+    );
+    let config_path = clone.join(".git").join("config");
+    let mut config = fs::read_to_string(&config_path).unwrap();
+    config.push_str("# Written by hand\n[Alias]\n\tst = status # short\n");
+    fs::write(&config_path, &config).unwrap();
+    let fetched = git(&clone, &fetch);
+    assert_eq!(fetched.lines().count(), 2);
+
+    vcs(&clone, &["submodule", "init"], "");
+    let updated = fs::read_to_string(&config_path).unwrap();
+    assert!(updated.starts_with(&config), "{updated}");
+    assert_eq!(git(&clone, &fetch), fetched);
+    assert_eq!(
+        git(&clone, &["config", "submodule.deps/lib.url"]),
+        format!("{}\n", lib.path.to_str().unwrap())
+    );
+    assert_eq!(git(&clone, &["config", "alias.st"]), "status\n");
+}
+
+#[test]
+fn submodule_errors_match_git() {
+    // Without a .gitmodules file there is nothing to do.
+    let plain = git_repo("submodule_errors_plain", "sha1");
+    for args in [
+        vec!["submodule", "status"],
+        vec!["submodule", "init"],
+        vec!["submodule", "update", "--init"],
+    ] {
+        let expected = git_output(&plain.path, &args);
+        let actual = vcs_output(&plain.path, &args, "", &[]);
+        assert_eq!(
+            actual.status.success(),
+            expected.status.success(),
+            "{args:?}"
+        );
+        assert_eq!(actual.stdout, expected.stdout, "{args:?}");
+    }
+
+    let lib = git_repo("submodule_errors_lib", "sha1");
+    let root = TmpDir::new("submodule_errors");
+    let superproject = root.path.join("super");
+    fs::create_dir(&superproject).unwrap();
+    git(&superproject, &["init", "-q", "-b", "master"]);
+    git(
+        &superproject,
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "-q",
+            lib.path.to_str().unwrap(),
+            "deps/lib",
+        ],
+    );
+    git(&superproject, &["commit", "-q", "-m", "Add lib"]);
+    git(&root.path, &["clone", "-q", "super", "clone"]);
+    let clone = root.path.join("clone");
+    let sub = clone.join("deps").join("lib");
+
+    // Tracked paths outside any submodule select nothing.
+    let status = ["submodule", "status", ".gitmodules"];
+    assert_eq!(vcs(&clone, &status, ""), git(&clone, &status));
+    vcs(&clone, &["submodule", "init", ".gitmodules"], "");
+    assert!(
+        git_output(&clone, &["config", "--get-regexp", "^submodule"])
+            .stdout
+            .is_empty()
+    );
+
+    // A url that can't be cloned fails and leaves nothing behind.
+    let missing = root.path.join("missing");
+    let missing = missing.to_str().unwrap();
+    git(&clone, &["config", "submodule.deps/lib.url", missing]);
+    assert!(!vcs_output(&clone, &["submodule", "update"], "", &[])
+        .status
+        .success());
+    assert!(!git_output(
+        &clone,
+        &["-c", "protocol.file.allow=always", "submodule", "update"]
+    )
+    .status
+    .success());
+    assert!(!sub.join(".git").exists());
+    let status = ["submodule", "status"];
+    assert_eq!(vcs(&clone, &status, ""), git(&clone, &status));
+}
+
+#[test]
+fn commit_graph_matches_git() {
+    let dir = git_repo("commit_graph", "sha1");
+    let commit = |branch: &str, message: &str| {
+        git(&dir.path, &["checkout", "-q", branch]);
+        git(&dir.path, &["commit", "-q", "--allow-empty", "-m", message]);
+    };
+    git(&dir.path, &["branch", "side"]);
+    git(&dir.path, &["branch", "other"]);
+    commit("master", "Main");
+    commit("side", "Side");
+    commit("other", "Other");
+    // Criss-cross merge with two merge bases.
+    git(&dir.path, &["branch", "left", "master"]);
+    git(&dir.path, &["branch", "right", "side"]);
+    git(&dir.path, &["checkout", "-q", "left"]);
+    git(&dir.path, &["merge", "-q", "--no-ff", "-m", "Left", "side"]);
+    git(&dir.path, &["checkout", "-q", "right"]);
+    git(
+        &dir.path,
+        &["merge", "-q", "--no-ff", "-m", "Right", "master"],
+    );
+    git(&dir.path, &["checkout", "-q", "master"]);
+    git(
+        &dir.path,
+        &["merge", "-q", "-m", "Octopus", "side", "other"],
+    );
+
+    let queries = [
+        vec!["merge-base", "--all", "left", "right"],
+        vec!["merge-base", "left", "other"],
+        vec!["merge-base", "--all", "master", "right"],
+        vec!["log", "--oneline", "master", "right"],
+        vec!["log", "--oneline", "right", "^other"],
+    ];
+    let expected = queries
+        .iter()
+        .map(|args| git(&dir.path, args))
+        .collect::<Vec<String>>();
+    for (args, expected) in queries.iter().zip(&expected) {
+        assert_eq!(&vcs(&dir.path, args, ""), expected, "{args:?}");
+    }
+
+    vcs(&dir.path, &["commit-graph", "write"], "");
+    git(&dir.path, &["commit-graph", "verify"]);
+    for (args, expected) in queries.iter().zip(&expected) {
+        assert_eq!(&vcs(&dir.path, args, ""), expected, "{args:?}");
+    }
+    // The graph git writes can be read as well.
+    git(&dir.path, &["commit-graph", "write", "--reachable"]);
+    for (args, expected) in queries.iter().zip(&expected) {
+        assert_eq!(&vcs(&dir.path, args, ""), expected, "{args:?}");
+    }
+}
+
+#[test]
+fn commit_graph_edge_cases_match_git() {
+    // Nothing to write without commits.
+    let empty = TmpDir::new("commit_graph_empty");
+    git(&empty.path, &["init", "-q", "-b", "master"]);
+    let write = ["commit-graph", "write"];
+    assert_eq!(
+        vcs_output(&empty.path, &write, "", &[]).status.success(),
+        git_output(&empty.path, &write).status.success()
+    );
+
+    let dir = git_repo("commit_graph_edge_cases", "sha256");
+    let graph = dir
+        .path
+        .join(".git")
+        .join("objects")
+        .join("info")
+        .join("commit-graph");
+    git(&dir.path, &["branch", "side"]);
+    git(&dir.path, &["commit", "-q", "--allow-empty", "-m", "Main"]);
+    vcs(&dir.path, &write, "");
+    git(&dir.path, &["commit-graph", "verify"]);
+
+    // Commits made after the graph was written are read from the objects.
+    git(&dir.path, &["checkout", "-q", "side"]);
+    git(&dir.path, &["commit", "-q", "--allow-empty", "-m", "Side"]);
+    git(&dir.path, &["checkout", "-q", "master"]);
+    git(&dir.path, &["merge", "-q", "-m", "Merge", "side"]);
+    let queries = [
+        vec!["merge-base", "--all", "master~1", "side"],
+        vec!["log", "--oneline", "master", "^side"],
+    ];
+    let expected = queries
+        .iter()
+        .map(|args| git(&dir.path, args))
+        .collect::<Vec<String>>();
+    for (args, expected) in queries.iter().zip(&expected) {
+        assert_eq!(&vcs(&dir.path, args, ""), expected, "{args:?}");
+    }
+
+    // A corrupt graph is ignored rather than trusted.
+    let mut data = fs::read(&graph).unwrap();
+    let middle = data.len() / 2;
+    data[middle] ^= 0xff;
+    fs::write(&graph, &data).unwrap();
+    for (args, expected) in queries.iter().zip(&expected) {
+        assert_eq!(&vcs(&dir.path, args, ""), expected, "{args:?}");
+    }
+    fs::write(&graph, &data[..data.len() / 3]).unwrap();
+    for (args, expected) in queries.iter().zip(&expected) {
+        assert_eq!(&vcs(&dir.path, args, ""), expected, "{args:?}");
+    }
+
+    // A held lock fails the write and is left alone, git doesn't write
+    // either.
+    let lock = graph.with_file_name("commit-graph.lock");
+    fs::write(&lock, "held").unwrap();
+    assert!(!vcs_output(&dir.path, &write, "", &[]).status.success());
+    git(&dir.path, &write);
+    assert_eq!(fs::read_to_string(&lock).unwrap(), "held");
+    assert_eq!(fs::read(&graph).unwrap(), &data[..data.len() / 3]);
+    fs::remove_file(&lock).unwrap();
+
+    // Rewriting replaces it with a valid graph of all commits.
+    vcs(&dir.path, &write, "");
+    git(&dir.path, &["commit-graph", "verify"]);
+    for (args, expected) in queries.iter().zip(&expected) {
+        assert_eq!(&vcs(&dir.path, args, ""), expected, "{args:?}");
+    }
+}
+
+#[test]
+fn status_matches_git() {
+    let dir = git_repo("status", "sha1");
+    let porcelain = |dir: &std::path::Path| {
+        let expected = git(
+            dir,
+            &["-c", "status.renames=false", "status", "--porcelain"],
+        );
+        assert_eq!(vcs(dir, &["status", "--porcelain"], ""), expected);
+        expected
+    };
+    assert_eq!(porcelain(&dir.path), "");
+
+    fs::write(dir.path.join("file.txt"), "changed\n").unwrap();
+    fs::remove_file(dir.path.join("dir").join("nested.txt")).unwrap();
+    fs::write(dir.path.join("staged.txt"), "staged\n").unwrap();
+    git(&dir.path, &["add", "staged.txt"]);
+    fs::write(dir.path.join("staged.txt"), "staged and changed\n").unwrap();
+    fs::create_dir(dir.path.join("new")).unwrap();
+    fs::write(dir.path.join("new").join("untracked.txt"), "new\n").unwrap();
+    assert_eq!(
+        porcelain(&dir.path),
+        " D dir/nested.txt\n M file.txt\nAM staged.txt\n?? new/\n"
+    );
+
+    // Same size, same second: only the racy check notices the change.
+    git(&dir.path, &["reset", "-q", "--hard"]);
+    fs::write(dir.path.join("file.txt"), "hello\n").unwrap();
+    vcs(&dir.path, &["status"], "");
+    fs::write(dir.path.join("file.txt"), "howdy\n").unwrap();
+    assert_eq!(porcelain(&dir.path), " M file.txt\n?? new/\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn status_ignores_modes_with_lowercase_filemode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = git_repo("status_filemode", "sha1");
+    // `git init` spells the key in lowercase.
+    git(&dir.path, &["config", "core.filemode", "false"]);
+    let file = dir.path.join("file.txt");
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(git(&dir.path, &["status", "--porcelain"]), "");
+    assert_eq!(vcs(&dir.path, &["status", "--porcelain"], ""), "");
+    assert_eq!(vcs(&dir.path, &["diff"], ""), "");
+    vcs::stage(
+        dir.path.to_string_lossy().to_string(),
+        vec![".".to_string()],
+    )
+    .unwrap();
+    assert_eq!(
+        git(&dir.path, &["ls-files", "-s", "file.txt"])[..6],
+        *"100644"
+    );
+}
+
+#[test]
+fn status_leaves_index_alone() {
+    let dir = git_repo("status_index", "sha1");
+    git(&dir.path, &["config", "core.untrackedCache", "true"]);
+    git(&dir.path, &["status", "--porcelain"]);
+    let index_path = dir.path.join(".git").join("index");
+    let index = fs::read(&index_path).unwrap();
+    // Rewritten with the same content, so the stat data no longer matches.
+    fs::write(dir.path.join("file.txt"), "hello\n").unwrap();
+    assert_eq!(vcs(&dir.path, &["status", "--porcelain"], ""), "");
+    assert_eq!(fs::read(&index_path).unwrap(), index);
+
+    let lock = dir.path.join(".git").join("index.lock");
+    fs::write(&lock, "held").unwrap();
+    assert_eq!(vcs(&dir.path, &["status", "--porcelain"], ""), "");
+    assert_eq!(fs::read_to_string(&lock).unwrap(), "held");
+}
+
+#[test]
+fn index_writes_smudge_racy_entries() {
+    let dir = git_repo("racy_index", "sha1");
+    let path = dir.path.to_string_lossy().to_string();
+    let size = |name: &str| {
+        let debug = git(&dir.path, &["ls-files", "--debug", name]);
+        debug
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("size: "))
+            .and_then(|size| size.split_whitespace().next())
+            .unwrap()
+            .to_string()
+    };
+    // Modified in the second the index is written, so a later change in
+    // that second could keep the same stat data.
+    fs::write(dir.path.join("file.txt"), "racy\n").unwrap();
+    vcs::stage(path.clone(), vec!["file.txt".to_string()]).unwrap();
+    assert_eq!(size("file.txt"), "0");
+
+    // Rewriting the index later keeps the entry smudged, while older
+    // entries keep their size.
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(dir.path.join("dir").join("nested.txt"), "later\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    vcs::stage(path, vec!["dir".to_string()]).unwrap();
+    assert_eq!(size("file.txt"), "0");
+    assert_eq!(size("dir/nested.txt"), "6");
+    assert_eq!(
+        vcs(&dir.path, &["status", "--porcelain"], ""),
+        "M  dir/nested.txt\nM  file.txt\n"
+    );
+}
+
+#[test]
+fn attributes_match_git() {
+    let dir = git_repo("attributes", "sha1");
+    let attributes = "*.txt text eol=crlf\n*.bin binary\nauto/** text=auto\n";
+    fs::write(dir.path.join(".gitattributes"), attributes).unwrap();
+    fs::create_dir(dir.path.join("auto")).unwrap();
+    fs::write(dir.path.join("crlf.txt"), "a\r\nb\r\n").unwrap();
+    fs::write(dir.path.join("data.bin"), "x\0y\r\n").unwrap();
+    fs::write(dir.path.join("auto").join("text.c"), "p\r\nq\r\n").unwrap();
+    fs::write(dir.path.join("auto").join("data.dat"), "bin\0\r\n").unwrap();
+    let stage = |dir: &std::path::Path| {
+        vcs::stage(dir.to_string_lossy().to_string(), vec![".".to_string()]).unwrap();
+        let staged = git(dir, &["ls-files", "--stage"]);
+        git(dir, &["add", "."]);
+        assert_eq!(staged, git(dir, &["ls-files", "--stage"]));
+    };
+    stage(&dir.path);
+    git(&dir.path, &["commit", "-q", "-m", "Attributes"]);
+    assert_eq!(
+        git(&dir.path, &["ls-files", "--eol", "crlf.txt", "auto/text.c"]),
+        "i/lf    w/crlf  attr/text=auto        \tauto/text.c\n\
+         i/lf    w/crlf  attr/text eol=crlf    \tcrlf.txt\n"
+    );
+
+    // Checking out converts like git, status and diff see no change.
+    for file in ["crlf.txt", "data.bin", "auto/text.c", "auto/data.dat"] {
+        fs::remove_file(dir.path.join(file)).unwrap();
+    }
+    vcs(&dir.path, &["reset", "--hard", "HEAD"], "");
+    assert_eq!(fs::read(dir.path.join("crlf.txt")).unwrap(), b"a\r\nb\r\n");
+    assert_eq!(
+        fs::read(dir.path.join("auto").join("text.c")).unwrap(),
+        b"p\nq\n"
+    );
+    assert_eq!(fs::read(dir.path.join("data.bin")).unwrap(), b"x\0y\r\n");
+    assert_eq!(vcs(&dir.path, &["status", "--porcelain"], ""), "");
+    assert_eq!(git(&dir.path, &["status", "--porcelain"]), "");
+
+    fs::write(dir.path.join("crlf.txt"), "a\r\nc\r\n").unwrap();
+    fs::write(dir.path.join("data.bin"), "x\0z\n").unwrap();
+    let expected = git(&dir.path, &["diff"]);
+    assert!(expected.contains("Binary files a/data.bin and b/data.bin differ\n"));
+    assert_eq!(vcs(&dir.path, &["diff"], ""), expected);
+    assert_eq!(
+        vcs(&dir.path, &["status", "--porcelain"], ""),
+        git(&dir.path, &["status", "--porcelain"])
+    );
+
+    // Files committed with CRLF before `core.autocrlf` stay unchanged.
+    git(&dir.path, &["config", "core.autocrlf", "true"]);
+    fs::write(dir.path.join("old.c"), "o\r\n").unwrap();
+    git(&dir.path, &["-c", "core.autocrlf=false", "add", "old.c"]);
+    fs::write(dir.path.join("old.c"), "o\r\nn\r\n").unwrap();
+    fs::write(dir.path.join("new.c"), "n\r\n").unwrap();
+    stage(&dir.path);
+}
+
+#[test]
+fn attribute_precedence_matches_git() {
+    let dir = git_repo("attribute_precedence", "sha1");
+    let write = |path: &str, data: &str| {
+        let path = dir.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+    };
+    // Deeper files beat higher ones, `info/attributes` beats both and
+    // `core.attributesFile` loses to all of them.
+    write(
+        ".gitattributes",
+        "*.txt text eol=crlf\n/top.md text\n*.cfg !text\n",
+    );
+    write("sub/.gitattributes", "*.txt -text\nkeep.txt text\n");
+    write(".git/info/attributes", "sub/keep.txt -text\n");
+    write(
+        ".git/global-attributes",
+        "*.cfg text\n*.md -text\n*.glob text\n",
+    );
+    // Config keys are case-insensitive.
+    let global = dir.path.join(".git").join("global-attributes");
+    git(
+        &dir.path,
+        &["config", "core.attributesfile", global.to_str().unwrap()],
+    );
+    for file in [
+        "root.txt",
+        "sub/raw.txt",
+        "sub/keep.txt",
+        "top.md",
+        "sub/top.md",
+        "unset.cfg",
+        "only.glob",
+    ] {
+        write(file, "a\r\nb\r\n");
+    }
+    let path = dir.path.to_string_lossy().to_string();
+    vcs::stage(path, vec![".".to_string()]).unwrap();
+    let staged = git(&dir.path, &["ls-files", "--stage"]);
+    git(&dir.path, &["add", "."]);
+    assert_eq!(staged, git(&dir.path, &["ls-files", "--stage"]));
+    assert_eq!(git(&dir.path, &["show", ":root.txt"]), "a\nb\n");
+    assert_eq!(git(&dir.path, &["show", ":only.glob"]), "a\nb\n");
+    assert_eq!(git(&dir.path, &["show", ":sub/keep.txt"]), "a\r\nb\r\n");
+    git(&dir.path, &["commit", "-q", "-m", "Attributes"]);
+
+    // `-diff` makes text binary and `diff` makes binary content text.
+    write(".gitattributes", "*.txt -diff\n*.dat diff\n");
+    write("sub/.gitattributes", "");
+    write("binary.dat", "x\0y\n");
+    git(&dir.path, &["add", "."]);
+    git(&dir.path, &["commit", "-q", "-m", "Diff attributes"]);
+    write("root.txt", "changed\n");
+    write("binary.dat", "x\0z\n");
+    let expected = git(&dir.path, &["diff"]);
+    assert!(expected.contains("Binary files a/root.txt and b/root.txt differ\n"));
+    assert!(expected.contains("-x\0y\n+x\0z\n"));
+    assert_eq!(vcs(&dir.path, &["diff"], ""), expected);
+}
+
+/// Long-running filter applying rot13 to clean and smudge, like the one in
+/// git's test suite.
+const ROT13_FILTER: &str = r#"
+binmode STDIN; binmode STDOUT; $| = 1;
+sub packet { my $len; read(STDIN, $len, 4) == 4 or exit 0; $len = hex $len;
+    return undef if $len == 0; my $data; read(STDIN, $data, $len - 4); $data }
+sub packets { my @list; while (defined(my $p = packet())) { push @list, $p } @list }
+sub send_packet { printf "%04x%s", length($_[0]) + 4, $_[0] }
+packets(); send_packet("git-filter-server\n"); send_packet("version=2\n"); print "0000";
+packets(); send_packet("capability=clean\n"); send_packet("capability=smudge\n"); print "0000";
+while (1) {
+    packets();
+    my $content = join("", packets());
+    $content =~ tr/a-zA-Z/n-za-mN-ZA-M/;
+    send_packet("status=success\n"); print "0000";
+    send_packet($content) if length $content; print "0000";
+    print "0000";
+}
+"#;
+
+#[test]
+fn filters_match_git() {
+    let dir = git_repo("filters", "sha1");
+    fs::write(dir.path.join(".git").join("rot13.pl"), ROT13_FILTER).unwrap();
+    let attributes = "*.r13 filter=rot13\n*.up filter=upper\n*.bad filter=broken\n";
+    fs::write(dir.path.join(".gitattributes"), attributes).unwrap();
+    git(
+        &dir.path,
+        &["config", "filter.rot13.process", "perl .git/rot13.pl"],
+    );
+    git(&dir.path, &["config", "filter.upper.clean", "tr a-z A-Z"]);
+    git(&dir.path, &["config", "filter.upper.smudge", "tr A-Z a-z"]);
+    fs::write(dir.path.join("one.r13"), "Hello\n").unwrap();
+    fs::write(dir.path.join("two.r13"), "World\n").unwrap();
+    fs::write(dir.path.join("text.up"), "shout\n").unwrap();
+
+    let path = dir.path.to_string_lossy().to_string();
+    vcs::stage(path.clone(), vec![".".to_string()]).unwrap();
+    let staged = git(&dir.path, &["ls-files", "--stage"]);
+    git(&dir.path, &["add", "."]);
+    assert_eq!(staged, git(&dir.path, &["ls-files", "--stage"]));
+    assert_eq!(git(&dir.path, &["show", ":one.r13"]), "Uryyb\n");
+    assert_eq!(git(&dir.path, &["show", ":text.up"]), "SHOUT\n");
+    git(&dir.path, &["commit", "-q", "-m", "Filters"]);
+
+    for file in ["one.r13", "two.r13", "text.up"] {
+        fs::remove_file(dir.path.join(file)).unwrap();
+    }
+    vcs(&dir.path, &["reset", "--hard", "HEAD"], "");
+    assert_eq!(
+        fs::read_to_string(dir.path.join("one.r13")).unwrap(),
+        "Hello\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path.join("text.up")).unwrap(),
+        "shout\n"
+    );
+    assert_eq!(vcs(&dir.path, &["status", "--porcelain"], ""), "");
+    fs::write(dir.path.join("two.r13"), "Planet\n").unwrap();
+    let expected = git(&dir.path, &["diff"]);
+    assert!(expected.contains("-Jbeyq\n+Cynarg\n"));
+    assert_eq!(vcs(&dir.path, &["diff"], ""), expected);
+
+    // A failing required filter stops staging, an optional one doesn't.
+    git(&dir.path, &["config", "filter.broken.clean", "false"]);
+    fs::write(dir.path.join("file.bad"), "bad\n").unwrap();
+    vcs::stage(path.clone(), vec!["file.bad".to_string()]).unwrap();
+    assert_eq!(git(&dir.path, &["show", ":file.bad"]), "bad\n");
+    git(&dir.path, &["config", "filter.broken.required", "true"]);
+    fs::write(dir.path.join("file.bad"), "worse\n").unwrap();
+    assert!(vcs::stage(path, vec!["file.bad".to_string()]).is_err());
+    assert_eq!(git(&dir.path, &["show", ":file.bad"]), "bad\n");
+}
+
+#[test]
+fn filter_edge_cases_match_git() {
+    let dir = git_repo("filter_edge_cases", "sha1");
+    let attributes = "*.tag filter=tag\n*.gone filter=gone\n*.half filter=half\n";
+    fs::write(dir.path.join(".gitattributes"), attributes).unwrap();
+    // `%f` is replaced by the quoted path, missing optional commands and
+    // directions keep the content as is.
+    git(&dir.path, &["config", "filter.tag.clean", "echo %f; cat"]);
+    git(
+        &dir.path,
+        &["config", "filter.gone.clean", "no-such-command"],
+    );
+    git(&dir.path, &["config", "filter.half.clean", "tr a-z A-Z"]);
+    fs::write(dir.path.join("with space.tag"), "line\n").unwrap();
+    fs::write(dir.path.join("it's.tag"), "quote\n").unwrap();
+    fs::write(dir.path.join("file.gone"), "kept\n").unwrap();
+    fs::write(dir.path.join("file.half"), "clean only\n").unwrap();
+    let stage = |dir: &std::path::Path| {
+        vcs::stage(dir.to_string_lossy().to_string(), vec![".".to_string()]).unwrap();
+        let staged = git(dir, &["ls-files", "--stage"]);
+        git_output(dir, &["add", "."]);
+        assert_eq!(staged, git(dir, &["ls-files", "--stage"]));
+    };
+    stage(&dir.path);
+    assert_eq!(
+        git(&dir.path, &["show", ":with space.tag"]),
+        "with space.tag\nline\n"
+    );
+    assert_eq!(git(&dir.path, &["show", ":file.gone"]), "kept\n");
+    git(&dir.path, &["commit", "-q", "-m", "Filters"]);
+
+    fs::remove_file(dir.path.join("file.half")).unwrap();
+    vcs(&dir.path, &["reset", "--hard", "HEAD"], "");
+    assert_eq!(
+        fs::read_to_string(dir.path.join("file.half")).unwrap(),
+        "CLEAN ONLY\n"
+    );
+
+    // A failing required smudge filter fails the checkout like in git.
+    git(&dir.path, &["config", "filter.half.smudge", "false"]);
+    git(&dir.path, &["config", "filter.half.required", "true"]);
+    fs::remove_file(dir.path.join("file.half")).unwrap();
+    let args = ["reset", "--hard", "HEAD"];
+    assert_eq!(
+        vcs_output(&dir.path, &args, "", &[]).status.success(),
+        git_output(&dir.path, &args).status.success()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_hooks() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = git_repo("hooks", "sha1");
+    git(&dir.path, &["config", "user.name", "Hooked"]);
+    git(&dir.path, &["config", "user.email", "hooked@example.com"]);
+    let write_hook = |dir: &std::path::Path, name: &str, script: &str| {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    };
+    let hooks = dir.path.join(".git").join("hooks");
+    write_hook(&hooks, "pre-commit", "test ! -e block");
+    write_hook(&hooks, "prepare-commit-msg", "echo \"$2\" >> .git/prepared");
+    write_hook(&hooks, "commit-msg", "echo 'Signed-off-by: Hook' >> \"$1\"");
+    write_hook(&hooks, "post-commit", "git rev-parse HEAD > .git/committed");
+    write_hook(
+        &hooks,
+        "post-checkout",
+        "echo \"$1 $2 $3\" > .git/checked-out",
+    );
+    let read = |name: &str| fs::read_to_string(dir.path.join(".git").join(name)).unwrap();
+
+    fs::write(dir.path.join("file.txt"), "changed\n").unwrap();
+    git(&dir.path, &["add", "file.txt"]);
+    fs::write(dir.path.join("block"), "").unwrap();
+    let out = vcs_output(&dir.path, &["commit", "-m", "Blocked"], "", &[]);
+    assert!(!out.status.success());
+    assert!(!dir.path.join(".git").join("prepared").exists());
+
+    fs::remove_file(dir.path.join("block")).unwrap();
+    let first = git(&dir.path, &["rev-parse", "HEAD"]);
+    vcs(&dir.path, &["commit", "-m", "Hooked"], "");
+    let second = git(&dir.path, &["rev-parse", "HEAD"]);
+    assert_eq!(
+        git(&dir.path, &["log", "-1", "--format=%B"]),
+        "Hooked\nSigned-off-by: Hook\n\n"
+    );
+    assert_eq!(read("prepared"), "message\n");
+    assert_eq!(read("committed"), second);
+
+    // --no-verify skips pre-commit and commit-msg, not the others.
+    fs::write(dir.path.join("block"), "").unwrap();
+    fs::write(dir.path.join("file.txt"), "again\n").unwrap();
+    git(&dir.path, &["add", "file.txt"]);
+    vcs(
+        &dir.path,
+        &["commit", "--no-verify", "-m", "Unverified"],
+        "",
+    );
+    assert_eq!(
+        git(&dir.path, &["log", "-1", "--format=%B"]),
+        "Unverified\n\n"
+    );
+    assert_eq!(read("prepared"), "message\nmessage\n");
+
+    vcs(&dir.path, &["checkout", first.trim()], "");
+    let third = git(&dir.path, &["rev-parse", "master"]);
+    assert_eq!(
+        read("checked-out"),
+        format!("{} {} 1\n", third.trim(), first.trim())
+    );
+
+    // core.hooksPath is relative to the work tree.
+    git(&dir.path, &["config", "core.hooksPath", "custom"]);
+    write_hook(
+        &dir.path.join("custom"),
+        "post-checkout",
+        "echo custom > .git/checked-out",
+    );
+    vcs(&dir.path, &["checkout", "master"], "");
+    assert_eq!(read("checked-out"), "custom\n");
+    assert_eq!(git(&dir.path, &["rev-parse", "HEAD"]), third);
+
+    // Config keys are case-insensitive.
+    git(&dir.path, &["config", "--unset", "core.hooksPath"]);
+    git(&dir.path, &["config", "core.hookspath", "lower"]);
+    write_hook(
+        &dir.path.join("lower"),
+        "post-checkout",
+        "echo lower > .git/checked-out",
+    );
+    vcs(&dir.path, &["checkout", "master"], "");
+    assert_eq!(read("checked-out"), "lower\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn hook_failures_match_git() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = git_repo("hook_failures", "sha1");
+    git(&dir.path, &["config", "user.name", "Hooked"]);
+    git(&dir.path, &["config", "user.email", "hooked@example.com"]);
+    let hooks = dir.path.join(".git").join("hooks");
+    fs::create_dir_all(&hooks).unwrap();
+    let write_hook = |name: &str, script: &str, mode: u32| {
+        let path = hooks.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    };
+    let commit = |message: &str| {
+        fs::write(dir.path.join("file.txt"), format!("{message}\n")).unwrap();
+        git(&dir.path, &["add", "file.txt"]);
+        let args = ["commit", "-m", message];
+        let head = git(&dir.path, &["rev-parse", "HEAD"]);
+        let vcs_success = vcs_output(&dir.path, &args, "", &[]).status.success();
+        let vcs_head = git(&dir.path, &["rev-parse", "HEAD"]);
+        git(&dir.path, &["reset", "-q", "--soft", head.trim()]);
+        let git_success = git_output(&dir.path, &args).status.success();
+        assert_eq!(vcs_success, git_success, "{message}");
+        assert_eq!(vcs_head == head, !vcs_success, "{message}");
+        git(&dir.path, &["reset", "-q", "--soft", head.trim()]);
+    };
+
+    // Hooks that aren't executable are skipped with a warning.
+    write_hook("pre-commit", "exit 1", 0o644);
+    write_hook("commit-msg", "exit 1", 0o644);
+    commit("Not executable");
+
+    // A failing commit-msg or prepare-commit-msg aborts, a failing
+    // post-commit doesn't.
+    write_hook("commit-msg", "exit 1", 0o755);
+    commit("Rejected message");
+    fs::remove_file(hooks.join("commit-msg")).unwrap();
+    write_hook("prepare-commit-msg", "exit 1", 0o755);
+    commit("Not prepared");
+    fs::remove_file(hooks.join("prepare-commit-msg")).unwrap();
+    write_hook("post-commit", "exit 1", 0o755);
+    commit("Post commit fails");
+
+    // Hooks see the index of the repository.
+    write_hook(
+        "pre-commit",
+        "test \"$GIT_INDEX_FILE\" -ef .git/index",
+        0o755,
+    );
+    commit("Index file");
+}
+
+#[test]
+#[cfg(unix)]
+fn archive_matches_git() {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let dir = git_repo("archive", "sha1");
+    let script = dir.path.join("dir").join("run.sh");
+    fs::write(&script, "#!/bin/sh\n").unwrap();
+    git(
+        &dir.path,
+        &["update-index", "--add", "--chmod=+x", "dir/run.sh"],
+    );
+    std::os::unix::fs::symlink("file.txt", dir.path.join("link")).unwrap();
+    let long = format!("long/{}/file.txt", "x".repeat(120));
+    fs::create_dir_all(dir.path.join(&long).parent().unwrap()).unwrap();
+    fs::write(dir.path.join(&long), "long\n").unwrap();
+    fs::write(dir.path.join(".gitattributes"), "secret export-ignore\n").unwrap();
+    fs::write(dir.path.join("secret"), "hidden\n").unwrap();
+    git(&dir.path, &["add", "."]);
+    git(&dir.path, &["commit", "-q", "-m", "Archive"]);
+
+    let read = |name: &str| fs::read(dir.path.join(name)).unwrap();
+    for args in [
+        vec!["HEAD"],
+        vec!["--prefix=release/", "HEAD"],
+        vec!["--prefix=release-", "HEAD", "dir"],
+        vec!["HEAD^{tree}", "long"],
+    ] {
+        // Trees are stamped with the current time, so a run that crosses a
+        // second boundary is repeated.
+        let same = (0..3).any(|_| {
+            git(
+                &dir.path,
+                &[&["archive", "-o", "git.tar"], &args[..]].concat(),
+            );
+            vcs(
+                &dir.path,
+                &[&["archive", "-o", "vcs.tar"], &args[..]].concat(),
+                "",
+            );
+            read("git.tar") == read("vcs.tar")
+        });
+        assert!(same, "archive {args:?}");
+    }
+
+    let out = vcs_output(&dir.path, &["archive", "--format=tar.gz", "HEAD"], "", &[]);
+    let mut tar = Vec::new();
+    GzDecoder::new(&out.stdout[..])
+        .read_to_end(&mut tar)
+        .unwrap();
+    git(&dir.path, &["archive", "-o", "git.tar", "HEAD"]);
+    assert!(tar == read("git.tar"));
+
+    vcs(&dir.path, &["archive", "-o", "out.zip", "HEAD"], "");
+    let zip = read("out.zip");
+    let head = git(&dir.path, &["rev-parse", "HEAD"]);
+    assert!(zip.starts_with(b"PK\x03\x04"));
+    assert!(zip.ends_with(head.trim().as_bytes()));
+    let contains = |name: &str| zip.windows(name.len()).any(|w| w == name.as_bytes());
+    assert!(contains("dir/run.sh") && contains(&long));
+    // Names are followed by the extended timestamp field `UT`.
+    assert!(contains("dir/run.shUT") && !contains("secretUT"));
+}
+
+#[test]
+fn archive_edge_cases_match_git() {
+    let dir = git_repo("archive_edge_cases", "sha256");
+    git(&dir.path, &["tag", "-a", "v1", "-m", "Release"]);
+    for args in [
+        vec!["archive", "nope"],
+        vec!["archive", "HEAD:file.txt"],
+        vec!["archive", "HEAD", "nope"],
+        vec!["archive", "--format=zip", "HEAD", "nope"],
+    ] {
+        let actual = vcs_output(&dir.path, &args, "", &[]);
+        assert!(!actual.status.success(), "{args:?}");
+        assert!(!git_output(&dir.path, &args).status.success(), "{args:?}");
+        assert!(actual.stdout.is_empty(), "{args:?}");
+    }
+
+    // Annotated tags archive their commit, with its id in the header.
+    for args in [
+        vec!["archive", "v1"],
+        vec!["archive", "v1", "dir/"],
+        vec!["archive", "--prefix=", "v1^{tree}", "dir", "file.txt"],
+    ] {
+        let actual = vcs_output(&dir.path, &args, "", &[]);
+        assert!(actual.status.success(), "{args:?}");
+        assert!(
+            actual.stdout == git_output(&dir.path, &args).stdout,
+            "{args:?}"
+        );
+    }
+
+    // An explicit format wins over the extension of the output file.
+    vcs(
+        &dir.path,
+        &["archive", "--format=tar", "-o", "out.zip", "HEAD"],
+        "",
+    );
+    git(&dir.path, &["archive", "-o", "git.tar", "HEAD"]);
+    assert!(
+        fs::read(dir.path.join("out.zip")).unwrap() == fs::read(dir.path.join("git.tar")).unwrap()
+    );
+}
+
+#[test]
+fn bisect_matches_git() {
+    let dir = git_repo("bisect", "sha1");
+    for i in 1..=20 {
+        fs::write(dir.path.join("file.txt"), format!("{i}\n")).unwrap();
+        if i == 13 {
+            fs::write(dir.path.join("bug"), "").unwrap();
+        }
+        git(&dir.path, &["add", "."]);
+        git(&dir.path, &["commit", "-q", "-m", &format!("Commit {i}")]);
+    }
+    let head = || git(&dir.path, &["rev-parse", "HEAD"]);
+    let steps = [
+        vec!["start", "HEAD", "HEAD~18"],
+        vec!["skip"],
+        vec!["good"],
+        vec!["skip", "HEAD~1"],
+        vec!["bad"],
+    ];
+    let mut expected = Vec::new();
+    for step in &steps {
+        git(&dir.path, &[&["bisect"], &step[..]].concat());
+        expected.push(head());
+    }
+    let expected_log = git(&dir.path, &["bisect", "log"]);
+    git(&dir.path, &["bisect", "reset"]);
+
+    for (step, expected) in steps.iter().zip(expected) {
+        vcs(&dir.path, &[&["bisect"], &step[..]].concat(), "");
+        assert_eq!(head(), expected, "bisect {step:?}");
+    }
+    assert_eq!(vcs(&dir.path, &["bisect", "log"], ""), expected_log);
+    vcs(&dir.path, &["bisect", "reset"], "");
+    assert_eq!(
+        git(&dir.path, &["symbolic-ref", "HEAD"]),
+        "refs/heads/master\n"
+    );
+    assert!(!dir.path.join(".git").join("BISECT_LOG").exists());
+
+    let first_bad = git(&dir.path, &["rev-parse", "HEAD~7"]);
+    vcs(&dir.path, &["bisect", "start", "HEAD", "HEAD~20"], "");
+    let out = vcs(&dir.path, &["bisect", "run", "test", "!", "-e", "bug"], "");
+    assert!(
+        out.contains(&format!("{} is the first bad commit\n", first_bad.trim())),
+        "{out}"
+    );
+    assert!(out.ends_with("bisect found first bad commit\n"));
+    vcs(&dir.path, &["bisect", "reset"], "");
+    assert!(!vcs_output(&dir.path, &["bisect", "log"], "", &[])
+        .status
+        .success());
+}
+
+#[test]
+fn bisect_skip_counts_from_chosen_commit() {
+    let dir = git_repo("bisect_skip", "sha1");
+    for i in 1..=20 {
+        fs::write(dir.path.join("file.txt"), format!("{i}\n")).unwrap();
+        git(
+            &dir.path,
+            &["commit", "-q", "-a", "-m", &format!("Commit {i}")],
+        );
+    }
+    let good = git(&dir.path, &["rev-parse", "HEAD~18"]);
+    let all: usize = git(
+        &dir.path,
+        &["rev-list", "--count", "HEAD", &format!("^{}", good.trim())],
+    )
+    .trim()
+    .parse()
+    .unwrap();
+    vcs(&dir.path, &["bisect", "start", "HEAD", "HEAD~18"], "");
+    let midpoint = git(&dir.path, &["rev-parse", "HEAD"]);
+    // The midpoint is skipped, so another commit is picked.
+    let out = vcs(&dir.path, &["bisect", "skip"], "");
+    let chosen = git(&dir.path, &["rev-parse", "HEAD"]);
+    assert_ne!(chosen, midpoint);
+    let reaches: usize = git(
+        &dir.path,
+        &[
+            "rev-list",
+            "--count",
+            chosen.trim(),
+            &format!("^{}", good.trim()),
+        ],
+    )
+    .trim()
+    .parse()
+    .unwrap();
+    let remaining = all - reaches - 1;
+    assert!(
+        out.starts_with(&format!("Bisecting: {remaining} revisions left")),
+        "{out}"
+    );
+
+    // Skipping everything left leaves the first bad commit undecided.
+    let candidates = git(
+        &dir.path,
+        &["rev-list", "master~1", &format!("^{}", good.trim())],
+    );
+    let mut args = vec!["bisect", "skip"];
+    args.extend(candidates.lines());
+    let out = vcs_output(&dir.path, &args, "", &[]);
+    assert!(!out.status.success());
+    let out = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        out.contains("There are only 'skip'ped commits left to test."),
+        "{out}"
+    );
+    vcs(&dir.path, &["bisect", "reset"], "");
+}
+
+#[test]
+fn bisect_errors_match_git() {
+    let dir = git_repo("bisect_errors", "sha1");
+    for i in 1..=8 {
+        fs::write(dir.path.join("file.txt"), format!("{i}\n")).unwrap();
+        git(
+            &dir.path,
+            &["commit", "-q", "-a", "-m", &format!("Commit {i}")],
+        );
+    }
+    let both = |args: &[&str]| {
+        let vcs_success = vcs_output(&dir.path, args, "", &[]).status.success();
+        let git_success = git_output(&dir.path, args).status.success();
+        assert_eq!(vcs_success, git_success, "{args:?}");
+        vcs_success
+    };
+    assert!(both(&["bisect", "reset"]));
+    assert!(!both(&["bisect", "start", "HEAD", "HEAD"]));
+    assert!(!dir.path.join(".git").join("BISECT_LOG").exists());
+    assert_eq!(
+        git(&dir.path, &["symbolic-ref", "HEAD"]),
+        "refs/heads/master\n"
+    );
+
+    // An unknown revision or a run exit code from 128 on stops without
+    // marking anything.
+    vcs(&dir.path, &["bisect", "start", "HEAD", "HEAD~8"], "");
+    let testing = git(&dir.path, &["rev-parse", "HEAD"]);
+    let log = vcs(&dir.path, &["bisect", "log"], "");
+    let args = ["bisect", "bad", "nope"];
+    assert!(!vcs_output(&dir.path, &args, "", &[]).status.success());
+    let args = ["bisect", "run", "sh", "-c", "exit 200"];
+    let out = vcs_output(&dir.path, &args, "", &[]);
+    assert!(!out.status.success());
+    assert_eq!(git(&dir.path, &["rev-parse", "HEAD"]), testing);
+    assert_eq!(vcs(&dir.path, &["bisect", "log"], ""), log);
+    vcs(&dir.path, &["bisect", "reset"], "");
+    assert_eq!(
+        git(&dir.path, &["symbolic-ref", "HEAD"]),
+        "refs/heads/master\n"
+    );
+}
+
+#[test]
+fn grep_matches_git() {
+    let dir = git_repo("grep", "sha1");
+    fs::write(dir.path.join("words.txt"), "foo_bar foo\nfoobar\nFoo\n").unwrap();
+    fs::write(dir.path.join("data.bin"), b"foo\0bar\n").unwrap();
+    fs::write(dir.path.join("dir").join("more.txt"), "nested foo\n").unwrap();
+    git(&dir.path, &["add", "."]);
+    git(&dir.path, &["commit", "-q", "-m", "Words"]);
+    // The work tree, the index and HEAD differ.
+    fs::write(dir.path.join("file.txt"), "hello foo\n").unwrap();
+    git(&dir.path, &["add", "file.txt"]);
+    fs::write(dir.path.join("file.txt"), "changed\nfoo again\n").unwrap();
+    fs::write(dir.path.join("untracked.txt"), "foo\n").unwrap();
+
+    for args in [
+        vec!["foo"],
+        vec!["-n", "foo"],
+        vec!["-w", "foo"],
+        vec!["-i", "-n", "f.o$"],
+        vec!["-l", "foo"],
+        vec!["--cached", "-n", "foo"],
+        vec!["-n", "foo", "HEAD"],
+        vec!["-w", "foo", "HEAD", "--", "dir", "words.txt"],
+        vec!["-l", "nested", "HEAD^{tree}"],
+    ] {
+        let expected = git(&dir.path, &[&["grep"], &args[..]].concat());
+        assert_eq!(
+            vcs(&dir.path, &[&["grep"], &args[..]].concat(), ""),
+            expected,
+            "grep {args:?}"
+        );
+    }
+    let out = vcs_output(&dir.path, &["grep", "missing"], "", &[]);
+    assert!(!out.status.success() && out.stdout.is_empty());
+}
+
+#[test]
+fn grep_edge_cases_match_git() {
+    let dir = git_repo("grep_edge_cases", "sha1");
+    fs::write(dir.path.join("last.txt"), "foo at the end").unwrap();
+    fs::write(dir.path.join("edges.txt"), "foo\n-foo-\nfood\n\n").unwrap();
+    git(&dir.path, &["add", "."]);
+    git(&dir.path, &["commit", "-q", "-m", "Edges"]);
+    // Tracked files missing from the work tree aren't searched there.
+    fs::remove_file(dir.path.join("edges.txt")).unwrap();
+
+    for args in [
+        vec!["grep", "-n", "foo"],
+        vec!["grep", "-w", "-n", "foo", "HEAD"],
+        vec!["grep", "--cached", "-n", "end$"],
+        vec!["grep", "-i", "-n", "FOO$", "HEAD"],
+        vec!["grep", "foo", "HEAD", "--", "nope"],
+        vec!["grep", "foo", "nope"],
+        vec!["grep", "fo(o"],
+        vec!["grep", "-l", "foo", "HEAD:file.txt"],
+    ] {
+        let expected = git_output(&dir.path, &args);
+        let actual = vcs_output(&dir.path, &args, "", &[]);
+        assert_eq!(
+            actual.status.success(),
+            expected.status.success(),
+            "{args:?}"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&actual.stdout),
+            String::from_utf8_lossy(&expected.stdout),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn describe_and_shortlog_match_git() {
+    let dir = git_repo("describe", "sha1");
+    git(&dir.path, &["tag", "light"]);
+    let commit = |message: &str, author: &str| {
+        fs::write(dir.path.join("file.txt"), message).unwrap();
+        let author = format!("--author={author}");
+        git(&dir.path, &["commit", "-q", "-a", "-m", message, &author]);
+    };
+    commit("Two", "Alice <alice@example.com>");
+    git(&dir.path, &["tag", "-a", "v1", "-m", "v1"]);
+    commit("Three", "bob <bob@old.example.com>");
+    commit("[PATCH 2/2] Four\nmore", "Alice <ALICE@example.com>");
+    fs::write(
+        dir.path.join(".mailmap"),
+        "Bob <bob@example.com> <bob@old.example.com>\nAlice Proper <alice@example.com>\n",
+    )
+    .unwrap();
+
+    for args in [
+        vec![],
+        vec!["--tags"],
+        vec!["--long"],
+        vec!["HEAD~1"],
+        vec!["v1"],
+        vec!["--long", "v1"],
+        vec!["--tags", "HEAD~3"],
+        vec!["--dirty"],
+    ] {
+        let expected = git(&dir.path, &[&["describe"], &args[..]].concat());
+        assert_eq!(
+            vcs(&dir.path, &[&["describe"], &args[..]].concat(), ""),
+            expected,
+            "describe {args:?}"
+        );
+    }
+    let out = vcs_output(&dir.path, &["describe", "HEAD~3"], "", &[]);
+    assert!(!out.status.success() && out.stdout.is_empty());
+    fs::write(dir.path.join("file.txt"), "changed\n").unwrap();
+    let expected = git(&dir.path, &["describe", "--dirty=-wip"]);
+    assert_eq!(vcs(&dir.path, &["describe", "--dirty=-wip"], ""), expected);
+
+    for args in [vec![], vec!["-sn"], vec!["-e"], vec!["-s", "^v1"]] {
+        let expected = git(&dir.path, &[&["shortlog"], &args[..], &["HEAD"]].concat());
+        assert_eq!(
+            vcs(
+                &dir.path,
+                &[&["shortlog"], &args[..], &["HEAD"]].concat(),
+                ""
+            ),
+            expected,
+            "shortlog {args:?}"
+        );
+    }
+}
+
+#[test]
+fn describe_and_mailmap_edge_cases_match_git() {
+    let dir = git_repo("describe_edge_cases", "sha1");
+    let compare = |args: &[&str]| {
+        let expected = git_output(&dir.path, args);
+        let actual = vcs_output(&dir.path, args, "", &[]);
+        assert_eq!(
+            actual.status.success(),
+            expected.status.success(),
+            "{args:?}"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&actual.stdout),
+            String::from_utf8_lossy(&expected.stdout),
+            "{args:?}"
+        );
+    };
+    // Nothing to describe without tags, or with only lightweight ones.
+    compare(&["describe"]);
+    git(&dir.path, &["tag", "light"]);
+    compare(&["describe"]);
+    compare(&["describe", "--tags"]);
+    compare(&["describe", "nope"]);
+
+    // Of two annotated tags on a commit the newer one wins.
+    let tag = |name: &str, date: &str| {
+        let args = ["tag", "-a", name, "-m", name];
+        let out = std::process::Command::new("git")
+            .args(args)
+            .current_dir(&dir.path)
+            .env("GIT_COMMITTER_NAME", "Tagger")
+            .env("GIT_COMMITTER_EMAIL", "tagger@example.com")
+            .env("GIT_COMMITTER_DATE", date)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+    };
+    tag("b-old", "1700000000 +0000");
+    tag("a-new", "1800000000 +0000");
+    compare(&["describe"]);
+    compare(&["describe", "--long", "HEAD"]);
+
+    // Old names map too, comments and malformed lines are skipped and
+    // `mailmap.file` adds to `.mailmap`.
+    let author = |author: &str| {
+        fs::write(dir.path.join("file.txt"), author).unwrap();
+        let author = format!("--author={author}");
+        git(&dir.path, &["commit", "-q", "-a", "-m", "Change", &author]);
+    };
+    author("Old Name <shared@example.com>");
+    author("Other Name <shared@example.com>");
+    author("Carol <carol@example.com>");
+    fs::write(
+        dir.path.join(".mailmap"),
+        "# comment\nNew Name <new@example.com> Old Name <shared@example.com>\n\
+         broken line\nCarol <carol@example.com> # trailing comment\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path.join(".git").join("extra-mailmap"),
+        "Caroline <carol@example.com>\n",
+    )
+    .unwrap();
+    git(&dir.path, &["config", "mailmap.file", ".git/extra-mailmap"]);
+    compare(&["shortlog", "-se", "HEAD"]);
+    compare(&["shortlog", "HEAD", "^HEAD~2"]);
+    compare(&["shortlog", "-s", "HEAD", "^HEAD"]);
+}
+
+#[test]
+fn sparse_checkout_matches_git() {
+    let dir = git_repo("sparse", "sha1");
+    for path in ["a/b/c/file.txt", "a/b/file.txt", "a/file.txt", "e/file.txt"] {
+        let path = dir.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "sparse\n").unwrap();
+    }
+    git(&dir.path, &["add", "."]);
+    git(&dir.path, &["commit", "-q", "-m", "Sparse"]);
+    let state = || {
+        let sparse = dir.path.join(".git").join("info").join("sparse-checkout");
+        [
+            git(&dir.path, &["ls-files", "-t"]),
+            git(&dir.path, &["status", "--porcelain"]),
+            git(&dir.path, &["status"]).lines().take(2).collect(),
+            fs::read_to_string(sparse).unwrap_or_default(),
+            dir.path.join("a").join("file.txt").exists().to_string(),
+        ]
+    };
+    let steps = [
+        vec!["init"],
+        vec!["set", "a/b/c", "e/"],
+        vec!["list"],
+        vec!["add", "a"],
+        vec!["set", "dir"],
+        vec!["disable"],
+    ];
+    let mut expected = Vec::new();
+    for step in &steps {
+        let out = git(&dir.path, &[&["sparse-checkout"], &step[..]].concat());
+        expected.push((out, state()));
+    }
+    fs::remove_file(dir.path.join(".git").join("info").join("sparse-checkout")).unwrap();
+
+    for (step, expected) in steps.iter().zip(expected) {
+        let out = vcs(&dir.path, &[&["sparse-checkout"], &step[..]].concat(), "");
+        assert_eq!((out, state()), expected, "sparse-checkout {step:?}");
+    }
+    let out = vcs_output(&dir.path, &["sparse-checkout", "list"], "", &[]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn sparse_checkout_keeps_config() {
+    let dir = git_repo("sparse_config", "sha1");
+    git(&dir.path, &["config", "remote.origin.url", "/elsewhere"]);
+    git(
+        &dir.path,
+        &[
+            "config",
+            "--add",
+            "remote.origin.fetch",
+            "+refs/heads/*:refs/remotes/origin/*",
+        ],
+    );
+    git(
+        &dir.path,
+        &[
+            "config",
+            "--add",
+            "remote.origin.fetch",
+            "+refs/tags/*:refs/tags/*",
+        ],
+    );
+    let config_path = dir.path.join(".git").join("config");
+    let mut config = fs::read_to_string(&config_path).unwrap();
+    config.push_str("; keep this comment\n");
+    fs::write(&config_path, &config).unwrap();
+
+    vcs(&dir.path, &["sparse-checkout", "set", "dir"], "");
+    let updated = fs::read_to_string(&config_path).unwrap();
+    assert!(updated.starts_with(&config), "{updated}");
+    assert_eq!(
+        git(&dir.path, &["config", "--get-all", "remote.origin.fetch"]),
+        "+refs/heads/*:refs/remotes/origin/*\n+refs/tags/*:refs/tags/*\n"
+    );
+    assert_eq!(git(&dir.path, &["config", "core.sparseCheckout"]), "true\n");
+    vcs(&dir.path, &["sparse-checkout", "disable"], "");
+    assert_eq!(
+        git(&dir.path, &["config", "core.sparseCheckout"]),
+        "false\n"
+    );
+    assert!(fs::read_to_string(&config_path)
+        .unwrap()
+        .starts_with(&config));
+}
+
+#[test]
+fn sparse_checkout_edge_cases_match_git() {
+    let origin = git_repo("sparse_edge_cases", "sha1");
+    for path in ["a/file.txt", "b/file.txt", "b/other.txt"] {
+        let path = origin.path.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "sparse\n").unwrap();
+    }
+    git(&origin.path, &["add", "."]);
+    git(&origin.path, &["commit", "-q", "-m", "Sparse"]);
+    let root = TmpDir::new("sparse_edge_cases_clones");
+    git(
+        &root.path,
+        &["clone", "-q", origin.path.to_str().unwrap(), "git"],
+    );
+    git(
+        &root.path,
+        &["clone", "-q", origin.path.to_str().unwrap(), "vcs"],
+    );
+    let state = |dir: &std::path::Path| {
+        let mut files = Vec::new();
+        for name in ["a", "b"] {
+            for file in fs::read_dir(dir.join(name)).into_iter().flatten() {
+                files.push(file.unwrap().path().strip_prefix(dir).unwrap().to_owned());
+            }
+        }
+        files.sort();
+        (
+            git(dir, &["ls-files", "-t"]),
+            git(dir, &["status", "--porcelain"]),
+            files,
+        )
+    };
+    let run = |args: &[&str], edit: &dyn Fn(&std::path::Path)| {
+        let git_dir = root.path.join("git");
+        let vcs_dir = root.path.join("vcs");
+        edit(&git_dir);
+        edit(&vcs_dir);
+        let args = [&["sparse-checkout"], args].concat();
+        let git_success = git_output(&git_dir, &args).status.success();
+        let vcs_success = vcs_output(&vcs_dir, &args, "", &[]).status.success();
+        assert_eq!(vcs_success, git_success, "{args:?}");
+        assert_eq!(state(&vcs_dir), state(&git_dir), "{args:?}");
+    };
+    let nothing = |_: &std::path::Path| {};
+
+    // Nothing to add to or list before it is enabled.
+    run(&["add", "a"], &nothing);
+    run(&["list"], &nothing);
+
+    // Modified and untracked files outside the new cone stay.
+    run(&["set", "a"], &|dir| {
+        fs::write(dir.join("b").join("file.txt"), "changed\n").unwrap();
+        fs::write(dir.join("b").join("untracked.txt"), "new\n").unwrap();
+    });
+    run(&["set", "a", "missing"], &nothing);
+    run(&["add", "b"], &nothing);
+    // Once committed, the file can be removed from the work tree.
+    run(&["set", "a"], &|dir| {
+        git(dir, &["commit", "-q", "-am", "Change"]);
+    });
+    run(&["disable"], &nothing);
+}
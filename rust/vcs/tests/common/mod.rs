@@ -0,0 +1,134 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A scratch directory that is removed when dropped.
+pub struct TmpDir {
+    pub path: PathBuf,
+}
+
+impl TmpDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "vcs_test_{}_{}_{}",
+            name,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        if path.exists() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        fs::create_dir_all(&path).unwrap();
+        TmpDir { path }
+    }
+}
+
+impl Drop for TmpDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Run git in [dir] with a fixed identity and return stdout.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let out = git_output(dir, args);
+    assert!(
+        out.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8_lossy(&out.stdout).to_string()
+}
+
+/// Like [git] without checking the exit status.
+pub fn git_output(dir: &Path, args: &[&str]) -> std::process::Output {
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "Jöhn Doe")
+        .env("GIT_AUTHOR_EMAIL", "john@example.com")
+        .env("GIT_AUTHOR_DATE", "1527025023 +0200")
+        .env("GIT_COMMITTER_NAME", "Jöhn Doe")
+        .env("GIT_COMMITTER_EMAIL", "john@example.com")
+        .env("GIT_COMMITTER_DATE", "1527025044 +0200")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .output()
+        .expect("git must be installed to run tests")
+}
+
+/// Create a git repository with one commit using real git.
+pub fn git_repo(name: &str, object_format: &str) -> TmpDir {
+    let dir = TmpDir::new(name);
+    git(
+        &dir.path,
+        &[
+            "init",
+            "-q",
+            "-b",
+            "master",
+            "--object-format",
+            object_format,
+        ],
+    );
+    fs::write(dir.path.join("file.txt"), "hello\n").unwrap();
+    fs::create_dir(dir.path.join("dir")).unwrap();
+    fs::write(dir.path.join("dir").join("nested.txt"), "nested\n").unwrap();
+    git(&dir.path, &["add", "."]);
+    git(
+        &dir.path,
+        &["commit", "-q", "-m", "First line\n\nBody with ünïcode\n"],
+    );
+    dir
+}
+
+/// Run the `vcs` binary in [dir] feeding [stdin] and return stdout.
+pub fn vcs(dir: &Path, args: &[&str], stdin: &str) -> String {
+    vcs_env(dir, args, stdin, &[])
+}
+
+/// Like [vcs] with additional environment variables.
+pub fn vcs_env(dir: &Path, args: &[&str], stdin: &str, envs: &[(&str, &str)]) -> String {
+    let out = vcs_output(dir, args, stdin, envs);
+    assert!(
+        out.status.success(),
+        "vcs {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8_lossy(&out.stdout).to_string()
+}
+
+/// Run the `vcs` binary in [dir] without checking its exit status.
+pub fn vcs_output(
+    dir: &Path,
+    args: &[&str],
+    stdin: &str,
+    envs: &[(&str, &str)],
+) -> std::process::Output {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_vcs"))
+        .args(args)
+        .envs(envs.iter().copied())
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
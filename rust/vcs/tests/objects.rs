@@ -0,0 +1,415 @@
+mod common;
+
+use common::{git, git_repo, vcs_output, TmpDir};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::Write;
+use vcs::git::object_id::{ObjectFormat, ObjectId};
+use vcs::git::objects::{GitObject, GitObjectType, GitTreeBuilder};
+use vcs::git::repo::Repository;
+
+fn rev_parse(dir: &std::path::Path, rev: &str) -> ObjectId {
+    git(dir, &["rev-parse", rev]).trim().parse().unwrap()
+}
+
+/// Read [id] from [source] and write it to a fresh repository.
+fn round_trip(source: &Repository, id: ObjectId, format: ObjectFormat) -> (TmpDir, ObjectId) {
+    let target = TmpDir::new("round_trip_target");
+    let target_repo = Repository::init(target.path.clone(), format).unwrap();
+    let obj = source.object_read(id).unwrap();
+    let written = target_repo.object_write(obj).unwrap();
+    (target, written)
+}
+
+#[test]
+fn hash_object_matches_git() {
+    for format in ["sha1", "sha256"] {
+        let dir = git_repo("hash_object", format);
+        fs::write(dir.path.join("new.txt"), "some content\n").unwrap();
+        let repo = Repository::new(dir.path.clone(), None).unwrap();
+
+        let id = repo
+            .hash_object(dir.path.join("new.txt"), GitObjectType::Blob)
+            .unwrap();
+        assert_eq!(
+            id.to_string(),
+            git(&dir.path, &["hash-object", "new.txt"]).trim()
+        );
+        // git can read what we wrote
+        assert_eq!(
+            git(&dir.path, &["cat-file", "-p", &id.to_string()]),
+            "some content\n"
+        );
+        assert_eq!(
+            git(&dir.path, &["cat-file", "-t", &id.to_string()]),
+            "blob\n"
+        );
+    }
+}
+
+#[test]
+fn objects_round_trip() {
+    for (format_name, format) in [
+        ("sha1", ObjectFormat::Sha1),
+        ("sha256", ObjectFormat::Sha256),
+    ] {
+        let dir = git_repo("round_trip", format_name);
+        git(&dir.path, &["tag", "-a", "v1", "-m", "Release\n\nnotes"]);
+        let repo = Repository::new(dir.path.clone(), None).unwrap();
+
+        for rev in ["HEAD", "HEAD:file.txt", "v1"] {
+            let id = rev_parse(&dir.path, rev);
+            let (target, written) = round_trip(&repo, id, format);
+            assert_eq!(written, id, "{rev} in {format_name}");
+            assert_eq!(
+                git(&target.path, &["cat-file", "-p", &id.to_string()]),
+                git(&dir.path, &["cat-file", "-p", &id.to_string()])
+            );
+        }
+    }
+}
+
+#[test]
+fn tree_with_non_utf8_name_round_trips() {
+    let dir = git_repo("non_utf8_tree", "sha1");
+    let blob = rev_parse(&dir.path, "HEAD:file.txt");
+    let mut data = b"100644 caf\xe9.txt\0".to_vec();
+    data.extend(blob.as_bytes());
+    fs::write(dir.path.join("tree"), data).unwrap();
+    let id: ObjectId = git(&dir.path, &["hash-object", "-t", "tree", "-w", "tree"])
+        .trim()
+        .parse()
+        .unwrap();
+    let repo = Repository::new(dir.path.clone(), None).unwrap();
+    let (_target, written) = round_trip(&repo, id, ObjectFormat::Sha1);
+    assert_eq!(written, id);
+}
+
+#[test]
+fn reads_malformed_tree_as_error() {
+    let dir = TmpDir::new("malformed_tree");
+    let repo = Repository::init(dir.path.clone(), ObjectFormat::Sha1).unwrap();
+    let id = repo
+        .object_write_raw(GitObjectType::Tree, b"100644 cut\0\xab")
+        .unwrap();
+    assert!(repo
+        .object_open(id)
+        .unwrap()
+        .into_object(ObjectFormat::Sha1)
+        .is_err());
+    assert!(repo.object_read(id).is_none());
+}
+
+#[test]
+fn reads_own_objects() {
+    let dir = TmpDir::new("reads_own");
+    let repo = Repository::init(dir.path.clone(), ObjectFormat::Sha1).unwrap();
+    let id = repo
+        .object_write_raw(GitObjectType::Blob, b"data\n")
+        .unwrap();
+    match repo.object_read(id) {
+        Some(GitObject::Blob(blob)) => assert_eq!(blob.data(), b"data\n"),
+        _ => panic!("Expected blob"),
+    }
+}
+
+#[test]
+fn writes_objects_from_many_threads() {
+    let dir = TmpDir::new("write_threads");
+    let repo = Repository::init(dir.path.clone(), ObjectFormat::Sha1).unwrap();
+    let ids = std::thread::scope(|scope| {
+        let workers = (0..8)
+            .map(|i| {
+                let repo = &repo;
+                scope.spawn(move || {
+                    // Every thread also writes the same shared object.
+                    let shared = repo.object_write_raw(GitObjectType::Blob, b"shared\n");
+                    let own =
+                        repo.object_write_raw(GitObjectType::Blob, format!("{i}\n").as_bytes());
+                    (shared.unwrap(), own.unwrap())
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    for (shared, own) in ids {
+        assert_eq!(
+            git(&dir.path, &["cat-file", "-p", &shared.to_string()]),
+            "shared\n"
+        );
+        git(&dir.path, &["cat-file", "-e", &own.to_string()]);
+    }
+    let leftovers = fs::read_dir(dir.path.join(".git").join("objects"))
+        .unwrap()
+        .flatten()
+        .filter_map(|entry| fs::read_dir(entry.path()).ok())
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("tmp_obj_"))
+        .count();
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn object_write_reports_errors() {
+    let dir = TmpDir::new("write_error");
+    let repo = Repository::init(dir.path.clone(), ObjectFormat::Sha1).unwrap();
+    let id = repo
+        .object_hash(GitObjectType::Blob, b"blocked\n")
+        .to_string();
+    // A file where the fan-out directory should be.
+    fs::write(dir.path.join(".git").join("objects").join(&id[..2]), "").unwrap();
+    assert!(repo
+        .object_write_raw(GitObjectType::Blob, b"blocked\n")
+        .is_err());
+}
+
+#[test]
+fn rejects_object_with_corrupt_size() {
+    let dir = TmpDir::new("corrupt_size");
+    let repo = Repository::init(dir.path.clone(), ObjectFormat::Sha1).unwrap();
+    let id = repo
+        .object_write_raw(GitObjectType::Blob, b"short\n")
+        .unwrap();
+    let hex = id.to_string();
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(b"blob 18446744073709551615\0short\n")
+        .unwrap();
+    let path = dir.path.join(".git").join("objects").join(&hex[..2]);
+    fs::write(path.join(&hex[2..]), encoder.finish().unwrap()).unwrap();
+
+    let reader = repo.object_open(id).unwrap();
+    assert_eq!(reader.size(), u64::MAX);
+    assert!(reader.into_object(ObjectFormat::Sha1).is_err());
+    assert!(repo.object_read(id).is_none());
+}
+
+#[test]
+fn streams_large_blob() {
+    let dir = git_repo("stream", "sha1");
+    let data = (0..8 * 1024 * 1024u32)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<u8>>();
+    fs::write(dir.path.join("large.bin"), &data).unwrap();
+    let id: ObjectId = git(&dir.path, &["hash-object", "-w", "large.bin"])
+        .trim()
+        .parse()
+        .unwrap();
+    let repo = Repository::new(dir.path.clone(), None).unwrap();
+
+    let mut reader = repo.object_open(id).unwrap();
+    assert_eq!(reader.obj_type(), GitObjectType::Blob);
+    assert_eq!(reader.size(), data.len() as u64);
+    let mut read = Vec::new();
+    std::io::Read::read_to_end(&mut reader, &mut read).unwrap();
+    assert!(read == data);
+}
+
+#[test]
+fn tree_builder_matches_git_mktree() {
+    let dir = git_repo("mktree", "sha1");
+    let repo = Repository::new(dir.path.clone(), None).unwrap();
+    let blob = rev_parse(&dir.path, "HEAD:file.txt");
+    let subtree = rev_parse(&dir.path, "HEAD:dir");
+
+    let entries = [
+        ("100644", "a-b", blob),
+        ("40000", "a", subtree),
+        ("100755", "a.sh", blob),
+        ("120000", "link", blob),
+        ("100644", "a0", blob),
+    ];
+    let mut builder = GitTreeBuilder::new();
+    let mut listing = String::new();
+    for (mode, name, id) in entries {
+        builder.insert(mode, name.to_string(), id).unwrap();
+        let obj_type = if mode == "40000" { "tree" } else { "blob" };
+        listing.push_str(&format!("{mode} {obj_type} {id}\t{name}\n"));
+    }
+    let id = repo.object_write(GitObject::Tree(builder.build())).unwrap();
+
+    let mut mktree = std::process::Command::new("git")
+        .arg("mktree")
+        .current_dir(&dir.path)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::io::Write::write_all(&mut mktree.stdin.take().unwrap(), listing.as_bytes()).unwrap();
+    let expected = mktree.wait_with_output().unwrap().stdout;
+    assert_eq!(id.to_string(), String::from_utf8(expected).unwrap().trim());
+}
+
+fn sorted_lines(out: &[u8]) -> Vec<String> {
+    let mut lines = String::from_utf8_lossy(out)
+        .lines()
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    lines.sort();
+    lines
+}
+
+#[test]
+fn fsck_matches_git() {
+    let dir = git_repo("fsck", "sha1");
+    fs::write(dir.path.join("extra.txt"), "extra\n").unwrap();
+    git(&dir.path, &["add", "extra.txt"]);
+    git(&dir.path, &["commit", "-q", "-m", "Extra"]);
+    git(&dir.path, &["repack", "-adq"]);
+    git(&dir.path, &["reset", "-q", "--hard", "HEAD~1"]);
+    fs::write(dir.path.join("loose.txt"), "loose\n").unwrap();
+    git(&dir.path, &["hash-object", "-w", "loose.txt"]);
+
+    for unreachable in [false, true] {
+        let mut args = vec!["fsck"];
+        if unreachable {
+            args.push("--unreachable");
+        }
+        let out = vcs_output(&dir.path, &args, "", &[]);
+        assert!(out.status.success());
+        args.push("--no-reflogs");
+        assert_eq!(
+            sorted_lines(&out.stdout),
+            sorted_lines(git(&dir.path, &args).as_bytes())
+        );
+    }
+}
+
+#[test]
+fn fsck_reports_corruption() {
+    let dir = git_repo("fsck_corrupt", "sha1");
+    let blob = git(&dir.path, &["rev-parse", "HEAD:file.txt"]);
+    let blob = blob.trim();
+    let path = dir
+        .path
+        .join(".git/objects")
+        .join(&blob[..2])
+        .join(&blob[2..]);
+    fs::remove_file(&path).unwrap();
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b"blob 5\0evil\n").unwrap();
+    fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    let nested = git(&dir.path, &["rev-parse", "HEAD:dir/nested.txt"]);
+    let nested = nested.trim();
+    fs::remove_file(
+        dir.path
+            .join(".git/objects")
+            .join(&nested[..2])
+            .join(&nested[2..]),
+    )
+    .unwrap();
+
+    let mut unsorted = b"100644 b\0".to_vec();
+    unsorted.extend([0x11; 20]);
+    unsorted.extend(b"100644 a\0");
+    unsorted.extend([0x22; 20]);
+    fs::write(dir.path.join("tree"), unsorted).unwrap();
+    let tree = git(
+        &dir.path,
+        &["hash-object", "--literally", "-t", "tree", "-w", "tree"],
+    );
+
+    let out = vcs_output(&dir.path, &["fsck"], "", &[]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains(&format!("error: hash mismatch for {blob}")),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains(&format!(
+            "error: in tree {}: not properly sorted",
+            tree.trim()
+        )),
+        "{stderr}"
+    );
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.contains(&format!("missing blob {nested}")),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn reads_deltified_packs() {
+    let dir = git_repo("deltified", "sha1");
+    let mut content = (0..2000).map(|i| format!("line {i}\n")).collect::<String>();
+    for i in 0..20 {
+        content.push_str(&format!("change {i}\n"));
+        fs::write(dir.path.join("file.txt"), &content).unwrap();
+        git(
+            &dir.path,
+            &["commit", "-q", "-a", "-m", &format!("Change {i}")],
+        );
+    }
+    git(&dir.path, &["repack", "-adfq", "--depth=50", "--window=50"]);
+    let repo = Repository::new(dir.path.clone(), None).unwrap();
+    // Twice, the second time from the delta base cache.
+    for _ in 0..2 {
+        for i in 0..20 {
+            let rev = format!("HEAD~{i}:file.txt");
+            let Some(GitObject::Blob(blob)) = repo.object_read(rev_parse(&dir.path, &rev)) else {
+                panic!("Expected blob for {rev}");
+            };
+            assert_eq!(
+                String::from_utf8_lossy(blob.data()),
+                git(&dir.path, &["cat-file", "-p", &rev])
+            );
+        }
+    }
+}
+
+#[test]
+fn fsck_reports_corrupt_pack() {
+    let dir = git_repo("fsck_corrupt_pack", "sha1");
+    git(&dir.path, &["repack", "-adq"]);
+    let pack = fs::read_dir(dir.path.join(".git/objects/pack"))
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "pack"))
+        .unwrap();
+    let mut data = fs::read(&pack).unwrap();
+    // Every entry header claims a size far beyond any real object.
+    for byte in &mut data[12..40] {
+        *byte = 0xff;
+    }
+    fs::write(&pack, data).unwrap();
+
+    let out = vcs_output(&dir.path, &["fsck"], "", &[]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("pack checksum mismatch"), "{stderr}");
+    assert!(stderr.contains("failed to read from pack"), "{stderr}");
+}
+
+#[test]
+fn fsck_skips_gitlinks_in_index() {
+    let dir = git_repo("fsck_gitlink", "sha1");
+    let commit = "1234567890123456789012345678901234567890";
+    git(
+        &dir.path,
+        &[
+            "update-index",
+            "--add",
+            "--cacheinfo",
+            &format!("160000,{commit},sub"),
+        ],
+    );
+    let out = vcs_output(&dir.path, &["fsck"], "", &[]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(
+        sorted_lines(&out.stdout),
+        sorted_lines(git(&dir.path, &["fsck", "--no-reflogs"]).as_bytes())
+    );
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use common::{git, git_repo, vcs, vcs_output};
+use std::fs;
+use std::process::Command;
+
+/// Repository with an SSH signed commit and tag, configured to trust the key.
+fn signed_repo(name: &str, format: &str) -> common::TmpDir {
+    let dir = git_repo(name, format);
+    let key = dir.path.join(".git").join("signing_key");
+    let status = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C", "test", "-f"])
+        .arg(&key)
+        .status()
+        .expect("ssh-keygen must be installed to run tests");
+    assert!(status.success());
+    let public_key = fs::read_to_string(key.with_extension("pub")).unwrap();
+    let allowed = dir.path.join(".git").join("allowed_signers");
+    fs::write(&allowed, format!("john@example.com {public_key}")).unwrap();
+
+    git(&dir.path, &["config", "gpg.format", "ssh"]);
+    git(
+        &dir.path,
+        &["config", "user.signingkey", key.to_str().unwrap()],
+    );
+    git(
+        &dir.path,
+        &[
+            "config",
+            "gpg.ssh.allowedSignersFile",
+            allowed.to_str().unwrap(),
+        ],
+    );
+    fs::write(dir.path.join("file.txt"), "signed\n").unwrap();
+    git(&dir.path, &["commit", "-q", "-a", "-S", "-m", "Signed"]);
+    git(&dir.path, &["tag", "-s", "-m", "Signed tag", "v1"]);
+    dir
+}
+
+#[test]
+fn verifies_good_ssh_signatures() {
+    let dir = signed_repo("verify_good", "sha1");
+    let out = vcs_output(&dir.path, &["verify-commit", "HEAD"], "", &[]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "Good signature from john@example.com\n"
+    );
+    let out = vcs_output(&dir.path, &["verify-tag", "v1"], "", &[]);
+    assert!(out.status.success());
+
+    let log = vcs(&dir.path, &["log", "--show-signature", "-n", "1"], "");
+    assert!(
+        log.contains("\nGood signature from john@example.com\n"),
+        "{log}"
+    );
+}
+
+#[test]
+fn writes_signatures_inside_git_dir() {
+    // A shared temporary directory isn't needed, nor are files left behind.
+    let dir = signed_repo("verify_git_dir", "sha1");
+    let missing = dir.path.join("missing");
+    let envs = [("TMPDIR", missing.to_str().unwrap())];
+    let out = vcs_output(&dir.path, &["verify-commit", "HEAD"], "", &envs);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let leftovers = fs::read_dir(dir.path.join(".git"))
+        .unwrap()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().contains("signature"))
+        .count();
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn reports_unknown_and_unsigned() {
+    let dir = signed_repo("verify_unknown", "sha1");
+    fs::write(dir.path.join(".git").join("allowed_signers"), "").unwrap();
+    let out = vcs_output(&dir.path, &["verify-commit", "HEAD"], "", &[]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr)
+        .starts_with("Can't check signature: unknown key SHA256:"));
+
+    let out = vcs_output(&dir.path, &["verify-commit", "HEAD~1"], "", &[]);
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "HEAD~1: no signature found\n"
+    );
+}
+
+#[test]
+fn detects_tampered_commit() {
+    let dir = signed_repo("verify_bad", "sha1");
+    let raw = git(&dir.path, &["cat-file", "commit", "HEAD"]).replace("Signed", "Tampered");
+    fs::write(dir.path.join("tampered"), raw).unwrap();
+    let tampered = git(
+        &dir.path,
+        &["hash-object", "-t", "commit", "-w", "tampered"],
+    );
+    let out = vcs_output(&dir.path, &["verify-commit", tampered.trim()], "", &[]);
+    assert!(!out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stderr), "BAD signature\n");
+}
+
+#[test]
+fn verifies_sha256_and_non_utf8_commits() {
+    let dir = signed_repo("verify_sha256", "sha256");
+    assert!(vcs_output(&dir.path, &["verify-commit", "HEAD"], "", &[])
+        .status
+        .success());
+    assert!(vcs_output(&dir.path, &["verify-tag", "v1"], "", &[])
+        .status
+        .success());
+
+    // The payload must be the stored bytes, not a lossy re-encoding.
+    fs::write(dir.path.join("message"), b"Caf\xe9\n").unwrap();
+    git(&dir.path, &["config", "i18n.commitEncoding", "ISO-8859-1"]);
+    git(
+        &dir.path,
+        &["commit", "-q", "-S", "--allow-empty", "-F", "message"],
+    );
+    let out = vcs_output(&dir.path, &["verify-commit", "HEAD"], "", &[]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}